@@ -0,0 +1,61 @@
+//! Benchmarks for buffering values into `OwnedValue`s.
+//!
+//! Run with `cargo bench --bench owned`. These use a simple timing loop
+//! rather than a benchmarking framework, so results are only indicative.
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+use valuable::{
+    owned::{OwnedStr, OwnedValue},
+    value::Value,
+};
+
+const ITERS: u32 = 200_000;
+
+/// Field values typical of a log event: short identifiers and names.
+const SHORT: &[&str] = &[
+    "GET",
+    "/api/v1/users",
+    "200",
+    "7f3a9c2e",
+    "us-east-1",
+    "frontend",
+    "alice",
+    "info",
+];
+
+fn bench(name: &str, mut f: impl FnMut()) -> Duration {
+    // Warm up the allocator and caches.
+    for _ in 0..ITERS / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{:<32} {:>10?}/iter", name, elapsed / ITERS);
+    elapsed
+}
+
+fn main() {
+    let heap = bench("short strings: String", || {
+        let strings: Vec<String> = SHORT.iter().map(|s| s.to_string()).collect();
+        black_box(strings);
+    });
+    let inline = bench("short strings: OwnedStr", || {
+        let strings: Vec<OwnedStr> = SHORT.iter().map(|&s| OwnedStr::from(s)).collect();
+        black_box(strings);
+    });
+    println!(
+        "{:<32} {:>9.2}x",
+        "speedup",
+        heap.as_secs_f64() / inline.as_secs_f64()
+    );
+
+    let list = SHORT.to_vec();
+    bench("OwnedValue::from_value(list)", || {
+        black_box(OwnedValue::from_value(&Value::borrowed(&list)).unwrap());
+    });
+}
@@ -0,0 +1,198 @@
+//! Structured backtraces.
+//!
+//! A `std::backtrace::Backtrace` can only be formatted as a multi-line
+//! string. [`Frames`] visits a captured backtrace as a list of frames
+//! instead, so that error reporting backends may record (and filter, or
+//! truncate) each frame separately.
+//!
+//! This module requires the `backtrace` feature.
+//!
+//! [`Frames`]: struct.Frames.html
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    fmt,
+};
+
+/// Visits a `Backtrace` as a list of frames.
+///
+/// Each frame is visited as a struct named `Frame`, with a `symbol` field
+/// containing the name of the frame's function, and `file` and `line`
+/// fields if the frame's source location could be resolved. Frames for
+/// functions which were inlined into another frame are visited as separate
+/// frames. A backtrace which was not captured (for example, because
+/// backtraces are disabled) is visited as an empty list.
+///
+/// The standard library only exposes the frames of a backtrace through its
+/// `Display` output, so visiting a `Frames` formats the backtrace and
+/// parses the result.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::{backtrace::Frames, value::Value};
+/// # use std::backtrace::Backtrace;
+/// let backtrace = Backtrace::force_capture();
+/// let frames = Frames::new(&backtrace).with_max_frames(Some(8));
+/// assert!(Value::borrowed(&frames).render_json().starts_with(r#"[{"symbol":"#));
+///
+/// let disabled = Backtrace::disabled();
+/// assert_eq!(Value::borrowed(&Frames::new(&disabled)).render_json(), "[]");
+/// ```
+pub struct Frames<'a> {
+    backtrace: &'a Backtrace,
+    skip: usize,
+    max_frames: Option<usize>,
+}
+
+/// A frame parsed from a backtrace's `Display` output.
+#[derive(Debug, PartialEq)]
+struct Frame<'a> {
+    symbol: &'a str,
+    location: Option<(&'a str, u64)>,
+}
+
+// === impl Frames ===
+
+impl<'a> Frames<'a> {
+    /// Returns a new `Frames` visiting every frame of `backtrace`.
+    pub fn new(backtrace: &'a Backtrace) -> Self {
+        Self {
+            backtrace,
+            skip: 0,
+            max_frames: None,
+        }
+    }
+
+    /// Sets the number of frames to omit from the start of the backtrace.
+    ///
+    /// The first frames of a backtrace are usually those of the code which
+    /// captured it, rather than the code which failed.
+    pub fn with_skip(self, skip: usize) -> Self {
+        Self { skip, ..self }
+    }
+
+    /// Sets the maximum number of frames to visit, after any which were
+    /// [skipped]. Frames beyond the maximum are omitted.
+    ///
+    /// [skipped]: #method.with_skip
+    pub fn with_max_frames(self, max_frames: Option<usize>) -> Self {
+        Self { max_frames, ..self }
+    }
+}
+
+impl Visitable for Frames<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if self.backtrace.status() != BacktraceStatus::Captured {
+            return visitor.visit_list(None);
+        }
+        let rendered = self.backtrace.to_string();
+        let frames = parse(&rendered)
+            .skip(self.skip)
+            .take(self.max_frames.unwrap_or(usize::MAX))
+            .collect::<Vec<_>>();
+        visitor.visit_list(frames.iter().map(Value::borrowed))
+    }
+}
+
+impl fmt::Debug for Frames<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frames")
+            .field("status", &self.backtrace.status())
+            .field("skip", &self.skip)
+            .field("max_frames", &self.max_frames)
+            .finish()
+    }
+}
+
+// === impl Frame ===
+
+impl Visitable for Frame<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let symbol = Value::borrowed(&self.symbol);
+        match self.location {
+            Some((file, line)) => visitor.visit_struct(
+                "Frame",
+                [
+                    ("symbol", symbol),
+                    ("file", Value::borrowed(&file)),
+                    ("line", Value::borrowed(&line)),
+                ],
+            ),
+            None => visitor.visit_struct("Frame", [("symbol", symbol)]),
+        }
+    }
+}
+
+/// Parses the frames from the `Display` output of a `Backtrace`.
+///
+/// Each symbol is written on a line of its own, prefixed by the index of its
+/// frame if it is the frame's first symbol, and followed by a line of the
+/// form `at file:line:column` if its location is known.
+fn parse(rendered: &str) -> impl Iterator<Item = Frame<'_>> {
+    let mut lines = rendered.lines().map(str::trim).peekable();
+    std::iter::from_fn(move || loop {
+        let line = lines.next()?;
+        if line.starts_with("at ") {
+            continue;
+        }
+        let symbol = match line.split_once(": ") {
+            Some((index, symbol)) if index.bytes().all(|b| b.is_ascii_digit()) => symbol,
+            _ => line,
+        };
+        let location = lines
+            .peek()
+            .and_then(|line| line.strip_prefix("at "))
+            .and_then(parse_location);
+        return Some(Frame { symbol, location });
+    })
+}
+
+/// Parses a location of the form `file:line:column`.
+fn parse_location(location: &str) -> Option<(&str, u64)> {
+    let (rest, _column) = location.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some((file, line.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owned::OwnedValue;
+
+    #[test]
+    fn parses_frames() {
+        let rendered = "   0: app::load\n             at ./src/main.rs:10:5\n         \
+            app::inlined\n   1: std::rt::lang_start\n";
+        let frames = parse(rendered).collect::<Vec<_>>();
+        assert_eq!(
+            frames,
+            [
+                Frame {
+                    symbol: "app::load",
+                    location: Some(("./src/main.rs", 10)),
+                },
+                Frame {
+                    symbol: "app::inlined",
+                    location: None,
+                },
+                Frame {
+                    symbol: "std::rt::lang_start",
+                    location: None,
+                },
+            ]
+        );
+
+        let backtrace = Backtrace::force_capture();
+        let frames = Frames::new(&backtrace).with_skip(1).with_max_frames(Some(2));
+        let value = OwnedValue::from_value(&Value::borrowed(&frames)).unwrap();
+        match value {
+            OwnedValue::List(frames) => assert_eq!(frames.len(), 2),
+            value => panic!("unexpected value: {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Collecting flat structured values into columns.
+//!
+//! Observability backends which store events columnarly (such as those built
+//! on [Apache Arrow]) write each field of an event to its own column, rather
+//! than storing each event as a whole. [`ColumnarVisit`] transposes visited
+//! records, structs, and maps into a [`Batch`] of typed columns, with one row
+//! for each value visited. The layout of each column (a vector of optional
+//! values together with the column's name) maps directly onto an Arrow
+//! array and field.
+//!
+//! [Apache Arrow]: https://arrow.apache.org/
+//! [`ColumnarVisit`]: struct.ColumnarVisit.html
+//! [`Batch`]: struct.Batch.html
+use crate::{
+    owned::OwnedValue,
+    record::FieldId,
+    value::{Value, ValueExt, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::{collections::HashMap, fmt, mem};
+
+/// A `Visit` implementation that appends each visited record, struct, or
+/// map to a [`Batch`] as a row.
+///
+/// Each top-level field becomes a column, named by the field's name or the
+/// map key. Columns are typed by the first value written to them; a column
+/// holding values of several types is converted into a string column, with
+/// values other than strings (including nested lists, maps, and structs)
+/// written as JSON. Rows that do not have a field have a null value in its
+/// column, as do unit values.
+///
+/// When records constructed from a [`FieldSet`] are visited, a column is
+/// looked up by its [`FieldId`] rather than by name after the first time the
+/// field is seen.
+///
+/// Any value visited which is not a record, struct, or map is ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{columnar::{ColumnData, ColumnarVisit}, value};
+/// let mut visit = ColumnarVisit::new();
+/// value!({ "status": 200u16, "path": "/" }).visit(&mut visit).unwrap();
+/// value!({ "status": 404u16 }).visit(&mut visit).unwrap();
+///
+/// let batch = visit.finish().unwrap();
+/// assert_eq!(batch.num_rows(), 2);
+/// assert_eq!(batch.column("status").unwrap().data(), &ColumnData::Uint(vec![Some(200), Some(404)]));
+/// assert_eq!(batch.column("path").unwrap().data(), &ColumnData::Str(vec![Some("/".to_owned()), None]));
+/// ```
+///
+/// [`Batch`]: struct.Batch.html
+/// [`FieldSet`]: ../record/struct.FieldSet.html
+/// [`FieldId`]: ../record/struct.FieldId.html
+#[derive(Debug, Default)]
+pub struct ColumnarVisit {
+    batch: Batch,
+    /// The index of each column, by field name.
+    by_name: HashMap<String, usize>,
+    /// The index of each column, by `FieldId`.
+    by_id: HashMap<FieldId, usize>,
+    depth: usize,
+}
+
+/// A set of columns of equal length, as collected by a [`ColumnarVisit`].
+///
+/// [`ColumnarVisit`]: struct.ColumnarVisit.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Batch {
+    columns: Vec<Column>,
+    rows: usize,
+}
+
+/// A named column of a [`Batch`].
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    name: String,
+    data: ColumnData,
+}
+
+/// The values of a [`Column`], with `None` representing nulls.
+///
+/// [`Column`]: struct.Column.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnData {
+    /// A column with only null values.
+    Null(usize),
+    /// A column of booleans.
+    Bool(Vec<Option<bool>>),
+    /// A column of unsigned integers.
+    Uint(Vec<Option<u64>>),
+    /// A column of signed integers.
+    Int(Vec<Option<i64>>),
+    /// A column of floats.
+    Float(Vec<Option<f64>>),
+    /// A column of strings.
+    Str(Vec<Option<String>>),
+    /// A column of byte strings.
+    Bytes(Vec<Option<Vec<u8>>>),
+}
+
+// === impl ColumnarVisit ===
+
+impl ColumnarVisit {
+    /// Returns a new `ColumnarVisit` with no columns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the rows collected so far, leaving an empty batch.
+    ///
+    /// Columns which have been seen are remembered, so that later batches
+    /// have the same columns in the same order.
+    pub fn take_batch(&mut self) -> Batch {
+        let columns = self
+            .batch
+            .columns
+            .iter()
+            .map(|column| Column {
+                name: column.name.clone(),
+                data: ColumnData::Null(0),
+            })
+            .collect();
+        mem::replace(&mut self.batch, Batch { columns, rows: 0 })
+    }
+
+    /// Consumes the `ColumnarVisit`, returning the rows it has collected.
+    ///
+    /// # Errors
+    ///
+    /// If a value is still being visited (some container was opened but not
+    /// closed), an error is returned.
+    pub fn finish(self) -> Result<Batch, Error> {
+        if self.depth != 0 {
+            return Err(Error::unbalanced());
+        }
+        Ok(self.batch)
+    }
+
+    fn column(&mut self, name: &str) -> usize {
+        if let Some(&idx) = self.by_name.get(name) {
+            return idx;
+        }
+        let idx = self.batch.columns.len();
+        self.batch.columns.push(Column {
+            name: name.to_owned(),
+            data: ColumnData::Null(self.batch.rows),
+        });
+        self.by_name.insert(name.to_owned(), idx);
+        idx
+    }
+
+    fn push(&mut self, column: usize, value: &Value<'_>) -> VisitResult {
+        let value = OwnedValue::from_value(value)?;
+        let column = &mut self.batch.columns[column];
+        // A field visited more than once in the same row keeps its first
+        // value.
+        if column.data.len() == self.batch.rows {
+            column.data.push(value);
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> VisitResult {
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn close(&mut self) -> VisitResult {
+        self.depth = self.depth.checked_sub(1).ok_or_else(Error::unbalanced)?;
+        if self.depth == 0 {
+            self.batch.rows += 1;
+            let rows = self.batch.rows;
+            for column in &mut self.batch.columns {
+                while column.data.len() < rows {
+                    column.data.push(OwnedValue::Unit);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Visit for ColumnarVisit {
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        if self.depth != 1 {
+            return Ok(());
+        }
+        let name = OwnedValue::from_value(&k)?.into_key();
+        let column = self.column(&name);
+        self.push(column, &v).map_err(|e| e.in_field(&name))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        if self.depth != 1 {
+            return Ok(());
+        }
+        let column = match self.by_id.get(&field) {
+            Some(&column) => column,
+            None => {
+                let column = self.column(field.name());
+                self.by_id.insert(field, column);
+                column
+            }
+        };
+        self.push(column, &value)
+            .map_err(|e| e.in_field(field.name()))
+    }
+
+    fn visit_fmt(&mut self, _: fmt::Arguments<'_>) -> VisitResult {
+        Ok(())
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close()
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close()
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        Ok(())
+    }
+}
+
+// === impl Batch ===
+
+impl Batch {
+    /// Returns the number of rows in this batch.
+    pub fn num_rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the columns of this batch, in the order their fields were
+    /// first seen.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the column named `name`, if there is one.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// Consumes the batch, returning its columns.
+    pub fn into_columns(self) -> Vec<Column> {
+        self.columns
+    }
+}
+
+// === impl Column ===
+
+impl Column {
+    /// Returns the name of this column.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the values in this column.
+    pub fn data(&self) -> &ColumnData {
+        &self.data
+    }
+
+    /// Consumes the column, returning its values.
+    pub fn into_data(self) -> ColumnData {
+        self.data
+    }
+}
+
+// === impl ColumnData ===
+
+impl ColumnData {
+    /// Returns the number of values in this column.
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Null(len) => *len,
+            ColumnData::Bool(vs) => vs.len(),
+            ColumnData::Uint(vs) => vs.len(),
+            ColumnData::Int(vs) => vs.len(),
+            ColumnData::Float(vs) => vs.len(),
+            ColumnData::Str(vs) => vs.len(),
+            ColumnData::Bytes(vs) => vs.len(),
+        }
+    }
+
+    /// Returns `true` if this column has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, value: OwnedValue) {
+        match (&mut *self, value) {
+            (ColumnData::Null(len), OwnedValue::Unit) => *len += 1,
+            (ColumnData::Bool(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Uint(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Int(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Float(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Str(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Bytes(vs), OwnedValue::Unit) => vs.push(None),
+            (ColumnData::Null(len), value) => {
+                let mut data = match value {
+                    OwnedValue::Bool(_) => ColumnData::Bool(Vec::new()),
+                    OwnedValue::Uint(_) => ColumnData::Uint(Vec::new()),
+                    OwnedValue::Int(_) => ColumnData::Int(Vec::new()),
+                    OwnedValue::Float(_) => ColumnData::Float(Vec::new()),
+                    OwnedValue::Bytes(_) => ColumnData::Bytes(Vec::new()),
+                    _ => ColumnData::Str(Vec::new()),
+                };
+                for _ in 0..*len {
+                    data.push(OwnedValue::Unit);
+                }
+                data.push(value);
+                *self = data;
+            }
+            (ColumnData::Bool(vs), OwnedValue::Bool(v)) => vs.push(Some(v)),
+            (ColumnData::Uint(vs), OwnedValue::Uint(v)) => vs.push(Some(v)),
+            (ColumnData::Int(vs), OwnedValue::Int(v)) => vs.push(Some(v)),
+            (ColumnData::Float(vs), OwnedValue::Float(v)) => vs.push(Some(v)),
+            (ColumnData::Str(vs), OwnedValue::Str(v)) => vs.push(Some(v.into_string())),
+            (ColumnData::Bytes(vs), OwnedValue::Bytes(v)) => vs.push(Some(v)),
+            (ColumnData::Str(vs), value) => vs.push(Some(Value::borrowed(&value).render_json())),
+            (_, value) => {
+                // The column's values are of more than one type, so store
+                // all of them as strings.
+                let mut data = ColumnData::Str(Vec::with_capacity(self.len() + 1));
+                for value in mem::replace(self, ColumnData::Null(0)).into_values() {
+                    data.push(value);
+                }
+                data.push(value);
+                *self = data;
+            }
+        }
+    }
+
+    fn into_values(self) -> Vec<OwnedValue> {
+        fn values<T>(vs: Vec<Option<T>>, f: impl Fn(T) -> OwnedValue) -> Vec<OwnedValue> {
+            vs.into_iter()
+                .map(|v| v.map(&f).unwrap_or(OwnedValue::Unit))
+                .collect()
+        }
+        match self {
+            ColumnData::Null(len) => vec![OwnedValue::Unit; len],
+            ColumnData::Bool(vs) => values(vs, OwnedValue::Bool),
+            ColumnData::Uint(vs) => values(vs, OwnedValue::Uint),
+            ColumnData::Int(vs) => values(vs, OwnedValue::Int),
+            ColumnData::Float(vs) => values(vs, OwnedValue::Float),
+            ColumnData::Str(vs) => values(vs, |v| OwnedValue::Str(v.into())),
+            ColumnData::Bytes(vs) => values(vs, OwnedValue::Bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::FieldSet;
+
+    #[test]
+    fn records_to_columns() {
+        static FIELDS: FieldSet = FieldSet::new(&["id", "level"]);
+        let mut visit = ColumnarVisit::new();
+        for (id, level) in [(1, "info"), (2, "warn")].iter() {
+            let values = [Value::borrowed(id), Value::borrowed(level)];
+            FIELDS.record(&values).visit(&mut visit).unwrap();
+        }
+        value!({ "id": "three", "extra": true })
+            .visit(&mut visit)
+            .unwrap();
+
+        let batch = visit.take_batch();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(
+            batch.into_columns(),
+            vec![
+                Column {
+                    name: "id".to_owned(),
+                    data: ColumnData::Str(vec![
+                        Some("1".to_owned()),
+                        Some("2".to_owned()),
+                        Some("three".to_owned()),
+                    ]),
+                },
+                Column {
+                    name: "level".to_owned(),
+                    data: ColumnData::Str(vec![
+                        Some("info".to_owned()),
+                        Some("warn".to_owned()),
+                        None,
+                    ]),
+                },
+                Column {
+                    name: "extra".to_owned(),
+                    data: ColumnData::Bool(vec![None, None, Some(true)]),
+                },
+            ]
+        );
+        assert_eq!(visit.finish().unwrap().columns().len(), 3);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, ErrorMode, Visit, VisitResult},
+};
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// A visitor wrapper that converts panics in the wrapped visitor into
+/// errors.
+///
+/// Each `Visit` method which returns a [`VisitResult`] calls the wrapped
+/// visitor inside [`panic::catch_unwind`], and returns an error (for which
+/// [`Error::is_panic`] returns `true`) if it panics. Since a visitor which
+/// panicked may have been left in an inconsistent state, the `CatchUnwind`
+/// is then poisoned: it never calls the wrapped visitor again, and every
+/// later method returns a panic error without a message. This allows a
+/// producer which visits values with several visitors (such as with a
+/// [`Tee`]) to keep running when one of them misbehaves.
+///
+/// Methods which answer queries (such as [`Visit::wants_value`]) are not
+/// guarded, but return their default answers once the `CatchUnwind` is
+/// poisoned. Catching a panic does not prevent the panic hook from running,
+/// so the panic is still reported as usual. Panics are only caught if the
+/// crate is compiled with `panic = "unwind"`.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::CatchUnwind, value, value::Visitable};
+/// # use valuable::visitor::{Visit, VisitResult};
+/// struct Fragile;
+///
+/// impl Visit for Fragile {
+///     fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+///         panic!("fragile");
+///     }
+/// }
+///
+/// # std::panic::set_hook(Box::new(|_| {}));
+/// let mut visit = CatchUnwind::new(Fragile);
+/// let error = value!(1).visit(&mut visit).unwrap_err();
+/// assert!(error.is_panic());
+/// assert_eq!(error.to_string(), "visitor panicked: fragile");
+/// assert!(visit.is_poisoned());
+/// ```
+///
+/// [`VisitResult`]: ../visitor/type.VisitResult.html
+/// [`panic::catch_unwind`]: https://doc.rust-lang.org/std/panic/fn.catch_unwind.html
+/// [`Error::is_panic`]: ../visitor/struct.Error.html#method.is_panic
+/// [`Tee`]: struct.Tee.html
+/// [`Visit::wants_value`]: ../visitor/trait.Visit.html#method.wants_value
+pub struct CatchUnwind<V> {
+    inner: V,
+    poisoned: bool,
+}
+
+// === impl CatchUnwind ===
+
+impl<V: Visit> CatchUnwind<V> {
+    /// Returns a new `CatchUnwind` wrapping `inner`.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            poisoned: false,
+        }
+    }
+
+    /// Returns `true` if the wrapped visitor has panicked.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `CatchUnwind`, returning the wrapped visitor.
+    ///
+    /// If the `CatchUnwind` is poisoned, the visitor may be in an
+    /// inconsistent state.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    /// Calls `f` with the wrapped visitor, catching any panic.
+    fn guard(&mut self, f: impl FnOnce(&mut V) -> VisitResult) -> VisitResult {
+        if self.poisoned {
+            return Err(Error::panicked(None));
+        }
+        let inner = &mut self.inner;
+        panic::catch_unwind(AssertUnwindSafe(|| f(inner))).unwrap_or_else(|payload| {
+            self.poisoned = true;
+            Err(Error::panicked(message(&*payload)))
+        })
+    }
+}
+
+macro_rules! guarded {
+    ($($method:ident($($arg:ident: $ty:ty),*)),+ $(,)?) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) -> VisitResult {
+                self.guard(|inner| inner.$method($($arg),*))
+            }
+        )+
+    };
+}
+
+impl<V: Visit> Visit for CatchUnwind<V> {
+    guarded! {
+        visit_uint(value: u64),
+        visit_u16(value: u16),
+        visit_u32(value: u32),
+        visit_i8(value: i8),
+        visit_i16(value: i16),
+        visit_i32(value: i32),
+        visit_int(value: i64),
+        visit_float(value: f64),
+        visit_f32(value: f32),
+        visit_str(value: &str),
+        visit_u8(value: u8),
+        visit_bytes(value: &[u8]),
+        visit_bool(value: bool),
+        visit_unit(),
+        visit_kv(k: Value<'_>, v: Value<'_>),
+        visit_field(field: FieldId, value: Value<'_>),
+        visit_named_field(name: &str, value: Value<'_>),
+        visit_fmt(args: fmt::Arguments<'_>),
+        visit_display(value: &dyn fmt::Display),
+        visit_record(record: &Record<'_>),
+        named_type(name: &str),
+        visit_annotation(key: &str, value: &str),
+        open_map(),
+        close_map(),
+        open_list(),
+        close_list(),
+        open_struct(),
+        close_struct(),
+        open_tuple(),
+        close_tuple(),
+        finish(),
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        !self.poisoned && self.inner.wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        if self.poisoned {
+            return ErrorMode::Abort;
+        }
+        self.inner.error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        if self.poisoned {
+            return Capabilities::empty();
+        }
+        self.inner.capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        !self.poisoned && self.inner.keys_only()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for CatchUnwind<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CatchUnwind")
+            .field("inner", &self.inner)
+            .field("poisoned", &self.poisoned)
+            .finish()
+    }
+}
+
+/// Returns the message of a panic, if its payload is a string.
+fn message(payload: &(dyn Any + Send)) -> Option<String> {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+}
@@ -0,0 +1,463 @@
+use crate::{
+    owned::OwnedValue,
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{self, PathSegment, Visit, VisitResult},
+};
+use std::{cell::RefCell, fmt, rc::Rc};
+
+/// A visitor wrapper that tracks where in the visited value the wrapped
+/// visitor currently is.
+///
+/// A `WithContext` records each container it visits, and the key or index of
+/// the value currently being visited inside it, in a shared [`Context`]. The
+/// wrapped visitor may hold a clone of the same `Context` and query it from
+/// any of its methods, rather than tracking the path itself.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::{Context, WithContext}, value, visitor::{Visit, VisitResult}, value::Visitable};
+/// struct Paths {
+///     cx: Context,
+///     paths: Vec<String>,
+/// }
+///
+/// impl Visit for Paths {
+///     fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+///         value.visit(self)
+///     }
+///
+///     fn visit_str(&mut self, _: &str) -> VisitResult {
+///         self.paths.push(self.cx.to_string());
+///         Ok(())
+///     }
+///     # fn visit_kv(&mut self, _: valuable::value::Value<'_>, v: valuable::value::Value<'_>) -> VisitResult { v.visit(self) }
+///     # fn open_map(&mut self) -> VisitResult { Ok(()) }
+///     # fn close_map(&mut self) -> VisitResult { Ok(()) }
+///     # fn open_list(&mut self) -> VisitResult { Ok(()) }
+///     # fn close_list(&mut self) -> VisitResult { Ok(()) }
+/// }
+///
+/// let cx = Context::new();
+/// let mut visit = WithContext::new(Paths { cx: cx.clone(), paths: Vec::new() }, cx);
+/// value!({ "user": { "names": ["a", "b"] } }).visit(&mut visit).unwrap();
+/// assert_eq!(visit.into_inner().paths, vec!["user.names[0]", "user.names[1]"]);
+/// ```
+///
+/// [`Context`]: struct.Context.html
+pub struct WithContext<V> {
+    inner: V,
+    cx: Context,
+}
+
+/// A shared handle to the position of a [`WithContext`] in the value it is
+/// visiting.
+///
+/// Cloning a `Context` returns a new handle to the same position. A
+/// `Context`'s `Display` implementation formats its [`path`] in the form
+/// `a.b[3]`.
+///
+/// [`WithContext`]: struct.WithContext.html
+/// [`path`]: #method.path
+#[derive(Clone, Default)]
+pub struct Context {
+    frames: Rc<RefCell<Vec<Frame>>>,
+}
+
+/// A container enclosing the value currently being visited, as returned by
+/// [`Context::frames`].
+///
+/// [`Context::frames`]: struct.Context.html#method.frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    kind: ContainerKind,
+    segment: Option<PathSegment>,
+    len: usize,
+}
+
+/// The kinds of container tracked by a [`Context`].
+///
+/// [`Context`]: struct.Context.html
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContainerKind {
+    /// A map.
+    Map,
+    /// A list.
+    List,
+    /// A struct.
+    Struct,
+    /// A tuple (or tuple struct).
+    Tuple,
+    /// A record.
+    Record,
+}
+
+/// A `Visitable` that visits a nested value through a `WithContext`,
+/// recording the key it is the value of, if any.
+struct Nested<'a> {
+    value: Value<'a>,
+    cx: Context,
+    segment: Option<PathSegment>,
+}
+
+// === impl WithContext ===
+
+impl<V: Visit> WithContext<V> {
+    /// Returns a new `WithContext` wrapping `inner`, which records its
+    /// position in `cx`.
+    pub fn new(inner: V, cx: Context) -> Self {
+        Self { inner, cx }
+    }
+
+    /// Returns a handle to the context this `WithContext` records its
+    /// position in.
+    pub fn context(&self) -> &Context {
+        &self.cx
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `WithContext`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    /// Records that a new value is being visited, which is the next element
+    /// of the enclosing list or tuple, if there is one.
+    fn element(&self) {
+        if let Some(frame) = self.cx.frames.borrow_mut().last_mut() {
+            if let ContainerKind::List | ContainerKind::Tuple = frame.kind {
+                frame.segment = Some(PathSegment::Index(frame.len));
+                frame.len += 1;
+            }
+        }
+    }
+
+    fn open(&self, kind: ContainerKind) {
+        self.element();
+        self.cx.frames.borrow_mut().push(Frame {
+            kind,
+            segment: None,
+            len: 0,
+        });
+    }
+
+    fn close(&self) {
+        self.cx.frames.borrow_mut().pop();
+    }
+
+    fn nested<'a>(&self, value: Value<'a>, segment: Option<PathSegment>) -> Nested<'a> {
+        Nested {
+            value,
+            cx: self.cx.clone(),
+            segment,
+        }
+    }
+}
+
+macro_rules! elements {
+    ($($method:ident($($ty:ty)?)),+ $(,)?) => {
+        $(
+            elements!(@method $method($($ty)?));
+        )+
+    };
+    (@method $method:ident($ty:ty)) => {
+        fn $method(&mut self, value: $ty) -> VisitResult {
+            self.element();
+            self.inner.$method(value)
+        }
+    };
+    (@method $method:ident()) => {
+        fn $method(&mut self) -> VisitResult {
+            self.element();
+            self.inner.$method()
+        }
+    };
+}
+
+macro_rules! containers {
+    ($($open:ident, $close:ident => $kind:ident),+ $(,)?) => {
+        $(
+            fn $open(&mut self) -> VisitResult {
+                self.open(ContainerKind::$kind);
+                self.inner.$open()
+            }
+
+            fn $close(&mut self) -> VisitResult {
+                self.close();
+                self.inner.$close()
+            }
+        )+
+    };
+}
+
+impl<V: Visit> Visit for WithContext<V> {
+    elements! {
+        visit_uint(u64),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_int(i64),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_float(f64),
+        visit_f32(f32),
+        visit_str(&str),
+        visit_bytes(&[u8]),
+        visit_bool(bool),
+        visit_fmt(fmt::Arguments<'_>),
+        visit_display(&dyn fmt::Display),
+        visit_unit(),
+    }
+
+    containers! {
+        open_map, close_map => Map,
+        open_list, close_list => List,
+        open_struct, close_struct => Struct,
+        open_tuple, close_tuple => Tuple,
+    }
+
+    forward_visit! {
+        inner =>
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        let key = OwnedValue::from_value(&k)?.into_key();
+        let k = self.nested(k, None);
+        let v = self.nested(v, Some(PathSegment::Field(key)));
+        self.inner
+            .visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        let segment = PathSegment::Field(field.name().to_owned());
+        let value = self.nested(value, Some(segment));
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let value = self.nested(value, Some(PathSegment::Field(name.to_owned())));
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.open(ContainerKind::Record);
+        let mut names = record.iter().map(|(name, _)| name);
+        let cx = &self.cx;
+        let inner = &mut self.inner;
+        let result = record.map_values(
+            |value| Nested {
+                value,
+                cx: cx.clone(),
+                segment: names.next().map(|name| PathSegment::Field(name.to_owned())),
+            },
+            |record| inner.visit_record(record),
+        );
+        self.close();
+        result
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for WithContext<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithContext")
+            .field("inner", &self.inner)
+            .field("cx", &self.cx)
+            .finish()
+    }
+}
+
+// === impl Context ===
+
+impl Context {
+    /// Returns a new `Context`, positioned at the root of a value.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of containers enclosing the value currently being
+    /// visited.
+    pub fn depth(&self) -> usize {
+        self.frames.borrow().len()
+    }
+
+    /// Returns the path from the root of the visited value to the value
+    /// currently being visited.
+    ///
+    /// Containers whose contents are not being visited (such as a map whose
+    /// key, rather than value, is being visited) do not contribute to the
+    /// path.
+    pub fn path(&self) -> Vec<PathSegment> {
+        self.frames
+            .borrow()
+            .iter()
+            .filter_map(|frame| frame.segment.clone())
+            .collect()
+    }
+
+    /// Returns the kind of the innermost container enclosing the value
+    /// currently being visited, if there is one.
+    pub fn container(&self) -> Option<ContainerKind> {
+        self.frames.borrow().last().map(|frame| frame.kind)
+    }
+
+    /// Calls `f` with the containers enclosing the value currently being
+    /// visited, from the outermost inwards.
+    pub fn frames<T>(&self, f: impl FnOnce(&[Frame]) -> T) -> T {
+        f(&self.frames.borrow())
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frames = self.frames.borrow();
+        visitor::fmt_segments(frames.iter().filter_map(|f| f.segment.as_ref()), f)
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Context")
+            .field(&&*self.frames.borrow())
+            .finish()
+    }
+}
+
+// === impl Frame ===
+
+impl Frame {
+    /// Returns the kind of this container.
+    pub fn kind(&self) -> ContainerKind {
+        self.kind
+    }
+
+    /// Returns the key or index of the value currently being visited inside
+    /// this container, if its contents are being visited.
+    pub fn segment(&self) -> Option<&PathSegment> {
+        self.segment.as_ref()
+    }
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if let Some(frame) = self.cx.frames.borrow_mut().last_mut() {
+            frame.segment = self.segment.clone();
+        }
+        self.value.visit(&mut WithContext {
+            inner: visitor,
+            cx: self.cx.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{owned::OwnedValue, record::FieldSet};
+
+    #[derive(Default)]
+    struct Paths {
+        cx: Context,
+        paths: Vec<String>,
+    }
+
+    impl Visit for Paths {
+        fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+            value.visit(self)
+        }
+
+        fn visit_uint(&mut self, _: u64) -> VisitResult {
+            self.paths
+                .push(format!("{} in {:?}", self.cx, self.cx.container()));
+            Ok(())
+        }
+
+        fn visit_str(&mut self, _: &str) -> VisitResult {
+            Ok(())
+        }
+
+        fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+            k.visit(self)?;
+            v.visit(self)
+        }
+
+        fn open_map(&mut self) -> VisitResult {
+            Ok(())
+        }
+
+        fn close_map(&mut self) -> VisitResult {
+            Ok(())
+        }
+
+        fn open_struct(&mut self) -> VisitResult {
+            Ok(())
+        }
+
+        fn close_struct(&mut self) -> VisitResult {
+            Ok(())
+        }
+
+        fn open_tuple(&mut self) -> VisitResult {
+            Ok(())
+        }
+
+        fn close_tuple(&mut self) -> VisitResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tracks_paths() {
+        static FIELDS: FieldSet = FieldSet::new(&["a", "b"]);
+        let point = OwnedValue::Struct {
+            name: "Point".to_owned(),
+            fields: vec![(
+                "xy".to_owned(),
+                OwnedValue::Tuple(vec![OwnedValue::Uint(1), OwnedValue::Uint(2)]),
+            )],
+        };
+        let values = [Value::borrowed(&point), Value::borrowed(&3u64)];
+
+        let cx = Context::new();
+        let mut visit = WithContext::new(
+            Paths {
+                cx: cx.clone(),
+                ..Paths::default()
+            },
+            cx.clone(),
+        );
+        FIELDS.record(&values).visit(&mut visit).unwrap();
+        assert_eq!(
+            visit.into_inner().paths,
+            vec![
+                "a.xy[0] in Some(Tuple)",
+                "a.xy[1] in Some(Tuple)",
+                "b in Some(Record)"
+            ]
+        );
+        assert_eq!(cx.depth(), 0);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::{cell::Cell, fmt};
+
+/// A visitor wrapper that detects cycles in the visited value.
+///
+/// Values which may be shared, such as the targets of `Arc`s and `Rc`s, are
+/// visited using [`Visit::visit_any`]. A `CycleGuard` tracks the addresses
+/// of the values passed to `visit_any` that are currently being visited. If
+/// a value is reached again while it is still being visited (i.e. the value
+/// contains itself), the guard visits the placeholder string `"<cycle>"`
+/// instead of recursing into the value again, which would otherwise never
+/// terminate.
+///
+/// Values are identified only by their address, and only values reached
+/// through `visit_any` are tracked.
+///
+/// [`Visit::visit_any`]: ../visitor/trait.Visit.html#tymethod.visit_any
+pub struct CycleGuard<V> {
+    inner: V,
+    /// The addresses of the values passed to `visit_any` which enclose the
+    /// value currently being visited.
+    ///
+    /// This is a `Cell` so that it can be temporarily moved into the guards
+    /// wrapping nested key-value pairs.
+    ancestors: Cell<Vec<usize>>,
+}
+
+/// A `Visitable` that visits a nested value through a `CycleGuard`, sharing
+/// the enclosing guard's ancestors.
+struct Nested<'a> {
+    value: Value<'a>,
+    ancestors: &'a Cell<Vec<usize>>,
+}
+
+/// The placeholder visited in place of a value that contains itself.
+const PLACEHOLDER: &str = "<cycle>";
+
+// === impl CycleGuard ===
+
+impl<V: Visit> CycleGuard<V> {
+    /// Returns a new `CycleGuard` wrapping `inner`.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            ancestors: Cell::new(Vec::new()),
+        }
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `CycleGuard`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V: Visit> Visit for CycleGuard<V> {
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        let addr = value as *const dyn Visitable as *const () as usize;
+        if self.ancestors.get_mut().contains(&addr) {
+            return self.inner.visit_str(PLACEHOLDER);
+        }
+        self.ancestors.get_mut().push(addr);
+        let result = value.visit(self);
+        self.ancestors.get_mut().pop();
+        result
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        // The wrapped visitor will visit the key and value itself, so wrap
+        // them so that any nested values are still visited through a guard.
+        let ancestors = &self.ancestors;
+        let k = Nested {
+            value: k,
+            ancestors,
+        };
+        let v = Nested {
+            value: v,
+            ancestors,
+        };
+        self.inner
+            .visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        let value = Nested {
+            value,
+            ancestors: &self.ancestors,
+        };
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let value = Nested {
+            value,
+            ancestors: &self.ancestors,
+        };
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        let (inner, ancestors) = (&mut self.inner, &self.ancestors);
+        record.map_values(
+            |value| Nested { value, ancestors },
+            |record| inner.visit_record(record),
+        )
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for CycleGuard<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CycleGuard")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        // The enclosing guard is suspended while its wrapped visitor visits
+        // this value, so its ancestors can be moved into the nested guard and
+        // restored afterwards.
+        let mut guard = CycleGuard {
+            inner: visitor,
+            ancestors: Cell::new(self.ancestors.take()),
+        };
+        let result = self.value.visit(&mut guard);
+        self.ancestors.set(guard.ancestors.into_inner());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::JsonVisit;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Node {
+        name: &'static str,
+        children: RefCell<Vec<Rc<Node>>>,
+    }
+
+    impl Visitable for Node {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            let children = self.children.borrow();
+            let children = Value::borrowed(&*children);
+            visitor.visit_struct(
+                "Node",
+                vec![
+                    ("name", Value::borrowed(&self.name)),
+                    ("children", children),
+                ],
+            )
+        }
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let leaf = Rc::new(Node {
+            name: "leaf",
+            children: RefCell::new(Vec::new()),
+        });
+        let root = Rc::new(Node {
+            name: "root",
+            children: RefCell::new(vec![leaf.clone(), leaf]),
+        });
+        root.children.borrow_mut().push(root.clone());
+
+        let mut guard = CycleGuard::new(JsonVisit::new(String::new()));
+        root.visit(&mut guard).unwrap();
+        assert_eq!(
+            guard.into_inner().into_inner(),
+            "{\"name\":\"root\",\"children\":[\
+                {\"name\":\"leaf\",\"children\":[]},\
+                {\"name\":\"leaf\",\"children\":[]},\
+                \"<cycle>\"\
+            ]}"
+        );
+
+        // Break the cycle so the nodes can be dropped.
+        root.children.borrow_mut().clear();
+    }
+}
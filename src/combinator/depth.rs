@@ -0,0 +1,247 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor wrapper that limits how deeply nested the visited value may
+/// be.
+///
+/// Each visit of a nested map, list, struct, tuple, or record recurses, so
+/// visiting a sufficiently deep value may overflow the stack. A `DepthLimit`
+/// counts how many containers enclose the value currently being visited, and
+/// returns an error rather than opening a container that would exceed the
+/// maximum depth. The error propagates out of the visit like any other, so
+/// the stack unwinds cleanly.
+pub struct DepthLimit<V> {
+    inner: V,
+    depth: usize,
+    max_depth: usize,
+}
+
+/// A `Visitable` that visits a nested value through a `DepthLimit` at the
+/// enclosing limit's depth.
+struct Nested<'a> {
+    value: Value<'a>,
+    depth: usize,
+    max_depth: usize,
+}
+
+// === impl DepthLimit ===
+
+impl<V: Visit> DepthLimit<V> {
+    /// Returns a new `DepthLimit` wrapping `inner`, which allows at most
+    /// `max_depth` nested containers.
+    pub fn new(inner: V, max_depth: usize) -> Self {
+        Self {
+            inner,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    /// Returns the maximum nesting depth allowed by this `DepthLimit`.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `DepthLimit`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    fn enter(&mut self) -> VisitResult {
+        if self.depth >= self.max_depth {
+            return Err(Error::depth_limit(self.max_depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    fn nested<'a>(&self, value: Value<'a>) -> Nested<'a> {
+        Nested {
+            value,
+            depth: self.depth,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl<V: Visit> Visit for DepthLimit<V> {
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        // The wrapped visitor will visit the key and value itself, so wrap
+        // them so that any nested containers are still counted.
+        let k = self.nested(k);
+        let v = self.nested(v);
+        self.inner
+            .visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        let value = self.nested(value);
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let value = self.nested(value);
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        // A record's fields are nested one level deeper than the record.
+        self.enter()?;
+        let (depth, max_depth) = (self.depth, self.max_depth);
+        self.exit();
+        let inner = &mut self.inner;
+        record.map_values(
+            |value| Nested {
+                value,
+                depth,
+                max_depth,
+            },
+            |record| inner.visit_record(record),
+        )
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.enter()?;
+        self.inner.open_map()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.exit();
+        self.inner.close_map()
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.enter()?;
+        self.inner.open_list()
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.exit();
+        self.inner.close_list()
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.enter()?;
+        self.inner.open_struct()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.exit();
+        self.inner.close_struct()
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.enter()?;
+        self.inner.open_tuple()
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.exit();
+        self.inner.close_tuple()
+    }
+
+    fn finish(&mut self) -> VisitResult {
+        self.inner.finish()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for DepthLimit<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DepthLimit")
+            .field("inner", &self.inner)
+            .field("depth", &self.depth)
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let mut limit = DepthLimit {
+            inner: visitor,
+            depth: self.depth,
+            max_depth: self.max_depth,
+        };
+        self.value.visit(&mut limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, owned::OwnedValue};
+
+    fn nested(depth: usize) -> OwnedValue {
+        (0..depth).fold(OwnedValue::Uint(1), |value, i| {
+            if i % 2 == 0 {
+                OwnedValue::List(vec![value])
+            } else {
+                OwnedValue::Map(vec![(OwnedValue::Str("a".into()), value)])
+            }
+        })
+    }
+
+    #[test]
+    fn limits_depth() {
+        let mut limit = DepthLimit::new(JsonVisit::new(String::new()), 4);
+        nested(4).visit(&mut limit).unwrap();
+        assert_eq!(limit.into_inner().into_inner(), r#"{"a":[{"a":[1]}]}"#);
+
+        let mut limit = DepthLimit::new(JsonVisit::new(String::new()), 4);
+        let err = nested(5).visit(&mut limit).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "exceeded maximum nesting depth of 4 at `[0].a[0].a`"
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Capabilities, ErrorMode, Visit, VisitResult},
+};
+use std::{
+    cell::Cell,
+    convert::TryFrom,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// A visitor wrapper that records how many times each `Visit` method is
+/// called, and how long the calls take.
+///
+/// Calls made while visiting nested values (such as the values of map
+/// entries, which the wrapped visitor visits itself) are recorded too, so
+/// the report covers every call made while visiting a value. The time
+/// recorded for a method includes the time spent in any calls it makes in
+/// turn: for example, the time of a `visit_kv` call includes the time taken
+/// to visit its value. The [`report`] may be read after visiting one or more
+/// values.
+///
+/// This type requires the `instrument` feature.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::InstrumentedVisit, json::JsonVisit, value};
+/// let mut visit = InstrumentedVisit::new(JsonVisit::new(String::new()));
+/// value!({ "user": { "name": "alice" }, "tags": ["a", "b"] })
+///     .visit(&mut visit)
+///     .unwrap();
+///
+/// let report = visit.report();
+/// // Three keys and three strings.
+/// assert_eq!(report.get("visit_str").unwrap().calls(), 6);
+/// assert_eq!(report.get("open_map").unwrap().calls(), 2);
+/// println!("{}", report);
+/// ```
+///
+/// [`report`]: #method.report
+pub struct InstrumentedVisit<V> {
+    inner: V,
+    stats: Stats,
+}
+
+/// The calls recorded by an [`InstrumentedVisit`].
+///
+/// The report's `Display` output is a table of the methods which were
+/// called, with their call counts and total times.
+///
+/// [`InstrumentedVisit`]: struct.InstrumentedVisit.html
+#[derive(Clone, Debug)]
+pub struct Report {
+    methods: Vec<MethodStats>,
+}
+
+/// The calls recorded for a single `Visit` method.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MethodStats {
+    name: &'static str,
+    calls: u64,
+    total: Duration,
+}
+
+/// Calls through an `InstrumentedVisit`, or through a nested value it
+/// wrapped, which record into the same `Stats`.
+struct Instrumented<'a, V> {
+    inner: V,
+    stats: &'a Stats,
+}
+
+/// A `Visitable` that visits a nested value through an `Instrumented`.
+struct Nested<'a> {
+    value: Value<'a>,
+    stats: &'a Stats,
+}
+
+macro_rules! methods {
+    ($($method:ident),+ $(,)?) => {
+        /// A `Visit` method which is instrumented.
+        #[allow(non_camel_case_types)]
+        #[derive(Copy, Clone)]
+        enum Method {
+            $($method),+
+        }
+
+        const METHODS: &[&str] = &[$(stringify!($method)),+];
+    };
+}
+
+methods! {
+    visit_uint,
+    visit_int,
+    visit_float,
+    visit_u8,
+    visit_u16,
+    visit_u32,
+    visit_i8,
+    visit_i16,
+    visit_i32,
+    visit_f32,
+    visit_str,
+    visit_bytes,
+    visit_bool,
+    visit_unit,
+    visit_any,
+    visit_kv,
+    visit_field,
+    visit_named_field,
+    visit_fmt,
+    visit_display,
+    visit_record,
+    named_type,
+    visit_annotation,
+    open_map,
+    close_map,
+    open_list,
+    close_list,
+    open_struct,
+    close_struct,
+    open_tuple,
+    close_tuple,
+    finish,
+}
+
+struct Stats {
+    calls: Vec<Cell<u64>>,
+    total: Vec<Cell<Duration>>,
+}
+
+// === impl InstrumentedVisit ===
+
+impl<V: Visit> InstrumentedVisit<V> {
+    /// Returns a new `InstrumentedVisit` wrapping `inner`.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            stats: Stats::new(),
+        }
+    }
+
+    /// Returns the calls recorded so far.
+    pub fn report(&self) -> Report {
+        self.stats.report()
+    }
+
+    /// Discards the calls recorded so far.
+    pub fn reset(&mut self) {
+        self.stats = Stats::new();
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `InstrumentedVisit`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    fn instrumented(&mut self) -> Instrumented<'_, &mut V> {
+        Instrumented {
+            inner: &mut self.inner,
+            stats: &self.stats,
+        }
+    }
+}
+
+impl<V: Visit> Visit for InstrumentedVisit<V> {
+    forward_visit! {
+        instrumented() =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_kv,
+            visit_field,
+            visit_named_field,
+            visit_fmt,
+            visit_display,
+            visit_record,
+            named_type,
+            visit_annotation,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        self.instrumented().visit_any(value)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.inner.wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        self.inner.error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        self.inner.keys_only()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for InstrumentedVisit<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentedVisit")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// === impl Instrumented ===
+
+impl<V: Visit> Instrumented<'_, V> {
+    fn time<R>(&mut self, method: Method, f: impl FnOnce(&mut V, &Stats) -> R) -> R {
+        let start = Instant::now();
+        let result = f(&mut self.inner, self.stats);
+        self.stats.record(method, start.elapsed());
+        result
+    }
+}
+
+macro_rules! timed {
+    ($($method:ident($($arg:ident: $ty:ty),*)),+ $(,)?) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) -> VisitResult {
+                self.time(Method::$method, |inner, _| inner.$method($($arg),*))
+            }
+        )+
+    };
+}
+
+impl<V: Visit> Visit for Instrumented<'_, V> {
+    timed! {
+        visit_uint(value: u64),
+        visit_int(value: i64),
+        visit_float(value: f64),
+        visit_u8(value: u8),
+        visit_u16(value: u16),
+        visit_u32(value: u32),
+        visit_i8(value: i8),
+        visit_i16(value: i16),
+        visit_i32(value: i32),
+        visit_f32(value: f32),
+        visit_str(value: &str),
+        visit_bytes(value: &[u8]),
+        visit_bool(value: bool),
+        visit_unit(),
+        visit_fmt(args: fmt::Arguments<'_>),
+        visit_display(value: &dyn fmt::Display),
+        named_type(name: &str),
+        visit_annotation(key: &str, value: &str),
+        open_map(),
+        close_map(),
+        open_list(),
+        close_list(),
+        open_struct(),
+        close_struct(),
+        open_tuple(),
+        close_tuple(),
+        finish(),
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        let start = Instant::now();
+        let result = value.visit(self);
+        self.stats.record(Method::visit_any, start.elapsed());
+        result
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        // The wrapped visitor will visit the key and value itself, so wrap
+        // them so that the calls made while visiting them are recorded.
+        self.time(Method::visit_kv, |inner, stats| {
+            let k = Nested { value: k, stats };
+            let v = Nested { value: v, stats };
+            inner.visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+        })
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        self.time(Method::visit_field, |inner, stats| {
+            let value = Nested { value, stats };
+            inner.visit_field(field, Value::borrowed(&value))
+        })
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        self.time(Method::visit_named_field, |inner, stats| {
+            let value = Nested { value, stats };
+            inner.visit_named_field(name, Value::borrowed(&value))
+        })
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.time(Method::visit_record, |inner, stats| {
+            record.map_values(
+                |value| Nested { value, stats },
+                |record| inner.visit_record(record),
+            )
+        })
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.inner.wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        self.inner.error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        self.inner.keys_only()
+    }
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut Instrumented {
+            inner: visitor,
+            stats: self.stats,
+        })
+    }
+}
+
+// === impl Stats ===
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            calls: METHODS.iter().map(|_| Cell::new(0)).collect(),
+            total: METHODS.iter().map(|_| Cell::new(Duration::ZERO)).collect(),
+        }
+    }
+
+    fn record(&self, method: Method, elapsed: Duration) {
+        let idx = method as usize;
+        self.calls[idx].set(self.calls[idx].get() + 1);
+        self.total[idx].set(self.total[idx].get() + elapsed);
+    }
+
+    fn report(&self) -> Report {
+        let methods = METHODS
+            .iter()
+            .zip(&self.calls)
+            .zip(&self.total)
+            .map(|((&name, calls), total)| MethodStats {
+                name,
+                calls: calls.get(),
+                total: total.get(),
+            })
+            .collect();
+        Report { methods }
+    }
+}
+
+// === impl Report ===
+
+impl Report {
+    /// Returns the calls recorded for the `Visit` method named `method`
+    /// (such as `"visit_str"`), if there is such a method.
+    pub fn get(&self, method: &str) -> Option<MethodStats> {
+        self.methods.iter().find(|m| m.name == method).copied()
+    }
+
+    /// Returns an iterator over the calls recorded for each method, in the
+    /// order the methods are declared in `Visit`.
+    ///
+    /// Methods which were not called are included, with no calls.
+    pub fn iter(&self) -> impl Iterator<Item = MethodStats> + '_ {
+        self.methods.iter().copied()
+    }
+
+    /// Returns the total number of calls recorded.
+    pub fn total_calls(&self) -> u64 {
+        self.methods.iter().map(|m| m.calls).sum()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<14} {:>10} {:>14}", "method", "calls", "total")?;
+        for method in self.methods.iter().filter(|m| m.calls > 0) {
+            write!(
+                f,
+                "\n{:<14} {:>10} {:>14}",
+                method.name,
+                method.calls,
+                format!("{:?}", method.total)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// === impl MethodStats ===
+
+impl MethodStats {
+    /// Returns the name of the method.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the number of times the method was called.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Returns the total time spent in calls to the method, including any
+    /// calls they made in turn.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the mean time spent in each call to the method, or zero if it
+    /// was not called.
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total / calls,
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.calls as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, record::FieldSet};
+
+    #[test]
+    fn counts_nested_calls() {
+        static FIELDS: FieldSet = FieldSet::new(&["a", "b"]);
+        let list = vec!["x", "y"];
+        let values = [Value::borrowed(&1), Value::borrowed(&list)];
+        let mut visit = InstrumentedVisit::new(JsonVisit::new(String::new()));
+        FIELDS.record(&values).visit(&mut visit).unwrap();
+        assert_eq!(visit.get_ref().get_ref(), r#"{"a":1,"b":["x","y"]}"#);
+
+        let report = visit.report();
+        assert_eq!(report.get("visit_record").unwrap().calls(), 1);
+        assert_eq!(report.get("visit_str").unwrap().calls(), 2);
+        assert_eq!(report.get("open_list").unwrap().calls(), 1);
+        assert!(report.get("visit_nothing").is_none());
+
+        visit.reset();
+        assert_eq!(visit.report().total_calls(), 0);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor wrapper that visits only the keys and field names of values.
+///
+/// A `KeysOnly` forwards the structure of each visited value (its maps,
+/// lists, structs, and tuples, their keys and field names, and type names)
+/// to the wrapped visitor, but replaces every leaf value with a unit value.
+/// This is useful for visitors which register schemas, or check filters
+/// before deciding whether to serialize a value.
+///
+/// A `KeysOnly` also returns `true` from [`Visit::keys_only`], so that
+/// producers which cooperate skip computing values entirely, rather than
+/// computing values which will be replaced.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::KeysOnly, json::JsonVisit, value, visitor::Visit};
+/// let mut visit = KeysOnly::new(JsonVisit::new(String::new()));
+/// let dyn_visit: &mut dyn Visit = &mut visit;
+///
+/// value!({ "id": 1, "tags": ["a"] }).visit(dyn_visit).unwrap();
+/// dyn_visit.open_map().unwrap();
+/// dyn_visit.visit_kv_lazy("report", || -> String { unreachable!() }).unwrap();
+/// dyn_visit.close_map().unwrap();
+/// assert_eq!(
+///     visit.into_inner().into_inner(),
+///     r#"{"id":null,"tags":[null]}{"report":null}"#
+/// );
+/// ```
+///
+/// [`Visit::keys_only`]: ../visitor/trait.Visit.html#method.keys_only
+pub struct KeysOnly<V> {
+    inner: V,
+}
+
+/// A `Visitable` that visits a nested value through a `KeysOnly`.
+struct Nested<'a> {
+    value: Value<'a>,
+}
+
+// === impl KeysOnly ===
+
+impl<V: Visit> KeysOnly<V> {
+    /// Returns a new `KeysOnly` wrapping `inner`.
+    pub fn new(inner: V) -> Self {
+        Self { inner }
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `KeysOnly`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+macro_rules! leaves {
+    ($($method:ident($($ty:ty)?)),+ $(,)?) => {
+        $(
+            fn $method(&mut self $(, _: $ty)?) -> VisitResult {
+                self.inner.visit_unit()
+            }
+        )+
+    };
+}
+
+impl<V: Visit> Visit for KeysOnly<V> {
+    forward_visit! {
+        inner =>
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    leaves! {
+        visit_uint(u64),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_int(i64),
+        visit_float(f64),
+        visit_f32(f32),
+        visit_str(&str),
+        visit_u8(u8),
+        visit_bytes(&[u8]),
+        visit_bool(bool),
+        visit_unit(),
+        visit_fmt(fmt::Arguments<'_>),
+        visit_display(&dyn fmt::Display),
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        // Keys are visited as-is, and only the value is wrapped.
+        let v = Nested { value: v };
+        self.inner.visit_kv(k, Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        let value = Nested { value };
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let value = Nested { value };
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        let inner = &mut self.inner;
+        record.map_values(|value| Nested { value }, |record| inner.visit_record(record))
+    }
+
+    fn keys_only(&self) -> bool {
+        true
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for KeysOnly<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeysOnly")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut KeysOnly { inner: visitor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::JsonVisit;
+
+    type Field<'a> = (&'a str, &'a dyn Fn(&mut dyn Visit) -> VisitResult);
+
+    #[test]
+    fn skips_lazy_fields() {
+        let mut visit = KeysOnly::new(JsonVisit::new(String::new()));
+        let id = |v: &mut dyn Visit| v.visit_uint(7);
+        let report = |_: &mut dyn Visit| -> VisitResult { unreachable!() };
+        let fields: [Field<'_>; 2] = [("id", &id), ("report", &report)];
+        (&mut visit as &mut dyn Visit)
+            .visit_struct_lazy("Job", fields)
+            .unwrap();
+
+        let pairs = [("a", value!({ "b": 1.5 }))];
+        Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+        assert_eq!(
+            visit.into_inner().into_inner(),
+            r#"{"id":null,"report":null}{"a":{"b":null}}"#
+        );
+    }
+}
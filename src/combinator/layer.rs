@@ -0,0 +1,205 @@
+use super::{Context, CycleGuard, DepthLimit, MaybeVisit, WithContext};
+use crate::visitor::Visit;
+use std::fmt;
+
+pub use valuable_core::layer::VisitLayer;
+
+/// A layer which returns the wrapped visitor unchanged.
+///
+/// This is the starting point for building a [`Stack`] of layers which is
+/// applied to a visitor later.
+///
+/// [`Stack`]: struct.Stack.html
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Identity {
+    _p: (),
+}
+
+/// Two layers applied in order: `inner` wraps the visitor first, and
+/// `outer` wraps the result.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{
+/// #     combinator::{CycleGuardLayer, DepthLimitLayer, Identity, VisitLayer},
+/// #     json::JsonVisit,
+/// #     value::Value,
+/// # };
+/// let layers = Identity::new()
+///     .with(CycleGuardLayer::new())
+///     .with(DepthLimitLayer::new(2));
+///
+/// let mut visit = layers.layer(JsonVisit::new(String::new()));
+/// assert!(Value::borrowed(&vec![vec![vec![1]]]).visit(&mut visit).is_err());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+/// A layer which wraps visitors in a [`DepthLimit`].
+///
+/// [`DepthLimit`]: struct.DepthLimit.html
+#[derive(Copy, Clone, Debug)]
+pub struct DepthLimitLayer {
+    max_depth: usize,
+}
+
+/// A layer which wraps visitors in a [`CycleGuard`].
+///
+/// [`CycleGuard`]: struct.CycleGuard.html
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CycleGuardLayer {
+    _p: (),
+}
+
+/// A layer which wraps visitors in a [`WithContext`].
+///
+/// [`WithContext`]: struct.WithContext.html
+#[derive(Clone, Debug)]
+pub struct ContextLayer {
+    cx: Context,
+}
+
+/// A layer which wraps visitors in a [`MaybeVisit`].
+///
+/// [`MaybeVisit`]: struct.MaybeVisit.html
+#[derive(Copy, Clone)]
+pub struct MaybeVisitLayer<F> {
+    wants: F,
+}
+
+// === impl Identity ===
+
+impl Identity {
+    /// Returns a new `Identity` layer.
+    pub fn new() -> Self {
+        Self { _p: () }
+    }
+
+    /// Returns a stack of layers which applies `outer` after this layer.
+    pub fn with<Outer>(self, outer: Outer) -> Stack<Self, Outer> {
+        Stack::new(self, outer)
+    }
+}
+
+impl<V: Visit> VisitLayer<V> for Identity {
+    type Visit = V;
+
+    fn layer(self, inner: V) -> V {
+        inner
+    }
+}
+
+// === impl Stack ===
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Returns a new `Stack` which applies `inner`, and then `outer`.
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+
+    /// Returns a stack of layers which applies `outer` after the layers in
+    /// this stack.
+    pub fn with<L>(self, outer: L) -> Stack<Self, L> {
+        Stack::new(self, outer)
+    }
+}
+
+impl<V, Inner, Outer> VisitLayer<V> for Stack<Inner, Outer>
+where
+    Inner: VisitLayer<V>,
+    Outer: VisitLayer<Inner::Visit>,
+{
+    type Visit = Outer::Visit;
+
+    fn layer(self, inner: V) -> Self::Visit {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+// === impl DepthLimitLayer ===
+
+impl DepthLimitLayer {
+    /// Returns a new `DepthLimitLayer`, which allows at most `max_depth`
+    /// nested containers.
+    pub fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl<V: Visit> VisitLayer<V> for DepthLimitLayer {
+    type Visit = DepthLimit<V>;
+
+    fn layer(self, inner: V) -> Self::Visit {
+        DepthLimit::new(inner, self.max_depth)
+    }
+}
+
+// === impl CycleGuardLayer ===
+
+impl CycleGuardLayer {
+    /// Returns a new `CycleGuardLayer`.
+    pub fn new() -> Self {
+        Self { _p: () }
+    }
+}
+
+impl<V: Visit> VisitLayer<V> for CycleGuardLayer {
+    type Visit = CycleGuard<V>;
+
+    fn layer(self, inner: V) -> Self::Visit {
+        CycleGuard::new(inner)
+    }
+}
+
+// === impl ContextLayer ===
+
+impl ContextLayer {
+    /// Returns a new `ContextLayer`, whose visitors record their position
+    /// in `cx`.
+    pub fn new(cx: Context) -> Self {
+        Self { cx }
+    }
+}
+
+impl<V: Visit> VisitLayer<V> for ContextLayer {
+    type Visit = WithContext<V>;
+
+    fn layer(self, inner: V) -> Self::Visit {
+        WithContext::new(inner, self.cx)
+    }
+}
+
+// === impl MaybeVisitLayer ===
+
+impl<F> MaybeVisitLayer<F>
+where
+    F: Fn(&str) -> bool,
+{
+    /// Returns a new `MaybeVisitLayer`, whose visitors visit only the fields
+    /// whose names `wants` returns `true` for.
+    pub fn new(wants: F) -> Self {
+        Self { wants }
+    }
+}
+
+impl<V, F> VisitLayer<V> for MaybeVisitLayer<F>
+where
+    V: Visit,
+    F: Fn(&str) -> bool,
+{
+    type Visit = MaybeVisit<V, F>;
+
+    fn layer(self, inner: V) -> Self::Visit {
+        MaybeVisit::new(inner, self.wants)
+    }
+}
+
+impl<F> fmt::Debug for MaybeVisitLayer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaybeVisitLayer").finish()
+    }
+}
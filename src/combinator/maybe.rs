@@ -0,0 +1,170 @@
+use crate::{
+    owned::OwnedValue,
+    record::FieldId,
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor wrapper that skips the values of unwanted fields.
+///
+/// A `MaybeVisit` answers [`Visit::wants_value`] by calling a predicate with
+/// the field's name, so that producers which ask before computing expensive
+/// values (such as with [`visit_kv_lazy`]) don't compute values which will
+/// be discarded. For example, a subscriber which samples events may wrap its
+/// visitor in a `MaybeVisit` whose predicate returns `false` for the
+/// expensive fields of events which were not sampled.
+///
+/// Key-value pairs and fields for which the predicate returns `false` are
+/// not forwarded to the wrapped visitor, even if the producer visits them
+/// without asking first. A field is only forwarded if both the predicate and
+/// the wrapped visitor want its value.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::MaybeVisit, json::JsonVisit, visitor::Visit};
+/// let mut visit = MaybeVisit::new(JsonVisit::new(String::new()), |key| key != "backtrace");
+/// let dyn_visit: &mut dyn Visit = &mut visit;
+///
+/// dyn_visit.open_map().unwrap();
+/// dyn_visit.visit_kv_lazy("message", || "oh no").unwrap();
+/// dyn_visit.visit_kv_lazy("backtrace", || -> String { unreachable!() }).unwrap();
+/// dyn_visit.close_map().unwrap();
+/// assert_eq!(visit.into_inner().into_inner(), r#"{"message":"oh no"}"#);
+/// ```
+///
+/// [`Visit::wants_value`]: ../visitor/trait.Visit.html#method.wants_value
+/// [`visit_kv_lazy`]: ../visitor/trait.Visit.html#method.visit_kv_lazy
+pub struct MaybeVisit<V, F> {
+    inner: V,
+    wants: F,
+}
+
+// === impl MaybeVisit ===
+
+impl<V, F> MaybeVisit<V, F>
+where
+    V: Visit,
+    F: Fn(&str) -> bool,
+{
+    /// Returns a new `MaybeVisit` wrapping `inner`, which visits only the
+    /// fields whose names `wants` returns `true` for.
+    pub fn new(inner: V, wants: F) -> Self {
+        Self { inner, wants }
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `MaybeVisit`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V, F> Visit for MaybeVisit<V, F>
+where
+    V: Visit,
+    F: Fn(&str) -> bool,
+{
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            error_mode,
+            capabilities,
+            keys_only,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        let key = OwnedValue::from_value(&k)?.into_key();
+        if !self.wants_value(&key) {
+            return Ok(());
+        }
+        self.inner.visit_kv(k, v)
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        if !self.wants_value(field.name()) {
+            return Ok(());
+        }
+        self.inner.visit_field(field, value)
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        if !self.wants_value(name) {
+            return Ok(());
+        }
+        self.inner.visit_named_field(name, value)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        (self.wants)(key) && self.inner.wants_value(key)
+    }
+}
+
+impl<V: fmt::Debug, F> fmt::Debug for MaybeVisit<V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaybeVisit")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, record::Record};
+
+    #[test]
+    fn skips_unwanted_fields() {
+        let pairs = [
+            ("a", Value::borrowed(&1)),
+            ("b", Value::borrowed(&2)),
+            ("c", Value::borrowed(&3)),
+        ];
+        let mut visit = MaybeVisit::new(JsonVisit::new(String::new()), |key| key != "b");
+        Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+        value!({ "b": { "b": 1 }, "c": [] })
+            .visit(&mut visit)
+            .unwrap();
+        assert_eq!(visit.into_inner().into_inner(), r#"{"a":1,"c":3}{"c":[]}"#);
+    }
+}
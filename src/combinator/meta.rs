@@ -0,0 +1,163 @@
+use crate::{
+    owned::OwnedValue,
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor wrapper that adds standard fields to every record.
+///
+/// A `WithMeta` generates the value of each of its fields (such as a
+/// timestamp, the host name, or a sequence number) by calling a closure
+/// each time a record is visited, and forwards the record to the wrapped
+/// visitor with the generated fields before the record's own fields. A
+/// field of the record with the same name as a generated field shadows it,
+/// as with [`Record::chain`].
+///
+/// Only records visited directly by `WithMeta` are extended; records nested
+/// inside other values are forwarded unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::WithMeta, json::JsonVisit, owned::OwnedValue};
+/// # use valuable::{record::Record, value};
+/// let mut seq = 0;
+/// let mut visit = WithMeta::new(JsonVisit::new(String::new()))
+///     .with_field("host", || OwnedValue::Str("web-1".into()))
+///     .with_field("seq", move || {
+///         seq += 1;
+///         OwnedValue::Uint(seq)
+///     });
+///
+/// let pairs = [("message", value!("started"))];
+/// Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+/// Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+/// assert_eq!(
+///     visit.into_inner().into_inner(),
+///     concat!(
+///         r#"{"host":"web-1","seq":1,"message":"started"}"#,
+///         r#"{"host":"web-1","seq":2,"message":"started"}"#,
+///     )
+/// );
+/// ```
+///
+/// [`Record::chain`]: ../record/struct.Record.html#method.chain
+pub struct WithMeta<V> {
+    inner: V,
+    fields: Vec<(&'static str, Generate)>,
+}
+
+type Generate = Box<dyn FnMut() -> OwnedValue + Send>;
+
+// === impl WithMeta ===
+
+impl<V: Visit> WithMeta<V> {
+    /// Returns a new `WithMeta` wrapping `inner`, which adds no fields.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field named `name`, whose value is generated for each record
+    /// by calling `generate`.
+    ///
+    /// Fields are added to records in the order in which they were added to
+    /// the `WithMeta`.
+    pub fn with_field<F>(mut self, name: &'static str, generate: F) -> Self
+    where
+        F: FnMut() -> OwnedValue + Send + 'static,
+    {
+        self.fields.push((name, Box::new(generate)));
+        self
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `WithMeta`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V: Visit> Visit for WithMeta<V> {
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_kv,
+            visit_field,
+            visit_named_field,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        if self.fields.is_empty() {
+            return self.inner.visit_record(record);
+        }
+        let values = self
+            .fields
+            .iter_mut()
+            .map(|(name, generate)| (*name, generate()))
+            .collect::<Vec<_>>();
+        let pairs = values
+            .iter()
+            .map(|(name, value)| (*name, Value::borrowed(value)))
+            .collect::<Vec<_>>();
+        let meta = Record::from_pairs(&pairs);
+        Record::chain(&meta, record).visit(&mut self.inner)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for WithMeta<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.fields.iter().map(|(name, _)| name);
+        f.debug_struct("WithMeta")
+            .field("inner", &self.inner)
+            .field("fields", &names.collect::<Vec<_>>())
+            .finish()
+    }
+}
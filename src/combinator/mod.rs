@@ -0,0 +1,40 @@
+//! Visitor combinators.
+//!
+//! The types in this module wrap another `Visit` implementation, changing
+//! how values are visited before (or instead of) forwarding them to the
+//! wrapped visitor.
+mod catch;
+mod context;
+mod cycle;
+mod depth;
+#[cfg(feature = "instrument")]
+mod instrument;
+mod keys;
+mod layer;
+mod maybe;
+mod meta;
+mod projected;
+mod tee;
+mod truncate;
+
+pub use self::{
+    catch::CatchUnwind,
+    context::{ContainerKind, Context, Frame, WithContext},
+    cycle::CycleGuard,
+    depth::DepthLimit,
+    keys::KeysOnly,
+    layer::{
+        ContextLayer, CycleGuardLayer, DepthLimitLayer, Identity, MaybeVisitLayer, Stack,
+        VisitLayer,
+    },
+    maybe::MaybeVisit,
+    meta::WithMeta,
+    projected::ProjectedVisit,
+    tee::{ErrorPolicy, MultiVisit, Tee},
+    truncate::Truncate,
+};
+
+#[cfg(feature = "instrument")]
+pub use self::instrument::{InstrumentedVisit, MethodStats, Report};
+
+pub(crate) use self::truncate::Prefix;
@@ -0,0 +1,241 @@
+use crate::{
+    key::KeyPolicy,
+    pool,
+    record::{FieldId, FieldSet},
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor wrapper that visits only an allowlisted set of fields.
+///
+/// A `ProjectedVisit` forwards only the fields of the outermost struct, map,
+/// or record whose names are in its [`FieldSet`]; other fields are skipped,
+/// along with their values. Values nested inside a forwarded field are
+/// forwarded as-is.
+///
+/// Since a `ProjectedVisit` answers [`Visit::wants_value`] using the field
+/// set, producers which ask before computing their fields (such as
+/// [`visit_struct_lazy`]) don't compute fields which will not be recorded.
+/// Unlike [`MaybeVisit`], which asks a predicate about the fields of every
+/// nested value, only the outermost fields are checked.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{combinator::ProjectedVisit, json::JsonVisit, record::FieldSet, value};
+/// static FIELDS: FieldSet = FieldSet::new(&["user", "status"]);
+/// let mut visit = ProjectedVisit::new(JsonVisit::new(String::new()), FIELDS);
+///
+/// value!({ "user": { "id": 1, "name": "alice" }, "path": "/", "status": 200 })
+///     .visit(&mut visit)
+///     .unwrap();
+/// assert_eq!(
+///     visit.into_inner().into_inner(),
+///     r#"{"user":{"id":1,"name":"alice"},"status":200}"#,
+/// );
+/// ```
+///
+/// [`FieldSet`]: ../record/struct.FieldSet.html
+/// [`Visit::wants_value`]: ../visitor/trait.Visit.html#method.wants_value
+/// [`visit_struct_lazy`]: ../visitor/trait.Visit.html#method.visit_struct_lazy
+/// [`MaybeVisit`]: struct.MaybeVisit.html
+pub struct ProjectedVisit<V> {
+    inner: V,
+    fields: FieldSet,
+    /// The number of containers opened through this visitor which have not
+    /// yet been closed.
+    depth: usize,
+}
+
+// === impl ProjectedVisit ===
+
+impl<V: Visit> ProjectedVisit<V> {
+    /// Returns a new `ProjectedVisit` wrapping `inner`, which visits only
+    /// the outermost fields named in `fields`.
+    pub fn new(inner: V, fields: FieldSet) -> Self {
+        Self {
+            inner,
+            fields,
+            depth: 0,
+        }
+    }
+
+    /// Returns the set of fields which are visited.
+    pub fn fields(&self) -> FieldSet {
+        self.fields
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `ProjectedVisit`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    /// Returns `true` if fields are currently being projected.
+    fn projecting(&self) -> bool {
+        self.depth <= 1
+    }
+
+    fn open(&mut self, result: VisitResult) -> VisitResult {
+        if result.is_ok() {
+            self.depth += 1;
+        }
+        result
+    }
+
+    fn close(&mut self, result: VisitResult) -> VisitResult {
+        self.depth = self.depth.saturating_sub(1);
+        result
+    }
+}
+
+impl<V: Visit> Visit for ProjectedVisit<V> {
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            error_mode,
+            capabilities,
+            keys_only,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        if self.projecting() {
+            let wanted = pool::with_string(|key| {
+                KeyPolicy::new().normalize_into(&k, key)?;
+                Ok::<_, Error>(self.wants_value(key))
+            })?;
+            if !wanted {
+                return Ok(());
+            }
+        }
+        self.inner.visit_kv(k, v)
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        if !self.wants_value(field.name()) {
+            return Ok(());
+        }
+        self.inner.visit_field(field, value)
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        if !self.wants_value(name) {
+            return Ok(());
+        }
+        self.inner.visit_named_field(name, value)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        (!self.projecting() || self.fields.contains(key)) && self.inner.wants_value(key)
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        let result = self.inner.open_map();
+        self.open(result)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        let result = self.inner.close_map();
+        self.close(result)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        let result = self.inner.open_list();
+        self.open(result)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        let result = self.inner.close_list();
+        self.close(result)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        let result = self.inner.open_struct();
+        self.open(result)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        let result = self.inner.close_struct();
+        self.close(result)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        let result = self.inner.open_tuple();
+        self.open(result)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        let result = self.inner.close_tuple();
+        self.close(result)
+    }
+
+    fn finish(&mut self) -> VisitResult {
+        self.inner.finish()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for ProjectedVisit<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProjectedVisit")
+            .field("inner", &self.inner)
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, record::Record};
+
+    #[test]
+    fn projects_outermost_fields() {
+        static FIELDS: FieldSet = FieldSet::new(&["a", "c"]);
+        let pairs = [
+            ("a", Value::borrowed(&1)),
+            ("b", Value::borrowed(&2)),
+            ("c", Value::borrowed(&3)),
+        ];
+        let mut visit = ProjectedVisit::new(JsonVisit::new(String::new()), FIELDS);
+        Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+        value!({ "b": 1, "c": { "b": 2 } })
+            .visit(&mut visit)
+            .unwrap();
+        Value::borrowed(&vec![1, 2]).visit(&mut visit).unwrap();
+        assert_eq!(
+            visit.into_inner().into_inner(),
+            r#"{"a":1,"c":3}{"c":{"b":2}}[1,2]"#
+        );
+    }
+}
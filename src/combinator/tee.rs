@@ -0,0 +1,407 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, ErrorMode, Visit, VisitResult},
+};
+use std::fmt;
+
+/// A visitor that forwards every call to two visitors.
+///
+/// This allows a value to be written to several outputs (such as a JSON log
+/// file and the console) while only traversing it once. Each `Visit` method
+/// called on the `Tee` is called on `A` and then on `B`.
+///
+/// Since visitors visit the key and value of a [`Visit::visit_kv`] pair
+/// themselves, each of the wrapped visitors visits those separately.
+///
+/// How errors returned by the wrapped visitors are handled is determined by
+/// the tee's [`ErrorPolicy`].
+///
+/// [`Visit::visit_kv`]: ../visitor/trait.Visit.html#tymethod.visit_kv
+/// [`ErrorPolicy`]: enum.ErrorPolicy.html
+pub struct Tee<A, B> {
+    a: Branch<A>,
+    b: Branch<B>,
+    policy: ErrorPolicy,
+}
+
+/// A visitor that forwards every call to any number of visitors.
+///
+/// This is the dynamically-sized equivalent of [`Tee`].
+///
+/// [`Tee`]: struct.Tee.html
+pub struct MultiVisit<'a> {
+    branches: Vec<Branch<Box<dyn Visit + 'a>>>,
+    policy: ErrorPolicy,
+}
+
+/// Determines how a [`Tee`] or [`MultiVisit`] handles errors returned by the
+/// visitors it wraps.
+///
+/// [`Tee`]: struct.Tee.html
+/// [`MultiVisit`]: struct.MultiVisit.html
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// Return the first error returned by any visitor, ending the visit.
+    ///
+    /// This is the default.
+    #[default]
+    FailFast,
+    /// Record the first error returned by each visitor and stop forwarding
+    /// calls to it, but continue visiting with the remaining visitors.
+    ///
+    /// The recorded errors can be inspected once the visit is complete.
+    Collect,
+}
+
+struct Branch<V> {
+    visitor: V,
+    error: Option<Error>,
+}
+
+type Call<'a> = dyn FnMut(&mut dyn Visit) -> VisitResult + 'a;
+
+macro_rules! fan_out {
+    ($($method:ident($($arg:ident: $ty:ty),*) => ($($call:expr),*);)+) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) -> VisitResult {
+                self.each(&mut |visitor| visitor.$method($($call),*))
+            }
+        )+
+    };
+}
+
+macro_rules! impl_fan_out {
+    () => {
+        fan_out! {
+            visit_uint(value: u64) => (value);
+            visit_u16(value: u16) => (value);
+            visit_u32(value: u32) => (value);
+            visit_i8(value: i8) => (value);
+            visit_i16(value: i16) => (value);
+            visit_i32(value: i32) => (value);
+            visit_f32(value: f32) => (value);
+            visit_int(value: i64) => (value);
+            visit_float(value: f64) => (value);
+            visit_str(value: &str) => (value);
+            visit_u8(value: u8) => (value);
+            visit_bytes(value: &[u8]) => (value);
+            visit_bool(value: bool) => (value);
+            visit_unit() => ();
+            visit_kv(k: Value<'_>, v: Value<'_>) => (k.by_ref(), v.by_ref());
+            visit_field(field: FieldId, value: Value<'_>) => (field, value.by_ref());
+            visit_named_field(name: &str, value: Value<'_>) => (name, value.by_ref());
+            visit_fmt(args: fmt::Arguments<'_>) => (args);
+            visit_display(value: &dyn fmt::Display) => (value);
+            visit_record(record: &Record<'_>) => (record);
+            named_type(name: &str) => (name);
+            visit_annotation(key: &str, value: &str) => (key, value);
+            open_map() => ();
+            close_map() => ();
+            open_list() => ();
+            close_list() => ();
+            open_struct() => ();
+            close_struct() => ();
+            open_tuple() => ();
+            close_tuple() => ();
+            finish() => ();
+        }
+
+        fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+            // Visit the value once, rather than having each visitor traverse
+            // it separately.
+            value.visit(self)
+        }
+    };
+}
+
+// === impl Tee ===
+
+impl<A: Visit, B: Visit> Tee<A, B> {
+    /// Returns a new `Tee` forwarding to `a` and `b`, with the
+    /// [`FailFast`] error policy.
+    ///
+    /// [`FailFast`]: enum.ErrorPolicy.html#variant.FailFast
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a: Branch::new(a),
+            b: Branch::new(b),
+            policy: ErrorPolicy::FailFast,
+        }
+    }
+
+    /// Sets the error policy of this `Tee`.
+    pub fn with_error_policy(self, policy: ErrorPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Borrows the wrapped visitors.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.a.visitor, &self.b.visitor)
+    }
+
+    /// Mutably borrows the wrapped visitors.
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a.visitor, &mut self.b.visitor)
+    }
+
+    /// Returns the errors recorded for each of the wrapped visitors under
+    /// the [`Collect`] error policy.
+    ///
+    /// [`Collect`]: enum.ErrorPolicy.html#variant.Collect
+    pub fn errors(&self) -> (Option<&Error>, Option<&Error>) {
+        (self.a.error.as_ref(), self.b.error.as_ref())
+    }
+
+    /// Consumes the `Tee`, returning the wrapped visitors.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a.visitor, self.b.visitor)
+    }
+
+    fn each(&mut self, f: &mut Call<'_>) -> VisitResult {
+        forward(&mut self.a.visitor, &mut self.a.error, self.policy, f)?;
+        forward(&mut self.b.visitor, &mut self.b.error, self.policy, f)
+    }
+}
+
+impl<A: Visit, B: Visit> Visit for Tee<A, B> {
+    impl_fan_out!();
+
+    fn wants_value(&self, key: &str) -> bool {
+        // A visitor which has failed under the `Collect` policy no longer
+        // records anything.
+        let a = self.a.error.is_none() && self.a.visitor.wants_value(key);
+        a || (self.b.error.is_none() && self.b.visitor.wants_value(key))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Values are visited once for both visitors, so only capabilities
+        // which both of them support may be used.
+        self.a.visitor.capabilities() & self.b.visitor.capabilities()
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        // Closing containers is harmless for a visitor which would rather
+        // abort, so close them if either visitor asks for it.
+        error_mode([self.a.visitor.error_mode(), self.b.visitor.error_mode()])
+    }
+
+    fn keys_only(&self) -> bool {
+        self.a.visitor.keys_only() && self.b.visitor.keys_only()
+    }
+}
+
+impl<A: fmt::Debug, B: fmt::Debug> fmt::Debug for Tee<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tee")
+            .field("a", &self.a.visitor)
+            .field("b", &self.b.visitor)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+// === impl MultiVisit ===
+
+impl<'a> MultiVisit<'a> {
+    /// Returns a new `MultiVisit` forwarding to `visitors`, with the
+    /// [`FailFast`] error policy.
+    ///
+    /// [`FailFast`]: enum.ErrorPolicy.html#variant.FailFast
+    pub fn new(visitors: Vec<Box<dyn Visit + 'a>>) -> Self {
+        Self {
+            branches: visitors.into_iter().map(Branch::new).collect(),
+            policy: ErrorPolicy::FailFast,
+        }
+    }
+
+    /// Sets the error policy of this `MultiVisit`.
+    pub fn with_error_policy(self, policy: ErrorPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Adds another visitor to forward to.
+    pub fn push(&mut self, visitor: Box<dyn Visit + 'a>) {
+        self.branches.push(Branch::new(visitor));
+    }
+
+    /// Returns the number of wrapped visitors.
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Returns `true` if there are no wrapped visitors.
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    /// Returns an iterator over the errors recorded under the [`Collect`]
+    /// error policy, along with the index of the visitor that returned each
+    /// error.
+    ///
+    /// [`Collect`]: enum.ErrorPolicy.html#variant.Collect
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &Error)> + '_ {
+        self.branches
+            .iter()
+            .enumerate()
+            .filter_map(|(i, branch)| branch.error.as_ref().map(|e| (i, e)))
+    }
+
+    /// Consumes the `MultiVisit`, returning the wrapped visitors.
+    pub fn into_inner(self) -> Vec<Box<dyn Visit + 'a>> {
+        self.branches
+            .into_iter()
+            .map(|branch| branch.visitor)
+            .collect()
+    }
+
+    fn each(&mut self, f: &mut Call<'_>) -> VisitResult {
+        for branch in &mut self.branches {
+            forward(&mut *branch.visitor, &mut branch.error, self.policy, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visit for MultiVisit<'_> {
+    impl_fan_out!();
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.branches
+            .iter()
+            .any(|branch| branch.error.is_none() && branch.visitor.wants_value(key))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.branches
+            .iter()
+            .map(|branch| branch.visitor.capabilities())
+            .reduce(Capabilities::intersection)
+            .unwrap_or_default()
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        error_mode(self.branches.iter().map(|branch| branch.visitor.error_mode()))
+    }
+
+    fn keys_only(&self) -> bool {
+        self.branches.iter().all(|branch| branch.visitor.keys_only())
+    }
+}
+
+impl fmt::Debug for MultiVisit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiVisit")
+            .field("visitors", &self.branches.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+// === impl Branch ===
+
+impl<V> Branch<V> {
+    fn new(visitor: V) -> Self {
+        Self {
+            visitor,
+            error: None,
+        }
+    }
+}
+
+/// Returns the error mode of visitors which forward to visitors with the
+/// given error modes: containers are closed if any of them asks for it.
+fn error_mode(modes: impl IntoIterator<Item = ErrorMode>) -> ErrorMode {
+    if modes.into_iter().any(|mode| mode == ErrorMode::BestEffortClose) {
+        ErrorMode::BestEffortClose
+    } else {
+        ErrorMode::Abort
+    }
+}
+
+/// Calls `f` with `visitor`, handling any error according to `policy`.
+fn forward(
+    visitor: &mut dyn Visit,
+    error: &mut Option<Error>,
+    policy: ErrorPolicy,
+    f: &mut Call<'_>,
+) -> VisitResult {
+    // Visitors which have already failed are not called again.
+    if error.is_some() {
+        return Ok(());
+    }
+    match (f(visitor), policy) {
+        (Ok(()), _) => Ok(()),
+        (Err(e), ErrorPolicy::FailFast) => Err(e),
+        (Err(e), ErrorPolicy::Collect) => {
+            *error = Some(e);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combinator::DepthLimit, debug::DebugVisit, json::JsonVisit};
+
+    #[test]
+    fn fans_out() {
+        let value = value!({ "a": [1, 2], "b": "c" });
+        let mut tee = Tee::new(
+            JsonVisit::new(String::new()),
+            DebugVisit::new(String::new()),
+        );
+        value.visit(&mut tee).unwrap();
+        let (json, debug) = tee.into_inner();
+        assert_eq!(json.into_inner(), r#"{"a":[1,2],"b":"c"}"#);
+        assert_eq!(debug.into_inner(), r#"{"a": [1, 2], "b": "c"}"#);
+    }
+
+    #[test]
+    fn error_policy() {
+        let value = value!({ "a": [1, 2] });
+        let visitors = || -> Vec<Box<dyn Visit>> {
+            vec![
+                Box::new(DepthLimit::new(JsonVisit::new(String::new()), 1)),
+                Box::new(JsonVisit::new(String::new())),
+            ]
+        };
+        let mut multi = MultiVisit::new(visitors());
+        assert!(value.visit(&mut multi).is_err());
+
+        let mut multi = MultiVisit::new(visitors()).with_error_policy(ErrorPolicy::Collect);
+        value.visit(&mut multi).unwrap();
+        let errors = multi.errors().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(errors, vec![0]);
+    }
+
+    #[test]
+    fn error_mode() {
+        struct Fails;
+
+        impl Visitable for Fails {
+            fn visit(&self, _: &mut dyn Visit) -> VisitResult {
+                Err(Error::unsupported("value"))
+            }
+        }
+
+        let bad = Fails;
+        let value = value!({ "a": { "b": bad } });
+        let closing = || JsonVisit::new(String::new()).with_error_mode(ErrorMode::BestEffortClose);
+
+        // The outer map is closed for both visitors, although only one of
+        // them asked for it.
+        let mut tee = Tee::new(closing(), JsonVisit::new(String::new()));
+        assert_eq!(tee.error_mode(), ErrorMode::BestEffortClose);
+        assert!(value.visit(&mut tee).is_err());
+        let (closed, aborted) = tee.into_inner();
+        assert_eq!(closed.into_inner(), r#"{"a":{"b":null}}"#);
+        assert_eq!(aborted.into_inner(), "{}");
+
+        let mut multi = MultiVisit::new(vec![Box::new(JsonVisit::new(String::new()))]);
+        assert_eq!(multi.error_mode(), ErrorMode::Abort);
+        multi.push(Box::new(closing()));
+        assert_eq!(multi.error_mode(), ErrorMode::BestEffortClose);
+    }
+}
@@ -0,0 +1,612 @@
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Capabilities, ErrorMode, Visit, VisitResult},
+};
+use std::{
+    cell::Cell,
+    fmt::{self, Write as _},
+};
+
+/// A visitor wrapper that bounds the size of the visited value.
+///
+/// A `Truncate` may limit the length of strings, the number of items in
+/// lists and tuples, the number of entries in maps, structs, and records,
+/// and the total number of values visited. By default, nothing is limited;
+/// limits are set with the `with_max_*` methods.
+///
+/// Anything past a limit is not forwarded to the wrapped visitor. Instead, an
+/// explicit marker is visited in its place, so that truncated output is never
+/// mistaken for the complete value:
+///
+/// - a truncated string is visited as its first characters followed by
+///   `…N more`, where `N` is the number of characters omitted,
+/// - a list or tuple with omitted items ends with the string `…N more`,
+/// - a map, struct, or record with omitted entries ends with an entry with
+///   the key `…` and the value `N more`.
+///
+/// Once the total number of values visited reaches the limit set by
+/// [`with_max_nodes`], any remaining list items and map entries are omitted
+/// in the same way.
+///
+/// [`with_max_nodes`]: #method.with_max_nodes
+pub struct Truncate<V> {
+    inner: V,
+    limits: Limits,
+    /// The number of values visited so far.
+    ///
+    /// This is a `Cell` so that it can be shared with the truncating visitors
+    /// wrapping nested key-value pairs.
+    nodes: Cell<usize>,
+    stack: Vec<Frame>,
+    /// The number of containers opened within an omitted value.
+    skipping: usize,
+    /// Whether the value following a call to `named_type` or
+    /// `visit_annotation` will be emitted, if that call has already decided
+    /// it.
+    named: Option<bool>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Limits {
+    max_str_len: usize,
+    max_list_len: usize,
+    max_map_entries: usize,
+    max_nodes: usize,
+}
+
+struct Frame {
+    kind: Container,
+    len: usize,
+    omitted: usize,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+/// A `Visitable` that visits a nested value through a `Truncate` with the
+/// enclosing wrapper's limits, sharing its node count.
+struct Nested<'a> {
+    value: Value<'a>,
+    limits: Limits,
+    nodes: &'a Cell<usize>,
+}
+
+/// The key of the entry visited in place of omitted map entries.
+const MARKER: &str = "…";
+
+// === impl Truncate ===
+
+impl<V: Visit> Truncate<V> {
+    /// Returns a new `Truncate` wrapping `inner`, with no limits.
+    pub fn new(inner: V) -> Self {
+        Self::with_limits(inner, Limits::UNLIMITED, Cell::new(0))
+    }
+
+    /// Limits strings to at most `max` characters.
+    pub fn with_max_str_len(self, max: usize) -> Self {
+        self.update(|limits| limits.max_str_len = max)
+    }
+
+    /// Limits lists and tuples to at most `max` items.
+    pub fn with_max_list_len(self, max: usize) -> Self {
+        self.update(|limits| limits.max_list_len = max)
+    }
+
+    /// Limits maps, structs, and records to at most `max` entries.
+    pub fn with_max_map_entries(self, max: usize) -> Self {
+        self.update(|limits| limits.max_map_entries = max)
+    }
+
+    /// Limits the total number of values visited to `max`.
+    ///
+    /// Each string, number, boolean, unit, and container counts as one
+    /// value.
+    pub fn with_max_nodes(self, max: usize) -> Self {
+        self.update(|limits| limits.max_nodes = max)
+    }
+
+    /// Borrows the wrapped visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Consumes the `Truncate`, returning the wrapped visitor.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    fn with_limits(inner: V, limits: Limits, nodes: Cell<usize>) -> Self {
+        Self {
+            inner,
+            limits,
+            nodes,
+            stack: Vec::new(),
+            skipping: 0,
+            named: None,
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Limits)) -> Self {
+        f(&mut self.limits);
+        self
+    }
+
+    fn over_budget(&mut self) -> bool {
+        *self.nodes.get_mut() >= self.limits.max_nodes
+    }
+
+    /// Called before visiting each value, returning whether it should be
+    /// forwarded to the wrapped visitor.
+    fn begin(&mut self) -> bool {
+        if let Some(emit) = self.named.take() {
+            return emit;
+        }
+        if self.skipping > 0 {
+            return false;
+        }
+        let over_budget = self.over_budget();
+        if let Some(frame) = self.stack.last_mut() {
+            if frame.kind == Container::List || frame.kind == Container::Tuple {
+                frame.len += 1;
+                if over_budget || frame.len > self.limits.max_list_len {
+                    frame.omitted += 1;
+                    return false;
+                }
+            }
+        }
+        *self.nodes.get_mut() += 1;
+        true
+    }
+
+    fn open(&mut self, kind: Container) -> bool {
+        if !self.begin() {
+            self.skipping += 1;
+            return false;
+        }
+        self.stack.push(Frame {
+            kind,
+            len: 0,
+            omitted: 0,
+        });
+        true
+    }
+
+    /// Counts an entry in the current map or struct, returning whether it
+    /// should be forwarded to the wrapped visitor.
+    fn entry(&mut self) -> bool {
+        if self.skipping > 0 {
+            return false;
+        }
+        let over_budget = self.over_budget();
+        if let Some(frame) = self.stack.last_mut() {
+            frame.len += 1;
+            if over_budget || frame.len > self.limits.max_map_entries {
+                frame.omitted += 1;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the number of omitted values in the closed container, or
+    /// `None` if the container itself was omitted.
+    fn close(&mut self) -> Option<usize> {
+        if self.skipping > 0 {
+            self.skipping -= 1;
+            return None;
+        }
+        self.stack.pop().map(|frame| frame.omitted)
+    }
+
+    fn close_seq(&mut self, omitted: Option<usize>) -> VisitResult {
+        match omitted {
+            Some(0) => Ok(()),
+            Some(n) => self.inner.visit_str(&format!("{}{} more", MARKER, n)),
+            None => Ok(()),
+        }
+    }
+
+    fn close_entries(&mut self, omitted: Option<usize>) -> VisitResult {
+        match omitted {
+            Some(0) | None => Ok(()),
+            Some(n) => self.inner.visit_kv(
+                Value::borrowed(&MARKER),
+                Value::borrowed(&format!("{} more", n)),
+            ),
+        }
+    }
+}
+
+macro_rules! truncate_scalars {
+    ($($method:ident($ty:ty)),+ $(,)?) => {
+        $(
+            fn $method(&mut self, value: $ty) -> VisitResult {
+                if !self.begin() {
+                    return Ok(());
+                }
+                self.inner.$method(value)
+            }
+        )+
+    };
+}
+
+impl<V: Visit> Visit for Truncate<V> {
+    truncate_scalars! {
+        visit_uint(u64),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_f32(f32),
+        visit_int(i64),
+        visit_float(f64),
+        visit_u8(u8),
+        visit_bool(bool),
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        if !self.begin() {
+            return Ok(());
+        }
+        match value.char_indices().nth(self.limits.max_str_len) {
+            Some((end, _)) => {
+                let omitted = value[end..].chars().count();
+                self.inner
+                    .visit_fmt(format_args!("{}{}{} more", &value[..end], MARKER, omitted))
+            }
+            None => self.inner.visit_str(value),
+        }
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> VisitResult {
+        if value.len() > self.limits.max_list_len {
+            // Visit long byte strings as lists, so that they are truncated
+            // like any other list.
+            return Value::borrowed(&value).visit(self);
+        }
+        if !self.begin() {
+            return Ok(());
+        }
+        self.inner.visit_bytes(value)
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        if !self.begin() {
+            return Ok(());
+        }
+        self.inner.visit_unit()
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        if !self.entry() {
+            return Ok(());
+        }
+        // The wrapped visitor will visit the key and value itself, so wrap
+        // them so that they are still truncated.
+        let (limits, nodes) = (self.limits, &self.nodes);
+        let k = Nested {
+            value: k,
+            limits,
+            nodes,
+        };
+        let v = Nested {
+            value: v,
+            limits,
+            nodes,
+        };
+        self.inner
+            .visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        if !self.entry() {
+            return Ok(());
+        }
+        let value = Nested {
+            value,
+            limits: self.limits,
+            nodes: &self.nodes,
+        };
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        if !self.entry() {
+            return Ok(());
+        }
+        let value = Nested {
+            value,
+            limits: self.limits,
+            nodes: &self.nodes,
+        };
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        if !self.begin() {
+            return Ok(());
+        }
+        if self.limits.max_str_len == usize::MAX {
+            return self.inner.visit_fmt(args);
+        }
+        let mut prefix = Prefix::new(self.limits.max_str_len);
+        let _ = prefix.write_fmt(args);
+        match prefix.chars.checked_sub(prefix.max_chars) {
+            Some(omitted) if omitted > 0 => self
+                .inner
+                .visit_fmt(format_args!("{}{}{} more", prefix.buf, MARKER, omitted)),
+            _ => self.inner.visit_fmt(args),
+        }
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        if !self.begin() {
+            return Ok(());
+        }
+        let over_budget = self.over_budget();
+        let (limits, nodes) = (self.limits, &self.nodes);
+        if !over_budget && record.len() <= limits.max_map_entries {
+            // Nothing is omitted, so the record's `FieldSet` may be kept.
+            let inner = &mut self.inner;
+            return record.map_values(
+                |value| Nested {
+                    value,
+                    limits,
+                    nodes,
+                },
+                |record| inner.visit_record(record),
+            );
+        }
+        let mut nested = Vec::new();
+        let mut omitted = 0;
+        for (name, value) in record {
+            if over_budget || nested.len() >= limits.max_map_entries {
+                omitted += 1;
+            } else {
+                nested.push((
+                    name,
+                    Nested {
+                        value,
+                        limits,
+                        nodes,
+                    },
+                ));
+            }
+        }
+        let marker = format!("{} more", omitted);
+        let mut fields = nested
+            .iter()
+            .map(|(name, value)| (*name, Value::borrowed(value)))
+            .collect::<Vec<_>>();
+        if omitted > 0 {
+            fields.push((MARKER, Value::borrowed(&marker)));
+        }
+        self.inner.visit_record(&Record::from_pairs(&fields))
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        let emit = self.begin();
+        self.named = Some(emit);
+        if emit {
+            self.inner.named_type(name)?;
+        }
+        Ok(())
+    }
+
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        // Annotations are emitted only if the value they annotate is, as
+        // type names are.
+        let emit = self.begin();
+        self.named = Some(emit);
+        if emit {
+            self.inner.visit_annotation(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.inner.wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        self.inner.error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        self.inner.keys_only()
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        if self.open(Container::Map) {
+            self.inner.open_map()?;
+        }
+        Ok(())
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        let omitted = self.close();
+        self.close_entries(omitted)?;
+        match omitted {
+            Some(_) => self.inner.close_map(),
+            None => Ok(()),
+        }
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        if self.open(Container::List) {
+            self.inner.open_list()?;
+        }
+        Ok(())
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        let omitted = self.close();
+        self.close_seq(omitted)?;
+        match omitted {
+            Some(_) => self.inner.close_list(),
+            None => Ok(()),
+        }
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        if self.open(Container::Struct) {
+            self.inner.open_struct()?;
+        }
+        Ok(())
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        let omitted = self.close();
+        self.close_entries(omitted)?;
+        match omitted {
+            Some(_) => self.inner.close_struct(),
+            None => Ok(()),
+        }
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        if self.open(Container::Tuple) {
+            self.inner.open_tuple()?;
+        }
+        Ok(())
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        let omitted = self.close();
+        self.close_seq(omitted)?;
+        match omitted {
+            Some(_) => self.inner.close_tuple(),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> VisitResult {
+        self.inner.finish()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Truncate<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Truncate")
+            .field("inner", &self.inner)
+            .field("limits", &self.limits)
+            .field("nodes", &self.nodes.get())
+            .finish()
+    }
+}
+
+// === impl Limits ===
+
+impl Limits {
+    const UNLIMITED: Self = Self {
+        max_str_len: usize::MAX,
+        max_list_len: usize::MAX,
+        max_map_entries: usize::MAX,
+        max_nodes: usize::MAX,
+    };
+}
+
+// === impl Nested ===
+
+impl Visitable for Nested<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let mut truncate = Truncate::with_limits(visitor, self.limits, Cell::new(self.nodes.get()));
+        let result = self.value.visit(&mut truncate);
+        self.nodes.set(truncate.nodes.get());
+        result
+    }
+}
+
+/// A writer that keeps the first `max_chars` characters written to it, and
+/// counts the rest.
+pub(crate) struct Prefix {
+    buf: String,
+    max_chars: usize,
+    chars: usize,
+}
+
+impl Prefix {
+    pub(crate) fn new(max_chars: usize) -> Self {
+        Self {
+            buf: String::new(),
+            max_chars,
+            chars: 0,
+        }
+    }
+
+    /// Returns the characters kept, followed by `…N more` if any were
+    /// omitted.
+    pub(crate) fn into_string(self) -> String {
+        match self.chars.checked_sub(self.max_chars) {
+            Some(omitted) if omitted > 0 => format!("{}{}{} more", self.buf, MARKER, omitted),
+            _ => self.buf,
+        }
+    }
+}
+
+impl fmt::Write for Prefix {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.chars < self.max_chars {
+                self.buf.push(c);
+            }
+            self.chars += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::JsonVisit;
+
+    fn truncated(
+        value: &Value<'_>,
+        f: impl FnOnce(Truncate<JsonVisit<String>>) -> Truncate<JsonVisit<String>>,
+    ) -> String {
+        let mut truncate = f(Truncate::new(JsonVisit::new(String::new())));
+        value.visit(&mut truncate).unwrap();
+        truncate.into_inner().into_inner()
+    }
+
+    #[test]
+    fn limits() {
+        let value = value!({
+            "str": "hello world",
+            "list": [1, 2, [3], { "a": 4 }],
+            "map": { "a": 1, "b": 2, "c": 3 },
+        });
+        assert_eq!(
+            truncated(&value, |t| t
+                .with_max_str_len(5)
+                .with_max_list_len(2)
+                .with_max_map_entries(2)),
+            r#"{"str":"hello…6 more","list":[1,2,"…2 more"],"…":"1 more"}"#
+        );
+        assert_eq!(
+            truncated(&value, |t| t.with_max_nodes(6)),
+            r#"{"str":"hello world","list":[1,"…3 more"],"…":"1 more"}"#
+        );
+    }
+}
@@ -0,0 +1,616 @@
+//! Human-readable output for terminals.
+//!
+//! This module provides [`ConsoleVisit`], a `Visit` implementation that
+//! writes values as indented, optionally colored text, for use in CLI tools
+//! and development loggers.
+//!
+//! [`ConsoleVisit`]: struct.ConsoleVisit.html
+use crate::{
+    debug::FormatOptions,
+    owned::{Builder, OwnedValue},
+    record::Record,
+    units::{Unit, UNIT},
+    value::{Value, Visitable},
+    visitor::{Capabilities, Visit, VisitResult},
+};
+use std::fmt::{self, Write as _};
+
+/// A `Visit` implementation that writes values as indented text, with
+/// optional ANSI colors.
+///
+/// Values are written in the style of Rust's `fmt::Debug` output (see
+/// [`DebugVisit`]). A container which fits within the configured width is
+/// written on a single line; otherwise, each of its items is written on its
+/// own indented line, as with `{:#?}`:
+///
+/// ```
+/// # use valuable::{console::ConsoleVisit, value};
+/// let mut visit = ConsoleVisit::new(String::new()).with_width(24);
+/// value!({ "name": "svc", "ports": [80, 443] }).visit(&mut visit).unwrap();
+/// assert_eq!(
+///     visit.into_inner(),
+///     "{\n  \"name\": \"svc\",\n  \"ports\": [80, 443],\n}"
+/// );
+/// ```
+///
+/// Strings are never broken across lines, so a line containing a long
+/// string may exceed the width. With [`FormatOptions`] whose `alternate`
+/// flag is set, every container with items is broken across lines, whether
+/// or not it fits; a width or precision in the options pads scalars and
+/// rounds floats, as with [`DebugVisit`].
+///
+/// Each value is buffered in full before it is written, so that the width
+/// of its containers is known. Formatted values (such as those constructed
+/// with `Value::display`) are written as strings. If more than one value is
+/// visited, each is written on a new line.
+///
+/// Numbers annotated with a [unit] are written for humans in that unit, as
+/// strings: a [`Bytes`] of 1572864 is written as `"1.5 MiB"`. A
+/// `ConsoleVisit` is always [human-readable], so values such as `Duration`s
+/// are also visited in their readable forms.
+///
+/// [`DebugVisit`]: ../debug/struct.DebugVisit.html
+/// [`FormatOptions`]: ../debug/struct.FormatOptions.html
+/// [unit]: ../units/index.html
+/// [`Bytes`]: ../units/struct.Bytes.html
+/// [human-readable]: ../visitor/struct.Capabilities.html#associatedconstant.HUMAN_READABLE
+pub struct ConsoleVisit<W> {
+    writer: W,
+    builder: Builder,
+    ansi: bool,
+    width: usize,
+    indent: usize,
+    options: FormatOptions,
+    /// Set once a value has been written, so that the next value is written
+    /// on a new line.
+    written: bool,
+    /// The unit with which the next value was annotated, if any.
+    unit: Option<Unit>,
+}
+
+/// Writes a buffered value, tracking the current column so that it can
+/// decide whether containers fit on the current line.
+struct Printer<'a, W: ?Sized> {
+    writer: &'a mut W,
+    ansi: bool,
+    width: usize,
+    indent: usize,
+    options: FormatOptions,
+    column: usize,
+}
+
+/// A `Visitable` that visits a value nested in a map or record entry
+/// through a `UnitVisit`, so that its units are not lost when it is
+/// buffered.
+struct Units<'a> {
+    value: Value<'a>,
+}
+
+/// A visitor wrapper that humanizes numbers annotated with a unit.
+struct UnitVisit<'a> {
+    inner: &'a mut dyn Visit,
+    unit: Option<Unit>,
+}
+
+/// A writer that fails once more than `remaining` characters are written to
+/// it.
+struct Budget {
+    remaining: usize,
+}
+
+#[derive(Copy, Clone)]
+enum Item<'a> {
+    Value(&'a OwnedValue),
+    Byte(u8),
+    Field(&'a str, &'a OwnedValue),
+    Entry(&'a OwnedValue, &'a OwnedValue),
+}
+
+const KEY: &str = "\x1b[34m";
+const STR: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const LITERAL: &str = "\x1b[35m";
+const NAME: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// === impl ConsoleVisit ===
+
+impl<W: fmt::Write> ConsoleVisit<W> {
+    /// Returns a new `ConsoleVisit` that writes to `writer`.
+    ///
+    /// By default, colors are disabled, lines are 80 characters wide, and
+    /// each level of nesting is indented by two spaces.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            builder: Builder::default(),
+            ansi: false,
+            width: 80,
+            indent: 2,
+            options: FormatOptions::new(),
+            written: false,
+            unit: None,
+        }
+    }
+
+    /// Sets whether to color the output using ANSI escape codes.
+    ///
+    /// When enabled, map keys and field names, strings, numbers, and type
+    /// names are each written in a different color.
+    pub fn with_ansi(self, ansi: bool) -> Self {
+        Self { ansi, ..self }
+    }
+
+    /// Sets the width, in characters, within which containers are written
+    /// on a single line.
+    pub fn with_width(self, width: usize) -> Self {
+        Self { width, ..self }
+    }
+
+    /// Sets the number of spaces by which each level of nesting is indented.
+    pub fn with_indent(self, indent: usize) -> Self {
+        Self { indent, ..self }
+    }
+
+    /// Sets the [`FormatOptions`] used to write values.
+    ///
+    /// [`FormatOptions`]: ../debug/struct.FormatOptions.html
+    pub fn with_options(self, options: FormatOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Mutably borrows the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `ConsoleVisit`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes the buffered value, if one has been completely visited.
+    fn flush(&mut self) -> VisitResult {
+        if let Some(value) = self.builder.take() {
+            if self.written {
+                self.writer.write_char('\n')?;
+            }
+            self.written = true;
+            let mut printer = Printer {
+                writer: &mut self.writer,
+                ansi: self.ansi,
+                width: self.width,
+                indent: self.indent,
+                options: self.options,
+                column: 0,
+            };
+            printer.value(&value, 0, true)?;
+        }
+        Ok(())
+    }
+
+    /// Visits a number, humanized if it was annotated with a unit.
+    fn number(
+        &mut self,
+        value: f64,
+        raw: impl FnOnce(&mut Builder) -> VisitResult,
+    ) -> VisitResult {
+        match self.unit.take() {
+            Some(unit) => self
+                .builder
+                .visit_fmt(format_args!("{}", unit.humanize(value)))?,
+            None => raw(&mut self.builder)?,
+        }
+        self.flush()
+    }
+}
+
+macro_rules! buffer {
+    ($($method:ident($($arg:ident: $ty:ty),*)),+ $(,)?) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) -> VisitResult {
+                // Only numbers have units.
+                self.unit = None;
+                self.builder.$method($($arg),*)?;
+                self.flush()
+            }
+        )+
+    };
+}
+
+macro_rules! clear_unit {
+    ($($method:ident($($arg:ident: $ty:ty),*)),+ $(,)?) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) -> VisitResult {
+                self.unit = None;
+                self.inner.$method($($arg),*)
+            }
+        )+
+    };
+}
+
+impl<W: fmt::Write> Visit for ConsoleVisit<W> {
+    buffer! {
+        visit_str(value: &str),
+        visit_bytes(value: &[u8]),
+        visit_bool(value: bool),
+        visit_unit(),
+        visit_fmt(args: fmt::Arguments<'_>),
+        named_type(name: &str),
+        open_map(),
+        close_map(),
+        open_list(),
+        close_list(),
+        open_struct(),
+        close_struct(),
+        open_tuple(),
+        close_tuple(),
+    }
+
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.number(value as f64, |b| b.visit_uint(value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.number(value as f64, |b| b.visit_int(value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.number(value, |b| b.visit_float(value))
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.unit = None;
+        let (k, v) = (Units { value: k }, Units { value: v });
+        self.builder.visit_kv(Value::borrowed(&k), Value::borrowed(&v))?;
+        self.flush()
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.unit = None;
+        let builder = &mut self.builder;
+        record.map_values(|value| Units { value }, |record| builder.visit_record(record))?;
+        self.flush()
+    }
+
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        if key == UNIT {
+            self.unit = Unit::from_annotation(value);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::HUMAN_READABLE
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+}
+
+impl<W: fmt::Debug> fmt::Debug for ConsoleVisit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsoleVisit")
+            .field("writer", &self.writer)
+            .field("ansi", &self.ansi)
+            .field("width", &self.width)
+            .field("indent", &self.indent)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+// === impl Units ===
+
+impl Visitable for Units<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut UnitVisit {
+            inner: visitor,
+            unit: None,
+        })
+    }
+}
+
+// === impl UnitVisit ===
+
+impl UnitVisit<'_> {
+    /// Visits a number, humanized if it was annotated with a unit.
+    fn number(
+        &mut self,
+        value: f64,
+        raw: impl FnOnce(&mut dyn Visit) -> VisitResult,
+    ) -> VisitResult {
+        match self.unit.take() {
+            Some(unit) => self
+                .inner
+                .visit_fmt(format_args!("{}", unit.humanize(value))),
+            None => raw(self.inner),
+        }
+    }
+}
+
+impl Visit for UnitVisit<'_> {
+    clear_unit! {
+        visit_str(value: &str),
+        visit_bytes(value: &[u8]),
+        visit_bool(value: bool),
+        visit_unit(),
+        visit_fmt(args: fmt::Arguments<'_>),
+        named_type(name: &str),
+        open_map(),
+        close_map(),
+        open_list(),
+        close_list(),
+        open_struct(),
+        close_struct(),
+        open_tuple(),
+        close_tuple(),
+    }
+
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.number(value as f64, |v| v.visit_uint(value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.number(value as f64, |v| v.visit_int(value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.number(value, |v| v.visit_float(value))
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.unit = None;
+        let (k, v) = (Units { value: k }, Units { value: v });
+        self.inner.visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.unit = None;
+        let inner = &mut self.inner;
+        record.map_values(|value| Units { value }, |record| inner.visit_record(record))
+    }
+
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        if key == UNIT {
+            self.unit = Unit::from_annotation(value);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::HUMAN_READABLE
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+}
+
+// === impl Printer ===
+
+impl<W: fmt::Write + ?Sized> Printer<'_, W> {
+    /// Writes `value`, breaking its containers across lines if `wrap` is set
+    /// and they don't fit on the current line.
+    fn value(&mut self, value: &OwnedValue, depth: usize, wrap: bool) -> fmt::Result {
+        let wrap = wrap && (self.options.alternate() || !self.fits(value));
+        let width = self.options.width().unwrap_or(0);
+        match value {
+            OwnedValue::Unit => self.styled(LITERAL, format_args!("{:1$?}", (), width)),
+            OwnedValue::Bool(v) => self.styled(LITERAL, format_args!("{:1$}", v, width)),
+            OwnedValue::Uint(v) => self.styled(NUMBER, format_args!("{:1$}", v, width)),
+            OwnedValue::Int(v) => self.styled(NUMBER, format_args!("{:1$}", v, width)),
+            OwnedValue::Float(v) => match self.options.precision() {
+                Some(precision) => {
+                    self.styled(NUMBER, format_args!("{:1$.2$?}", v, width, precision))
+                }
+                None => self.styled(NUMBER, format_args!("{:1$?}", v, width)),
+            },
+            OwnedValue::Str(v) => self.styled(STR, format_args!("{:?}", v)),
+            OwnedValue::Bytes(v) => {
+                let items = v.iter().map(|&b| Item::Byte(b));
+                self.items(("[", "]"), items, depth, wrap)
+            }
+            OwnedValue::List(vs) => self.items(("[", "]"), vs.iter().map(Item::Value), depth, wrap),
+            OwnedValue::Tuple(vs) if vs.len() == 1 && !wrap => {
+                self.write_char('(')?;
+                self.value(&vs[0], depth + 1, false)?;
+                self.write_str(",)")
+            }
+            OwnedValue::Tuple(vs) => {
+                self.items(("(", ")"), vs.iter().map(Item::Value), depth, wrap)
+            }
+            OwnedValue::Map(kvs) => {
+                let items = kvs.iter().map(|(k, v)| Item::Entry(k, v));
+                self.items(("{", "}"), items, depth, wrap)
+            }
+            OwnedValue::Struct { name, fields } => {
+                self.styled(NAME, format_args!("{}", name))?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+                let items = fields.iter().map(|(k, v)| Item::Field(k, v));
+                if wrap {
+                    self.items((" {", "}"), items, depth, true)
+                } else {
+                    self.items((" { ", " }"), items, depth, false)
+                }
+            }
+            OwnedValue::TupleStruct { name, fields } => {
+                self.styled(NAME, format_args!("{}", name))?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+                self.items(("(", ")"), fields.iter().map(Item::Value), depth, wrap)
+            }
+        }
+    }
+
+    fn items<'a>(
+        &mut self,
+        (open, close): (&str, &str),
+        items: impl Iterator<Item = Item<'a>>,
+        depth: usize,
+        wrap: bool,
+    ) -> fmt::Result {
+        self.write_str(open)?;
+        let mut empty = true;
+        for (i, item) in items.enumerate() {
+            empty = false;
+            if wrap {
+                self.newline(depth + 1)?;
+            } else if i > 0 {
+                self.write_str(", ")?;
+            }
+            match item {
+                Item::Value(value) => self.value(value, depth + 1, wrap)?,
+                Item::Byte(b) => self.styled(NUMBER, format_args!("{}", b))?,
+                Item::Field(name, value) => {
+                    self.styled(KEY, format_args!("{}", name))?;
+                    self.write_str(": ")?;
+                    self.value(value, depth + 1, wrap)?;
+                }
+                Item::Entry(OwnedValue::Str(key), value) => {
+                    self.styled(KEY, format_args!("{:?}", key))?;
+                    self.write_str(": ")?;
+                    self.value(value, depth + 1, wrap)?;
+                }
+                Item::Entry(key, value) => {
+                    self.value(key, depth + 1, false)?;
+                    self.write_str(": ")?;
+                    self.value(value, depth + 1, wrap)?;
+                }
+            }
+            if wrap {
+                self.write_char(',')?;
+            }
+        }
+        if wrap && !empty {
+            self.newline(depth)?;
+        }
+        self.write_str(close)
+    }
+
+    /// Returns `true` if `value` can be written on a single line without
+    /// exceeding the width.
+    fn fits(&self, value: &OwnedValue) -> bool {
+        let mut budget = Budget {
+            remaining: self.width.saturating_sub(self.column),
+        };
+        let mut printer = Printer {
+            writer: &mut budget,
+            ansi: false,
+            width: usize::MAX,
+            indent: 0,
+            options: self.options.with_alternate(false),
+            column: 0,
+        };
+        printer.value(value, 0, false).is_ok()
+    }
+
+    fn newline(&mut self, depth: usize) -> fmt::Result {
+        self.writer.write_char('\n')?;
+        self.column = 0;
+        for _ in 0..depth * self.indent {
+            self.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    fn styled(&mut self, style: &str, args: fmt::Arguments<'_>) -> fmt::Result {
+        if self.ansi {
+            self.writer.write_str(style)?;
+        }
+        self.write_fmt(args)?;
+        if self.ansi {
+            self.writer.write_str(RESET)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: fmt::Write + ?Sized> fmt::Write for Printer<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.column += s.chars().count();
+        self.writer.write_str(s)
+    }
+}
+
+// === impl Budget ===
+
+impl fmt::Write for Budget {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.chars().count();
+        if len > self.remaining {
+            return Err(fmt::Error);
+        }
+        self.remaining -= len;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OwnedValue {
+        OwnedValue::Struct {
+            name: "Config".to_owned(),
+            fields: vec![
+                ("name".to_owned(), OwnedValue::Str("svc".into())),
+                (
+                    "ports".to_owned(),
+                    OwnedValue::List(vec![OwnedValue::Uint(80), OwnedValue::Uint(443)]),
+                ),
+                ("debug".to_owned(), OwnedValue::Bool(true)),
+            ],
+        }
+    }
+
+    fn console(
+        value: &OwnedValue,
+        f: impl FnOnce(ConsoleVisit<String>) -> ConsoleVisit<String>,
+    ) -> String {
+        let mut visit = f(ConsoleVisit::new(String::new()));
+        Value::borrowed(value).visit(&mut visit).unwrap();
+        visit.into_inner()
+    }
+
+    #[test]
+    fn wraps_to_width() {
+        let value = config();
+        assert_eq!(
+            console(&value, |v| v),
+            r#"Config { name: "svc", ports: [80, 443], debug: true }"#
+        );
+        assert_eq!(
+            console(&value, |v| v.with_width(20).with_indent(4)),
+            "Config {\n    name: \"svc\",\n    ports: [80, 443],\n    debug: true,\n}"
+        );
+        assert_eq!(
+            console(&OwnedValue::List(vec![value]), |v| v.with_width(10)),
+            "[\n  Config {\n    name: \"svc\",\n    ports: [\n      80,\n      443,\n    ],\n    debug: true,\n  },\n]"
+        );
+
+        let options = FormatOptions::new()
+            .with_alternate(true)
+            .with_width(Some(4));
+        assert_eq!(
+            console(&config(), |v| v.with_options(options)),
+            "Config {\n  name: \"svc\",\n  ports: [\n      80,\n     443,\n  ],\n  debug: true,\n}"
+        );
+    }
+
+    #[test]
+    fn ansi_colors() {
+        let colored = console(&config(), |v| v.with_ansi(true));
+        assert!(colored
+            .starts_with("\x1b[1mConfig\x1b[0m { \x1b[34mname\x1b[0m: \x1b[32m\"svc\"\x1b[0m"));
+    }
+}
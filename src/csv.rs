@@ -0,0 +1,310 @@
+//! CSV output.
+//!
+//! This module provides [`CsvVisit`], a `Visit` implementation that writes
+//! each visited record as a row of CSV, for flat exports of structured
+//! events.
+//!
+//! [`CsvVisit`]: struct.CsvVisit.html
+use crate::{
+    escape,
+    owned::OwnedValue,
+    record::{FieldSet, Record},
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt::{self, Write as _};
+
+/// A `Visit` implementation that writes records as rows of CSV.
+///
+/// A `CsvVisit` is constructed with a [`FieldSet`] naming its columns. Each
+/// record, map, or struct it visits is written as one row, containing the
+/// value of the field with each column's name, in the order of the columns.
+/// A column for which the visited value has no field is left empty, and
+/// fields which are not columns are ignored.
+///
+/// Each field's value must be a scalar: numbers, booleans, and strings are
+/// written as text, and unit values as empty fields. A field whose value is
+/// a list, map, or other container cannot be written as a CSV field, and
+/// visiting it fails with an [unsupported] error. Fields are quoted only if
+/// necessary, and rows end with `\n`.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{csv::CsvVisit, record::{FieldSet, Record}, value::Value};
+/// static COLUMNS: FieldSet = FieldSet::new(&["id", "msg", "user"]);
+///
+/// let mut visit = CsvVisit::new(String::new(), COLUMNS);
+/// visit.write_header().unwrap();
+///
+/// let pairs = [("msg", Value::borrowed(&"hello, world")), ("id", Value::borrowed(&1))];
+/// Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+///
+/// let pairs = [("id", Value::borrowed(&2)), ("user", Value::borrowed(&"eliza"))];
+/// Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+///
+/// assert_eq!(visit.into_inner(), "id,msg,user\n1,\"hello, world\",\n2,,eliza\n");
+/// ```
+///
+/// [`FieldSet`]: ../record/struct.FieldSet.html
+/// [unsupported]: ../visitor/struct.Error.html#method.is_unsupported
+pub struct CsvVisit<W> {
+    writer: W,
+    columns: FieldSet,
+    /// The fields of the row being visited, if a map or struct is being
+    /// visited.
+    row: Option<Vec<Option<String>>>,
+}
+
+/// A `Visit` implementation which writes a single scalar value as the text
+/// of a CSV field.
+struct Cell<'a> {
+    buf: &'a mut String,
+}
+
+// === impl CsvVisit ===
+
+impl<W: fmt::Write> CsvVisit<W> {
+    /// Returns a new `CsvVisit` that writes rows with the given `columns` to
+    /// `writer`.
+    pub fn new(writer: W, columns: FieldSet) -> Self {
+        Self {
+            writer,
+            columns,
+            row: None,
+        }
+    }
+
+    /// Writes a header row containing the name of each column.
+    pub fn write_header(&mut self) -> fmt::Result {
+        for (i, name) in self.columns.names().iter().enumerate() {
+            if i > 0 {
+                self.writer.write_char(',')?;
+            }
+            escape::write_csv_str(&mut self.writer, name)?;
+        }
+        self.writer.write_char('\n')
+    }
+
+    /// Returns the columns written by this `CsvVisit`.
+    pub fn columns(&self) -> &FieldSet {
+        &self.columns
+    }
+
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Mutably borrows the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `CsvVisit`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn empty_row(&self) -> Vec<Option<String>> {
+        vec![None; self.columns.len()]
+    }
+
+    /// Renders `value` into the field of `row` for the column `name`, if
+    /// there is one.
+    fn cell(&self, row: &mut [Option<String>], name: &str, value: &Value<'_>) -> VisitResult {
+        if let Some(idx) = self.columns.index_of(name) {
+            let mut buf = String::new();
+            value.visit(&mut Cell { buf: &mut buf })?;
+            row[idx] = Some(buf);
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: Vec<Option<String>>) -> VisitResult {
+        for (i, field) in row.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_char(',')?;
+            }
+            if let Some(field) = field {
+                escape::write_csv_str(&mut self.writer, field)?;
+            }
+        }
+        self.writer.write_char('\n')?;
+        Ok(())
+    }
+
+    fn open_row(&mut self) -> VisitResult {
+        if self.row.is_some() {
+            return Err(Error::unsupported("nested value"));
+        }
+        self.row = Some(self.empty_row());
+        Ok(())
+    }
+
+    fn close_row(&mut self) -> VisitResult {
+        match self.row.take() {
+            Some(row) => self.write_row(row),
+            None => Err(Error::unbalanced()),
+        }
+    }
+}
+
+impl<W: fmt::Write> Visit for CsvVisit<W> {
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_uint(&mut self, _: u64) -> VisitResult {
+        Err(Error::unsupported("CSV row which is not a record"))
+    }
+
+    fn visit_int(&mut self, _: i64) -> VisitResult {
+        Err(Error::unsupported("CSV row which is not a record"))
+    }
+
+    fn visit_float(&mut self, _: f64) -> VisitResult {
+        Err(Error::unsupported("CSV row which is not a record"))
+    }
+
+    fn visit_str(&mut self, _: &str) -> VisitResult {
+        Err(Error::unsupported("CSV row which is not a record"))
+    }
+
+    fn visit_bool(&mut self, _: bool) -> VisitResult {
+        Err(Error::unsupported("CSV row which is not a record"))
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        let name = OwnedValue::from_value(&k)?.into_key();
+        let mut row = match self.row.take() {
+            Some(row) => row,
+            None => return Err(Error::unbalanced()),
+        };
+        // If the field can't be written, the row is discarded, so that
+        // later rows may still be visited.
+        self.cell(&mut row, &name, &v)?;
+        self.row = Some(row);
+        Ok(())
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        if self.row.is_some() {
+            return Err(Error::unsupported("nested value"));
+        }
+        let mut row = self.empty_row();
+        for (name, value) in record.iter() {
+            self.cell(&mut row, name, &value)
+                .map_err(|e| e.in_field(name))?;
+        }
+        self.write_row(row)
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.columns.contains(key)
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open_row()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close_row()
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open_row()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close_row()
+    }
+}
+
+impl<W: fmt::Debug> fmt::Debug for CsvVisit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsvVisit")
+            .field("writer", &self.writer)
+            .field("columns", &self.columns)
+            .finish()
+    }
+}
+
+// === impl Cell ===
+
+impl Visit for Cell<'_> {
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.visit_fmt(format_args!("{}", value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.visit_fmt(format_args!("{}", value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.visit_fmt(format_args!("{}", value))
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.buf.push_str(value);
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.visit_fmt(format_args!("{}", value))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.buf.write_fmt(args)?;
+        Ok(())
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_and_errors() {
+        static COLUMNS: FieldSet = FieldSet::new(&["a", "b"]);
+        let mut visit = CsvVisit::new(String::new(), COLUMNS);
+
+        let struct_row = OwnedValue::Struct {
+            name: "Row".to_owned(),
+            fields: vec![
+                ("b".to_owned(), OwnedValue::Str("say \"hi\"".into())),
+                ("c".to_owned(), OwnedValue::List(Vec::new())),
+                ("a".to_owned(), OwnedValue::Unit),
+            ],
+        };
+        Value::borrowed(&struct_row).visit(&mut visit).unwrap();
+        value!({ "a": 1.5, "b": true }).visit(&mut visit).unwrap();
+
+        let err = value!({ "a": [1] }).visit(&mut visit).unwrap_err();
+        assert!(err.is_unsupported());
+        assert_eq!(err.to_string(), "visiting a list is not supported at `a`");
+        assert!(Value::borrowed(&1).visit(&mut visit).is_err());
+        value!({ "b": "x,y" }).visit(&mut visit).unwrap();
+
+        assert_eq!(
+            visit.into_inner(),
+            ",\"say \"\"hi\"\"\"\n1.5,true\n,\"x,y\"\n"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+pub use valuable_core::debug::*;
+
+#[cfg(feature = "debug-tree")]
+mod parse;
+
+#[cfg(feature = "debug-tree")]
+pub use self::parse::from_str;
+
+#[cfg(test)]
+mod tests {
+    use crate::{owned::OwnedValue, value::Value};
+
+    fn s(s: &str) -> OwnedValue {
+        OwnedValue::Str(s.into())
+    }
+
+    #[test]
+    fn matches_std_debug() {
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Point {
+            x: u64,
+            y: u64,
+            tag: &'static str,
+        }
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Wrapper(u64, (i64,));
+        #[derive(Debug)]
+        struct Empty;
+
+        let value = OwnedValue::List(vec![
+            OwnedValue::Struct {
+                name: "Point".to_owned(),
+                fields: vec![
+                    ("x".to_owned(), OwnedValue::Uint(1)),
+                    ("y".to_owned(), OwnedValue::Uint(2)),
+                    ("tag".to_owned(), s("a\"b")),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Wrapper".to_owned(),
+                fields: vec![
+                    OwnedValue::Uint(3),
+                    OwnedValue::Tuple(vec![OwnedValue::Int(-4)]),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Empty".to_owned(),
+                fields: Vec::new(),
+            },
+        ]);
+        let expected = format!(
+            "{:?}",
+            (
+                Point {
+                    x: 1,
+                    y: 2,
+                    tag: "a\"b"
+                },
+                Wrapper(3, (-4,)),
+                Empty
+            )
+        );
+        // `std` writes a tuple here, and the visitor a list.
+        let expected = format!("[{}]", &expected[1..expected.len() - 1]);
+        assert_eq!(Value::borrowed(&value).render_debug(), expected);
+    }
+
+    #[test]
+    fn matches_std_options() {
+        use std::collections::BTreeMap;
+
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Point {
+            x: f64,
+            y: i64,
+            ok: bool,
+        }
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Wrapper(u64, (f64,), ());
+        #[derive(Debug)]
+        struct Empty;
+
+        let value = OwnedValue::Tuple(vec![
+            OwnedValue::Struct {
+                name: "Point".to_owned(),
+                fields: vec![
+                    ("x".to_owned(), OwnedValue::Float(1.25)),
+                    ("y".to_owned(), OwnedValue::Int(-3)),
+                    ("ok".to_owned(), OwnedValue::Bool(true)),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Wrapper".to_owned(),
+                fields: vec![
+                    OwnedValue::Uint(7),
+                    OwnedValue::Tuple(vec![OwnedValue::Float(0.5)]),
+                    OwnedValue::Unit,
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Empty".to_owned(),
+                fields: Vec::new(),
+            },
+            OwnedValue::List(Vec::new()),
+            OwnedValue::Map(vec![(
+                s("a"),
+                OwnedValue::List(vec![OwnedValue::Uint(1), OwnedValue::Uint(22)]),
+            )]),
+        ]);
+        let mut map = BTreeMap::new();
+        map.insert("a", vec![1, 22]);
+        let expected = (
+            Point {
+                x: 1.25,
+                y: -3,
+                ok: true,
+            },
+            Wrapper(7, (0.5,), ()),
+            Empty,
+            Vec::<u8>::new(),
+            map,
+        );
+        let value = Value::borrowed(&value);
+        assert_eq!(format!("{:#?}", value), format!("{:#?}", expected));
+        assert_eq!(format!("{:6?}", value), format!("{:6?}", expected));
+        assert_eq!(format!("{:#4?}", value), format!("{:#4?}", expected));
+
+        // Unlike `std`, precision only affects floats.
+        let floats = [1.25, -0.5];
+        assert_eq!(
+            format!("{:6.1?}", Value::borrowed(&&floats[..])),
+            format!("{:6.1?}", floats)
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let value = value!({ "a": [1, 2], "b": () });
+        assert_eq!(value.render_debug(), r#"{"a": [1, 2], "b": ()}"#);
+        let display = "not quoted";
+        assert_eq!(Value::display(&display).render_debug(), "not quoted");
+    }
+}
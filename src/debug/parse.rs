@@ -0,0 +1,437 @@
+use crate::owned::OwnedValue;
+use std::char;
+
+/// Parses the output of a type's `fmt::Debug` implementation into an
+/// [`OwnedValue`], on a best-effort basis.
+///
+/// This understands the output of `#[derive(Debug)]` and of the
+/// `fmt::Formatter::debug_*` builders, in either their compact (`{:?}`) or
+/// pretty (`{:#?}`) forms:
+///
+/// - `Name { field: value, .. }` is parsed as an [`OwnedValue::Struct`],
+/// - `Name(value, ..)` is parsed as an [`OwnedValue::TupleStruct`], and a
+///   bare `Name` (such as a unit struct, or `None`) as a tuple struct with no
+///   fields,
+/// - `[value, ..]` and sets (`{value, ..}`) are parsed as
+///   [`OwnedValue::List`]s, `{key: value, ..}` as an [`OwnedValue::Map`], and
+///   `(value, ..)` as an [`OwnedValue::Tuple`] (or [`OwnedValue::Unit`], if
+///   it is empty),
+/// - quoted strings and characters are unescaped into [`OwnedValue::Str`]s,
+///   and `true`, `false`, and numbers are parsed as booleans, integers, and
+///   floats, as they are by [`json::from_str`].
+///
+/// `Debug` output is not a data format, and a hand-written implementation
+/// may write anything at all. If `input` cannot be parsed, it is returned
+/// as-is, as a single [`OwnedValue::Str`].
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{debug, owned::OwnedValue};
+/// #[derive(Debug)]
+/// struct Point {
+///     x: i32,
+///     label: Option<&'static str>,
+/// }
+///
+/// let point = Point { x: -1, label: Some("origin") };
+/// assert_eq!(
+///     debug::from_str(&format!("{:#?}", point)),
+///     OwnedValue::Struct {
+///         name: "Point".to_owned(),
+///         fields: vec![
+///             ("x".to_owned(), OwnedValue::Int(-1)),
+///             ("label".to_owned(), OwnedValue::TupleStruct {
+///                 name: "Some".to_owned(),
+///                 fields: vec![OwnedValue::Str("origin".into())],
+///             }),
+///         ],
+///     },
+/// );
+/// assert_eq!(debug::from_str("<opaque>"), OwnedValue::Str("<opaque>".into()));
+/// ```
+///
+/// This function requires the `debug-tree` feature.
+///
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+/// [`OwnedValue::Struct`]: ../owned/enum.OwnedValue.html#variant.Struct
+/// [`OwnedValue::TupleStruct`]: ../owned/enum.OwnedValue.html#variant.TupleStruct
+/// [`OwnedValue::List`]: ../owned/enum.OwnedValue.html#variant.List
+/// [`OwnedValue::Map`]: ../owned/enum.OwnedValue.html#variant.Map
+/// [`OwnedValue::Tuple`]: ../owned/enum.OwnedValue.html#variant.Tuple
+/// [`OwnedValue::Unit`]: ../owned/enum.OwnedValue.html#variant.Unit
+/// [`OwnedValue::Str`]: ../owned/enum.OwnedValue.html#variant.Str
+/// [`json::from_str`]: ../json/fn.from_str.html
+pub fn from_str(input: &str) -> OwnedValue {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    match parser.parse_value() {
+        Some(value) if parser.at_end() => value,
+        _ => OwnedValue::Str(input.into()),
+    }
+}
+
+/// The maximum nesting depth accepted by the parser.
+///
+/// This bounds the parser's recursion so that adversarial input cannot
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+// === impl Parser ===
+
+impl<'a> Parser<'a> {
+    fn parse_value(&mut self) -> Option<OwnedValue> {
+        match self.peek()? {
+            '"' => self.parse_str().map(|s| OwnedValue::Str(s.into())),
+            '\'' => self.parse_char().map(|c| OwnedValue::Str(c.into())),
+            '[' => self.parse_seq('[', ']').map(OwnedValue::List),
+            '(' => self.parse_seq('(', ')').map(|values| {
+                if values.is_empty() {
+                    OwnedValue::Unit
+                } else {
+                    OwnedValue::Tuple(values)
+                }
+            }),
+            '{' => self.parse_map(),
+            '-' | '0'..='9' => self.parse_number(),
+            c if is_ident_start(c) => self.parse_named(),
+            _ => None,
+        }
+    }
+
+    /// Parses a (possibly path-qualified) name, followed by the struct
+    /// fields or tuple struct values which follow it, if any.
+    fn parse_named(&mut self) -> Option<OwnedValue> {
+        let start = self.pos;
+        loop {
+            self.skip_while(|c| is_ident_start(c) || c.is_ascii_digit());
+            if !self.input[self.pos..].starts_with("::") {
+                break;
+            }
+            self.pos += 2;
+        }
+        let name = &self.input[start..self.pos];
+        let after_name = self.pos;
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_fields(name),
+            Some('(') => {
+                let fields = self.parse_seq('(', ')')?;
+                Some(OwnedValue::TupleStruct {
+                    name: name.to_owned(),
+                    fields,
+                })
+            }
+            _ => {
+                self.pos = after_name;
+                Some(match name {
+                    "true" => OwnedValue::Bool(true),
+                    "false" => OwnedValue::Bool(false),
+                    "inf" => OwnedValue::Float(f64::INFINITY),
+                    "NaN" => OwnedValue::Float(f64::NAN),
+                    _ => OwnedValue::TupleStruct {
+                        name: name.to_owned(),
+                        fields: Vec::new(),
+                    },
+                })
+            }
+        }
+    }
+
+    fn parse_fields(&mut self, name: &str) -> Option<OwnedValue> {
+        self.enter()?;
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                break;
+            }
+            // `finish_non_exhaustive` ends the fields with `..`.
+            if self.input[self.pos..].starts_with("..") {
+                self.pos += 2;
+                self.skip_whitespace();
+                self.expect('}')?;
+                break;
+            }
+            let start = self.pos;
+            self.skip_while(|c| is_ident_start(c) || c.is_ascii_digit());
+            if start == self.pos {
+                return None;
+            }
+            let field = self.input[start..self.pos].to_owned();
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            fields.push((field, self.parse_value()?));
+            if !self.separator('}') {
+                return None;
+            }
+        }
+        self.depth -= 1;
+        Some(OwnedValue::Struct {
+            name: name.to_owned(),
+            fields,
+        })
+    }
+
+    fn parse_seq(&mut self, open: char, close: char) -> Option<Vec<OwnedValue>> {
+        self.enter()?;
+        self.expect(open)?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(close) {
+                break;
+            }
+            values.push(self.parse_value()?);
+            if !self.separator(close) {
+                return None;
+            }
+        }
+        self.depth -= 1;
+        Some(values)
+    }
+
+    /// Parses a map, or a set (which is written like a map without values).
+    fn parse_map(&mut self) -> Option<OwnedValue> {
+        self.enter()?;
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                break;
+            }
+            let value = self.parse_value()?;
+            self.skip_whitespace();
+            if values.is_empty() && self.eat(':') {
+                self.skip_whitespace();
+                entries.push((value, self.parse_value()?));
+            } else if entries.is_empty() {
+                values.push(value);
+            } else {
+                return None;
+            }
+            if !self.separator('}') {
+                return None;
+            }
+        }
+        self.depth -= 1;
+        if values.is_empty() {
+            Some(OwnedValue::Map(entries))
+        } else {
+            Some(OwnedValue::List(values))
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<OwnedValue> {
+        let start = self.pos;
+        let negative = self.eat('-');
+        if negative && self.input[self.pos..].starts_with("inf") {
+            self.pos += 3;
+            return Some(OwnedValue::Float(f64::NEG_INFINITY));
+        }
+        self.skip_while(|c| c.is_ascii_digit());
+        let mut is_float = false;
+        if self.input[self.pos..].starts_with('.') {
+            is_float = true;
+            self.pos += 1;
+            self.skip_while(|c| c.is_ascii_digit());
+        }
+        if let Some('e') | Some('E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some('+') | Some('-') = self.peek() {
+                self.pos += 1;
+            }
+            self.skip_while(|c| c.is_ascii_digit());
+        }
+        let text = &self.input[start..self.pos];
+        if !is_float {
+            if negative {
+                if let Ok(n) = text.parse::<i64>() {
+                    return Some(OwnedValue::Int(n));
+                }
+            } else if let Ok(n) = text.parse::<u64>() {
+                return Some(OwnedValue::Uint(n));
+            }
+        }
+        text.parse::<f64>().ok().map(OwnedValue::Float)
+    }
+
+    fn parse_str(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(s),
+                '\\' => s.push(self.parse_escape()?),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_char(&mut self) -> Option<char> {
+        self.expect('\'')?;
+        let c = match self.next()? {
+            '\\' => self.parse_escape()?,
+            c => c,
+        };
+        self.expect('\'')?;
+        Some(c)
+    }
+
+    /// Parses the remainder of an escape sequence, as written by
+    /// `char::escape_debug`.
+    fn parse_escape(&mut self) -> Option<char> {
+        Some(match self.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                self.expect('{')?;
+                let start = self.pos;
+                self.skip_while(|c| c.is_ascii_hexdigit());
+                let code = u32::from_str_radix(&self.input[start..self.pos], 16).ok()?;
+                self.expect('}')?;
+                char::from_u32(code)?
+            }
+            c @ '\\' | c @ '"' | c @ '\'' => c,
+            _ => return None,
+        })
+    }
+
+    /// Consumes the separator following an item in a container which ends
+    /// with `close`, returning `false` if there is neither a separator nor
+    /// the end of the container.
+    fn separator(&mut self, close: char) -> bool {
+        self.skip_whitespace();
+        self.eat(',') || self.peek() == Some(close)
+    }
+
+    fn enter(&mut self) -> Option<()> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        Some(())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        if self.eat(expected) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn skip_while(&mut self, f: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.skip_while(char::is_whitespace);
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos == self.input.len()
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Wrapper(Vec<u8>, ());
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum Shape {
+        Circle { radius: f64 },
+        Empty,
+    }
+
+    #[test]
+    fn derived_output() {
+        let mut map = BTreeMap::new();
+        map.insert("a\n", (Shape::Circle { radius: 1.5 }, Shape::Empty));
+        let tree = OwnedValue::List(vec![
+            OwnedValue::TupleStruct {
+                name: "Wrapper".to_owned(),
+                fields: vec![
+                    OwnedValue::List(vec![OwnedValue::Uint(1), OwnedValue::Uint(2)]),
+                    OwnedValue::Unit,
+                ],
+            },
+            OwnedValue::Map(vec![(
+                OwnedValue::Str("a\n".into()),
+                OwnedValue::Tuple(vec![
+                    OwnedValue::Struct {
+                        name: "Circle".to_owned(),
+                        fields: vec![("radius".to_owned(), OwnedValue::Float(1.5))],
+                    },
+                    OwnedValue::TupleStruct {
+                        name: "Empty".to_owned(),
+                        fields: Vec::new(),
+                    },
+                ]),
+            )]),
+        ]);
+
+        let wrapper = Wrapper(vec![1, 2], ());
+        let compact = format!("[{:?}, {:?}]", wrapper, map);
+        let pretty = format!("[{:#?}, {:#?}]", wrapper, map);
+        assert_eq!(from_str(&compact), tree);
+        assert_eq!(from_str(&pretty), tree);
+
+        assert_eq!(
+            from_str("Foo { a: 1 } trailing"),
+            OwnedValue::Str("Foo { a: 1 } trailing".into())
+        );
+    }
+}
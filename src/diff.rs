@@ -0,0 +1,326 @@
+//! Structural differences between values.
+//!
+//! [`diff`] compares two values field by field and element by element,
+//! producing a [`ValueDiff`] listing the paths at which they differ. This is
+//! useful for assertions in tests, and for logging what changed between two
+//! versions of a value (such as a program's configuration).
+//!
+//! [`diff`]: fn.diff.html
+//! [`ValueDiff`]: struct.ValueDiff.html
+use crate::{
+    owned::OwnedValue,
+    value::{Value, ValueExt},
+    visitor::{self, Error, PathSegment},
+};
+use std::{fmt, slice, vec};
+
+/// The differences between two values, as returned by [`diff`].
+///
+/// [`diff`]: fn.diff.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueDiff {
+    changes: Vec<Change>,
+}
+
+/// A single difference between two values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// A value is present only in the second value.
+    Added {
+        /// The path to the added value.
+        path: Vec<PathSegment>,
+        /// The added value.
+        value: OwnedValue,
+    },
+    /// A value is present only in the first value.
+    Removed {
+        /// The path to the removed value.
+        path: Vec<PathSegment>,
+        /// The removed value.
+        value: OwnedValue,
+    },
+    /// A value differs between the first and second values.
+    Changed {
+        /// The path to the changed value.
+        path: Vec<PathSegment>,
+        /// The value in the first value.
+        old: OwnedValue,
+        /// The value in the second value.
+        new: OwnedValue,
+    },
+}
+
+/// Compares `a` and `b`, returning the differences between them.
+///
+/// Both values are buffered into [`OwnedValue`]s, which are then compared
+/// recursively:
+///
+/// - Structs of the same type, and maps, are compared by field name or key.
+///   Fields present in only one of them are [added] or [removed].
+/// - Lists are compared element-wise. If one list is longer than the other,
+///   its remaining elements are added or removed.
+/// - Tuples and tuple structs of the same type and length are compared
+///   element-wise.
+/// - Any other values which are not equal are [changed].
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{diff::diff, value};
+/// let old = value!({ "port": 8080, "hosts": ["a"], "debug": true });
+/// let new = value!({ "port": 8081, "hosts": ["a", "b"] });
+///
+/// let diff = diff(&old, &new).unwrap();
+/// assert_eq!(
+///     diff.to_string(),
+///     r#"changed 8080 to 8081 at `port`; added "b" at `hosts[1]`; removed true at `debug`"#,
+/// );
+/// ```
+///
+/// # Errors
+///
+/// If buffering either value fails, the error is returned.
+///
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+/// [added]: enum.Change.html#variant.Added
+/// [removed]: enum.Change.html#variant.Removed
+/// [changed]: enum.Change.html#variant.Changed
+pub fn diff(a: &Value<'_>, b: &Value<'_>) -> Result<ValueDiff, Error> {
+    let a = OwnedValue::from_value(a)?;
+    let b = OwnedValue::from_value(b)?;
+    Ok(ValueDiff::between(a, b))
+}
+
+// === impl ValueDiff ===
+
+impl ValueDiff {
+    /// Returns the differences between two buffered values.
+    ///
+    /// This is equivalent to [`diff`], for values which have already been
+    /// buffered.
+    ///
+    /// [`diff`]: fn.diff.html
+    pub fn between(a: OwnedValue, b: OwnedValue) -> Self {
+        let mut diff = ValueDiff::default();
+        diff.value(&mut Vec::new(), a, b);
+        diff
+    }
+
+    /// Returns `true` if the values were equal.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the number of differences between the values.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns an iterator over the differences between the values.
+    pub fn iter(&self) -> slice::Iter<'_, Change> {
+        self.changes.iter()
+    }
+
+    /// Returns an iterator over the values which were added.
+    pub fn added(&self) -> impl Iterator<Item = (&[PathSegment], &OwnedValue)> {
+        self.iter().filter_map(|change| match change {
+            Change::Added { path, value } => Some((&path[..], value)),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the values which were removed.
+    pub fn removed(&self) -> impl Iterator<Item = (&[PathSegment], &OwnedValue)> {
+        self.iter().filter_map(|change| match change {
+            Change::Removed { path, value } => Some((&path[..], value)),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the values which were changed, with their
+    /// old and new values.
+    pub fn changed(&self) -> impl Iterator<Item = (&[PathSegment], &OwnedValue, &OwnedValue)> {
+        self.iter().filter_map(|change| match change {
+            Change::Changed { path, old, new } => Some((&path[..], old, new)),
+            _ => None,
+        })
+    }
+
+    fn value(&mut self, path: &mut Vec<PathSegment>, a: OwnedValue, b: OwnedValue) {
+        match (a, b) {
+            (OwnedValue::List(a), OwnedValue::List(b)) => self.list(path, a, b),
+            (OwnedValue::Tuple(a), OwnedValue::Tuple(b)) if a.len() == b.len() => {
+                self.list(path, a, b)
+            }
+            (
+                OwnedValue::TupleStruct {
+                    name: an,
+                    fields: af,
+                },
+                OwnedValue::TupleStruct {
+                    name: bn,
+                    fields: bf,
+                },
+            ) if an == bn && af.len() == bf.len() => self.list(path, af, bf),
+            (OwnedValue::Map(a), OwnedValue::Map(b)) => self.fields(
+                path,
+                a.into_iter().map(|(k, v)| (k.into_key(), v)).collect(),
+                b.into_iter().map(|(k, v)| (k.into_key(), v)).collect(),
+            ),
+            (
+                OwnedValue::Struct {
+                    name: an,
+                    fields: af,
+                },
+                OwnedValue::Struct {
+                    name: bn,
+                    fields: bf,
+                },
+            ) if an == bn => self.fields(path, af, bf),
+            (a, b) if a == b => {}
+            (old, new) => self.changes.push(Change::Changed {
+                path: path.clone(),
+                old,
+                new,
+            }),
+        }
+    }
+
+    fn list(&mut self, path: &mut Vec<PathSegment>, a: Vec<OwnedValue>, b: Vec<OwnedValue>) {
+        let (mut a, mut b) = (a.into_iter(), b.into_iter());
+        for i in 0.. {
+            path.push(PathSegment::Index(i));
+            match (a.next(), b.next()) {
+                (Some(a), Some(b)) => self.value(path, a, b),
+                (Some(value), None) => self.changes.push(Change::Removed {
+                    path: path.clone(),
+                    value,
+                }),
+                (None, Some(value)) => self.changes.push(Change::Added {
+                    path: path.clone(),
+                    value,
+                }),
+                (None, None) => {
+                    path.pop();
+                    return;
+                }
+            }
+            path.pop();
+        }
+    }
+
+    fn fields(
+        &mut self,
+        path: &mut Vec<PathSegment>,
+        a: Vec<(String, OwnedValue)>,
+        mut b: Vec<(String, OwnedValue)>,
+    ) {
+        for (name, a) in a {
+            let b = b
+                .iter()
+                .position(|(n, _)| *n == name)
+                .map(|idx| b.remove(idx).1);
+            path.push(PathSegment::Field(name));
+            match b {
+                Some(b) => self.value(path, a, b),
+                None => self.changes.push(Change::Removed {
+                    path: path.clone(),
+                    value: a,
+                }),
+            }
+            path.pop();
+        }
+        for (name, value) in b {
+            path.push(PathSegment::Field(name));
+            self.changes.push(Change::Added {
+                path: path.clone(),
+                value,
+            });
+            path.pop();
+        }
+    }
+}
+
+impl fmt::Display for ValueDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            fmt::Display::fmt(change, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for ValueDiff {
+    type Item = Change;
+    type IntoIter = vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ValueDiff {
+    type Item = &'a Change;
+    type IntoIter = slice::Iter<'a, Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// === impl Change ===
+
+impl Change {
+    /// Returns the path from the root of the compared values to the value
+    /// which differs.
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = |value: &OwnedValue| Value::borrowed(value).render_json();
+        match self {
+            Change::Added { value, .. } => write!(f, "added {}", json(value))?,
+            Change::Removed { value, .. } => write!(f, "removed {}", json(value))?,
+            Change::Changed { old, new, .. } => {
+                write!(f, "changed {} to {}", json(old), json(new))?
+            }
+        }
+        visitor::fmt_path(self.path(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_changes() {
+        let old = value!({ "a": { "b": [1, 2, 3] }, "c": true });
+        let new = value!({ "a": { "b": [1, 5] }, "c": "true" });
+        let diff = diff(&old, &new).unwrap();
+
+        assert_eq!(diff.len(), 3);
+        assert_eq!(
+            diff.changed()
+                .map(|(path, ..)| path.len())
+                .collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+        assert_eq!(diff.removed().count(), 1);
+        assert_eq!(
+            diff.to_string(),
+            "changed 2 to 5 at `a.b[1]`; removed 3 at `a.b[2]`; changed true to \"true\" at `c`"
+        );
+        assert!(super::diff(&old, &old).unwrap().is_empty());
+    }
+}
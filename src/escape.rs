@@ -0,0 +1,187 @@
+//! String escaping utilities for visitors which write text formats.
+//!
+//! These are used by the visitors in this crate, and are provided so that
+//! other `Visit` implementations escape strings consistently with them.
+use std::{borrow::Cow, fmt};
+
+/// A writer adapter that escapes everything written to it for inclusion in
+/// a JSON string.
+///
+/// The surrounding quotes are not written; see [`write_json_str`] for a
+/// function that writes a complete, quoted JSON string.
+///
+/// [`write_json_str`]: fn.write_json_str.html
+#[derive(Debug)]
+pub struct JsonEscape<W>(pub W);
+
+/// A writer adapter that escapes everything written to it for inclusion in
+/// a quoted logfmt value.
+///
+/// The surrounding quotes are not written; see [`write_logfmt_str`] for a
+/// function that writes a complete logfmt value, quoting it only if
+/// necessary.
+///
+/// [`write_logfmt_str`]: fn.write_logfmt_str.html
+#[derive(Debug)]
+pub struct LogfmtEscape<W>(pub W);
+
+/// A writer adapter that escapes everything written to it for inclusion in
+/// HTML text or a quoted attribute value.
+#[derive(Debug)]
+pub struct HtmlEscape<W>(pub W);
+
+/// Writes `s` as a quoted and escaped JSON string.
+pub fn write_json_str<W: fmt::Write + ?Sized>(writer: &mut W, s: &str) -> fmt::Result {
+    writer.write_char('"')?;
+    fmt::Write::write_str(&mut JsonEscape(&mut *writer), s)?;
+    writer.write_char('"')
+}
+
+/// Returns `true` if `s` must be quoted to be written as a logfmt value.
+///
+/// Values must be quoted if they are empty, or contain spaces, `=`, `"`, or
+/// control characters.
+pub fn needs_logfmt_quotes(s: &str) -> bool {
+    s.is_empty()
+        || s.chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c.is_control())
+}
+
+/// Writes `s` as a logfmt value, quoting and escaping it if necessary.
+pub fn write_logfmt_str<W: fmt::Write + ?Sized>(writer: &mut W, s: &str) -> fmt::Result {
+    if !needs_logfmt_quotes(s) {
+        return writer.write_str(s);
+    }
+    writer.write_char('"')?;
+    fmt::Write::write_str(&mut LogfmtEscape(&mut *writer), s)?;
+    writer.write_char('"')
+}
+
+/// Returns `true` if `s` must be quoted to be written as a CSV field.
+///
+/// Fields must be quoted if they contain commas, double quotes, or line
+/// breaks, or begin or end with whitespace.
+pub fn needs_csv_quotes(s: &str) -> bool {
+    s.chars().any(|c| c == ',' || c == '"' || c == '\n' || c == '\r')
+        || s.starts_with(char::is_whitespace)
+        || s.ends_with(char::is_whitespace)
+}
+
+/// Writes `s` as a CSV field, quoting it (and doubling any quotes it
+/// contains) only if necessary.
+pub fn write_csv_str<W: fmt::Write + ?Sized>(writer: &mut W, s: &str) -> fmt::Result {
+    if !needs_csv_quotes(s) {
+        return writer.write_str(s);
+    }
+    writer.write_char('"')?;
+    for (i, part) in s.split('"').enumerate() {
+        if i > 0 {
+            writer.write_str("\"\"")?;
+        }
+        writer.write_str(part)?;
+    }
+    writer.write_char('"')
+}
+
+/// Removes all control characters (such as newlines and terminal escape
+/// sequences) from `s`.
+///
+/// This is useful for writing untrusted strings to outputs, such as
+/// terminals, which don't escape them. If `s` contains no control
+/// characters, it is returned without allocating.
+pub fn strip_control(s: &str) -> Cow<'_, str> {
+    if s.chars().any(char::is_control) {
+        Cow::Owned(s.chars().filter(|c| !c.is_control()).collect())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for JsonEscape<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        escape(&mut self.0, s, |b| match b {
+            b'"' => Some("\\\""),
+            b'\\' => Some("\\\\"),
+            b'\n' => Some("\\n"),
+            b'\r' => Some("\\r"),
+            b'\t' => Some("\\t"),
+            0x08 => Some("\\b"),
+            0x0C => Some("\\f"),
+            0x00..=0x1F => Some(""),
+            _ => None,
+        })
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for LogfmtEscape<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        escape(&mut self.0, s, |b| match b {
+            b'"' => Some("\\\""),
+            b'\\' => Some("\\\\"),
+            b'\n' => Some("\\n"),
+            b'\r' => Some("\\r"),
+            b'\t' => Some("\\t"),
+            0x00..=0x1F | 0x7F => Some(""),
+            _ => None,
+        })
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for HtmlEscape<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        escape(&mut self.0, s, |b| match b {
+            b'&' => Some("&amp;"),
+            b'<' => Some("&lt;"),
+            b'>' => Some("&gt;"),
+            b'"' => Some("&quot;"),
+            b'\'' => Some("&#39;"),
+            _ => None,
+        })
+    }
+}
+
+/// Writes `s` to `writer`, replacing each ASCII byte for which `escape`
+/// returns `Some` with the returned escape sequence, or with a `\u` escape
+/// if the returned sequence is empty.
+fn escape<W: fmt::Write>(
+    writer: &mut W,
+    s: &str,
+    escape: impl Fn(u8) -> Option<&'static str>,
+) -> fmt::Result {
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape = match escape(b) {
+            Some(escape) => escape,
+            None => continue,
+        };
+        writer.write_str(&s[start..i])?;
+        if escape.is_empty() {
+            write!(writer, "\\u{:04x}", b)?;
+        } else {
+            writer.write_str(escape)?;
+        }
+        start = i + 1;
+    }
+    writer.write_str(&s[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logfmt() {
+        let mut out = String::new();
+        for s in &["plain", "", "has space", "a=b", "line\nbreak \"q\""] {
+            write_logfmt_str(&mut out, s).unwrap();
+            out.push(' ');
+        }
+        assert_eq!(out, r#"plain "" "has space" "a=b" "line\nbreak \"q\"" "#);
+    }
+
+    #[test]
+    fn strips_control() {
+        assert!(matches!(strip_control("fine"), Cow::Borrowed("fine")));
+        assert_eq!(strip_control("\u{1b}[31mred\u{1b}[0m\n"), "[31mred[0m");
+    }
+}
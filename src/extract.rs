@@ -0,0 +1,752 @@
+//! Extracting typed data from values.
+//!
+//! The [`FromValue`] trait reconstructs a concrete Rust type from a visited
+//! [`Value`] or a buffered [`OwnedValue`], the reverse of [`Visitable`].
+//! This allows consumers which receive values (such as a configuration
+//! reloader) to pull typed data back out of them, rather than only
+//! re-serializing them.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::{extract::FromValue, value};
+//! use std::collections::BTreeMap;
+//!
+//! let value = value!({ "ports": [80, 443], "name": "edge" });
+//! let config = BTreeMap::<String, valuable::owned::OwnedValue>::from_value(&value).unwrap();
+//! assert_eq!(Vec::<u16>::from_owned(config["ports"].clone()).unwrap(), [80, 443]);
+//!
+//! let err = Vec::<u8>::from_value(&value!([1, 256])).unwrap_err();
+//! assert_eq!(err.to_string(), "256 is out of range for `u8` at `[1]`");
+//! ```
+//!
+//! [`FromValue`]: trait.FromValue.html
+//! [`Value`]: ../value/struct.Value.html
+//! [`OwnedValue`]: ../owned/enum.OwnedValue.html
+//! [`Visitable`]: ../value/trait.Visitable.html
+use crate::{
+    owned::OwnedValue,
+    value::Value,
+    visitor::{self, fmt_path, PathSegment},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    error, fmt,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// A type which can be reconstructed from a value.
+///
+/// Implementations convert a buffered [`OwnedValue`] in
+/// [`from_owned`]; [`from_value`] buffers a [`Value`] and converts it.
+///
+/// Conversions are lossless: integers are converted between widths and
+/// signedness only if the value fits, and to floats only if the float
+/// represents the integer exactly. Strings are never parsed as numbers.
+///
+/// # Implementing `FromValue`
+///
+/// ```
+/// # use valuable::{extract::{ExtractError, FromValue}, owned::OwnedValue, value};
+/// struct Port(u16);
+///
+/// impl FromValue for Port {
+///     fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+///         match u16::from_owned(value)? {
+///             0 => Err(ExtractError::invalid_value("a non-zero port")),
+///             port => Ok(Port(port)),
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Port::from_value(&value!(8080)).unwrap().0, 8080);
+/// assert!(Port::from_value(&value!(0)).is_err());
+/// ```
+///
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+/// [`from_owned`]: #tymethod.from_owned
+/// [`from_value`]: #method.from_value
+/// [`Value`]: ../value/struct.Value.html
+pub trait FromValue: Sized {
+    /// Converts a buffered value into `Self`.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError>;
+
+    /// Visits `value` and converts the visited data into `Self`.
+    ///
+    /// # Errors
+    ///
+    /// If visiting `value` fails, or the visited data cannot be converted,
+    /// an error is returned.
+    fn from_value(value: &Value<'_>) -> Result<Self, ExtractError> {
+        let owned = OwnedValue::from_value(value).map_err(ExtractError::visit)?;
+        Self::from_owned(owned)
+    }
+}
+
+/// Derives [`FromValue`] for a struct or enum.
+///
+/// The derived implementation is the reverse of `#[derive(Visitable)]`: it
+/// reads the struct or enum from a value as a derived `Visitable`
+/// implementation with the same `#[visitable(...)]` attributes would visit
+/// it, so that a value visited and buffered into an [`OwnedValue`] may be
+/// extracted again. Structs with named fields may also be extracted from
+/// maps, such as those parsed from JSON; fields which are not extracted are
+/// ignored.
+///
+/// The attributes affect extraction as follows:
+///
+/// - fields are found by their name after `rename` and `rename_all`, and
+///   enum variants by theirs; enum variants are selected according to the
+///   enum's representation (`external`, `tag`, `content`, or `untagged`),
+/// - `skip` fields are not extracted, and are set to their `Default` value,
+/// - `skip_if_none` fields are set to `None` if they are missing, and
+///   `skip_if` fields to their `Default` value,
+/// - a `flatten` field is extracted from a map of the fields which remain
+///   after every other field has been extracted. Only one field may be
+///   flattened.
+///
+/// Since their values cannot be recovered, fields using `with` or `redact`
+/// must also be `skip`ped. Each type parameter used in the type of an
+/// extracted field is required to implement `FromValue`, unless the bounds
+/// are replaced with `#[visitable(bound = "...")]`, which replaces the
+/// bounds of both derives.
+///
+/// This macro requires the `derive` feature.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{extract::FromValue, owned::OwnedValue, value::{Value, Visitable}};
+/// #[derive(Debug, PartialEq, Visitable, FromValue)]
+/// #[visitable(tag = "kind", rename_all = "snake_case")]
+/// enum Event {
+///     Login { user: String, #[visitable(skip_if_none)] ip: Option<String> },
+///     Logout { user: String },
+/// }
+///
+/// let event = Event::Login { user: "alice".to_owned(), ip: None };
+/// let owned = OwnedValue::from_value(&Value::borrowed(&event)).unwrap();
+/// assert_eq!(Event::from_owned(owned).unwrap(), event);
+///
+/// // As would be parsed from `{"kind":"logout","user":"bob"}`.
+/// let parsed = OwnedValue::Map(vec![
+///     (OwnedValue::Str("kind".into()), OwnedValue::Str("logout".into())),
+///     (OwnedValue::Str("user".into()), OwnedValue::Str("bob".into())),
+/// ]);
+/// assert_eq!(Event::from_owned(parsed).unwrap(), Event::Logout { user: "bob".to_owned() });
+/// ```
+///
+/// [`FromValue`]: trait.FromValue.html
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+#[cfg(feature = "derive")]
+pub use valuable_derive::FromValue;
+
+/// An error returned when extracting a type from a value fails.
+///
+/// The error records the path from the root of the value to the value which
+/// could not be converted, which is included in its `Display` output.
+#[derive(Debug)]
+pub struct ExtractError {
+    kind: ErrorKind,
+    path: Vec<PathSegment>,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Visit(visitor::Error),
+    InvalidType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    InvalidValue(&'static str),
+    OutOfRange {
+        value: String,
+        ty: &'static str,
+    },
+    MissingField(&'static str),
+    UnknownVariant(String),
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+}
+
+// === impl ExtractError ===
+
+impl ExtractError {
+    fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns an error for a value which was not of the `expected` kind
+    /// (such as `"a string"`).
+    pub fn invalid_type(expected: &'static str, found: &OwnedValue) -> Self {
+        Self::new(ErrorKind::InvalidType {
+            expected,
+            found: describe(found),
+        })
+    }
+
+    /// Returns an error for a value of the right kind which was not
+    /// `expected` (such as `"a non-zero port"`).
+    pub fn invalid_value(expected: &'static str) -> Self {
+        Self::new(ErrorKind::InvalidValue(expected))
+    }
+
+    /// Returns an error for a struct or map which did not have the field
+    /// `name`.
+    pub fn missing_field(name: &'static str) -> Self {
+        Self::new(ErrorKind::MissingField(name))
+    }
+
+    /// Returns an error for an enum variant named `name` which does not
+    /// exist.
+    pub fn unknown_variant(name: &str) -> Self {
+        Self::new(ErrorKind::UnknownVariant(name.to_owned()))
+    }
+
+    /// Returns an error for a list or tuple of length `found`, when one of
+    /// length `expected` was required.
+    pub fn invalid_length(expected: usize, found: usize) -> Self {
+        Self::new(ErrorKind::InvalidLength { expected, found })
+    }
+
+    fn out_of_range(value: impl fmt::Display, ty: &'static str) -> Self {
+        Self::new(ErrorKind::OutOfRange {
+            value: value.to_string(),
+            ty,
+        })
+    }
+
+    fn visit(error: visitor::Error) -> Self {
+        let path = error.path().to_vec();
+        Self {
+            kind: ErrorKind::Visit(error),
+            path,
+        }
+    }
+
+    /// Records that this error occurred in the field or map entry `name`.
+    ///
+    /// Implementations of [`FromValue`] for containers should call this (or
+    /// [`in_index`]) on errors returned when converting their contents.
+    ///
+    /// [`FromValue`]: trait.FromValue.html
+    /// [`in_index`]: #method.in_index
+    pub fn in_field(self, name: impl Into<String>) -> Self {
+        self.in_segment(PathSegment::Field(name.into()))
+    }
+
+    /// Records that this error occurred in the element at `idx`.
+    pub fn in_index(self, idx: usize) -> Self {
+        self.in_segment(PathSegment::Index(idx))
+    }
+
+    fn in_segment(mut self, segment: PathSegment) -> Self {
+        // Segments are recorded as the error propagates outwards, so each
+        // one is the parent of those recorded before it.
+        self.path.insert(0, segment);
+        self
+    }
+
+    /// Returns the path from the root of the value to the value which could
+    /// not be converted.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// Returns `true` if this error was returned because a value was not of
+    /// the expected kind.
+    pub fn is_invalid_type(&self) -> bool {
+        matches!(self.kind, ErrorKind::InvalidType { .. })
+    }
+
+    /// Returns `true` if this error was returned because a number could not
+    /// be represented by the type it was converted to.
+    pub fn is_out_of_range(&self) -> bool {
+        matches!(self.kind, ErrorKind::OutOfRange { .. })
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            // The visitor error's message already includes its path.
+            ErrorKind::Visit(ref error) => return fmt::Display::fmt(error, f),
+            ErrorKind::InvalidType { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)?
+            }
+            ErrorKind::InvalidValue(expected) => write!(f, "invalid value, expected {}", expected)?,
+            ErrorKind::OutOfRange { ref value, ty } => {
+                write!(f, "{} is out of range for `{}`", value, ty)?
+            }
+            ErrorKind::MissingField(name) => write!(f, "missing field `{}`", name)?,
+            ErrorKind::UnknownVariant(ref name) => write!(f, "unknown variant `{}`", name)?,
+            ErrorKind::InvalidLength { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)?
+            }
+        }
+        fmt_path(&self.path, f)
+    }
+}
+
+impl error::Error for ExtractError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.kind {
+            ErrorKind::Visit(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returns a description of the kind of `value`, for error messages.
+fn describe(value: &OwnedValue) -> &'static str {
+    match value {
+        OwnedValue::Unit => "unit",
+        OwnedValue::Bool(_) => "a bool",
+        OwnedValue::Uint(_) => "an unsigned integer",
+        OwnedValue::Int(_) => "a signed integer",
+        OwnedValue::Float(_) => "a float",
+        OwnedValue::Str(_) => "a string",
+        OwnedValue::Bytes(_) => "bytes",
+        OwnedValue::List(_) => "a list",
+        OwnedValue::Tuple(_) => "a tuple",
+        OwnedValue::Map(_) => "a map",
+        OwnedValue::Struct { .. } => "a struct",
+        OwnedValue::TupleStruct { .. } => "a tuple struct",
+    }
+}
+
+// === impl OwnedValue ===
+
+/// Coercions.
+///
+/// Unlike [`FromValue`], these methods convert between kinds of values
+/// where doing so loses no information, such as from a float with no
+/// fractional part to an integer, or from a string containing a number to
+/// that number. They are intended for normalizing data whose kinds are not
+/// known ahead of time, such as user input.
+///
+/// [`FromValue`]: trait.FromValue.html
+impl OwnedValue {
+    /// Converts this value to a `u64`.
+    ///
+    /// Integers are converted if they are not negative, floats if they are
+    /// whole numbers in range, and strings if they contain such a number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::owned::OwnedValue;
+    /// assert_eq!(OwnedValue::Float(3.0).coerce_u64().unwrap(), 3);
+    /// assert_eq!(OwnedValue::Str("42".into()).coerce_u64().unwrap(), 42);
+    /// assert!(OwnedValue::Int(-1).coerce_u64().unwrap_err().is_out_of_range());
+    /// assert!(OwnedValue::Float(1.5).coerce_u64().is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the value is not a number (or a string containing one), or the
+    /// number cannot be represented exactly as a `u64`, an error is
+    /// returned.
+    pub fn coerce_u64(&self) -> Result<u64, ExtractError> {
+        match *self {
+            OwnedValue::Uint(v) => Ok(v),
+            OwnedValue::Int(v) => {
+                u64::try_from(v).map_err(|_| ExtractError::out_of_range(v, "u64"))
+            }
+            // `u64::MAX as f64` is 2^64, which is out of range.
+            OwnedValue::Float(v) if v.fract() == 0.0 && v >= 0.0 && v < u64::MAX as f64 => {
+                Ok(v as u64)
+            }
+            OwnedValue::Float(v) => Err(ExtractError::out_of_range(v, "u64")),
+            OwnedValue::Str(ref s) => match s.parse() {
+                Ok(v) => Ok(v),
+                Err(_) => parse_float(s)?.coerce_u64(),
+            },
+            ref value => Err(ExtractError::invalid_type("a number", value)),
+        }
+    }
+
+    /// Converts this value to an `f64`.
+    ///
+    /// Integers are converted if the float represents them exactly, and
+    /// strings if they contain a number.
+    ///
+    /// # Errors
+    ///
+    /// If the value is not a number (or a string containing one), or an
+    /// integer cannot be represented exactly as an `f64`, an error is
+    /// returned.
+    pub fn coerce_f64(&self) -> Result<f64, ExtractError> {
+        match *self {
+            OwnedValue::Str(ref s) => parse_float(s)?.coerce_f64(),
+            ref value => f64::from_owned(value.clone()),
+        }
+    }
+
+    /// Converts this value to a `String`.
+    ///
+    /// Strings are returned as-is, and other scalars are formatted as they
+    /// would be as map keys (so the float `1.0` becomes `"1.0"`). Byte
+    /// strings are converted if they are valid UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// If the value is not a scalar, or is a byte string which is not valid
+    /// UTF-8, an error is returned.
+    pub fn coerce_string(&self) -> Result<String, ExtractError> {
+        match self {
+            OwnedValue::Str(_)
+            | OwnedValue::Bool(_)
+            | OwnedValue::Uint(_)
+            | OwnedValue::Int(_)
+            | OwnedValue::Float(_) => Ok(self.clone().into_key()),
+            OwnedValue::Bytes(bytes) => String::from_utf8(bytes.clone())
+                .map_err(|_| ExtractError::invalid_value("valid UTF-8")),
+            value => Err(ExtractError::invalid_type("a scalar", value)),
+        }
+    }
+}
+
+/// Parses a string being coerced to a number.
+fn parse_float(s: &str) -> Result<OwnedValue, ExtractError> {
+    s.parse()
+        .map(OwnedValue::Float)
+        .map_err(|_| ExtractError::invalid_value("a number"))
+}
+
+// === impl FromValue ===
+
+impl FromValue for OwnedValue {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        Ok(value)
+    }
+}
+
+impl FromValue for () {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::Unit => Ok(()),
+            // Named unit values, such as unit structs and variants, are
+            // buffered as empty tuple structs.
+            OwnedValue::Tuple(ref fields) | OwnedValue::TupleStruct { ref fields, .. }
+                if fields.is_empty() =>
+            {
+                Ok(())
+            }
+            value => Err(ExtractError::invalid_type("unit", &value)),
+        }
+    }
+}
+
+impl<T: ?Sized> FromValue for PhantomData<T> {
+    /// Unit values are converted to `PhantomData`, as they are for `()`.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        <()>::from_owned(value).map(|()| PhantomData)
+    }
+}
+
+impl FromValue for bool {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::Bool(v) => Ok(v),
+            value => Err(ExtractError::invalid_type("a bool", &value)),
+        }
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl FromValue for $ty {
+                fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+                    let ty = stringify!($ty);
+                    match value {
+                        OwnedValue::Uint(v) => {
+                            <$ty>::try_from(v).map_err(|_| ExtractError::out_of_range(v, ty))
+                        }
+                        OwnedValue::Int(v) => {
+                            <$ty>::try_from(v).map_err(|_| ExtractError::out_of_range(v, ty))
+                        }
+                        value => Err(ExtractError::invalid_type("an integer", &value)),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+from_integer!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl FromValue for f64 {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::Float(v) => Ok(v),
+            OwnedValue::Uint(v) if v as f64 as u64 == v && v != u64::MAX => Ok(v as f64),
+            OwnedValue::Int(v) if v as f64 as i64 == v && v != i64::MAX => Ok(v as f64),
+            OwnedValue::Uint(v) => Err(ExtractError::out_of_range(v, "f64")),
+            OwnedValue::Int(v) => Err(ExtractError::out_of_range(v, "f64")),
+            value => Err(ExtractError::invalid_type("a number", &value)),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        let v = f64::from_owned(value)?;
+        let narrowed = v as f32;
+        // NaN is not equal to itself, but is represented as an `f32`.
+        if f64::from(narrowed) == v || v.is_nan() {
+            Ok(narrowed)
+        } else {
+            Err(ExtractError::out_of_range(v, "f32"))
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::Str(v) => Ok(v.into_string()),
+            value => Err(ExtractError::invalid_type("a string", &value)),
+        }
+    }
+}
+
+impl FromValue for char {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        let s = String::from_owned(value)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(ExtractError::invalid_value("a single character")),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    /// Unit values are converted to `None`, and any other value to `Some`.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::Unit => Ok(None),
+            value => T::from_owned(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Box<T> {
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        T::from_owned(value).map(Box::new)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    /// Lists and tuples are converted element by element. Byte strings are
+    /// converted as lists of unsigned integers, so they may be extracted as
+    /// a `Vec<u8>`.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        match value {
+            OwnedValue::List(values) | OwnedValue::Tuple(values) => values
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| T::from_owned(v).map_err(|e| e.in_index(i)))
+                .collect(),
+            OwnedValue::Bytes(bytes) => bytes
+                .into_iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    T::from_owned(OwnedValue::Uint(u64::from(b))).map_err(|e| e.in_index(i))
+                })
+                .collect(),
+            value => Err(ExtractError::invalid_type("a list", &value)),
+        }
+    }
+}
+
+impl<K, V> FromValue for BTreeMap<K, V>
+where
+    K: FromValue + Ord,
+    V: FromValue,
+{
+    /// Maps are converted entry by entry. Structs are converted as maps from
+    /// their field names to their values.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        entries(value)?.collect()
+    }
+}
+
+impl<K, V, S> FromValue for HashMap<K, V, S>
+where
+    K: FromValue + Eq + Hash,
+    V: FromValue,
+    S: BuildHasher + Default,
+{
+    /// Maps are converted entry by entry. Structs are converted as maps from
+    /// their field names to their values.
+    fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+        entries(value)?.collect()
+    }
+}
+
+/// Returns an iterator converting the entries of a map or struct.
+fn entries<K, V>(
+    value: OwnedValue,
+) -> Result<impl Iterator<Item = Result<(K, V), ExtractError>>, ExtractError>
+where
+    K: FromValue,
+    V: FromValue,
+{
+    let kvs = match value {
+        OwnedValue::Map(kvs) => kvs,
+        OwnedValue::Struct { fields, .. } => fields
+            .into_iter()
+            .map(|(k, v)| (OwnedValue::Str(k.into()), v))
+            .collect(),
+        value => return Err(ExtractError::invalid_type("a map", &value)),
+    };
+    Ok(kvs.into_iter().map(|(k, v)| {
+        let name = k.clone().into_key();
+        let k = K::from_owned(k).map_err(|e| e.in_field(name.clone()))?;
+        let v = V::from_owned(v).map_err(|e| e.in_field(name))?;
+        Ok((k, v))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalars_and_containers() {
+        assert_eq!(u8::from_value(&Value::borrowed(&255u64)).unwrap(), 255);
+        assert_eq!(i8::from_value(&Value::borrowed(&-1i64)).unwrap(), -1);
+        assert_eq!(f64::from_value(&Value::borrowed(&3u64)).unwrap(), 3.0);
+        assert!(f64::from_value(&Value::borrowed(&u64::MAX)).is_err());
+        assert!(u32::from_value(&Value::borrowed(&-1i64)).is_err());
+        assert_eq!(
+            Option::<String>::from_value(&Value::borrowed(&"a")).unwrap(),
+            Some("a".to_owned())
+        );
+        assert_eq!(Option::<String>::from_value(&Value::empty()).unwrap(), None);
+
+        let bytes = crate::value::Bytes(b"hi");
+        assert_eq!(
+            Vec::<u8>::from_value(&Value::borrowed(&bytes)).unwrap(),
+            b"hi"
+        );
+
+        let nested = value!({ "a": [1], "b": [2, -3] });
+        let err = HashMap::<String, Vec<u8>>::from_value(&nested).unwrap_err();
+        assert_eq!(
+            err.path(),
+            [PathSegment::Field("b".to_owned()), PathSegment::Index(1)]
+        );
+        assert_eq!(err.to_string(), "-3 is out of range for `u8` at `b[1]`");
+
+        let err = String::from_value(&nested).unwrap_err();
+        assert_eq!(err.to_string(), "expected a string, found a map");
+    }
+
+    #[test]
+    fn coercions() {
+        let s = |s: &str| OwnedValue::Str(s.into());
+        assert_eq!(s("-2.5e1").coerce_f64().unwrap(), -25.0);
+        assert_eq!(OwnedValue::Uint(7).coerce_f64().unwrap(), 7.0);
+        assert!(OwnedValue::Uint(u64::MAX)
+            .coerce_f64()
+            .unwrap_err()
+            .is_out_of_range());
+        assert!(s("1e20").coerce_u64().unwrap_err().is_out_of_range());
+        assert!(!s("ten").coerce_u64().unwrap_err().is_out_of_range());
+        assert!(OwnedValue::Unit.coerce_u64().unwrap_err().is_invalid_type());
+        assert_eq!(OwnedValue::Float(1.0).coerce_string().unwrap(), "1.0");
+        assert_eq!(OwnedValue::Bool(true).coerce_string().unwrap(), "true");
+        assert!(OwnedValue::List(Vec::new()).coerce_string().is_err());
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived() {
+        use crate::value::Visitable;
+        use std::fmt::Debug;
+
+        fn round_trip<T: Visitable + FromValue + Debug + PartialEq>(value: T) {
+            let owned = OwnedValue::from_value(&Value::borrowed(&value)).unwrap();
+            assert_eq!(T::from_owned(owned).unwrap(), value);
+        }
+
+        #[derive(Debug, Default, PartialEq, Visitable, FromValue)]
+        struct Unit;
+
+        #[derive(Debug, PartialEq, Visitable, FromValue)]
+        struct Pair(u8, #[visitable(skip)] Unit, String);
+
+        #[derive(Debug, PartialEq, Visitable, FromValue)]
+        #[visitable(rename_all = "kebab-case")]
+        struct Event<T> {
+            r#type: T,
+            #[visitable(skip_if_none)]
+            trace_id: Option<u64>,
+            #[visitable(flatten)]
+            extra: BTreeMap<String, u64>,
+        }
+
+        round_trip(Unit);
+        round_trip(Pair(1, Unit, "a".to_owned()));
+        let mut extra = BTreeMap::new();
+        extra.insert("x".to_owned(), 1);
+        round_trip(Event {
+            r#type: "click".to_owned(),
+            trace_id: None,
+            extra,
+        });
+
+        macro_rules! shape {
+            ($(#[$meta:meta])* $name:ident) => {
+                #[derive(Debug, PartialEq, Visitable, FromValue)]
+                $(#[$meta])*
+                enum $name {
+                    Unit,
+                    Newtype(BTreeMap<String, bool>),
+                    #[visitable(rename_all = "camelCase")]
+                    Point { x_pos: i64, y_pos: i64 },
+                }
+
+                let mut map = BTreeMap::new();
+                map.insert("k".to_owned(), true);
+                round_trip($name::Unit);
+                round_trip($name::Newtype(map));
+                round_trip($name::Point { x_pos: 1, y_pos: -1 });
+            };
+        }
+
+        shape!(Named);
+        shape!(
+            #[visitable(external, rename_all = "snake_case")]
+            External
+        );
+        shape!(
+            #[visitable(tag = "type")]
+            Internal
+        );
+        shape!(
+            #[visitable(tag = "t", content = "c")]
+            Adjacent
+        );
+        shape!(
+            #[visitable(untagged)]
+            Untagged
+        );
+
+        let err = Adjacent::from_value(&value!({ "t": "Point", "c": { "xPos": 1 } })).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `yPos` at `c`");
+        let err = Internal::from_value(&value!({ "type": "Nope" })).unwrap_err();
+        assert_eq!(err.to_string(), "unknown variant `Nope`");
+    }
+}
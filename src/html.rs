@@ -0,0 +1,296 @@
+//! HTML output.
+//!
+//! This module provides [`HtmlVisit`], a `Visit` implementation that writes
+//! values as HTML fragments, for embedding in web-based debugging tools.
+//!
+//! This module requires the `html` feature.
+//!
+//! [`HtmlVisit`]: struct.HtmlVisit.html
+use crate::{
+    escape::HtmlEscape,
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt::{self, Write as _};
+
+/// A `Visit` implementation that writes values as HTML.
+///
+/// Values are written as follows:
+///
+/// - maps, structs, and records are written as definition lists
+///   (`<dl>`), with each key in a `<dt>` and each value in a `<dd>`,
+/// - lists and tuples are written as ordered lists (`<ol>`), with each item
+///   in an `<li>`,
+/// - all other values are written as text, in a `<span>` whose class names
+///   the kind of value: `str`, `num`, `bool`, or `unit`.
+///
+/// The type name of a named struct, tuple struct, or unit value is written
+/// in its element's `data-type` attribute. All text is escaped, so the
+/// output may be embedded in a page even if it contains untrusted strings.
+/// No styles are included; the output is intended to be styled by the page
+/// it is embedded in.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{html::HtmlVisit, value};
+/// let mut visit = HtmlVisit::new(String::new());
+/// value!({ "tags": ["<b>"], "n": 1 }).visit(&mut visit).unwrap();
+/// assert_eq!(
+///     visit.into_inner(),
+///     "<dl><dt>tags</dt><dd><ol><li><span class=\"str\">&lt;b&gt;</span></li></ol></dd>\
+///      <dt>n</dt><dd><span class=\"num\">1</span></dd></dl>",
+/// );
+/// ```
+pub struct HtmlVisit<W> {
+    writer: W,
+    stack: Vec<Container>,
+    /// The name passed to `named_type` for the next value, if any.
+    name: Option<String>,
+    /// Set when scalars should be written as bare text, as map keys are.
+    raw: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+// === impl HtmlVisit ===
+
+impl<W: fmt::Write> HtmlVisit<W> {
+    /// Returns a new `HtmlVisit` that writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            stack: Vec::new(),
+            name: None,
+            raw: false,
+        }
+    }
+
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Mutably borrows the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `HtmlVisit`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn in_list(&self) -> bool {
+        matches!(
+            self.stack.last(),
+            Some(Container::List) | Some(Container::Tuple)
+        )
+    }
+
+    /// Writes the start of a list item, if the value is in a list.
+    fn begin_value(&mut self) -> fmt::Result {
+        if self.in_list() {
+            self.writer.write_str("<li>")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the end of a list item, if the value is in a list.
+    fn end_value(&mut self) -> fmt::Result {
+        if self.in_list() {
+            self.writer.write_str("</li>")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the `data-type` attribute for the current value, if it is
+    /// named.
+    fn type_attr(&mut self) -> fmt::Result {
+        if let Some(name) = self.name.take() {
+            self.writer.write_str(" data-type=\"")?;
+            HtmlEscape(&mut self.writer).write_str(&name)?;
+            self.writer.write_char('"')?;
+        }
+        Ok(())
+    }
+
+    fn scalar(&mut self, class: &str, args: fmt::Arguments<'_>) -> VisitResult {
+        if self.raw {
+            HtmlEscape(&mut self.writer).write_fmt(args)?;
+            return Ok(());
+        }
+        self.begin_value()?;
+        write!(self.writer, "<span class=\"{}\"", class)?;
+        self.type_attr()?;
+        self.writer.write_char('>')?;
+        HtmlEscape(&mut self.writer).write_fmt(args)?;
+        self.writer.write_str("</span>")?;
+        self.end_value()?;
+        Ok(())
+    }
+
+    fn open(&mut self, kind: Container) -> VisitResult {
+        self.begin_value()?;
+        match kind {
+            Container::Map | Container::Struct => self.writer.write_str("<dl")?,
+            Container::List | Container::Tuple => self.writer.write_str("<ol")?,
+        }
+        self.type_attr()?;
+        self.writer.write_char('>')?;
+        self.stack.push(kind);
+        Ok(())
+    }
+
+    fn close(&mut self, kind: Container) -> VisitResult {
+        match self.stack.pop() {
+            Some(open) if open == kind => {}
+            _ => return Err(Error::unbalanced()),
+        }
+        match kind {
+            Container::Map | Container::Struct => self.writer.write_str("</dl>")?,
+            Container::List | Container::Tuple => self.writer.write_str("</ol>")?,
+        }
+        self.end_value()?;
+        Ok(())
+    }
+}
+
+impl<W: fmt::Write> Visit for HtmlVisit<W> {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.scalar("num", format_args!("{}", value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.scalar("num", format_args!("{}", value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.scalar("num", format_args!("{:?}", value))
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.scalar("str", format_args!("{}", value))
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.scalar("bool", format_args!("{}", value))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        // A named unit value (such as a unit struct or enum variant) is
+        // written as its name.
+        match self.name.take() {
+            Some(name) => self.scalar("unit", format_args!("{}", name)),
+            None => self.scalar("unit", format_args!("()")),
+        }
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.writer.write_str("<dt>")?;
+        // Always use a trait object for the key's writer, so that visiting
+        // keys doesn't recursively instantiate `HtmlVisit<&mut W>`.
+        let writer: &mut dyn fmt::Write = &mut self.writer;
+        let mut key = HtmlVisit::new(writer);
+        key.raw = true;
+        k.visit(&mut key)?;
+        self.writer.write_str("</dt><dd>")?;
+        v.visit(self)?;
+        self.writer.write_str("</dd>")?;
+        Ok(())
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.scalar("str", args)
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        self.name = Some(name.to_owned());
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple)
+    }
+}
+
+impl<W: fmt::Debug> fmt::Debug for HtmlVisit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HtmlVisit")
+            .field("writer", &self.writer)
+            .field("depth", &self.stack.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owned::OwnedValue;
+
+    #[test]
+    fn named_and_escaped() {
+        let value = OwnedValue::List(vec![
+            OwnedValue::Struct {
+                name: "Point<'a>".to_owned(),
+                fields: vec![("x".to_owned(), OwnedValue::Int(-1))],
+            },
+            OwnedValue::TupleStruct {
+                name: "None".to_owned(),
+                fields: Vec::new(),
+            },
+            OwnedValue::Map(vec![(
+                OwnedValue::Str("\"k\" & co".into()),
+                OwnedValue::Unit,
+            )]),
+        ]);
+        let mut visit = HtmlVisit::new(String::new());
+        Value::borrowed(&value).visit(&mut visit).unwrap();
+        assert_eq!(
+            visit.into_inner(),
+            "<ol>\
+             <li><dl data-type=\"Point&lt;&#39;a&gt;\"><dt>x</dt><dd><span class=\"num\">-1</span></dd></dl></li>\
+             <li><span class=\"unit\">None</span></li>\
+             <li><dl><dt>&quot;k&quot; &amp; co</dt><dd><span class=\"unit\">()</span></dd></dl></li>\
+             </ol>"
+        );
+    }
+}
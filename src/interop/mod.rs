@@ -0,0 +1,7 @@
+//! Conversions between `OwnedValue`s and types from other crates.
+#[cfg(feature = "opentelemetry")]
+mod opentelemetry;
+#[cfg(feature = "serde_json")]
+mod serde_json;
+#[cfg(feature = "toml")]
+mod toml;
@@ -0,0 +1,154 @@
+use crate::{
+    owned::OwnedValue,
+    value::{Value, ValueExt},
+};
+use opentelemetry::{Array, KeyValue, StringValue, Value as OtelValue};
+
+/// The key used for the attribute produced from a value which is not a map
+/// or struct.
+const VALUE_KEY: &str = "value";
+
+impl OwnedValue {
+    /// Converts this value into a sequence of OpenTelemetry attributes.
+    ///
+    /// OpenTelemetry attributes are flat, so nested maps and structs are
+    /// flattened following the OpenTelemetry attribute naming conventions:
+    /// the key of each nested field is the keys of its enclosing fields and
+    /// its own key joined with `.`, so `{ "http": { "method": "GET" } }`
+    /// becomes the attribute `http.method = "GET"`.
+    ///
+    /// Lists, tuples, and tuple structs are converted as described in the
+    /// `From<OwnedValue>` implementation for `opentelemetry::Value`. Unit
+    /// values, which have no OpenTelemetry representation, are omitted. A
+    /// value which is not a map or struct produces a single attribute with
+    /// the key `value`.
+    pub fn into_key_values(self) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        flatten(&mut String::new(), self, &mut attributes);
+        attributes
+    }
+}
+
+/// Converts an `OwnedValue` into an OpenTelemetry attribute value.
+///
+/// Lists of booleans, integers, floats, or strings are converted into arrays.
+/// Since OpenTelemetry arrays must be homogeneous and may not be nested, any
+/// other list, map, or struct is converted into a string containing its JSON
+/// representation. Unsigned integers too large for an `i64` are converted
+/// into strings, and unit values into empty strings.
+impl From<OwnedValue> for OtelValue {
+    fn from(value: OwnedValue) -> Self {
+        match value {
+            OwnedValue::Unit => OtelValue::String(StringValue::from("")),
+            OwnedValue::Bool(v) => OtelValue::Bool(v),
+            OwnedValue::Bytes(v) => {
+                OtelValue::Array(Array::I64(v.into_iter().map(i64::from).collect()))
+            }
+            OwnedValue::Uint(v) if v <= i64::MAX as u64 => OtelValue::I64(v as i64),
+            OwnedValue::Uint(v) => OtelValue::String(v.to_string().into()),
+            OwnedValue::Int(v) => OtelValue::I64(v),
+            OwnedValue::Float(v) => OtelValue::F64(v),
+            OwnedValue::Str(v) => OtelValue::String(v.into_string().into()),
+            OwnedValue::List(ref vs)
+            | OwnedValue::Tuple(ref vs)
+            | OwnedValue::TupleStruct { fields: ref vs, .. } => match array(vs) {
+                Some(array) => OtelValue::Array(array),
+                None => json(&value),
+            },
+            value => json(&value),
+        }
+    }
+}
+
+fn flatten(key: &mut String, value: OwnedValue, attributes: &mut Vec<KeyValue>) {
+    let fields = match value {
+        OwnedValue::Unit => return,
+        OwnedValue::Map(pairs) => pairs.into_iter().map(|(k, v)| (k.into_key(), v)).collect(),
+        OwnedValue::Struct { fields, .. } => fields,
+        value if key.is_empty() => {
+            attributes.push(KeyValue::new(VALUE_KEY, OtelValue::from(value)));
+            return;
+        }
+        value => {
+            attributes.push(KeyValue::new(key.clone(), OtelValue::from(value)));
+            return;
+        }
+    };
+    let len = key.len();
+    for (name, value) in fields {
+        if len > 0 {
+            key.push('.');
+        }
+        key.push_str(&name);
+        flatten(key, value, attributes);
+        key.truncate(len);
+    }
+}
+
+/// Converts a list of values into a homogeneous OpenTelemetry array, if
+/// possible.
+fn array(values: &[OwnedValue]) -> Option<Array> {
+    fn collect<T>(values: &[OwnedValue], f: impl Fn(&OwnedValue) -> Option<T>) -> Option<Vec<T>> {
+        values.iter().map(f).collect()
+    }
+
+    let array = match values.first()? {
+        OwnedValue::Bool(_) => Array::Bool(collect(values, |v| match v {
+            OwnedValue::Bool(v) => Some(*v),
+            _ => None,
+        })?),
+        OwnedValue::Uint(_) | OwnedValue::Int(_) => Array::I64(collect(values, |v| match *v {
+            OwnedValue::Uint(v) if v <= i64::MAX as u64 => Some(v as i64),
+            OwnedValue::Int(v) => Some(v),
+            _ => None,
+        })?),
+        OwnedValue::Float(_) => Array::F64(collect(values, |v| match v {
+            OwnedValue::Float(v) => Some(*v),
+            _ => None,
+        })?),
+        OwnedValue::Str(_) => Array::String(collect(values, |v| match v {
+            OwnedValue::Str(v) => Some(v.to_string().into()),
+            _ => None,
+        })?),
+        _ => return None,
+    };
+    Some(array)
+}
+
+fn json(value: &OwnedValue) -> OtelValue {
+    OtelValue::String(Value::borrowed(value).render_json().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_nested_values() {
+        let method = "GET";
+        let value = value!({
+            "http": { "method": method, "status": 200 },
+            "tags": ["a", "b"],
+            "mixed": [1, "a"],
+            "none": (),
+        });
+        let attributes = value.to_key_values().unwrap();
+        assert_eq!(
+            attributes,
+            vec![
+                KeyValue::new("http.method", "GET"),
+                KeyValue::new("http.status", 200i64),
+                KeyValue::new(
+                    "tags",
+                    OtelValue::Array(Array::String(vec!["a".into(), "b".into()]))
+                ),
+                KeyValue::new("mixed", r#"[1,"a"]"#),
+            ]
+        );
+
+        assert_eq!(
+            Value::borrowed(&1.5).to_key_values().unwrap(),
+            vec![KeyValue::new("value", 1.5)]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use crate::owned::OwnedValue;
+use serde_json::{Number, Value as JsonValue};
+
+impl From<JsonValue> for OwnedValue {
+    fn from(value: JsonValue) -> Self {
+        match value {
+            JsonValue::Null => OwnedValue::Unit,
+            JsonValue::Bool(v) => OwnedValue::Bool(v),
+            JsonValue::Number(n) => n.into(),
+            JsonValue::String(v) => OwnedValue::Str(v.into()),
+            JsonValue::Array(vs) => OwnedValue::List(vs.into_iter().map(Into::into).collect()),
+            JsonValue::Object(map) => OwnedValue::Map(
+                map.into_iter()
+                    .map(|(k, v)| (OwnedValue::Str(k.into()), v.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Number> for OwnedValue {
+    fn from(n: Number) -> Self {
+        if let Some(n) = n.as_u64() {
+            OwnedValue::Uint(n)
+        } else if let Some(n) = n.as_i64() {
+            OwnedValue::Int(n)
+        } else if let Some(n) = n.as_f64() {
+            OwnedValue::Float(n)
+        } else {
+            OwnedValue::Str(n.to_string().into())
+        }
+    }
+}
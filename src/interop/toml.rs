@@ -0,0 +1,21 @@
+use crate::owned::OwnedValue;
+use toml::Value as TomlValue;
+
+impl From<TomlValue> for OwnedValue {
+    fn from(value: TomlValue) -> Self {
+        match value {
+            TomlValue::String(v) => OwnedValue::Str(v.into()),
+            TomlValue::Integer(v) => OwnedValue::Int(v),
+            TomlValue::Float(v) => OwnedValue::Float(v),
+            TomlValue::Boolean(v) => OwnedValue::Bool(v),
+            TomlValue::Datetime(v) => OwnedValue::Str(v.to_string().into()),
+            TomlValue::Array(vs) => OwnedValue::List(vs.into_iter().map(Into::into).collect()),
+            TomlValue::Table(table) => OwnedValue::Map(
+                table
+                    .into_iter()
+                    .map(|(k, v)| (OwnedValue::Str(k.into()), v.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
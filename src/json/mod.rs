@@ -0,0 +1,23 @@
+//! JSON support.
+//!
+//! This module provides [`JsonVisit`], a `Visit` implementation that writes
+//! visited values as JSON, and [`NdjsonWriter`], which writes successive
+//! values to an I/O stream as newline-delimited JSON. When the `json`
+//! feature is enabled, it also provides a parser that reads JSON text into
+//! an [`OwnedValue`], which may then be re-emitted through any `Visit`
+//! implementation.
+//!
+//! [`JsonVisit`]: struct.JsonVisit.html
+//! [`NdjsonWriter`]: struct.NdjsonWriter.html
+//! [`OwnedValue`]: ../owned/enum.OwnedValue.html
+mod ndjson;
+mod ser;
+
+pub use self::ndjson::NdjsonWriter;
+pub use self::ser::JsonVisit;
+
+#[cfg(feature = "json")]
+mod parse;
+
+#[cfg(feature = "json")]
+pub use self::parse::{from_str, ParseError};
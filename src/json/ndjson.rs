@@ -0,0 +1,262 @@
+use super::JsonVisit;
+use crate::{
+    float::FloatFormat,
+    key::KeyPolicy,
+    record::Record,
+    value::Value,
+    visitor::{Error, SerializeConfig, Visit, VisitResult},
+    writer::{FlushPolicy, IoWriter},
+};
+use std::{fmt, io};
+
+/// Writes successive values as newline-delimited JSON.
+///
+/// Each value or record written to an `NdjsonWriter` is written as JSON (as
+/// by a [`JsonVisit`]) on a line of its own. Since JSON strings cannot
+/// contain a raw newline, every line is one complete value, which may be
+/// parsed separately from the others.
+///
+/// Each value is first written to a line buffer, which is reused for every
+/// value, and the line is only written to the stream once the value has been
+/// visited successfully. If visiting a value fails, nothing is written, so
+/// the stream never contains a partial line. By default, the stream is
+/// flushed after each line ([`FlushPolicy::EachLine`]), so that a reader
+/// following the stream sees every value as soon as it is written.
+///
+/// An `NdjsonWriter` is also a `Visit`, so that it may be wrapped in other
+/// visitors (such as a [`WithMeta`]). When visited directly, a line is
+/// written each time [`Visit::finish`] is called.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{json::NdjsonWriter, record::Record, value};
+/// let mut writer = NdjsonWriter::new(Vec::new());
+/// writer.write(&value!({ "id": 1 })).unwrap();
+/// writer.write_record(&Record::from_pairs(&[("id", value!(2))])).unwrap();
+///
+/// let bytes = writer.finish().unwrap();
+/// assert_eq!(bytes, b"{\"id\":1}\n{\"id\":2}\n");
+/// ```
+///
+/// [`JsonVisit`]: struct.JsonVisit.html
+/// [`FlushPolicy::EachLine`]: ../writer/enum.FlushPolicy.html#variant.EachLine
+/// [`WithMeta`]: ../combinator/struct.WithMeta.html
+/// [`Visit::finish`]: ../visitor/trait.Visit.html#method.finish
+pub struct NdjsonWriter<W: io::Write> {
+    json: JsonVisit<String>,
+    writer: IoWriter<W>,
+}
+
+// === impl NdjsonWriter ===
+
+impl<W: io::Write> NdjsonWriter<W> {
+    /// Returns a new `NdjsonWriter` which writes lines to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            json: JsonVisit::new(String::new()),
+            writer: IoWriter::new(writer).with_flush_policy(FlushPolicy::EachLine),
+        }
+    }
+
+    /// Sets when written lines are flushed to the underlying stream.
+    pub fn with_flush_policy(self, policy: FlushPolicy) -> Self {
+        Self {
+            writer: self.writer.with_flush_policy(policy),
+            ..self
+        }
+    }
+
+    /// Sets how floats are written, as with [`JsonVisit::with_float_format`].
+    ///
+    /// [`JsonVisit::with_float_format`]: struct.JsonVisit.html#method.with_float_format
+    pub fn with_float_format(self, float_format: FloatFormat) -> Self {
+        Self {
+            json: self.json.with_float_format(float_format),
+            ..self
+        }
+    }
+
+    /// Sets how map keys which are not strings are converted to strings, as
+    /// with [`JsonVisit::with_key_policy`].
+    ///
+    /// [`JsonVisit::with_key_policy`]: struct.JsonVisit.html#method.with_key_policy
+    pub fn with_key_policy(self, key_policy: KeyPolicy) -> Self {
+        Self {
+            json: self.json.with_key_policy(key_policy),
+            ..self
+        }
+    }
+
+    /// Sets the [`SerializeConfig`], as with [`JsonVisit::with_config`].
+    ///
+    /// [`SerializeConfig`]: ../visitor/struct.SerializeConfig.html
+    /// [`JsonVisit::with_config`]: struct.JsonVisit.html#method.with_config
+    pub fn with_config(self, config: SerializeConfig) -> Self {
+        Self {
+            json: self.json.with_config(config),
+            ..self
+        }
+    }
+
+    /// Borrows the underlying stream.
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    /// Mutably borrows the underlying stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    /// Writes `value` as a line of JSON.
+    ///
+    /// # Errors
+    ///
+    /// If visiting the value or writing the line fails, the error is
+    /// returned, and no part of the value is written.
+    pub fn write(&mut self, value: &Value<'_>) -> Result<(), Error> {
+        let result = value.visit_and_finish(self);
+        self.recover(result)
+    }
+
+    /// Writes `record` as a line of JSON, as with [`write`].
+    ///
+    /// [`write`]: #method.write
+    pub fn write_record(&mut self, record: &Record<'_>) -> Result<(), Error> {
+        let result = record.visit_and_finish(self);
+        self.recover(result)
+    }
+
+    /// Writes any buffered lines to the underlying stream, and flushes it.
+    ///
+    /// # Errors
+    ///
+    /// As with [`IoWriter::flush`].
+    ///
+    /// [`IoWriter::flush`]: ../writer/struct.IoWriter.html#method.flush
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    /// Flushes the `NdjsonWriter`, returning the underlying stream.
+    ///
+    /// Any partial line which was visited but not [finished] is discarded.
+    ///
+    /// # Errors
+    ///
+    /// As with [`IoWriter::finish`].
+    ///
+    /// [finished]: ../visitor/trait.Visit.html#method.finish
+    /// [`IoWriter::finish`]: ../writer/struct.IoWriter.html#method.finish
+    pub fn finish(self) -> Result<W, Error> {
+        self.writer.finish()
+    }
+
+    /// Discards the partial line if visiting a value failed.
+    fn recover(&mut self, result: VisitResult) -> VisitResult {
+        if result.is_err() {
+            self.json.reset();
+            self.json.get_mut().clear();
+        }
+        result
+    }
+}
+
+impl<W: io::Write> Visit for NdjsonWriter<W> {
+    forward_visit! {
+        json =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_any,
+            visit_kv,
+            visit_field,
+            visit_named_field,
+            visit_fmt,
+            visit_display,
+            visit_record,
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+    }
+
+    fn finish(&mut self) -> VisitResult {
+        if !self.json.is_complete() {
+            return Err(Error::unbalanced());
+        }
+        let line = self.json.get_mut();
+        if line.is_empty() {
+            return Ok(());
+        }
+        line.push('\n');
+        let result = fmt::Write::write_str(&mut self.writer, line);
+        line.clear();
+        if result.is_err() {
+            // The `IoWriter` stores the I/O error, and returns it when
+            // flushed.
+            self.writer.flush()?;
+            return Err(fmt::Error.into());
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write + fmt::Debug> fmt::Debug for NdjsonWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NdjsonWriter")
+            .field("writer", &self.writer)
+            .field("buffered", &self.json.get_ref().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Visitable;
+
+    /// A value which fails after writing part of itself.
+    struct Partial;
+
+    impl Visitable for Partial {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            visitor.open_map()?;
+            visitor.visit_kv(Value::borrowed(&"id"), Value::borrowed(&1))?;
+            Err(Error::unsupported("partial"))
+        }
+    }
+
+    #[test]
+    fn discards_failed_lines() {
+        let mut writer = NdjsonWriter::new(Vec::new());
+        writer.write(&value!([1, 2])).unwrap();
+        assert_eq!(writer.get_ref(), b"[1,2]\n");
+
+        assert!(writer.write(&Value::borrowed(&Partial)).is_err());
+        writer.write(&value!("ok")).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"[1,2]\n\"ok\"\n");
+    }
+}
@@ -0,0 +1,454 @@
+use crate::owned::OwnedValue;
+use std::{char, error, fmt, str};
+
+/// Parses a JSON document into an [`OwnedValue`].
+///
+/// JSON values are mapped to `OwnedValue`s as follows:
+///
+/// - `null` is parsed as [`OwnedValue::Unit`],
+/// - `true` and `false` are parsed as [`OwnedValue::Bool`],
+/// - integers are parsed as [`OwnedValue::Uint`] if they are non-negative and
+///   as [`OwnedValue::Int`] if they are negative. Numbers with a fractional
+///   part or an exponent, and integers that do not fit in 64 bits, are parsed
+///   as [`OwnedValue::Float`],
+/// - strings are parsed as [`OwnedValue::Str`],
+/// - arrays are parsed as [`OwnedValue::List`],
+/// - objects are parsed as [`OwnedValue::Map`]s with [`OwnedValue::Str`]
+///   keys, preserving the order in which the keys appear in the input.
+///
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+/// [`OwnedValue::Unit`]: ../owned/enum.OwnedValue.html#variant.Unit
+/// [`OwnedValue::Bool`]: ../owned/enum.OwnedValue.html#variant.Bool
+/// [`OwnedValue::Uint`]: ../owned/enum.OwnedValue.html#variant.Uint
+/// [`OwnedValue::Int`]: ../owned/enum.OwnedValue.html#variant.Int
+/// [`OwnedValue::Float`]: ../owned/enum.OwnedValue.html#variant.Float
+/// [`OwnedValue::Str`]: ../owned/enum.OwnedValue.html#variant.Str
+/// [`OwnedValue::List`]: ../owned/enum.OwnedValue.html#variant.List
+/// [`OwnedValue::Map`]: ../owned/enum.OwnedValue.html#variant.Map
+pub fn from_str(input: &str) -> Result<OwnedValue, ParseError> {
+    let mut parser = Parser {
+        input: input.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.input.len() {
+        return Err(parser.error(ErrorKind::TrailingCharacters));
+    }
+    Ok(value)
+}
+
+/// An error returned when parsing JSON fails.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ErrorKind {
+    UnexpectedEof,
+    UnexpectedChar(char),
+    InvalidNumber,
+    InvalidEscape,
+    InvalidUnicode,
+    TrailingCharacters,
+    RecursionLimit,
+}
+
+/// The maximum nesting depth of arrays and objects accepted by the parser.
+///
+/// This bounds the parser's recursion so that adversarial input cannot
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+// === impl Parser ===
+
+impl<'a> Parser<'a> {
+    fn parse_value(&mut self) -> Result<OwnedValue, ParseError> {
+        match self.peek() {
+            None => Err(self.error(ErrorKind::UnexpectedEof)),
+            Some(b'n') => self.parse_ident("null", OwnedValue::Unit),
+            Some(b't') => self.parse_ident("true", OwnedValue::Bool(true)),
+            Some(b'f') => self.parse_ident("false", OwnedValue::Bool(false)),
+            Some(b'"') => self.parse_str().map(|s| OwnedValue::Str(s.into())),
+            Some(b'[') => self.parse_list(),
+            Some(b'{') => self.parse_map(),
+            Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_ident(&mut self, ident: &str, value: OwnedValue) -> Result<OwnedValue, ParseError> {
+        for &expected in ident.as_bytes() {
+            match self.peek() {
+                Some(b) if b == expected => self.pos += 1,
+                Some(_) => return Err(self.unexpected()),
+                None => return Err(self.error(ErrorKind::UnexpectedEof)),
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_list(&mut self) -> Result<OwnedValue, ParseError> {
+        self.enter()?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b']') {
+            self.depth -= 1;
+            return Ok(OwnedValue::List(values));
+        }
+        loop {
+            self.skip_whitespace();
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.eat(b',') {
+                continue;
+            }
+            self.expect(b']')?;
+            break;
+        }
+        self.depth -= 1;
+        Ok(OwnedValue::List(values))
+    }
+
+    fn parse_map(&mut self) -> Result<OwnedValue, ParseError> {
+        self.enter()?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            self.depth -= 1;
+            return Ok(OwnedValue::Map(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.unexpected());
+            }
+            let key = self.parse_str()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            entries.push((OwnedValue::Str(key.into()), value));
+            self.skip_whitespace();
+            if self.eat(b',') {
+                continue;
+            }
+            self.expect(b'}')?;
+            break;
+        }
+        self.depth -= 1;
+        Ok(OwnedValue::Map(entries))
+    }
+
+    fn parse_number(&mut self) -> Result<OwnedValue, ParseError> {
+        let start = self.pos;
+        let negative = self.eat(b'-');
+
+        // Integer part: either a single zero, or a non-zero digit followed by
+        // any number of digits.
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error(ErrorKind::InvalidNumber)),
+        }
+
+        let mut is_float = false;
+        if self.eat(b'.') {
+            is_float = true;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error(ErrorKind::InvalidNumber));
+            }
+            self.skip_digits();
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error(ErrorKind::InvalidNumber));
+            }
+            self.skip_digits();
+        }
+
+        // The scanned bytes are all ASCII, so this cannot fail.
+        let text = str::from_utf8(&self.input[start..self.pos]).expect("number must be ASCII");
+        if !is_float {
+            if negative {
+                if let Ok(n) = text.parse::<i64>() {
+                    return Ok(OwnedValue::Int(n));
+                }
+            } else if let Ok(n) = text.parse::<u64>() {
+                return Ok(OwnedValue::Uint(n));
+            }
+        }
+        text.parse::<f64>()
+            .map(OwnedValue::Float)
+            .map_err(|_| ParseError {
+                kind: ErrorKind::InvalidNumber,
+                offset: start,
+            })
+    }
+
+    fn parse_str(&mut self) -> Result<String, ParseError> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            // Copy runs of unescaped characters all at once.
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // The input is a `str` and we only stop on ASCII bytes, so the run
+            // is always valid UTF-8.
+            out.push_str(str::from_utf8(&self.input[start..self.pos]).expect("input is UTF-8"));
+
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::UnexpectedEof)),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    out.push(self.parse_escape()?);
+                }
+                Some(_) => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, ParseError> {
+        let c = match self.next() {
+            None => return Err(self.error(ErrorKind::UnexpectedEof)),
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => return self.parse_unicode_escape(),
+            Some(_) => {
+                self.pos -= 1;
+                return Err(self.error(ErrorKind::InvalidEscape));
+            }
+        };
+        Ok(c)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos;
+        let first = self.parse_hex4()?;
+        let code = match first {
+            // A leading surrogate must be followed by an escaped trailing
+            // surrogate.
+            0xD800..=0xDBFF => {
+                if !(self.eat(b'\\') && self.eat(b'u')) {
+                    return Err(ParseError {
+                        kind: ErrorKind::InvalidUnicode,
+                        offset: start,
+                    });
+                }
+                let second = self.parse_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&second) {
+                    return Err(ParseError {
+                        kind: ErrorKind::InvalidUnicode,
+                        offset: start,
+                    });
+                }
+                0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or(ParseError {
+            kind: ErrorKind::InvalidUnicode,
+            offset: start,
+        })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = match self.peek() {
+                None => return Err(self.error(ErrorKind::UnexpectedEof)),
+                Some(b) => (b as char)
+                    .to_digit(16)
+                    .ok_or_else(|| self.error(ErrorKind::InvalidEscape))?,
+            };
+            self.pos += 1;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(ErrorKind::RecursionLimit));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\n') | Some(b'\r') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(b) if b == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.unexpected()),
+            None => Err(self.error(ErrorKind::UnexpectedEof)),
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        // Report the whole character at the current position, rather than a
+        // single byte of a multi-byte character.
+        let rest = &self.input[self.pos..];
+        let c = str::from_utf8(&rest[..rest.len().min(4)])
+            .or_else(|e| str::from_utf8(&rest[..e.valid_up_to()]))
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.error(ErrorKind::UnexpectedChar(c))
+    }
+
+    fn error(&self, kind: ErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            offset: self.pos,
+        }
+    }
+}
+
+// === impl ParseError ===
+
+impl ParseError {
+    /// Returns the byte offset in the input at which the error occurred.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::UnexpectedEof => f.write_str("unexpected end of input")?,
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c)?,
+            ErrorKind::InvalidNumber => f.write_str("invalid number")?,
+            ErrorKind::InvalidEscape => f.write_str("invalid escape sequence")?,
+            ErrorKind::InvalidUnicode => f.write_str("invalid unicode code point")?,
+            ErrorKind::TrailingCharacters => f.write_str("trailing characters after value")?,
+            ErrorKind::RecursionLimit => {
+                write!(f, "exceeded maximum nesting depth of {}", MAX_DEPTH)?
+            }
+        }
+        write!(f, " at offset {}", self.offset)
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(s: &str) -> OwnedValue {
+        OwnedValue::Str(s.into())
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(from_str("null"), Ok(OwnedValue::Unit));
+        assert_eq!(from_str(" true "), Ok(OwnedValue::Bool(true)));
+        assert_eq!(from_str("false"), Ok(OwnedValue::Bool(false)));
+        assert_eq!(from_str("42"), Ok(OwnedValue::Uint(42)));
+        assert_eq!(from_str("-42"), Ok(OwnedValue::Int(-42)));
+        assert_eq!(from_str("1.5e3"), Ok(OwnedValue::Float(1500.0)));
+        assert_eq!(
+            from_str("18446744073709551616"),
+            Ok(OwnedValue::Float(18446744073709551616.0))
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(from_str(r#""hello""#), Ok(s("hello")));
+        assert_eq!(from_str(r#""a\"b\\c\né😀""#), Ok(s("a\"b\\c\né\u{1F600}")));
+        assert!(from_str(r#""\ud83d""#).is_err());
+        assert!(from_str("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn nested() {
+        let value = from_str(r#"{"user": {"id": 42, "tags": ["a", null]}, "ok": true}"#);
+        let expected = OwnedValue::Map(vec![
+            (
+                s("user"),
+                OwnedValue::Map(vec![
+                    (s("id"), OwnedValue::Uint(42)),
+                    (s("tags"), OwnedValue::List(vec![s("a"), OwnedValue::Unit])),
+                ]),
+            ),
+            (s("ok"), OwnedValue::Bool(true)),
+        ]);
+        assert_eq!(value, Ok(expected));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(from_str("[1, 2").unwrap_err().offset(), 5);
+        assert_eq!(from_str("[1] x").unwrap_err().offset(), 4);
+        assert!(from_str("01").is_err());
+        assert!(from_str("{\"a\" 1}").is_err());
+        assert!(from_str("[1,]").is_err());
+        assert!(from_str(&"[".repeat(MAX_DEPTH + 1)).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    escape::{write_json_str as write_str, JsonEscape},
+    float::{FloatFormat, NonFinite},
+    key::KeyPolicy,
+    pool,
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, ErrorMode, SerializeConfig, Visit, VisitResult},
+};
+use std::{fmt, mem};
+
+/// A `Visit` implementation that writes values as JSON.
+///
+/// Values are mapped to JSON as follows:
+///
+/// - maps, structs, and records are written as JSON objects. Struct names are
+///   not written,
+/// - lists and tuples are written as JSON arrays,
+/// - strings and formatted values (such as those constructed with
+///   `Value::display` or `Value::debug`) are written as JSON strings,
+/// - numbers and booleans are written as JSON numbers and booleans. By
+///   default, floats which cannot be represented in JSON (NaN and
+///   infinities) are written as `null`; this may be changed with
+///   [`with_float_format`],
+/// - unit values are written as `null`.
+///
+/// Since JSON object keys must be strings, map keys which are not strings are
+/// converted to strings according to a [`KeyPolicy`] (so the key `1` is
+/// written as `"1"`). By default, keys which are not scalar values are written
+/// as a string containing their JSON representation; this may be changed with
+/// [`with_key_policy`].
+///
+/// By default, if visiting a value fails partway through, the JSON written so
+/// far is left unterminated. With [`ErrorMode::BestEffortClose`], each open
+/// container is closed as the error propagates (and a map entry whose value
+/// failed is written with a `null` value), so that the output is still
+/// well-formed JSON; see [`with_error_mode`].
+///
+/// [`with_float_format`]: #method.with_float_format
+/// [`KeyPolicy`]: ../key/struct.KeyPolicy.html
+/// [`with_key_policy`]: #method.with_key_policy
+/// [`ErrorMode::BestEffortClose`]: ../visitor/enum.ErrorMode.html#variant.BestEffortClose
+/// [`with_error_mode`]: #method.with_error_mode
+pub struct JsonVisit<W> {
+    writer: W,
+    stack: Vec<Frame>,
+    /// Set while visiting the value half of a key-value pair, so that no
+    /// separator is written before it.
+    in_kv_value: bool,
+    float_format: FloatFormat,
+    key_policy: KeyPolicy,
+    error_mode: ErrorMode,
+    config: SerializeConfig,
+}
+
+struct Frame {
+    kind: Container,
+    first: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+// === impl JsonVisit ===
+
+impl<W: fmt::Write> JsonVisit<W> {
+    /// Returns a new `JsonVisit` that writes JSON to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            stack: Vec::new(),
+            in_kv_value: false,
+            float_format: FloatFormat::new(),
+            key_policy: KeyPolicy::new(),
+            error_mode: ErrorMode::Abort,
+            config: SerializeConfig::new(),
+        }
+    }
+
+    /// Sets how floats are written.
+    ///
+    /// Since JSON has no representation for NaN or infinities, the
+    /// [`NonFinite::Literal`] policy is written as `NonFinite::Str` would be.
+    ///
+    /// [`NonFinite::Literal`]: ../float/enum.NonFinite.html#variant.Literal
+    pub fn with_float_format(self, float_format: FloatFormat) -> Self {
+        Self {
+            float_format,
+            ..self
+        }
+    }
+
+    /// Sets how map keys which are not strings are converted to strings.
+    pub fn with_key_policy(self, key_policy: KeyPolicy) -> Self {
+        Self { key_policy, ..self }
+    }
+
+    /// Sets whether containers are closed when visiting one of their
+    /// elements fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{combinator::DepthLimit, json::JsonVisit, value, visitor::ErrorMode};
+    /// let mut json = JsonVisit::new(String::new()).with_error_mode(ErrorMode::BestEffortClose);
+    ///
+    /// // Opening the nested list exceeds the depth limit.
+    /// let value = value!({ "id": 1, "tags": ["a"], "ok": true });
+    /// assert!(value.visit(&mut DepthLimit::new(&mut json, 1)).is_err());
+    /// assert_eq!(json.into_inner(), r#"{"id":1,"tags":null}"#);
+    /// ```
+    pub fn with_error_mode(self, error_mode: ErrorMode) -> Self {
+        Self { error_mode, ..self }
+    }
+
+    /// Sets the [`SerializeConfig`].
+    ///
+    /// By default, output is machine-readable.
+    ///
+    /// [`SerializeConfig`]: ../visitor/struct.SerializeConfig.html
+    pub fn with_config(self, config: SerializeConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Mutably borrows the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `JsonVisit`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Returns `true` if no container is open.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Forgets any open containers, so that a new value may be written
+    /// after visiting a value failed.
+    pub(crate) fn reset(&mut self) {
+        self.stack.clear();
+        self.in_kv_value = false;
+    }
+
+    /// Writes a separator before a value, if one is needed.
+    fn begin_value(&mut self) -> VisitResult {
+        if mem::replace(&mut self.in_kv_value, false) {
+            return Ok(());
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            if !mem::replace(&mut frame.first, false) {
+                self.writer.write_char(',')?;
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, kind: Container, delim: char) -> VisitResult {
+        self.begin_value()?;
+        self.writer.write_char(delim)?;
+        self.stack.push(Frame { kind, first: true });
+        Ok(())
+    }
+
+    fn close(&mut self, kind: Container, delim: char) -> VisitResult {
+        // If visiting the value of a key-value pair failed before anything
+        // was written, write a value so that the output remains valid.
+        if mem::replace(&mut self.in_kv_value, false) {
+            self.writer.write_str("null")?;
+        }
+        match self.stack.pop() {
+            Some(frame) if frame.kind == kind => {}
+            _ => return Err(Error::unbalanced()),
+        }
+        self.writer.write_char(delim)?;
+        Ok(())
+    }
+
+    fn write_key(&mut self, key: Value<'_>) -> VisitResult {
+        let (policy, writer) = (&self.key_policy, &mut self.writer);
+        pool::with_string(|buf| {
+            policy.normalize_into(&key, buf)?;
+            write_str(writer, buf)?;
+            Ok(())
+        })
+    }
+}
+
+impl<W: fmt::Write> Visit for JsonVisit<W> {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.begin_value()?;
+        write!(self.writer, "{}", value)?;
+        Ok(())
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.begin_value()?;
+        write!(self.writer, "{}", value)?;
+        Ok(())
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.begin_value()?;
+        let mut format = self.float_format;
+        if format.non_finite() == NonFinite::Literal {
+            format = format.with_non_finite(NonFinite::Str);
+        }
+        format.write(&mut self.writer, value, "null", write_str)
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.begin_value()?;
+        write_str(&mut self.writer, value)?;
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.begin_value()?;
+        self.writer
+            .write_str(if value { "true" } else { "false" })?;
+        Ok(())
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        self.begin_value()?;
+        self.writer.write_str("null")?;
+        Ok(())
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.begin_value()?;
+        self.write_key(k)?;
+        self.writer.write_char(':')?;
+        self.in_kv_value = true;
+        v.visit(self)
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        // String keys are written as they are, so they need not be
+        // normalized by the key policy.
+        self.begin_value()?;
+        write_str(&mut self.writer, name)?;
+        self.writer.write_char(':')?;
+        self.in_kv_value = true;
+        value.visit(self)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.begin_value()?;
+        self.writer.write_char('"')?;
+        fmt::write(&mut JsonEscape(&mut self.writer), args)?;
+        self.writer.write_char('"')?;
+        Ok(())
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        // JSON has no representation for type names.
+        Ok(())
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        self.error_mode
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::UNIT
+            | Capabilities::MAPS
+            | Capabilities::LISTS
+            | Capabilities::STRUCTS
+            | Capabilities::TUPLES
+            | self.config.capabilities()
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map, '{')
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map, '}')
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List, '[')
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List, ']')
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct, '{')
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct, '}')
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple, '[')
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple, ']')
+    }
+}
+
+impl<W: fmt::Debug> fmt::Debug for JsonVisit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonVisit")
+            .field("writer", &self.writer)
+            .field("depth", &self.stack.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        owned::OwnedValue,
+        value::{Value, ValueExt},
+    };
+
+    #[test]
+    fn nested() {
+        let name = "eliza";
+        let value = value!({
+            "user": { "id": 42, "name": name },
+            "tags": ["a", "b"],
+            "ok": true,
+            "nothing": (),
+        });
+        assert_eq!(
+            value.render_json(),
+            r#"{"user":{"id":42,"name":"eliza"},"tags":["a","b"],"ok":true,"nothing":null}"#
+        );
+    }
+
+    #[test]
+    fn structs_and_tuples() {
+        let value = OwnedValue::Struct {
+            name: "Point".to_owned(),
+            fields: vec![
+                ("x".to_owned(), OwnedValue::Float(1.0)),
+                ("y".to_owned(), OwnedValue::Float(f64::NAN)),
+                (
+                    "tags".to_owned(),
+                    OwnedValue::Tuple(vec![OwnedValue::Int(-1), OwnedValue::Uint(2)]),
+                ),
+            ],
+        };
+        assert_eq!(
+            Value::borrowed(&value).render_json(),
+            r#"{"x":1.0,"y":null,"tags":[-1,2]}"#
+        );
+    }
+
+    #[test]
+    fn static_dispatch_matches_dynamic() {
+        use crate::{json::JsonVisit, visitor::VisitExt};
+
+        let value = OwnedValue::List(vec![
+            OwnedValue::Uint(1),
+            OwnedValue::Tuple(vec![OwnedValue::Str("a".into()), OwnedValue::Unit]),
+            OwnedValue::Map(vec![(OwnedValue::Int(-1), OwnedValue::Bool(false))]),
+        ]);
+        let mut visitor = JsonVisit::new(String::new());
+        visitor.visit_value(&value).unwrap();
+        assert_eq!(visitor.into_inner(), Value::borrowed(&value).render_json());
+    }
+
+    #[test]
+    fn escapes_strings_and_keys() {
+        let value = value!({ "a\"b": "line\nbreak\u{1}", 1: [], (true): {} });
+        assert_eq!(
+            value.render_json(),
+            r#"{"a\"b":"line\nbreak\u0001","1":[],"true":{}}"#
+        );
+        let display = 12.5;
+        assert_eq!(Value::display(&display).render_json(), r#""12.5""#);
+        assert_eq!(Value::debug(&"quoted").render_json(), r#""\"quoted\"""#);
+    }
+}
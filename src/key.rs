@@ -0,0 +1,388 @@
+//! Normalizing map keys to strings.
+//!
+//! A map's keys may be any `Value`, but many formats (such as JSON) only
+//! permit string keys. A [`KeyPolicy`] describes how visitors which require
+//! string keys convert other keys into strings, and a [`Key`] interprets a
+//! string key (such as one segment of a path like `foo.0.bar`) as an index
+//! or number.
+//!
+//! [`KeyPolicy`]: struct.KeyPolicy.html
+//! [`Key`]: struct.Key.html
+use crate::{
+    owned::OwnedValue,
+    value::{Value, ValueExt, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt::{self, Write as _};
+
+/// Describes how map keys are converted to strings.
+///
+/// String keys are always used as-is, and keys which are numbers, booleans,
+/// or unit values are written as text (so the key `1` becomes `"1"`, and the
+/// key `()` becomes `"null"`). Keys which are lists, maps, structs, tuples,
+/// or byte strings are handled according to the policy's
+/// [`ComplexKeys`] setting.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{key::{ComplexKeys, KeyPolicy}, value::Value};
+/// let policy = KeyPolicy::new();
+/// assert_eq!(policy.normalize(&Value::borrowed(&true)).unwrap(), "true");
+/// assert_eq!(policy.normalize(&Value::borrowed(&vec![1, 2])).unwrap(), "[1,2]");
+///
+/// let policy = policy.with_complex_keys(ComplexKeys::Reject);
+/// assert!(policy.normalize(&Value::borrowed(&vec![1, 2])).is_err());
+/// ```
+///
+/// [`ComplexKeys`]: enum.ComplexKeys.html
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyPolicy {
+    complex: ComplexKeys,
+}
+
+/// A string map key, struct field name, or path segment.
+///
+/// Keys which are written the same way as an integer may be interpreted as
+/// one, so that a path segment such as `0` may refer either to a list
+/// element or to a map entry. Only the canonical decimal representation of
+/// an integer, as written by `KeyPolicy` (with no sign for non-negative
+/// numbers, and no leading zeros), is interpreted as a number, so `"01"`
+/// and `"+1"` are only ever string keys.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{key::Key, owned::OwnedValue};
+/// assert_eq!(Key::new("3").as_index(), Some(3));
+/// assert_eq!(Key::new("03").as_index(), None);
+/// assert_eq!(Key::new("-3").as_int(), Some(-3));
+///
+/// // The key `"1"` matches both string and integer map keys.
+/// assert!(Key::new("1").matches(&OwnedValue::Uint(1)));
+/// assert!(Key::new("1").matches(&OwnedValue::Str("1".into())));
+/// ```
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Key<'a> {
+    key: &'a str,
+}
+
+/// How map keys which are not scalar values are converted to strings.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ComplexKeys {
+    /// Write the key's JSON representation.
+    #[default]
+    Json,
+    /// Fail with an [unsupported] error.
+    ///
+    /// [unsupported]: ../visitor/struct.Error.html#method.is_unsupported
+    Reject,
+    /// Write a `#` followed by a hash of the key's JSON representation, as 16
+    /// hexadecimal digits.
+    ///
+    /// Equal keys always hash to the same string, regardless of platform or
+    /// version of this crate, so hashed keys may be compared across
+    /// processes.
+    Hash,
+}
+
+// === impl KeyPolicy ===
+
+impl KeyPolicy {
+    /// Returns the default `KeyPolicy`, which writes complex keys as JSON.
+    pub const fn new() -> Self {
+        Self {
+            complex: ComplexKeys::Json,
+        }
+    }
+
+    /// Sets how keys which are not scalar values are converted.
+    pub fn with_complex_keys(self, complex: ComplexKeys) -> Self {
+        Self { complex }
+    }
+
+    /// Converts `key` into a string according to this policy.
+    ///
+    /// # Errors
+    ///
+    /// If visiting `key` fails, or `key` is not a scalar value and complex
+    /// keys are rejected, the error is returned.
+    pub fn normalize(&self, key: &Value<'_>) -> Result<String, Error> {
+        self.normalize_owned(OwnedValue::from_value(key)?)
+    }
+
+    /// Converts `key` into a string according to this policy, appending it
+    /// to `buf`.
+    ///
+    /// Scalar keys are written directly into `buf`, without buffering the
+    /// key as an `OwnedValue`.
+    pub(crate) fn normalize_into(&self, key: &Value<'_>, buf: &mut String) -> Result<(), Error> {
+        let start = buf.len();
+        let mut scalar = ScalarKey {
+            buf,
+            written: false,
+        };
+        if key.visit(&mut scalar).is_ok() && scalar.written {
+            return Ok(());
+        }
+        // The key is not a scalar, or visiting it failed; let `normalize`
+        // handle (or report) it.
+        buf.truncate(start);
+        buf.push_str(&self.normalize(key)?);
+        Ok(())
+    }
+
+    pub(crate) fn normalize_owned(&self, key: OwnedValue) -> Result<String, Error> {
+        if is_scalar(&key) {
+            return Ok(stringify(key));
+        }
+        match self.complex {
+            ComplexKeys::Json => Ok(stringify(key)),
+            ComplexKeys::Reject => Err(Error::unsupported("map key which is not a scalar")),
+            ComplexKeys::Hash => Ok(format!("#{:016x}", fnv1a(stringify(key).as_bytes()))),
+        }
+    }
+}
+
+// === impl Key ===
+
+impl<'a> Key<'a> {
+    /// Returns a new `Key`.
+    pub const fn new(key: &'a str) -> Self {
+        Self { key }
+    }
+
+    /// Returns the key as a string.
+    pub fn as_str(&self) -> &'a str {
+        self.key
+    }
+
+    /// Returns the list or tuple index this key refers to, if it is a
+    /// non-negative integer that fits in a `usize`.
+    pub fn as_index(&self) -> Option<usize> {
+        if !is_canonical_uint(self.key) {
+            return None;
+        }
+        self.key.parse().ok()
+    }
+
+    /// Returns the key as an unsigned integer, if it is one.
+    pub fn as_uint(&self) -> Option<u64> {
+        if !is_canonical_uint(self.key) {
+            return None;
+        }
+        self.key.parse().ok()
+    }
+
+    /// Returns the key as a signed integer, if it is one.
+    pub fn as_int(&self) -> Option<i64> {
+        let digits = self.key.strip_prefix('-').unwrap_or(self.key);
+        // `-0` is not written by any integer.
+        if !is_canonical_uint(digits) || self.key == "-0" {
+            return None;
+        }
+        self.key.parse().ok()
+    }
+
+    /// Returns `true` if `key` (a buffered map key) is converted to this key
+    /// by the default [`KeyPolicy`].
+    ///
+    /// [`KeyPolicy`]: struct.KeyPolicy.html
+    pub fn matches(&self, key: &OwnedValue) -> bool {
+        match key {
+            OwnedValue::Str(s) => s == self.key,
+            OwnedValue::Uint(v) => self.as_uint() == Some(*v),
+            OwnedValue::Int(v) => self.as_int() == Some(*v),
+            OwnedValue::Unit => self.key == "null",
+            OwnedValue::Bool(v) => self.key == if *v { "true" } else { "false" },
+            other => stringify(other.clone()) == self.key,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Key<'a> {
+    fn from(key: &'a str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key)
+    }
+}
+
+/// Returns `true` if `s` is a non-negative integer as `u64`'s `Display`
+/// implementation writes it.
+fn is_canonical_uint(s: &str) -> bool {
+    match s.as_bytes() {
+        [] => false,
+        [b'0'] => true,
+        [b'0', ..] => false,
+        digits => digits.iter().all(u8::is_ascii_digit),
+    }
+}
+
+/// Converts `key` into a string using the default policy.
+pub(crate) fn stringify(key: OwnedValue) -> String {
+    match key {
+        OwnedValue::Str(s) => s.into_string(),
+        OwnedValue::Unit => "null".to_owned(),
+        OwnedValue::Bool(v) => v.to_string(),
+        OwnedValue::Uint(v) => v.to_string(),
+        OwnedValue::Int(v) => v.to_string(),
+        OwnedValue::Float(v) => format!("{:?}", v),
+        other => Value::borrowed(&other).render_json(),
+    }
+}
+
+/// Writes a scalar key as `stringify` would.
+///
+/// Visiting anything other than a single scalar value returns an error.
+struct ScalarKey<'a> {
+    buf: &'a mut String,
+    written: bool,
+}
+
+impl ScalarKey<'_> {
+    fn write(&mut self, f: impl FnOnce(&mut String) -> fmt::Result) -> VisitResult {
+        if self.written {
+            return Err(Error::unsupported("key which is not a single scalar"));
+        }
+        self.written = true;
+        f(self.buf)?;
+        Ok(())
+    }
+}
+
+impl Visit for ScalarKey<'_> {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.write(|buf| write!(buf, "{}", value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.write(|buf| write!(buf, "{}", value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.write(|buf| write!(buf, "{:?}", value))
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.write(|buf| buf.write_str(value))
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.write(|buf| write!(buf, "{}", value))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        self.write(|buf| buf.write_str("null"))
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.write(|buf| buf.write_fmt(args))
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        // Named unit values are tuple structs, which are not scalars.
+        Err(Error::unsupported("named key"))
+    }
+}
+
+fn is_scalar(key: &OwnedValue) -> bool {
+    matches!(
+        key,
+        OwnedValue::Str(_)
+            | OwnedValue::Unit
+            | OwnedValue::Bool(_)
+            | OwnedValue::Uint(_)
+            | OwnedValue::Int(_)
+            | OwnedValue::Float(_)
+    )
+}
+
+/// The 64-bit FNV-1a hash, which (unlike `std`'s `DefaultHasher`) is
+/// guaranteed not to change.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let key = OwnedValue::List(vec![OwnedValue::Uint(1)]);
+        let policy = KeyPolicy::new();
+        assert_eq!(policy.normalize_owned(key.clone()).unwrap(), "[1]");
+        assert_eq!(
+            policy.normalize_owned(OwnedValue::Float(f64::NAN)).unwrap(),
+            "NaN"
+        );
+
+        let hash = policy.with_complex_keys(ComplexKeys::Hash);
+        assert_eq!(
+            hash.normalize_owned(key.clone()).unwrap(),
+            "#dc72d61a20e83324"
+        );
+        assert_eq!(hash.normalize_owned(OwnedValue::Int(-1)).unwrap(), "-1");
+
+        let reject = policy.with_complex_keys(ComplexKeys::Reject);
+        let err = reject.normalize_owned(key).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "visiting a map key which is not a scalar is not supported"
+        );
+    }
+
+    #[test]
+    fn numeric_keys() {
+        assert_eq!(Key::new("0").as_index(), Some(0));
+        assert_eq!(Key::new("").as_index(), None);
+        assert_eq!(Key::new("1a").as_index(), None);
+        assert_eq!(Key::new("18446744073709551616").as_uint(), None);
+        assert_eq!(Key::new("-0").as_int(), None);
+        assert_eq!(Key::new("-9223372036854775808").as_int(), Some(i64::MIN));
+        assert_eq!(Key::new("9223372036854775808").as_int(), None);
+
+        assert!(Key::new("-1").matches(&OwnedValue::Int(-1)));
+        assert!(!Key::new("-1").matches(&OwnedValue::Uint(1)));
+        assert!(Key::new("1.5").matches(&OwnedValue::Float(1.5)));
+        assert!(Key::new("null").matches(&OwnedValue::Unit));
+        assert!(Key::new("[1]").matches(&OwnedValue::List(vec![OwnedValue::Uint(1)])));
+    }
+
+    #[test]
+    fn scalar_keys_are_written_directly() {
+        let display = 2.5;
+        let list = vec![1, 2];
+        let tuple_struct = OwnedValue::TupleStruct {
+            name: "Unit".to_owned(),
+            fields: Vec::new(),
+        };
+        let keys = [
+            Value::borrowed(&"a\"b"),
+            Value::borrowed(&7u16),
+            Value::borrowed(&-1i8),
+            Value::borrowed(&1.0f32),
+            Value::borrowed(&()),
+            Value::display(&display),
+            Value::borrowed(&tuple_struct),
+            Value::borrowed(&list),
+        ];
+        let policy = KeyPolicy::new().with_complex_keys(ComplexKeys::Hash);
+        for key in &keys {
+            let mut buf = "prefix ".to_owned();
+            policy.normalize_into(key, &mut buf).unwrap();
+            assert_eq!(buf, format!("prefix {}", policy.normalize(key).unwrap()));
+        }
+    }
+}
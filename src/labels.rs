@@ -0,0 +1,321 @@
+//! Extracting metrics labels from structured values.
+//!
+//! Most metrics systems (such as Prometheus) attach a flat set of string
+//! key-value labels to each measurement, rather than arbitrary structured
+//! data. [`ExtractLabels`] bridges the two by collecting the scalar fields
+//! of a value as labels.
+//!
+//! [`ExtractLabels`]: struct.ExtractLabels.html
+use crate::{
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt;
+
+/// A `Visit` implementation that collects the top-level fields of a map,
+/// struct, or record as string labels.
+///
+/// Only fields whose key and value are both strings, numbers, booleans, or
+/// formatted values are collected; fields of any other type (such as nested
+/// maps or lists) are ignored, as is anything nested inside the top-level
+/// value.
+///
+/// Since each distinct label creates a new time series in most metrics
+/// systems, the number of labels collected may be limited with
+/// [`with_max_labels`]. Fields past the limit are dropped.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{labels::ExtractLabels, value};
+/// let value = value!({ "method": "GET", "status": 200, "headers": { "host": "example.com" } });
+/// let mut labels = ExtractLabels::new();
+/// value.visit(&mut labels).unwrap();
+/// assert_eq!(
+///     labels.into_labels(),
+///     vec![
+///         ("method".to_owned(), "GET".to_owned()),
+///         ("status".to_owned(), "200".to_owned()),
+///     ]
+/// );
+/// ```
+///
+/// [`with_max_labels`]: #method.with_max_labels
+#[derive(Clone, Debug)]
+pub struct ExtractLabels {
+    labels: Vec<(String, String)>,
+    max_labels: usize,
+    dropped: usize,
+    depth: usize,
+}
+
+/// Captures a single scalar value as a string.
+#[derive(Default)]
+struct Scalar {
+    value: Option<String>,
+    /// Set if the value is not a scalar.
+    invalid: bool,
+}
+
+// === impl ExtractLabels ===
+
+impl ExtractLabels {
+    /// Returns a new `ExtractLabels` with no limit on the number of labels.
+    pub fn new() -> Self {
+        Self::with_max_labels(usize::MAX)
+    }
+
+    /// Returns a new `ExtractLabels` which collects at most `max_labels`
+    /// labels.
+    pub fn with_max_labels(max_labels: usize) -> Self {
+        Self {
+            labels: Vec::new(),
+            max_labels,
+            dropped: 0,
+            depth: 0,
+        }
+    }
+
+    /// Returns the labels collected so far.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// Returns the number of labels which were dropped because the maximum
+    /// number of labels had already been collected.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Consumes the `ExtractLabels`, returning the collected labels.
+    pub fn into_labels(self) -> Vec<(String, String)> {
+        self.labels
+    }
+
+    fn extract(&mut self, k: &Value<'_>, v: &Value<'_>) -> VisitResult {
+        let (key, value) = match (Scalar::capture(k)?, Scalar::capture(v)?) {
+            (Some(key), Some(value)) => (key, value),
+            _ => return Ok(()),
+        };
+        if self.labels.len() < self.max_labels {
+            self.labels.push((key, value));
+        } else {
+            self.dropped += 1;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> VisitResult {
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn close(&mut self) -> VisitResult {
+        self.depth = self.depth.saturating_sub(1);
+        Ok(())
+    }
+}
+
+impl Default for ExtractLabels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visit for ExtractLabels {
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        if self.depth == 1 {
+            self.extract(&k, &v)?;
+        }
+        Ok(())
+    }
+
+    fn visit_fmt(&mut self, _: fmt::Arguments<'_>) -> VisitResult {
+        Ok(())
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        if self.depth == 0 {
+            for (name, value) in record {
+                self.extract(&Value::borrowed(&name), &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close()
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close()
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close()
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open()
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close()
+    }
+}
+
+// === impl Scalar ===
+
+impl Scalar {
+    /// Returns `value` as a string, or `None` if it is not a scalar.
+    fn capture(value: &Value<'_>) -> Result<Option<String>, Error> {
+        let mut scalar = Scalar::default();
+        value.visit(&mut scalar)?;
+        if scalar.invalid {
+            return Ok(None);
+        }
+        Ok(scalar.value)
+    }
+
+    fn set(&mut self, value: impl fmt::Display) -> VisitResult {
+        if self.value.is_some() {
+            self.invalid = true;
+        } else {
+            self.value = Some(value.to_string());
+        }
+        Ok(())
+    }
+
+    fn nested(&mut self) -> VisitResult {
+        self.invalid = true;
+        Ok(())
+    }
+}
+
+impl Visit for Scalar {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.set(value)
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.set(value)
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.set(value)
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.set(value)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.set(value)
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        self.nested()
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, _: Value<'_>, _: Value<'_>) -> VisitResult {
+        self.nested()
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.set(args)
+    }
+
+    fn visit_record(&mut self, _: &Record<'_>) -> VisitResult {
+        self.nested()
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.nested()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.nested()
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.nested()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.nested()
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::FieldSet;
+
+    #[test]
+    fn max_labels() {
+        static FIELDS: FieldSet = FieldSet::new(&["a", "b", "nested", "c"]);
+        let nested = value!([1, 2]);
+        let values = [
+            Value::borrowed(&1.5),
+            Value::display(&"shown"),
+            nested,
+            Value::borrowed(&true),
+        ];
+        let record = FIELDS.record(&values);
+        let mut labels = ExtractLabels::with_max_labels(2);
+        record.visit(&mut labels).unwrap();
+        assert_eq!(labels.dropped(), 1);
+        assert_eq!(
+            labels.into_labels(),
+            vec![
+                ("a".to_owned(), "1.5".to_owned()),
+                ("b".to_owned(), "shown".to_owned()),
+            ]
+        );
+    }
+}
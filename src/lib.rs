@@ -1,5 +1,49 @@
+#[macro_use]
+extern crate valuable_core;
+
+#[macro_use]
+mod macros;
+
 pub mod visitor;
 pub mod value;
+pub use valuable_core::record;
+pub mod owned;
+pub mod json;
+pub mod debug;
+pub mod console;
+#[cfg(feature = "html")]
+pub mod html;
+pub mod escape;
+pub use valuable_core::float;
+pub mod key;
+pub mod combinator;
+pub mod labels;
+pub mod schema;
+pub mod diff;
+pub mod columnar;
+pub mod csv;
+pub use valuable_core::pool;
+pub use valuable_core::sample;
+pub mod extract;
+pub mod size;
+pub mod units;
+pub mod writer;
+#[cfg(feature = "scope")]
+pub mod scope;
+#[cfg(feature = "backtrace")]
+pub mod backtrace;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+mod interop;
+
+#[doc(hidden)]
+pub use crate::macros::__private;
+
+// Allows the code generated by the `Visitable` derive, which refers to
+// `::valuable`, to be used within this crate.
+#[cfg(feature = "derive")]
+extern crate self as valuable;
 
 #[cfg(test)]
 mod tests {
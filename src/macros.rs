@@ -0,0 +1,602 @@
+/// Constructs an ad-hoc structured [`Value`] inline.
+///
+/// The syntax is similar to that of `serde_json`'s `json!` macro: `{ ... }`
+/// constructs a map of `key: value` pairs, `[ ... ]` constructs a list, and
+/// any other expression is borrowed as a [`Visitable`] value. Maps and lists
+/// may be nested arbitrarily.
+///
+/// ```
+/// use valuable::value;
+///
+/// let name = "eliza";
+/// let value = value!({
+///     "user": { "id": 42, "name": name },
+///     "tags": ["a", "b"],
+/// });
+/// # drop(value);
+/// ```
+///
+/// Map keys must be a single token tree: a literal, an identifier, or a
+/// parenthesized expression.
+///
+/// Because leaf values are borrowed rather than moved into the `Value`, any
+/// temporaries used as leaves (such as the result of a function call) only
+/// live until the end of the enclosing statement, as with `format_args!`.
+/// Bind such values to a variable first if the `Value` must outlive the
+/// statement that creates it.
+///
+/// [`Value`]: value/struct.Value.html
+/// [`Visitable`]: value/trait.Visitable.html
+#[macro_export]
+macro_rules! value {
+    // === maps ===
+
+    (@map [$($out:tt)*]) => {
+        $crate::value::Value::with_visit(
+            $crate::__private::Map([$($out)*]),
+            $crate::value::Visitable::visit,
+        )
+    };
+    (@map [$($out:tt)*] $key:tt : { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out)* ($crate::value!(@key $key), $crate::value!({ $($inner)* })),] $($($rest)*)?)
+    };
+    (@map [$($out:tt)*] $key:tt : [ $($inner:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out)* ($crate::value!(@key $key), $crate::value!([ $($inner)* ])),] $($($rest)*)?)
+    };
+    (@map [$($out:tt)*] $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out)* ($crate::value!(@key $key), $crate::value!($value)),] $($($rest)*)?)
+    };
+    (@key $key:tt) => {
+        $crate::value::Value::borrowed(&$key)
+    };
+
+    // === lists ===
+
+    (@list [$($out:tt)*]) => {
+        $crate::value::Value::with_visit(
+            $crate::__private::List([$($out)*]),
+            $crate::value::Visitable::visit,
+        )
+    };
+    (@list [$($out:tt)*] { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::value!(@list [$($out)* $crate::value!({ $($inner)* }),] $($($rest)*)?)
+    };
+    (@list [$($out:tt)*] [ $($inner:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::value!(@list [$($out)* $crate::value!([ $($inner)* ]),] $($($rest)*)?)
+    };
+    (@list [$($out:tt)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::value!(@list [$($out)* $crate::value!($value),] $($($rest)*)?)
+    };
+
+    // === entry points ===
+
+    ({ $($map:tt)* }) => {
+        $crate::value!(@map [] $($map)*)
+    };
+    ([ $($list:tt)* ]) => {
+        $crate::value!(@list [] $($list)*)
+    };
+    ($value:expr) => {
+        $crate::value::Value::borrowed(&$value)
+    };
+}
+
+/// Asserts that a `Visitable` value's canonical text matches an inline
+/// snapshot.
+///
+/// The value is rendered with [`test_util::to_canonical_string`], and
+/// compared with the snapshot using [`test_util::assert_snapshot`], which
+/// ignores the snapshot's surrounding blank lines and common indentation.
+///
+/// This macro requires the `test-util` feature.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::assert_value_snapshot;
+/// let ids = vec![1, 2];
+/// assert_value_snapshot!(ids, @r#"
+///     [
+///         1,
+///         2,
+///     ]
+/// "#);
+/// ```
+///
+/// [`test_util::to_canonical_string`]: test_util/fn.to_canonical_string.html
+/// [`test_util::assert_snapshot`]: test_util/fn.assert_snapshot.html
+#[cfg(feature = "test-util")]
+#[macro_export]
+macro_rules! assert_value_snapshot {
+    ($value:expr, @$snapshot:literal $(,)?) => {
+        $crate::test_util::assert_snapshot(
+            &$crate::test_util::to_canonical_string(&$crate::value::Value::borrowed(&$value)),
+            $snapshot,
+        )
+    };
+}
+
+/// Constructs a [`Value`] which is visited as formatted text.
+///
+/// This takes the same arguments as [`format!`], but rather than formatting
+/// them into a `String`, the returned `Value` passes them to
+/// [`Visit::visit_fmt`] each time it is visited, so a visitor which writes
+/// its output directly (such as [`JsonVisit`]) need not allocate.
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// use valuable::format_value;
+///
+/// let n = 3;
+/// let value = format_value!("{} items", n);
+/// assert_eq!(value.render_json(), r#""3 items""#);
+/// ```
+///
+/// The `Value` borrows any variables referenced by the format arguments.
+/// Because they are formatted lazily, argument expressions are evaluated
+/// each time the value is visited.
+///
+/// [`Value`]: value/struct.Value.html
+/// [`format!`]: https://doc.rust-lang.org/std/macro.format.html
+/// [`Visit::visit_fmt`]: visitor/trait.Visit.html#method.visit_fmt
+/// [`JsonVisit`]: json/struct.JsonVisit.html
+#[macro_export]
+macro_rules! format_value {
+    ($($args:tt)+) => {
+        $crate::value::Value::with_visit((), |_, visitor: &mut dyn $crate::visitor::Visit| {
+            visitor.visit_fmt(format_args!($($args)+))
+        })
+    };
+}
+
+/// Defines a wrapper type implementing [`Visitable`] for a type from another
+/// crate.
+///
+/// Neither this crate nor yours may implement `Visitable` for a type defined
+/// in a third crate. Instead, this macro defines a transparent newtype
+/// around the remote type, which is visited as a struct with the remote
+/// type's name and the listed fields:
+///
+/// ```
+/// use std::time::Duration;
+/// use valuable::{impl_visitable_remote, value::Value};
+///
+/// impl_visitable_remote! {
+///     /// Visits a `Duration` as its whole seconds and nanoseconds.
+///     pub struct DurationDef(Duration) as d {
+///         secs: d.as_secs(),
+///         nanos: d.subsec_nanos(),
+///     }
+/// }
+///
+/// let timeout = DurationDef::from(Duration::from_millis(1500));
+/// assert_eq!(
+///     Value::borrowed(&timeout).render_debug(),
+///     "Duration { secs: 1, nanos: 500000000 }",
+/// );
+/// ```
+///
+/// The identifier following `as` names the wrapped value in the field
+/// expressions, each of which must evaluate to a `Visitable` value. A field
+/// without an expression visits the remote type's field of the same name,
+/// as `name: &d.name`. The wrapper dereferences to the wrapped value, and
+/// converts to and from it with `From`.
+///
+/// A wrapper may also borrow the remote value, so that values which are
+/// only available by reference can be visited without cloning them:
+///
+/// ```
+/// # use std::ops::Range;
+/// # use valuable::{impl_visitable_remote, value::Value};
+/// impl_visitable_remote! {
+///     struct RangeRef<'a>(&'a Range<u32>) as r { start, end }
+/// }
+///
+/// let range = 1..4;
+/// assert_eq!(
+///     Value::borrowed(&RangeRef(&range)).render_debug(),
+///     "Range { start: 1, end: 4 }",
+/// );
+/// ```
+///
+/// [`Visitable`]: value/trait.Visitable.html
+#[macro_export]
+macro_rules! impl_visitable_remote {
+    (@field $bind:ident $field:ident) => {
+        &$bind.$field
+    };
+    (@field $bind:ident $field:ident $value:expr) => {
+        &$value
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident $(<$lt:lifetime>)? ($remote:ty) as $bind:ident {
+            $( $field:ident $(: $value:expr)? ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        $vis struct $name $(<$lt>)? (pub $remote);
+
+        impl $(<$lt>)? $crate::value::Visitable for $name $(<$lt>)? {
+            fn visit(&self, visitor: &mut dyn $crate::visitor::Visit) -> $crate::visitor::VisitResult {
+                let $bind = &self.0;
+                visitor.visit_struct(
+                    $crate::__private::remote_name(stringify!($remote)),
+                    [$(
+                        (
+                            stringify!($field),
+                            $crate::value::Value::borrowed(
+                                $crate::impl_visitable_remote!(@field $bind $field $($value)?)
+                            ),
+                        )
+                    ),*],
+                )
+            }
+        }
+
+        impl $(<$lt>)? ::std::convert::From<$remote> for $name $(<$lt>)? {
+            fn from(value: $remote) -> Self {
+                $name(value)
+            }
+        }
+
+        impl $(<$lt>)? ::std::convert::From<$name $(<$lt>)?> for $remote {
+            fn from(value: $name $(<$lt>)?) -> Self {
+                value.0
+            }
+        }
+
+        impl $(<$lt>)? ::std::ops::Deref for $name $(<$lt>)? {
+            type Target = $remote;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+    };
+}
+
+/// Types used by the expansions of this crate's macros.
+///
+/// These are not public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{
+        extract::{ExtractError, FromValue},
+        json::JsonVisit,
+        key,
+        owned::OwnedValue,
+        record::FieldId,
+        value::{Value, Visitable},
+        visitor::{Capabilities, Error, ErrorMode, Visit, VisitResult},
+    };
+    use std::fmt;
+
+    pub struct Map<'a, const N: usize>(pub [(Value<'a>, Value<'a>); N]);
+
+    pub struct List<'a, const N: usize>(pub [Value<'a>; N]);
+
+    /// Returns the type name used by `impl_visitable_remote!` for the
+    /// stringified remote type `ty`: its last path segment, without any
+    /// generic arguments or reference.
+    pub fn remote_name(ty: &'static str) -> &'static str {
+        let ty = ty.split('<').next().unwrap_or(ty);
+        ty.rsplit(|c: char| c == ':' || c == '&' || c.is_whitespace())
+            .find(|segment| !segment.is_empty())
+            .unwrap_or(ty)
+    }
+
+    /// The value visited in place of a `#[visitable(redact)]` field.
+    pub const REDACTED: &str = "[REDACTED]";
+
+    /// A `#[visitable(redact = "hash")]` field, which is visited as a hash
+    /// of its JSON representation.
+    pub struct Hashed<'a>(pub &'a dyn Visitable);
+
+    impl Visitable for Hashed<'_> {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            if visitor.keys_only() {
+                return visitor.visit_unit();
+            }
+            let mut json = JsonVisit::new(String::new());
+            self.0.visit(&mut json)?;
+            let hash = key::fnv1a(json.into_inner().as_bytes());
+            visitor.visit_str(&format!("#{:016x}", hash))
+        }
+    }
+
+    /// A field of a struct deriving `Visitable` which is visited by a
+    /// `#[visitable(with = "...")]` function.
+    pub struct With<'a, T: ?Sized> {
+        value: &'a T,
+        visit: fn(&T, &mut dyn Visit) -> VisitResult,
+    }
+
+    impl<'a, T: ?Sized> With<'a, T> {
+        pub fn new(value: &'a T, visit: fn(&T, &mut dyn Visit) -> VisitResult) -> Self {
+            Self { value, visit }
+        }
+    }
+
+    impl<T: ?Sized> Visitable for With<'_, T> {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            if visitor.keys_only() {
+                return visitor.visit_unit();
+            }
+            (self.visit)(self.value, visitor)
+        }
+    }
+
+    /// The contents of an enum variant deriving `Visitable`, visited by a
+    /// closure.
+    pub struct Content<F>(pub F);
+
+    impl<F> Visitable for Content<F>
+    where
+        F: Fn(&mut dyn Visit) -> VisitResult,
+    {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            (self.0)(visitor)
+        }
+    }
+
+    /// Visits a named field of a struct deriving `Visitable`.
+    pub fn visit_field(visitor: &mut dyn Visit, name: &str, value: &dyn Visitable) -> VisitResult {
+        visitor
+            .visit_named_field(name, Value::borrowed(&value))
+            .map_err(|e| e.in_field(name))
+    }
+
+    /// Visits the `idx`th field of a tuple struct deriving `Visitable`.
+    pub fn visit_element(visitor: &mut dyn Visit, idx: usize, value: &dyn Visitable) -> VisitResult {
+        value.visit(visitor).map_err(|e| e.in_index(idx))
+    }
+
+    /// Visits the fields of `value`, which must be a map, struct, or record,
+    /// as fields of the struct currently being visited by `visitor`.
+    pub fn visit_flattened(visitor: &mut dyn Visit, value: &dyn Visitable) -> VisitResult {
+        value.visit(&mut Flatten {
+            inner: visitor,
+            open: false,
+        })
+    }
+
+    /// A `Visit` implementation which forwards the key-value pairs of a map
+    /// or struct to the enclosing visitor, without opening a new container.
+    ///
+    /// The values of the pairs are visited by the enclosing visitor
+    /// directly, so only the outermost container is seen here.
+    struct Flatten<'a> {
+        inner: &'a mut dyn Visit,
+        open: bool,
+    }
+
+    impl Flatten<'_> {
+        fn open(&mut self) -> VisitResult {
+            if self.open {
+                return Err(Error::unbalanced());
+            }
+            self.open = true;
+            Ok(())
+        }
+
+        fn close(&mut self) -> VisitResult {
+            if !self.open {
+                return Err(Error::unbalanced());
+            }
+            self.open = false;
+            Ok(())
+        }
+    }
+
+    impl Visit for Flatten<'_> {
+        fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+            value.visit(self)
+        }
+
+        fn visit_uint(&mut self, _: u64) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_int(&mut self, _: i64) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_float(&mut self, _: f64) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_str(&mut self, _: &str) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_bytes(&mut self, _: &[u8]) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_bool(&mut self, _: bool) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_unit(&mut self) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_fmt(&mut self, _: fmt::Arguments<'_>) -> VisitResult {
+            Err(Error::unsupported("flattened value which is not a map"))
+        }
+
+        fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+            self.inner.visit_kv(k, v)
+        }
+
+        fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+            self.inner.visit_field(field, value)
+        }
+
+        fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+            self.inner.visit_named_field(name, value)
+        }
+
+        fn named_type(&mut self, _: &str) -> VisitResult {
+            Ok(())
+        }
+
+        fn wants_value(&self, key: &str) -> bool {
+            self.inner.wants_value(key)
+        }
+
+        fn error_mode(&self) -> ErrorMode {
+            self.inner.error_mode()
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            self.inner.capabilities()
+        }
+
+        fn keys_only(&self) -> bool {
+            self.inner.keys_only()
+        }
+
+        fn open_map(&mut self) -> VisitResult {
+            self.open()
+        }
+
+        fn close_map(&mut self) -> VisitResult {
+            self.close()
+        }
+
+        fn open_struct(&mut self) -> VisitResult {
+            self.open()
+        }
+
+        fn close_struct(&mut self) -> VisitResult {
+            self.close()
+        }
+    }
+
+    /// The named fields of a struct or map being extracted by code
+    /// generated by `#[derive(FromValue)]`.
+    pub struct Fields {
+        fields: Vec<(String, OwnedValue)>,
+    }
+
+    impl Fields {
+        /// Returns the fields of `value`, which must be a struct or a map.
+        pub fn from_owned(value: OwnedValue) -> Result<Self, ExtractError> {
+            let fields = match value {
+                OwnedValue::Struct { fields, .. } => fields,
+                OwnedValue::Map(kvs) => kvs.into_iter().map(|(k, v)| (k.into_key(), v)).collect(),
+                value => return Err(ExtractError::invalid_type("a struct", &value)),
+            };
+            Ok(Self { fields })
+        }
+
+        fn remove(&mut self, name: &str) -> Option<OwnedValue> {
+            let idx = self.fields.iter().position(|(k, _)| k == name)?;
+            Some(self.fields.remove(idx).1)
+        }
+
+        /// Extracts the field `name`, which must be present.
+        pub fn take<T: FromValue>(&mut self, name: &'static str) -> Result<T, ExtractError> {
+            let value = self
+                .remove(name)
+                .ok_or_else(|| ExtractError::missing_field(name))?;
+            T::from_owned(value).map_err(|e| e.in_field(name))
+        }
+
+        /// Extracts the field `name`, if it is present.
+        pub fn take_optional<T: FromValue>(
+            &mut self,
+            name: &'static str,
+        ) -> Result<Option<T>, ExtractError> {
+            match self.remove(name) {
+                Some(value) => T::from_owned(value).map(Some).map_err(|e| e.in_field(name)),
+                None => Ok(None),
+            }
+        }
+
+        /// Extracts the fields which have not been taken, as a map, for a
+        /// `#[visitable(flatten)]` field.
+        pub fn rest<T: FromValue>(self) -> Result<T, ExtractError> {
+            let kvs = self
+                .fields
+                .into_iter()
+                .map(|(k, v)| (OwnedValue::Str(k.into()), v))
+                .collect();
+            T::from_owned(OwnedValue::Map(kvs))
+        }
+    }
+
+    /// The unnamed fields of a tuple struct or tuple being extracted by code
+    /// generated by `#[derive(FromValue)]`.
+    pub struct Elements {
+        elements: std::vec::IntoIter<OwnedValue>,
+        idx: usize,
+    }
+
+    impl Elements {
+        /// Returns the elements of `value`, which must be a tuple struct,
+        /// tuple, or list of `len` elements. If `len` is 0, `value` may also
+        /// be unit.
+        pub fn from_owned(value: OwnedValue, len: usize) -> Result<Self, ExtractError> {
+            let elements = match value {
+                OwnedValue::TupleStruct { fields, .. } => fields,
+                OwnedValue::Tuple(elements) | OwnedValue::List(elements) => elements,
+                OwnedValue::Unit if len == 0 => Vec::new(),
+                value => return Err(ExtractError::invalid_type("a tuple struct", &value)),
+            };
+            if elements.len() != len {
+                return Err(ExtractError::invalid_length(len, elements.len()));
+            }
+            Ok(Self {
+                elements: elements.into_iter(),
+                idx: 0,
+            })
+        }
+
+        /// Extracts the next element.
+        pub fn take<T: FromValue>(&mut self) -> Result<T, ExtractError> {
+            let idx = self.idx;
+            self.idx += 1;
+            // The number of elements was checked by `from_owned`.
+            let value = self.elements.next().unwrap_or(OwnedValue::Unit);
+            T::from_owned(value).map_err(|e| e.in_index(idx))
+        }
+    }
+
+    /// Returns the type name of a struct or tuple struct, for selecting the
+    /// variant of an enum being extracted.
+    pub fn type_name(value: &OwnedValue) -> Result<&str, ExtractError> {
+        match value {
+            OwnedValue::Struct { name, .. } | OwnedValue::TupleStruct { name, .. } => Ok(name),
+            value => Err(ExtractError::invalid_type("a struct", value)),
+        }
+    }
+
+    /// Splits an externally tagged enum variant into its name and contents.
+    pub fn external_variant(value: OwnedValue) -> Result<(String, OwnedValue), ExtractError> {
+        match value {
+            OwnedValue::Str(name) => Ok((name.into_string(), OwnedValue::Unit)),
+            OwnedValue::Map(mut kvs) if kvs.len() == 1 => {
+                let (name, content) = kvs.remove(0);
+                let name = String::from_owned(name)?;
+                Ok((name, content))
+            }
+            value => Err(ExtractError::invalid_type("a string or a map with one entry", &value)),
+        }
+    }
+
+    impl<const N: usize> Visitable for Map<'_, N> {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            visitor.visit_map(self.0.iter().map(|(k, v)| (k.by_ref(), v.by_ref())))
+        }
+    }
+
+    impl<const N: usize> Visitable for List<'_, N> {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            visitor.visit_list(self.0.iter().map(Value::by_ref))
+        }
+    }
+}
@@ -0,0 +1,685 @@
+use super::OwnedValue;
+use crate::{
+    key,
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, Visit, VisitResult},
+};
+use std::{fmt, fmt::Write as _, mem, ops::Range};
+
+/// A reusable buffer that stores many structured values in a few flat
+/// allocations.
+///
+/// An [`OwnedValue`] allocates separately for every string and container in
+/// the value it buffers. An `Arena` instead stores all of the values
+/// buffered into it in a handful of shared buffers, addressing each node by
+/// its index. Once the arena is [cleared], its buffers are reused by the
+/// next values buffered into it, so a pipeline which buffers and discards
+/// large numbers of values (such as the fields of every event it processes)
+/// need not allocate at all once the arena has grown to fit them.
+///
+/// Values are buffered with [`buffer`], which returns an [`ArenaValue`]
+/// handle that may be visited like any other `Visitable`. The values in an
+/// arena are buffered in the same way as by [`OwnedValue::from_value`].
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::{owned::Arena, value::Value};
+/// let mut arena = Arena::new();
+/// for id in 0..3 {
+///     let event = vec![id, id * 2];
+///     arena.buffer(&Value::borrowed(&event)).unwrap();
+/// }
+///
+/// let json: Vec<_> = arena.iter().map(|v| Value::borrowed(&v).render_json()).collect();
+/// assert_eq!(json, ["[0,0]", "[1,2]", "[2,4]"]);
+///
+/// // Reuse the arena's buffers for the next batch.
+/// arena.clear();
+/// assert!(arena.is_empty());
+/// ```
+///
+/// [`OwnedValue`]: enum.OwnedValue.html
+/// [cleared]: #method.clear
+/// [`buffer`]: #method.buffer
+/// [`ArenaValue`]: struct.ArenaValue.html
+/// [`OwnedValue::from_value`]: enum.OwnedValue.html#method.from_value
+#[derive(Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    /// The indices of the children of each container node. The children of
+    /// a container are stored contiguously; map and struct entries are
+    /// stored as a key followed by a value.
+    links: Vec<usize>,
+    text: String,
+    bytes: Vec<u8>,
+    /// The index of the root node of each buffered value.
+    roots: Vec<usize>,
+    /// The builder's allocations, kept for reuse by the next value.
+    scratch: (Vec<Frame>, Vec<usize>),
+}
+
+/// A handle to a value buffered in an [`Arena`].
+///
+/// [`Arena`]: struct.Arena.html
+#[derive(Copy, Clone)]
+pub struct ArenaValue<'a> {
+    arena: &'a Arena,
+    node: usize,
+}
+
+/// An iterator over the values buffered in an [`Arena`], in the order they
+/// were buffered.
+///
+/// This is returned by [`Arena::iter`].
+///
+/// [`Arena`]: struct.Arena.html
+/// [`Arena::iter`]: struct.Arena.html#method.iter
+pub struct Iter<'a> {
+    arena: &'a Arena,
+    roots: std::slice::Iter<'a, usize>,
+}
+
+#[derive(Clone)]
+enum Node {
+    Unit,
+    Bool(bool),
+    Uint(u64),
+    Int(i64),
+    Float(f64),
+    Str(Range<usize>),
+    Bytes(Range<usize>),
+    List(Range<usize>),
+    Tuple(Range<usize>),
+    Map(Range<usize>),
+    Struct {
+        name: Range<usize>,
+        fields: Range<usize>,
+    },
+    TupleStruct {
+        name: Range<usize>,
+        fields: Range<usize>,
+    },
+}
+
+/// A `Visit` implementation that buffers a visited value into an `Arena`.
+struct Builder<'a> {
+    arena: &'a mut Arena,
+    stack: Vec<Frame>,
+    /// The indices of the children of the open containers, innermost last.
+    pending: Vec<usize>,
+    /// The name passed to `named_type` for the next value, if any.
+    name: Option<Range<usize>>,
+    value: Option<usize>,
+}
+
+struct Frame {
+    kind: Container,
+    name: Option<Range<usize>>,
+    /// The index in `pending` of this container's first child.
+    start: usize,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+    /// The key or value of a map or struct entry, which must be exactly one
+    /// value.
+    Entry,
+}
+
+// === impl Arena ===
+
+impl Arena {
+    /// Returns a new, empty `Arena`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers the data visited by `value` into this arena, returning a
+    /// handle to the buffered value.
+    ///
+    /// # Errors
+    ///
+    /// If visiting `value` fails, or the value does not visit a balanced
+    /// sequence of containers, the error is returned, and the arena is left
+    /// as it was before `buffer` was called.
+    pub fn buffer(&mut self, value: &Value<'_>) -> Result<ArenaValue<'_>, Error> {
+        let (nodes, links, text, bytes) = (
+            self.nodes.len(),
+            self.links.len(),
+            self.text.len(),
+            self.bytes.len(),
+        );
+        let (stack, pending) = mem::take(&mut self.scratch);
+        let mut builder = Builder {
+            arena: self,
+            stack,
+            pending,
+            name: None,
+            value: None,
+        };
+        let result = match value.visit(&mut builder) {
+            Ok(()) => builder.finish(),
+            Err(e) => {
+                builder.reuse();
+                Err(e)
+            }
+        };
+        match result {
+            Ok(node) => {
+                self.roots.push(node);
+                Ok(ArenaValue { arena: self, node })
+            }
+            Err(e) => {
+                self.nodes.truncate(nodes);
+                self.links.truncate(links);
+                self.text.truncate(text);
+                self.bytes.truncate(bytes);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns an iterator over the values buffered in this arena, in the
+    /// order they were buffered.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            arena: self,
+            roots: self.roots.iter(),
+        }
+    }
+
+    /// Returns the number of values buffered in this arena.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Returns `true` if no values are buffered in this arena.
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Removes all values from this arena, keeping its allocated buffers
+    /// for reuse.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.links.clear();
+        self.text.clear();
+        self.bytes.clear();
+        self.roots.clear();
+    }
+
+    fn push_node(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn push_text(&mut self, s: &str) -> Range<usize> {
+        let start = self.text.len();
+        self.text.push_str(s);
+        start..self.text.len()
+    }
+
+    fn get(&self, node: usize) -> ArenaValue<'_> {
+        ArenaValue { arena: self, node }
+    }
+
+    fn children(&self, range: &Range<usize>) -> impl Iterator<Item = ArenaValue<'_>> {
+        self.links[range.clone()]
+            .iter()
+            .map(move |&node| self.get(node))
+    }
+}
+
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Arena {
+    type Item = ArenaValue<'a>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+// === impl ArenaValue ===
+
+impl ArenaValue<'_> {
+    /// Copies this value out of its arena into a new `OwnedValue`.
+    pub fn to_owned_value(&self) -> OwnedValue {
+        let arena = self.arena;
+        let list = |range: &Range<usize>| arena.children(range).map(|v| v.to_owned_value());
+        match &arena.nodes[self.node] {
+            Node::Unit => OwnedValue::Unit,
+            Node::Bool(v) => OwnedValue::Bool(*v),
+            Node::Uint(v) => OwnedValue::Uint(*v),
+            Node::Int(v) => OwnedValue::Int(*v),
+            Node::Float(v) => OwnedValue::Float(*v),
+            Node::Str(range) => OwnedValue::Str(arena.text[range.clone()].into()),
+            Node::Bytes(range) => OwnedValue::Bytes(arena.bytes[range.clone()].to_vec()),
+            Node::List(range) => OwnedValue::List(list(range).collect()),
+            Node::Tuple(range) => OwnedValue::Tuple(list(range).collect()),
+            Node::Map(range) => OwnedValue::Map(pairs(list(range)).collect()),
+            Node::Struct { name, fields } => OwnedValue::Struct {
+                name: arena.text[name.clone()].to_owned(),
+                fields: pairs(list(fields))
+                    .map(|(k, v)| (k.into_key(), v))
+                    .collect(),
+            },
+            Node::TupleStruct { name, fields } => OwnedValue::TupleStruct {
+                name: arena.text[name.clone()].to_owned(),
+                fields: list(fields).collect(),
+            },
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match &self.arena.nodes[self.node] {
+            Node::Str(range) => &self.arena.text[range.clone()],
+            // Struct field names are always buffered as strings.
+            _ => unreachable!("struct field name must be a string"),
+        }
+    }
+}
+
+impl Visitable for ArenaValue<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let arena = self.arena;
+        match &arena.nodes[self.node] {
+            Node::Unit => visitor.visit_unit(),
+            Node::Bool(v) => visitor.visit_bool(*v),
+            Node::Uint(v) => visitor.visit_uint(*v),
+            Node::Int(v) => visitor.visit_int(*v),
+            Node::Float(v) => visitor.visit_float(*v),
+            Node::Str(range) => visitor.visit_str(&arena.text[range.clone()]),
+            Node::Bytes(range) => visitor.visit_bytes(&arena.bytes[range.clone()]),
+            Node::List(range) => {
+                visitor.open_list()?;
+                visit_elements(arena, range, visitor)?;
+                visitor.close_list()
+            }
+            Node::Tuple(range) => {
+                visitor.open_tuple()?;
+                visit_elements(arena, range, visitor)?;
+                visitor.close_tuple()
+            }
+            Node::Map(range) => {
+                visitor.open_map()?;
+                for (k, v) in pairs(arena.children(range)) {
+                    visitor
+                        .visit_kv(Value::borrowed(&k), Value::borrowed(&v))
+                        .map_err(|e| e.in_key(&Value::borrowed(&k)))?;
+                }
+                visitor.close_map()
+            }
+            Node::Struct { name, fields } => {
+                visitor.named_type(&arena.text[name.clone()])?;
+                visitor.open_struct()?;
+                for (k, v) in pairs(arena.children(fields)) {
+                    let name = k.as_str();
+                    visitor
+                        .visit_kv(Value::borrowed(&name), Value::borrowed(&v))
+                        .map_err(|e| e.in_field(name))?;
+                }
+                visitor.close_struct()
+            }
+            Node::TupleStruct { name, fields } if fields.is_empty() => {
+                visitor.named_type(&arena.text[name.clone()])?;
+                visitor.visit_unit()
+            }
+            Node::TupleStruct { name, fields } => {
+                visitor.named_type(&arena.text[name.clone()])?;
+                visitor.open_tuple()?;
+                visit_elements(arena, fields, visitor)?;
+                visitor.close_tuple()
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ArenaValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_owned_value(), f)
+    }
+}
+
+fn visit_elements(arena: &Arena, range: &Range<usize>, visitor: &mut dyn Visit) -> VisitResult {
+    for (idx, v) in arena.children(range).enumerate() {
+        v.visit(visitor).map_err(|e| e.in_index(idx))?;
+    }
+    Ok(())
+}
+
+/// Groups the children of a map or struct node into key-value pairs.
+fn pairs<T>(mut children: impl Iterator<Item = T>) -> impl Iterator<Item = (T, T)> {
+    std::iter::from_fn(move || Some((children.next()?, children.next()?)))
+}
+
+// === impl Iter ===
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = ArenaValue<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.roots.next().map(|&node| self.arena.get(node))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.roots.size_hint()
+    }
+}
+
+impl fmt::Debug for Iter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter")
+            .field("remaining", &self.roots.len())
+            .finish()
+    }
+}
+
+// === impl Builder ===
+
+impl<'a> Builder<'a> {
+    fn finish(mut self) -> Result<usize, Error> {
+        let balanced = self.stack.is_empty();
+        let value = self.value.take();
+        let arena = self.reuse();
+        if !balanced {
+            return Err(Error::unbalanced());
+        }
+        match value {
+            Some(node) => Ok(node),
+            None => Ok(arena.push_node(Node::Unit)),
+        }
+    }
+
+    /// Returns the builder's allocations to the arena.
+    fn reuse(self) -> &'a mut Arena {
+        let (mut stack, mut pending) = (self.stack, self.pending);
+        stack.clear();
+        pending.clear();
+        self.arena.scratch = (stack, pending);
+        self.arena
+    }
+
+    fn push(&mut self, node: Node) -> VisitResult {
+        // A name applies only to the value immediately following it.
+        self.name = None;
+        match self.stack.last() {
+            Some(frame) if frame.kind == Container::Entry && self.pending.len() > frame.start => {
+                return Err(Error::unbalanced())
+            }
+            Some(frame) if frame.kind != Container::Map && frame.kind != Container::Struct => {}
+            // Values in maps and structs must be visited with `visit_kv`.
+            Some(_) => return Err(Error::unbalanced()),
+            None if self.value.is_some() => return Err(Error::unbalanced()),
+            None => {}
+        }
+        let node = self.arena.push_node(node);
+        match self.stack.last() {
+            Some(_) => self.pending.push(node),
+            None => self.value = Some(node),
+        }
+        Ok(())
+    }
+
+    fn push_str(&mut self, s: &str) -> VisitResult {
+        let range = self.arena.push_text(s);
+        self.push(Node::Str(range))
+    }
+
+    fn open(&mut self, kind: Container) -> VisitResult {
+        let name = self.name.take();
+        self.stack.push(Frame {
+            kind,
+            name,
+            start: self.pending.len(),
+        });
+        Ok(())
+    }
+
+    fn close(&mut self, kind: Container) -> VisitResult {
+        let frame = match self.stack.pop() {
+            Some(frame) if frame.kind == kind => frame,
+            _ => return Err(Error::unbalanced()),
+        };
+        let start = self.arena.links.len();
+        if kind == Container::Struct && frame.name.is_some() {
+            self.link_fields(frame.start);
+        } else {
+            self.arena.links.extend(self.pending.drain(frame.start..));
+        }
+        let links = start..self.arena.links.len();
+        let node = match (frame.kind, frame.name) {
+            (Container::List, _) => Node::List(links),
+            (Container::Tuple, None) => Node::Tuple(links),
+            (Container::Tuple, Some(name)) => Node::TupleStruct {
+                name,
+                fields: links,
+            },
+            (Container::Struct, Some(name)) => Node::Struct {
+                name,
+                fields: links,
+            },
+            (Container::Map, _) | (Container::Struct, None) => Node::Map(links),
+            (Container::Entry, _) => unreachable!("entries are closed by `entry`"),
+        };
+        self.push(node)
+    }
+
+    /// Links the pending fields of a struct, converting any field names which
+    /// are not strings into strings.
+    fn link_fields(&mut self, start: usize) {
+        for (i, node) in self.pending.drain(start..).enumerate() {
+            let is_key = i % 2 == 0;
+            let node = match self.arena.nodes[node] {
+                Node::Str(_) => node,
+                _ if is_key => {
+                    let key = key::stringify(self.arena.get(node).to_owned_value());
+                    let range = self.arena.push_text(&key);
+                    self.arena.push_node(Node::Str(range))
+                }
+                _ => node,
+            };
+            self.arena.links.push(node);
+        }
+    }
+
+    /// Visits one key or value of a map or struct entry.
+    fn entry(&mut self, value: &Value<'_>) -> VisitResult {
+        self.open(Container::Entry)?;
+        let start = self.pending.len();
+        value.visit(self)?;
+        match self.stack.pop() {
+            Some(frame) if frame.kind == Container::Entry => {}
+            _ => return Err(Error::unbalanced()),
+        }
+        if self.pending.len() == start {
+            let node = self.arena.push_node(Node::Unit);
+            self.pending.push(node);
+        }
+        Ok(())
+    }
+}
+
+impl Visit for Builder<'_> {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.push(Node::Uint(value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.push(Node::Int(value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.push(Node::Float(value))
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.push_str(value)
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> VisitResult {
+        let start = self.arena.bytes.len();
+        self.arena.bytes.extend_from_slice(value);
+        self.push(Node::Bytes(start..self.arena.bytes.len()))
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.push(Node::Bool(value))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        match self.name.take() {
+            Some(name) => self.push(Node::TupleStruct { name, fields: 0..0 }),
+            None => self.push(Node::Unit),
+        }
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        match self.stack.last() {
+            Some(frame) if frame.kind == Container::Map || frame.kind == Container::Struct => {}
+            _ => return Err(Error::unbalanced()),
+        }
+        self.name = None;
+        self.entry(&k)?;
+        self.entry(&v)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        let start = self.arena.text.len();
+        let _ = self.arena.text.write_fmt(args);
+        self.push(Node::Str(start..self.arena.text.len()))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.open_map()?;
+        for (name, value) in record.iter() {
+            self.visit_named_field(name, value)
+                .map_err(|e| e.in_field(name))?;
+        }
+        self.close_map()
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        self.name = Some(self.arena.push_text(name));
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::BYTES
+            | Capabilities::NAMED_TYPES
+            | Capabilities::UNIT
+            | Capabilities::MAPS
+            | Capabilities::LISTS
+            | Capabilities::STRUCTS
+            | Capabilities::TUPLES
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ValueExt;
+
+    #[test]
+    fn matches_owned_value() {
+        let value = OwnedValue::List(vec![
+            OwnedValue::Struct {
+                name: "Point".to_owned(),
+                fields: vec![
+                    ("x".to_owned(), OwnedValue::Int(-1)),
+                    ("y".to_owned(), OwnedValue::Float(2.5)),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Unit".to_owned(),
+                fields: Vec::new(),
+            },
+            OwnedValue::Tuple(vec![OwnedValue::Bytes(vec![1, 2]), OwnedValue::Unit]),
+            OwnedValue::Map(vec![(
+                OwnedValue::List(vec![OwnedValue::Uint(1)]),
+                OwnedValue::Str("a".into()),
+            )]),
+        ]);
+
+        let mut arena = Arena::new();
+        let buffered = arena.buffer(&Value::borrowed(&value)).unwrap();
+        assert_eq!(buffered.to_owned_value(), value);
+        let json = Value::borrowed(&buffered).render_json();
+        assert_eq!(json, Value::borrowed(&value).render_json());
+
+        let display = 1.5;
+        arena.buffer(&Value::display(&display)).unwrap();
+        let pairs = [("msg", Value::borrowed(&"hi"))];
+        let record = Value::with_visit(&pairs[..], |pairs, visitor: &mut dyn Visit| {
+            visitor.visit_record(&Record::from_pairs(pairs))
+        });
+        arena.buffer(&record).unwrap();
+        let values: Vec<_> = arena.iter().map(|v| v.to_owned_value()).collect();
+        assert_eq!(values[1], OwnedValue::Str("1.5".into()));
+        assert_eq!(
+            values[2],
+            OwnedValue::Map(vec![(
+                OwnedValue::Str("msg".into()),
+                OwnedValue::Str("hi".into())
+            )])
+        );
+
+        // A failed visit leaves the arena unchanged.
+        let unbalanced = Value::with_visit((), |_, visitor: &mut dyn Visit| visitor.open_list());
+        assert!(arena.buffer(&unbalanced).is_err());
+        assert_eq!(arena.len(), 3);
+        assert_eq!(arena.iter().last().unwrap().to_owned_value(), values[2]);
+
+        arena.clear();
+        assert!(arena.is_empty());
+        assert!(arena.nodes.capacity() > 0);
+    }
+}
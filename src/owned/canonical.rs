@@ -0,0 +1,207 @@
+use super::OwnedValue;
+use std::cmp::Ordering;
+
+// === impl OwnedValue ===
+
+impl OwnedValue {
+    /// Converts this value into its canonical form.
+    ///
+    /// Two values which represent the same data have the same canonical
+    /// form, regardless of the order in which their maps were built or of
+    /// which process built them. So the canonical form may be compared,
+    /// hashed (for example, by hashing its [`wire`] encoding), or used as a
+    /// deduplication key. In the canonical form, recursively:
+    ///
+    /// - map entries are sorted by key, and if a map has several entries
+    ///   with the same key, only the last is kept,
+    /// - struct fields are sorted by name, and if a struct has several
+    ///   fields with the same name, only the last is kept,
+    /// - non-negative `Int`s are converted into `Uint`s, so the same integer
+    ///   is always represented in the same way,
+    /// - `-0.0` is converted into `0.0`, and every NaN is converted into
+    ///   the same NaN.
+    ///
+    /// Lists, tuples, and the fields of tuple structs keep their order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::{owned::OwnedValue, value};
+    /// let a = value!({ "b": [-0.0], "a": 1 }).canonicalize().unwrap();
+    /// let b = value!({ "a": 1u8, "b": [0.0] }).canonicalize().unwrap();
+    /// assert_eq!(a, b);
+    /// assert_eq!(a.get_path("b.0"), Some(&OwnedValue::Float(0.0)));
+    /// ```
+    ///
+    /// [`wire`]: fn.visit_from.html
+    pub fn canonicalize(self) -> OwnedValue {
+        match self {
+            OwnedValue::Int(v) if v >= 0 => OwnedValue::Uint(v as u64),
+            OwnedValue::Float(v) => OwnedValue::Float(canonical_float(v)),
+            OwnedValue::List(vs) => OwnedValue::List(canonicalize_all(vs)),
+            OwnedValue::Tuple(vs) => OwnedValue::Tuple(canonicalize_all(vs)),
+            OwnedValue::TupleStruct { name, fields } => OwnedValue::TupleStruct {
+                name,
+                fields: canonicalize_all(fields),
+            },
+            OwnedValue::Map(kvs) => {
+                let kvs = kvs
+                    .into_iter()
+                    .map(|(k, v)| (k.canonicalize(), v.canonicalize()))
+                    .collect();
+                OwnedValue::Map(sort_unique(kvs, total_cmp))
+            }
+            OwnedValue::Struct { name, fields } => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(k, v)| (k, v.canonicalize()))
+                    .collect();
+                OwnedValue::Struct {
+                    name,
+                    fields: sort_unique(fields, |a: &String, b| a.cmp(b)),
+                }
+            }
+            value => value,
+        }
+    }
+}
+
+fn canonicalize_all(values: Vec<OwnedValue>) -> Vec<OwnedValue> {
+    values.into_iter().map(OwnedValue::canonicalize).collect()
+}
+
+fn canonical_float(v: f64) -> f64 {
+    if v.is_nan() {
+        f64::NAN
+    } else if v == 0.0 {
+        0.0
+    } else {
+        v
+    }
+}
+
+/// Sorts `entries` by key, keeping only the last entry with each key.
+fn sort_unique<K, V>(mut entries: Vec<(K, V)>, cmp: impl Fn(&K, &K) -> Ordering) -> Vec<(K, V)> {
+    // The sort is stable, so once the entries are reversed, the last entry
+    // with each key is the first of its run, which `dedup_by` keeps.
+    entries.sort_by(|(a, _), (b, _)| cmp(a, b));
+    entries.reverse();
+    entries.dedup_by(|(a, _), (b, _)| cmp(a, b) == Ordering::Equal);
+    entries.reverse();
+    entries
+}
+
+/// A total order over values, used to sort map keys.
+///
+/// Values of different kinds are ordered by kind, and values of the same
+/// kind by their contents.
+fn total_cmp(a: &OwnedValue, b: &OwnedValue) -> Ordering {
+    use OwnedValue::*;
+    match (a, b) {
+        (Bool(a), Bool(b)) => a.cmp(b),
+        (Uint(a), Uint(b)) => a.cmp(b),
+        (Int(a), Int(b)) => a.cmp(b),
+        (Float(a), Float(b)) => a.total_cmp(b),
+        (Str(a), Str(b)) => a.cmp(b),
+        (Bytes(a), Bytes(b)) => a.cmp(b),
+        (List(a), List(b)) | (Tuple(a), Tuple(b)) => cmp_seq(a, b),
+        (Map(a), Map(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((ak, av), (bk, bv))| total_cmp(ak, bk).then_with(|| total_cmp(av, bv)))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (
+            Struct {
+                name: an,
+                fields: af,
+            },
+            Struct {
+                name: bn,
+                fields: bf,
+            },
+        ) => an.cmp(bn).then_with(|| {
+            af.iter()
+                .zip(bf)
+                .map(|((ak, av), (bk, bv))| ak.cmp(bk).then_with(|| total_cmp(av, bv)))
+                .find(|ord| ord.is_ne())
+                .unwrap_or_else(|| af.len().cmp(&bf.len()))
+        }),
+        (
+            TupleStruct {
+                name: an,
+                fields: af,
+            },
+            TupleStruct {
+                name: bn,
+                fields: bf,
+            },
+        ) => an.cmp(bn).then_with(|| cmp_seq(af, bf)),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn cmp_seq(a: &[OwnedValue], b: &[OwnedValue]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| total_cmp(a, b))
+        .find(|ord| ord.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+fn rank(value: &OwnedValue) -> u8 {
+    match value {
+        OwnedValue::Unit => 0,
+        OwnedValue::Bool(_) => 1,
+        OwnedValue::Int(_) => 2,
+        OwnedValue::Uint(_) => 3,
+        OwnedValue::Float(_) => 4,
+        OwnedValue::Str(_) => 5,
+        OwnedValue::Bytes(_) => 6,
+        OwnedValue::List(_) => 7,
+        OwnedValue::Tuple(_) => 8,
+        OwnedValue::Map(_) => 9,
+        OwnedValue::Struct { .. } => 10,
+        OwnedValue::TupleStruct { .. } => 11,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_and_dedups() {
+        let s = |s: &str| OwnedValue::Str(s.into());
+        let value = OwnedValue::Map(vec![
+            (s("b"), OwnedValue::Int(-1)),
+            (OwnedValue::Int(2), OwnedValue::Float(f64::NAN)),
+            (s("a"), OwnedValue::Int(1)),
+            (s("b"), OwnedValue::Int(3)),
+        ]);
+        let canonical = value.canonicalize();
+        let expected = OwnedValue::Map(vec![
+            (OwnedValue::Uint(2), OwnedValue::Float(f64::NAN)),
+            (s("a"), OwnedValue::Uint(1)),
+            (s("b"), OwnedValue::Uint(3)),
+        ]);
+        // NaNs are never equal, so compare their encodings.
+        assert_eq!(format!("{:?}", canonical), format!("{:?}", expected));
+
+        let value = OwnedValue::Struct {
+            name: "S".to_owned(),
+            fields: vec![
+                ("z".to_owned(), OwnedValue::Float(-0.0)),
+                ("a".to_owned(), OwnedValue::Unit),
+            ],
+        };
+        match value.canonicalize() {
+            OwnedValue::Struct { fields, .. } => {
+                assert_eq!(fields[0].0, "a");
+                assert!(matches!(fields[1].1, OwnedValue::Float(v) if v.is_sign_positive()));
+            }
+            value => panic!("unexpected value: {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Randomized tests which generate `OwnedValue` trees and check that they
+//! survive every built-in conversion.
+use super::{Arena, OwnedValue};
+use crate::{
+    schema::{Schema, ValidateVisit},
+    value::Value,
+};
+
+/// The number of random values checked by each test.
+const CASES: u64 = 2_000;
+
+/// A deterministic generator of random `OwnedValue`s.
+///
+/// This uses xorshift64*, seeded per case, so that a failing case may be
+/// reproduced from its seed alone.
+struct Gen {
+    state: u64,
+}
+
+impl Gen {
+    fn new(seed: u64) -> Self {
+        // Xorshift never leaves the all-zero state.
+        Self {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn string(&mut self) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', ' ', '"', '\\', '\n', '\u{1}', 'é', '😀'];
+        (0..self.below(6))
+            .map(|_| CHARS[self.below(CHARS.len() as u64) as usize])
+            .collect()
+    }
+
+    fn len(&mut self, depth: usize) -> usize {
+        if depth == 0 {
+            0
+        } else {
+            self.below(4) as usize
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> OwnedValue {
+        let kinds = if depth == 0 { 7 } else { 12 };
+        match self.below(kinds) {
+            0 => OwnedValue::Unit,
+            1 => OwnedValue::Bool(self.below(2) == 1),
+            2 => OwnedValue::Uint(self.next() >> self.below(64)),
+            3 => OwnedValue::Int((self.next() as i64) >> self.below(64)),
+            // Only finite floats, since `NaN` is not equal to itself.
+            4 => OwnedValue::Float((self.next() as i64 >> 11) as f64 / 1024.0),
+            5 => OwnedValue::Str(self.string().into()),
+            6 => OwnedValue::Bytes(self.string().into_bytes()),
+            7 => OwnedValue::List(
+                (0..self.len(depth))
+                    .map(|_| self.value(depth - 1))
+                    .collect(),
+            ),
+            8 => OwnedValue::Tuple(
+                (0..self.len(depth))
+                    .map(|_| self.value(depth - 1))
+                    .collect(),
+            ),
+            9 => OwnedValue::Map(
+                (0..self.len(depth))
+                    .map(|_| (self.value(depth - 1), self.value(depth - 1)))
+                    .collect(),
+            ),
+            10 => OwnedValue::Struct {
+                name: self.string(),
+                // Field names are made unique, as they are in Rust structs.
+                fields: (0..self.len(depth))
+                    .map(|i| (format!("{}{}", self.string(), i), self.value(depth - 1)))
+                    .collect(),
+            },
+            _ => OwnedValue::TupleStruct {
+                name: self.string(),
+                fields: (0..self.len(depth))
+                    .map(|_| self.value(depth - 1))
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Runs `check` with each random value, reporting the seed of any failure.
+fn for_each_value(mut check: impl FnMut(&OwnedValue)) {
+    for seed in 0..CASES {
+        let value = Gen::new(seed).value(4);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| check(&value)));
+        if result.is_err() {
+            panic!("check failed for seed {}: {:?}", seed, value);
+        }
+    }
+}
+
+#[test]
+fn buffers_round_trip() {
+    let mut arena = Arena::new();
+    for_each_value(|value| {
+        let visited = Value::borrowed(value);
+        assert_eq!(&OwnedValue::from_value(&visited).unwrap(), value);
+
+        arena.clear();
+        let buffered = arena.buffer(&visited).unwrap().to_owned_value();
+        assert_eq!(&buffered, value);
+
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        let mut reader = &buf[..];
+        assert_eq!(&OwnedValue::read_from(&mut reader).unwrap(), value);
+        assert!(reader.is_empty());
+
+        let mut streamed = super::Builder::default();
+        super::visit_from(&mut &buf[..], &mut streamed).unwrap();
+        assert_eq!(&streamed.finish().unwrap(), value);
+    });
+}
+
+#[test]
+fn values_match_inferred_schemas() {
+    for_each_value(|value| {
+        let schema = Schema::of(&Value::borrowed(value)).unwrap();
+        let mut validate = ValidateVisit::new(&schema);
+        Value::borrowed(value).visit(&mut validate).unwrap();
+        assert_eq!(validate.finish().unwrap(), []);
+    });
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_round_trips() {
+    use crate::value::ValueExt;
+
+    for_each_value(|value| {
+        let json = Value::borrowed(value).render_json();
+        let parsed = crate::json::from_str(&json).unwrap();
+        // Type names and non-string keys are lost, but the parsed value
+        // must be written the same way again.
+        assert_eq!(Value::borrowed(&parsed).render_json(), json);
+    });
+}
@@ -0,0 +1,153 @@
+use super::OwnedValue;
+
+/// How [`OwnedValue::merge`] resolves a conflict between two values with
+/// the same key.
+///
+/// Conflicting maps, and conflicting structs with the same name, are always
+/// merged recursively; the strategy decides what happens to any other pair
+/// of conflicting values.
+///
+/// [`OwnedValue::merge`]: enum.OwnedValue.html#method.merge
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MergeStrategy {
+    /// The merged value replaces the existing value.
+    Overwrite,
+    /// The existing value is kept, and the merged value is discarded.
+    KeepFirst,
+    /// The items of a merged list are appended to an existing list;
+    /// otherwise, the merged value replaces the existing value.
+    AppendLists,
+}
+
+// === impl OwnedValue ===
+
+impl OwnedValue {
+    /// Deeply merges `other` into this value.
+    ///
+    /// If both values are maps, each entry of `other` is merged into the
+    /// entry of this map with the same key, or appended to this map if it
+    /// has no such entry. Structs with the same type name are merged in the
+    /// same way, by field name. Any other pair of values is resolved by
+    /// `strategy`.
+    ///
+    /// This allows fields common to many values (such as the fields of a
+    /// request, shared by each event logged while handling it) to be
+    /// buffered once and combined with each value's own fields, without
+    /// visiting the common fields again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::{owned::{MergeStrategy, OwnedValue}, value, value::Value};
+    /// let base = value!({ "request": { "id": 7 }, "tags": ["api"] });
+    /// let event = value!({ "request": { "path": "/" }, "tags": ["slow"] });
+    ///
+    /// let mut merged = OwnedValue::from_value(&base).unwrap();
+    /// merged.merge(
+    ///     &OwnedValue::from_value(&event).unwrap(),
+    ///     MergeStrategy::AppendLists,
+    /// );
+    /// assert_eq!(
+    ///     Value::borrowed(&merged).render_json(),
+    ///     r#"{"request":{"id":7,"path":"/"},"tags":["api","slow"]}"#
+    /// );
+    /// ```
+    pub fn merge(&mut self, other: &OwnedValue, strategy: MergeStrategy) {
+        match (&mut *self, other) {
+            (OwnedValue::Map(kvs), OwnedValue::Map(others)) => {
+                for (key, value) in others {
+                    match kvs.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, existing)) => existing.merge(value, strategy),
+                        None => kvs.push((key.clone(), value.clone())),
+                    }
+                }
+            }
+            (
+                OwnedValue::Struct { name, fields },
+                OwnedValue::Struct {
+                    name: other_name,
+                    fields: others,
+                },
+            ) if name == other_name => {
+                for (key, value) in others {
+                    match fields.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, existing)) => existing.merge(value, strategy),
+                        None => fields.push((key.clone(), value.clone())),
+                    }
+                }
+            }
+            (OwnedValue::List(vs), OwnedValue::List(others))
+                if strategy == MergeStrategy::AppendLists =>
+            {
+                vs.extend(others.iter().cloned())
+            }
+            _ if strategy == MergeStrategy::KeepFirst => {}
+            _ => *self = other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies() {
+        let s = |s: &str| OwnedValue::Str(s.into());
+        let base = OwnedValue::Map(vec![
+            (s("a"), OwnedValue::Uint(1)),
+            (s("list"), OwnedValue::List(vec![OwnedValue::Uint(1)])),
+        ]);
+        let other = OwnedValue::Map(vec![
+            (s("list"), OwnedValue::List(vec![OwnedValue::Uint(2)])),
+            (s("a"), OwnedValue::Uint(2)),
+            (s("b"), OwnedValue::Unit),
+        ]);
+        let merged = |strategy| {
+            let mut value = base.clone();
+            value.merge(&other, strategy);
+            value
+        };
+        let list = |vs: &[u64]| {
+            OwnedValue::List(vs.iter().copied().map(OwnedValue::Uint).collect())
+        };
+
+        assert_eq!(
+            merged(MergeStrategy::Overwrite),
+            OwnedValue::Map(vec![
+                (s("a"), OwnedValue::Uint(2)),
+                (s("list"), list(&[2])),
+                (s("b"), OwnedValue::Unit),
+            ])
+        );
+        assert_eq!(
+            merged(MergeStrategy::KeepFirst),
+            OwnedValue::Map(vec![
+                (s("a"), OwnedValue::Uint(1)),
+                (s("list"), list(&[1])),
+                (s("b"), OwnedValue::Unit),
+            ])
+        );
+        assert_eq!(
+            merged(MergeStrategy::AppendLists),
+            OwnedValue::Map(vec![
+                (s("a"), OwnedValue::Uint(2)),
+                (s("list"), list(&[1, 2])),
+                (s("b"), OwnedValue::Unit),
+            ])
+        );
+
+        // Structs with different names conflict, rather than being merged.
+        let mut a = OwnedValue::Struct {
+            name: "A".to_owned(),
+            fields: vec![("x".to_owned(), OwnedValue::Uint(1))],
+        };
+        let b = OwnedValue::Struct {
+            name: "B".to_owned(),
+            fields: vec![("y".to_owned(), OwnedValue::Uint(2))],
+        };
+        a.merge(&b, MergeStrategy::Overwrite);
+        assert_eq!(a, b);
+    }
+}
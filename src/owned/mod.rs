@@ -0,0 +1,538 @@
+use crate::{
+    combinator::Prefix,
+    key::{self, Key},
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, Visit, VisitExt, VisitResult},
+};
+use std::fmt::{self, Write as _};
+
+mod arena;
+mod canonical;
+mod merge;
+mod string;
+mod wire;
+
+#[cfg(test)]
+mod fuzz;
+
+pub use self::{
+    arena::{Arena, ArenaValue},
+    merge::MergeStrategy,
+    string::OwnedStr,
+    wire::visit_from,
+};
+
+/// An owned, buffered structured value.
+///
+/// Unlike [`Value`], which borrows (or boxes) an erased `Visitable`, an
+/// `OwnedValue` is a concrete tree of data that may be stored, inspected, and
+/// re-visited any number of times with any `Visit` implementation.
+///
+/// An `OwnedValue` may be produced by parsing a structured payload (such as
+/// JSON, using [`json::from_str`] when the `json` feature is enabled), or
+/// constructed directly.
+///
+/// [`Value`]: ../value/struct.Value.html
+/// [`json::from_str`]: ../json/fn.from_str.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedValue {
+    /// A unit value (such as `()` or JSON `null`).
+    Unit,
+    /// A boolean value.
+    Bool(bool),
+    /// An unsigned integer value.
+    Uint(u64),
+    /// A signed integer value.
+    Int(i64),
+    /// A floating-point value.
+    Float(f64),
+    /// A string value.
+    ///
+    /// Short strings are stored inline, rather than allocated; see
+    /// [`OwnedStr`].
+    ///
+    /// [`OwnedStr`]: struct.OwnedStr.html
+    Str(OwnedStr),
+    /// A byte string, containing binary data.
+    Bytes(Vec<u8>),
+    /// An ordered list of values.
+    List(Vec<OwnedValue>),
+    /// A tuple of values.
+    Tuple(Vec<OwnedValue>),
+    /// A map of key-value pairs, in the order they were visited.
+    Map(Vec<(OwnedValue, OwnedValue)>),
+    /// A named `struct` with named fields.
+    Struct {
+        /// The name of the struct's type.
+        name: String,
+        /// The struct's field names and values, in order.
+        fields: Vec<(String, OwnedValue)>,
+    },
+    /// A named tuple `struct` with unnamed fields.
+    ///
+    /// A named unit value (such as a unit struct) is buffered as a
+    /// `TupleStruct` with no fields, and is visited as a named unit value.
+    TupleStruct {
+        /// The name of the struct's type.
+        name: String,
+        /// The struct's field values, in order.
+        fields: Vec<OwnedValue>,
+    },
+}
+
+/// A `Visit` implementation that buffers a visited value into an
+/// `OwnedValue`.
+#[derive(Default)]
+pub(crate) struct Builder {
+    stack: Vec<Frame>,
+    /// The name passed to `named_type` for the next value, if any.
+    name: Option<String>,
+    value: Option<OwnedValue>,
+    /// The maximum number of characters to keep when rendering formatted
+    /// values, if limited.
+    max_fmt_len: Option<usize>,
+}
+
+struct Frame {
+    kind: Container,
+    name: Option<String>,
+    values: Vec<OwnedValue>,
+    pairs: Vec<(OwnedValue, OwnedValue)>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+// === impl OwnedValue ===
+
+impl OwnedValue {
+    /// Buffers the data visited by `value` into a new `OwnedValue`.
+    ///
+    /// Formatted values (such as those constructed with `Value::display`)
+    /// are rendered into strings, and records and structs without a type
+    /// name are buffered as maps. A named value with no fields (such as a
+    /// unit struct) is buffered as an empty tuple struct.
+    ///
+    /// # Errors
+    ///
+    /// If visiting `value` fails, or the value does not visit a balanced
+    /// sequence of containers, the error is returned.
+    pub fn from_value(value: &Value<'_>) -> Result<Self, Error> {
+        Builder::default().buffer(value)
+    }
+
+    /// Buffers the data visited by `value` into a new `OwnedValue`, keeping
+    /// at most `max` characters of each formatted value.
+    ///
+    /// This behaves like [`from_value`], except that formatted values (such
+    /// as those constructed with `Value::display` or `Value::debug`) with
+    /// more than `max` characters are rendered as their first `max`
+    /// characters followed by `…N more`, where `N` is the number of
+    /// characters omitted. The rest of the formatted output is discarded as
+    /// it is written, so a value with very large `Debug` output does not
+    /// need to be rendered in full. Strings visited with `visit_str` are
+    /// not shortened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{owned::OwnedValue, value::Value};
+    /// let long = vec![0u8; 100];
+    /// let owned = OwnedValue::from_value_with_max_fmt_len(&Value::debug(&long), 6).unwrap();
+    /// assert_eq!(owned, OwnedValue::Str("[0, 0,…294 more".into()));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If visiting `value` fails, or the value does not visit a balanced
+    /// sequence of containers, the error is returned.
+    ///
+    /// [`from_value`]: #method.from_value
+    pub fn from_value_with_max_fmt_len(value: &Value<'_>, max: usize) -> Result<Self, Error> {
+        let builder = Builder {
+            max_fmt_len: Some(max),
+            ..Builder::default()
+        };
+        builder.buffer(value)
+    }
+
+    /// Returns the value of the map entry, struct field, or list, tuple, or
+    /// tuple struct element identified by `key`, if there is one.
+    ///
+    /// Map entries are found by [`Key::matches`], so the key `"0"` finds
+    /// both the entry with the string key `"0"` and the entry with the
+    /// integer key `0`. If a map has several entries which match, the first
+    /// is returned. Elements are found by [`Key::as_index`].
+    ///
+    /// [`Key::matches`]: ../key/struct.Key.html#method.matches
+    /// [`Key::as_index`]: ../key/struct.Key.html#method.as_index
+    pub fn get<'a>(&self, key: impl Into<Key<'a>>) -> Option<&OwnedValue> {
+        let key = key.into();
+        match self {
+            OwnedValue::List(vs)
+            | OwnedValue::Tuple(vs)
+            | OwnedValue::TupleStruct { fields: vs, .. } => vs.get(key.as_index()?),
+            OwnedValue::Map(kvs) => kvs.iter().find(|(k, _)| key.matches(k)).map(|(_, v)| v),
+            OwnedValue::Struct { fields, .. } => fields
+                .iter()
+                .find(|(name, _)| name == key.as_str())
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value at `path`, a sequence of keys separated by `.`s
+    /// (such as `foo.0.bar`), if there is one.
+    ///
+    /// Each key is looked up with [`get`], so a segment which is an integer
+    /// may refer either to an element of a list, tuple, or tuple struct, or
+    /// to a map entry. An empty path refers to this value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{owned::OwnedValue, value};
+    /// let value = OwnedValue::from_value(&value!({
+    ///     "users": [{ "name": "alice" }],
+    ///     "codes": { 404: "not found" },
+    /// }))
+    /// .unwrap();
+    ///
+    /// let name = value.get_path("users.0.name");
+    /// assert_eq!(name, Some(&OwnedValue::Str("alice".into())));
+    /// assert!(value.get_path("codes.404").is_some());
+    /// assert!(value.get_path("users.1").is_none());
+    /// ```
+    ///
+    /// [`get`]: #method.get
+    pub fn get_path(&self, path: &str) -> Option<&OwnedValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.').try_fold(self, |value, key| value.get(key))
+    }
+
+    /// Converts a buffered map key into a struct field name.
+    pub(crate) fn into_key(self) -> String {
+        key::stringify(self)
+    }
+}
+
+impl Visitable for OwnedValue {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self {
+            OwnedValue::Unit => visitor.visit_unit(),
+            OwnedValue::Bool(v) => visitor.visit_bool(*v),
+            OwnedValue::Uint(v) => visitor.visit_uint(*v),
+            OwnedValue::Int(v) => visitor.visit_int(*v),
+            OwnedValue::Float(v) => visitor.visit_float(*v),
+            OwnedValue::Str(v) => visitor.visit_str(v),
+            OwnedValue::Bytes(v) => visitor.visit_bytes(v),
+            OwnedValue::List(vs) => visitor.visit_list(vs.iter().map(Value::borrowed)),
+            OwnedValue::Tuple(vs) => visitor.visit_tuple(vs.iter().map(Value::borrowed)),
+            OwnedValue::Map(kvs) => visitor.visit_map(
+                kvs.iter()
+                    .map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))),
+            ),
+            OwnedValue::Struct { name, fields } => visitor.visit_struct(
+                name,
+                fields.iter().map(|(k, v)| (k.as_str(), Value::borrowed(v))),
+            ),
+            OwnedValue::TupleStruct { name, fields } if fields.is_empty() => {
+                visitor.named_type(name)?;
+                visitor.visit_unit()
+            }
+            OwnedValue::TupleStruct { name, fields } => {
+                visitor.visit_tuple_struct(name, fields.iter().map(Value::borrowed))
+            }
+        }
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        match self {
+            OwnedValue::Unit => visitor.visit_unit(),
+            OwnedValue::Bool(v) => visitor.visit_bool(*v),
+            OwnedValue::Uint(v) => visitor.visit_uint(*v),
+            OwnedValue::Int(v) => visitor.visit_int(*v),
+            OwnedValue::Float(v) => visitor.visit_float(*v),
+            OwnedValue::Str(v) => visitor.visit_str(v),
+            OwnedValue::Bytes(v) => visitor.visit_bytes(v),
+            OwnedValue::List(vs) => visitor.visit_list_of(vs),
+            OwnedValue::Tuple(vs) => visitor.visit_tuple_of(vs),
+            // Maps and structs pass their keys and values to `visit_kv` as
+            // erased `Value`s, so there's nothing to gain here.
+            _ => self.visit(visitor),
+        }
+    }
+}
+
+// === impl Builder ===
+
+impl Builder {
+    fn buffer(mut self, value: &Value<'_>) -> Result<OwnedValue, Error> {
+        value.visit(&mut self)?;
+        self.finish()
+    }
+
+    /// Buffers a nested value, with the same options as this builder.
+    fn nested(&self, value: &Value<'_>) -> Result<OwnedValue, Error> {
+        let builder = Builder {
+            max_fmt_len: self.max_fmt_len,
+            ..Builder::default()
+        };
+        builder.buffer(value)
+    }
+
+    /// Takes the buffered value, if a complete value has been visited.
+    pub(crate) fn take(&mut self) -> Option<OwnedValue> {
+        self.value.take()
+    }
+
+    pub(crate) fn finish(self) -> Result<OwnedValue, Error> {
+        if !self.stack.is_empty() {
+            return Err(Error::unbalanced());
+        }
+        Ok(self.value.unwrap_or(OwnedValue::Unit))
+    }
+
+    /// Adds an entry to the map or struct being built.
+    fn push_pair(&mut self, pair: (OwnedValue, OwnedValue)) -> VisitResult {
+        match self.stack.last_mut() {
+            Some(frame) if frame.kind == Container::Map || frame.kind == Container::Struct => {
+                frame.pairs.push(pair);
+                Ok(())
+            }
+            _ => Err(Error::unbalanced()),
+        }
+    }
+
+    fn push(&mut self, value: OwnedValue) -> VisitResult {
+        // A name applies only to the value immediately following it.
+        self.name = None;
+        match self.stack.last_mut() {
+            Some(frame) if frame.kind == Container::List || frame.kind == Container::Tuple => {
+                frame.values.push(value);
+            }
+            // Values in maps and structs must be visited with `visit_kv`.
+            Some(_) => return Err(Error::unbalanced()),
+            None => self.value = Some(value),
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, kind: Container) -> VisitResult {
+        let name = self.name.take();
+        self.stack.push(Frame {
+            kind,
+            name,
+            values: Vec::new(),
+            pairs: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn close(&mut self, kind: Container) -> VisitResult {
+        let frame = match self.stack.pop() {
+            Some(frame) if frame.kind == kind => frame,
+            _ => return Err(Error::unbalanced()),
+        };
+        let value = match (frame.kind, frame.name) {
+            (Container::List, _) => OwnedValue::List(frame.values),
+            (Container::Tuple, None) => OwnedValue::Tuple(frame.values),
+            (Container::Tuple, Some(name)) => OwnedValue::TupleStruct {
+                name,
+                fields: frame.values,
+            },
+            (Container::Struct, Some(name)) => OwnedValue::Struct {
+                name,
+                fields: frame
+                    .pairs
+                    .into_iter()
+                    .map(|(k, v)| (k.into_key(), v))
+                    .collect(),
+            },
+            (Container::Map, _) | (Container::Struct, None) => OwnedValue::Map(frame.pairs),
+        };
+        self.push(value)
+    }
+}
+
+impl Visit for Builder {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.push(OwnedValue::Uint(value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.push(OwnedValue::Int(value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.push(OwnedValue::Float(value))
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.push(OwnedValue::Str(value.into()))
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> VisitResult {
+        self.push(OwnedValue::Bytes(value.to_vec()))
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.push(OwnedValue::Bool(value))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        match self.name.take() {
+            Some(name) => self.push(OwnedValue::TupleStruct {
+                name,
+                fields: Vec::new(),
+            }),
+            None => self.push(OwnedValue::Unit),
+        }
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        let pair = (self.nested(&k)?, self.nested(&v)?);
+        self.push_pair(pair)
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let pair = (OwnedValue::Str(name.into()), self.nested(&value)?);
+        self.push_pair(pair)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        let rendered = match self.max_fmt_len {
+            Some(max) => {
+                let mut prefix = Prefix::new(max);
+                let _ = prefix.write_fmt(args);
+                prefix.into_string()
+            }
+            None => args.to_string(),
+        };
+        self.push(OwnedValue::Str(rendered.into()))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        let pairs = record
+            .iter()
+            .map(|(name, value)| {
+                let value = self.nested(&value).map_err(|e| e.in_field(name))?;
+                Ok((OwnedValue::Str(name.into()), value))
+            })
+            .collect::<Result<_, Error>>()?;
+        self.push(OwnedValue::Map(pairs))
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        self.name = Some(name.to_owned());
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::BYTES
+            | Capabilities::NAMED_TYPES
+            | Capabilities::UNIT
+            | Capabilities::MAPS
+            | Capabilities::LISTS
+            | Capabilities::STRUCTS
+            | Capabilities::TUPLES
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_value_round_trips() {
+        let value = OwnedValue::List(vec![
+            OwnedValue::Struct {
+                name: "Point".to_owned(),
+                fields: vec![
+                    ("x".to_owned(), OwnedValue::Int(-1)),
+                    ("y".to_owned(), OwnedValue::Float(2.5)),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Unit".to_owned(),
+                fields: Vec::new(),
+            },
+            OwnedValue::Tuple(vec![OwnedValue::Bytes(vec![1, 2]), OwnedValue::Unit]),
+            OwnedValue::Map(vec![(OwnedValue::Uint(1), OwnedValue::Str("a".into()))]),
+        ]);
+        let buffered = OwnedValue::from_value(&Value::borrowed(&value)).unwrap();
+        assert_eq!(buffered, value);
+
+        let display = 1.5;
+        assert_eq!(
+            OwnedValue::from_value(&Value::display(&display)).unwrap(),
+            OwnedValue::Str("1.5".into())
+        );
+
+        let debug = "hello";
+        let pairs = [("msg", Value::debug(&debug)), ("s", Value::borrowed(&debug))];
+        let mut builder = Builder {
+            max_fmt_len: Some(3),
+            ..Builder::default()
+        };
+        Record::from_pairs(&pairs).visit(&mut builder).unwrap();
+        let value = builder.finish().unwrap();
+        assert_eq!(
+            value,
+            OwnedValue::Map(vec![
+                (
+                    OwnedValue::Str("msg".into()),
+                    OwnedValue::Str("\"he…4 more".into())
+                ),
+                (
+                    OwnedValue::Str("s".into()),
+                    OwnedValue::Str("hello".into())
+                ),
+            ])
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    str,
+};
+
+/// The string stored by an [`OwnedValue::Str`].
+///
+/// Most strings in structured data are short (such as identifiers, names,
+/// and enum-like values), and allocating each of them separately dominates
+/// the cost of buffering a value. So strings of up to
+/// [`INLINE_CAPACITY`] bytes are stored inline, in the `OwnedStr` itself,
+/// and only longer strings are allocated. An `OwnedStr` is the same size as
+/// a `String`, so inlining does not make `OwnedValue` any larger.
+///
+/// An `OwnedStr` dereferences to a `str`, and it may be compared with
+/// `str`s and `String`s.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::owned::OwnedStr;
+/// let short = OwnedStr::from("request_id");
+/// assert!(short.is_inline());
+/// assert_eq!(short, "request_id");
+///
+/// let long = OwnedStr::from("a string which is too long to be inlined");
+/// assert!(!long.is_inline());
+/// assert_eq!(long.len(), 40);
+/// ```
+///
+/// [`OwnedValue::Str`]: enum.OwnedValue.html#variant.Str
+/// [`INLINE_CAPACITY`]: #associatedconstant.INLINE_CAPACITY
+#[derive(Clone)]
+pub struct OwnedStr(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        buf: [u8; OwnedStr::INLINE_CAPACITY],
+    },
+    Heap(Box<str>),
+}
+
+// === impl OwnedStr ===
+
+impl OwnedStr {
+    /// The length, in bytes, of the longest string stored inline.
+    pub const INLINE_CAPACITY: usize = 22;
+
+    /// Returns a new, empty `OwnedStr`.
+    pub const fn new() -> Self {
+        OwnedStr(Repr::Inline {
+            len: 0,
+            buf: [0; Self::INLINE_CAPACITY],
+        })
+    }
+
+    /// Returns the string as a `str`.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // Inline strings are only ever copied from a `str`, so this
+            // cannot fail.
+            Repr::Inline { len, buf } => {
+                str::from_utf8(&buf[..*len as usize]).expect("inline strings are valid UTF-8")
+            }
+            Repr::Heap(s) => s,
+        }
+    }
+
+    /// Returns `true` if the string is stored inline, rather than allocated.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+
+    /// Converts the `OwnedStr` into a `String`.
+    ///
+    /// This allocates if the string is stored inline.
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Inline { .. } => self.as_str().to_owned(),
+            Repr::Heap(s) => s.into_string(),
+        }
+    }
+}
+
+impl Default for OwnedStr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for OwnedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for OwnedStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for OwnedStr {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for OwnedStr {
+    /// Copies `s`, storing it inline if it is short enough.
+    fn from(s: &str) -> Self {
+        if s.len() > Self::INLINE_CAPACITY {
+            return OwnedStr(Repr::Heap(s.into()));
+        }
+        let mut buf = [0; Self::INLINE_CAPACITY];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        OwnedStr(Repr::Inline {
+            len: s.len() as u8,
+            buf,
+        })
+    }
+}
+
+impl From<String> for OwnedStr {
+    /// Takes ownership of `s`, storing it inline if it is short enough.
+    ///
+    /// Short strings are copied and `s` is dropped, so that the `OwnedStr`
+    /// does not keep its allocation.
+    fn from(s: String) -> Self {
+        if s.len() > Self::INLINE_CAPACITY {
+            OwnedStr(Repr::Heap(s.into_boxed_str()))
+        } else {
+            OwnedStr::from(s.as_str())
+        }
+    }
+}
+
+impl From<char> for OwnedStr {
+    fn from(c: char) -> Self {
+        OwnedStr::from(c.encode_utf8(&mut [0; 4]) as &str)
+    }
+}
+
+impl From<OwnedStr> for String {
+    fn from(s: OwnedStr) -> Self {
+        s.into_string()
+    }
+}
+
+impl fmt::Debug for OwnedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for OwnedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for OwnedStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for OwnedStr {}
+
+impl PartialEq<str> for OwnedStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for OwnedStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for OwnedStr {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<OwnedStr> for str {
+    fn eq(&self, other: &OwnedStr) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<OwnedStr> for &str {
+    fn eq(&self, other: &OwnedStr) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<OwnedStr> for String {
+    fn eq(&self, other: &OwnedStr) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialOrd for OwnedStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OwnedStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for OwnedStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owned::OwnedValue;
+    use std::mem::size_of;
+
+    #[test]
+    fn inlines_short_strings() {
+        let max = "a".repeat(OwnedStr::INLINE_CAPACITY);
+        let s = OwnedStr::from(max.clone());
+        assert!(s.is_inline());
+        assert_eq!(s, max);
+
+        let s = OwnedStr::from(max.clone() + "é");
+        assert!(!s.is_inline());
+        assert_eq!(s.into_string(), max + "é");
+
+        assert_eq!(OwnedStr::from('é'), "é");
+        assert_eq!(size_of::<OwnedStr>(), size_of::<String>());
+        assert!(size_of::<OwnedValue>() <= size_of::<String>() + size_of::<Vec<u8>>() + 8);
+    }
+}
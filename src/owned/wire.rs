@@ -0,0 +1,623 @@
+//! A compact binary encoding for `OwnedValue`s.
+//!
+//! Each value is encoded as a one-byte tag, followed by the value's
+//! contents:
+//!
+//! | tag | value         | contents                                         |
+//! |-----|---------------|--------------------------------------------------|
+//! | 0   | `Unit`        | none                                             |
+//! | 1   | `Bool(false)` | none                                             |
+//! | 2   | `Bool(true)`  | none                                             |
+//! | 3   | `Uint`        | a varint                                         |
+//! | 4   | `Int`         | a zigzag-encoded varint                          |
+//! | 5   | `Float`       | 8 bytes, little-endian                           |
+//! | 6   | `Str`         | a varint length, then that many bytes of UTF-8   |
+//! | 7   | `Bytes`       | a varint length, then that many bytes            |
+//! | 8   | `List`        | a varint count, then that many values            |
+//! | 9   | `Tuple`       | a varint count, then that many values            |
+//! | 10  | `Map`         | a varint count, then that many key-value pairs   |
+//! | 11  | `Struct`      | a name, a varint count, then name-value pairs    |
+//! | 12  | `TupleStruct` | a name, a varint count, then that many values    |
+//!
+//! Varints are unsigned LEB128, and names are encoded like the contents of a
+//! `Str`.
+use super::OwnedValue;
+use crate::{
+    pool,
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Read, Write},
+};
+
+const UNIT: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const UINT: u8 = 3;
+const INT: u8 = 4;
+const FLOAT: u8 = 5;
+const STR: u8 = 6;
+const BYTES: u8 = 7;
+const LIST: u8 = 8;
+const TUPLE: u8 = 9;
+const MAP: u8 = 10;
+const STRUCT: u8 = 11;
+const TUPLE_STRUCT: u8 = 12;
+
+/// The maximum nesting depth of a decoded value, so that malicious input
+/// cannot overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// The most elements preallocated for a decoded container, so that a
+/// corrupt length cannot exhaust memory before the input runs out.
+const MAX_PREALLOC: usize = 1024;
+
+impl OwnedValue {
+    /// Writes this value to `writer` in a compact, self-describing binary
+    /// encoding.
+    ///
+    /// The encoded value may be decoded with [`read_from`], for example to
+    /// spool buffered values to disk, or to send them to another process.
+    /// Values are not delimited, so several values may be written to the
+    /// same writer and read back in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::owned::OwnedValue;
+    /// let value = OwnedValue::List(vec![OwnedValue::Int(-1), OwnedValue::Str("hi".into())]);
+    /// let mut buf = Vec::new();
+    /// value.write_to(&mut buf).unwrap();
+    /// assert_eq!(buf, [8, 2, 4, 1, 6, 2, b'h', b'i']);
+    ///
+    /// assert_eq!(OwnedValue::read_from(&mut &buf[..]).unwrap(), value);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If writing to `writer` fails, the error is returned.
+    ///
+    /// [`read_from`]: #method.read_from
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            OwnedValue::Unit => writer.write_all(&[UNIT]),
+            OwnedValue::Bool(false) => writer.write_all(&[FALSE]),
+            OwnedValue::Bool(true) => writer.write_all(&[TRUE]),
+            OwnedValue::Uint(v) => {
+                writer.write_all(&[UINT])?;
+                write_varint(writer, *v)
+            }
+            OwnedValue::Int(v) => {
+                writer.write_all(&[INT])?;
+                write_varint(writer, ((v << 1) ^ (v >> 63)) as u64)
+            }
+            OwnedValue::Float(v) => {
+                writer.write_all(&[FLOAT])?;
+                writer.write_all(&v.to_le_bytes())
+            }
+            OwnedValue::Str(v) => {
+                writer.write_all(&[STR])?;
+                write_bytes(writer, v.as_bytes())
+            }
+            OwnedValue::Bytes(v) => {
+                writer.write_all(&[BYTES])?;
+                write_bytes(writer, v)
+            }
+            OwnedValue::List(vs) => write_values(writer, LIST, vs),
+            OwnedValue::Tuple(vs) => write_values(writer, TUPLE, vs),
+            OwnedValue::Map(kvs) => {
+                writer.write_all(&[MAP])?;
+                write_varint(writer, kvs.len() as u64)?;
+                for (k, v) in kvs {
+                    k.write_to(writer)?;
+                    v.write_to(writer)?;
+                }
+                Ok(())
+            }
+            OwnedValue::Struct { name, fields } => {
+                writer.write_all(&[STRUCT])?;
+                write_bytes(writer, name.as_bytes())?;
+                write_varint(writer, fields.len() as u64)?;
+                for (k, v) in fields {
+                    write_bytes(writer, k.as_bytes())?;
+                    v.write_to(writer)?;
+                }
+                Ok(())
+            }
+            OwnedValue::TupleStruct { name, fields } => {
+                writer.write_all(&[TUPLE_STRUCT])?;
+                write_bytes(writer, name.as_bytes())?;
+                write_varint(writer, fields.len() as u64)?;
+                fields.iter().try_for_each(|v| v.write_to(writer))
+            }
+        }
+    }
+
+    /// Reads a value written by [`write_to`] from `reader`.
+    ///
+    /// Exactly the bytes of one value are read, so `reader` is left at the
+    /// start of the next value, if any. Since small reads are made for each
+    /// value, a `reader` which makes system calls (such as a `File`) should
+    /// be wrapped in an `io::BufReader`.
+    ///
+    /// # Errors
+    ///
+    /// If reading from `reader` fails, the error is returned. If the input
+    /// is not a valid encoded value (or is nested more than 128 containers
+    /// deep), an error of kind `InvalidData` is returned, and if the input
+    /// ends partway through a value, an error of kind `UnexpectedEof` is
+    /// returned.
+    ///
+    /// [`write_to`]: #method.write_to
+    pub fn read_from<R: Read + ?Sized>(reader: &mut R) -> io::Result<OwnedValue> {
+        read_value(reader, 0)
+    }
+}
+
+/// Visits a value written by [`OwnedValue::write_to`] as it is read from
+/// `reader`, without buffering it.
+///
+/// This allows values received from another process (such as events sent by
+/// an application to a log forwarding daemon) to be processed incrementally,
+/// as `visitor` receives each part of the value as soon as it is read. Only
+/// map keys are buffered, so that they may be visited (or rendered into
+/// error messages) more than once.
+///
+/// The values passed to [`Visit::visit_kv`] must be visited at most once,
+/// and before `visit_kv` returns, since visiting them reads from `reader`.
+/// Values which are not visited are skipped. Visiting a value a second
+/// time returns an [unsupported] error, so a visitor which visits values
+/// more than once (such as a [`Tee`]) should instead be passed a value read
+/// with [`OwnedValue::read_from`].
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{json::JsonVisit, owned::{self, OwnedValue}};
+/// let value = OwnedValue::Map(vec![(OwnedValue::Str("id".into()), OwnedValue::Uint(7))]);
+/// let mut buf = Vec::new();
+/// value.write_to(&mut buf).unwrap();
+///
+/// let mut json = JsonVisit::new(String::new());
+/// owned::visit_from(&mut &buf[..], &mut json).unwrap();
+/// assert_eq!(json.into_inner(), r#"{"id":7}"#);
+/// ```
+///
+/// # Errors
+///
+/// If reading from `reader` fails, or the input is not a valid encoded value,
+/// an error is returned whose [`source`] is the `io::Error` that
+/// [`OwnedValue::read_from`] would return. If `visitor` returns an error, it
+/// is returned. After an error, `reader` may be left partway through the
+/// value.
+///
+/// [`OwnedValue::write_to`]: enum.OwnedValue.html#method.write_to
+/// [`OwnedValue::read_from`]: enum.OwnedValue.html#method.read_from
+/// [`Visit::visit_kv`]: ../visitor/trait.Visit.html#tymethod.visit_kv
+/// [unsupported]: ../visitor/struct.Error.html#method.is_unsupported
+/// [`Tee`]: ../combinator/struct.Tee.html
+/// [`source`]: https://doc.rust-lang.org/std/error/trait.Error.html#method.source
+pub fn visit_from<R: Read + ?Sized>(reader: &mut R, visitor: &mut dyn Visit) -> VisitResult {
+    visit_value(reader, visitor, 0)
+}
+
+/// A value being streamed from a reader, passed to `visit_kv`.
+struct Streamed<'a, R: ?Sized> {
+    reader: RefCell<&'a mut R>,
+    depth: usize,
+    visited: Cell<bool>,
+}
+
+/// A visitor which discards everything it visits, used to skip values which
+/// were not visited.
+struct Skip;
+
+fn visit_value<R: Read + ?Sized>(
+    reader: &mut R,
+    visitor: &mut dyn Visit,
+    depth: usize,
+) -> VisitResult {
+    match read_byte(reader)? {
+        UNIT => visitor.visit_unit(),
+        FALSE => visitor.visit_bool(false),
+        TRUE => visitor.visit_bool(true),
+        UINT => visitor.visit_uint(read_varint(reader)?),
+        INT => visitor.visit_int(read_int(reader)?),
+        FLOAT => visitor.visit_float(read_float(reader)?),
+        // Strings are read into scratch buffers, since they are only
+        // borrowed by the visitor.
+        STR => pool::with_bytes(|buf| visitor.visit_str(read_str_into(reader, buf)?)),
+        BYTES => pool::with_bytes(|buf| {
+            read_bytes_into(reader, buf)?;
+            visitor.visit_bytes(buf)
+        }),
+        tag @ LIST..=TUPLE_STRUCT => {
+            if depth >= MAX_DEPTH {
+                return Err(invalid("value is nested too deeply").into());
+            }
+            let depth = depth + 1;
+            if tag == STRUCT || tag == TUPLE_STRUCT {
+                pool::with_bytes(|buf| visitor.named_type(read_str_into(reader, buf)?))?;
+            }
+            let len = read_len(reader)?;
+            match tag {
+                MAP => {
+                    visitor.open_map()?;
+                    for _ in 0..len {
+                        let key = read_value(reader, depth)?;
+                        let key = Value::borrowed(&key);
+                        visit_streamed(reader, depth, |value| {
+                            visitor.visit_kv(key.by_ref(), value)
+                        })
+                        .map_err(|e| e.in_key(&key))?;
+                    }
+                    visitor.close_map()
+                }
+                STRUCT => {
+                    visitor.open_struct()?;
+                    for _ in 0..len {
+                        pool::with_bytes(|buf| {
+                            let key = read_str_into(reader, buf)?;
+                            visit_streamed(reader, depth, |value| {
+                                visitor.visit_named_field(key, value)
+                            })
+                            .map_err(|e| e.in_field(key))
+                        })?;
+                    }
+                    visitor.close_struct()
+                }
+                _ => {
+                    if tag == LIST {
+                        visitor.open_list()?;
+                    } else {
+                        visitor.open_tuple()?;
+                    }
+                    for idx in 0..len {
+                        visit_value(reader, visitor, depth).map_err(|e| e.in_index(idx))?;
+                    }
+                    if tag == LIST {
+                        visitor.close_list()
+                    } else {
+                        visitor.close_tuple()
+                    }
+                }
+            }
+        }
+        tag => Err(invalid(format!("unknown value tag {}", tag)).into()),
+    }
+}
+
+/// Calls `f` with the next value in `reader`, streamed, and skips the value
+/// if `f` did not visit it.
+fn visit_streamed<R: Read + ?Sized>(
+    reader: &mut R,
+    depth: usize,
+    f: impl FnOnce(Value<'_>) -> VisitResult,
+) -> VisitResult {
+    let value = Streamed {
+        reader: RefCell::new(reader),
+        depth,
+        visited: Cell::new(false),
+    };
+    f(Value::borrowed(&value))?;
+    if !value.visited.get() {
+        visit_value(*value.reader.borrow_mut(), &mut Skip, depth)?;
+    }
+    Ok(())
+}
+
+impl<R: Read + ?Sized> Visitable for Streamed<'_, R> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if self.visited.replace(true) {
+            return Err(crate::visitor::Error::unsupported(
+                "streamed value more than once",
+            ));
+        }
+        visit_value(*self.reader.borrow_mut(), visitor, self.depth)
+    }
+}
+
+impl Visit for Skip {
+    fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+        Ok(())
+    }
+
+    fn visit_kv(&mut self, _: Value<'_>, _: Value<'_>) -> VisitResult {
+        Ok(())
+    }
+
+    fn named_type(&mut self, _: &str) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        Ok(())
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        Ok(())
+    }
+}
+
+fn write_varint<W: Write + ?Sized>(writer: &mut W, mut v: u64) -> io::Result<()> {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    writer.write_all(&buf[..len])
+}
+
+fn write_bytes<W: Write + ?Sized>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_varint(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn write_values<W: Write + ?Sized>(writer: &mut W, tag: u8, vs: &[OwnedValue]) -> io::Result<()> {
+    writer.write_all(&[tag])?;
+    write_varint(writer, vs.len() as u64)?;
+    vs.iter().try_for_each(|v| v.write_to(writer))
+}
+
+fn read_value<R: Read + ?Sized>(reader: &mut R, depth: usize) -> io::Result<OwnedValue> {
+    let value = match read_byte(reader)? {
+        UNIT => OwnedValue::Unit,
+        FALSE => OwnedValue::Bool(false),
+        TRUE => OwnedValue::Bool(true),
+        UINT => OwnedValue::Uint(read_varint(reader)?),
+        INT => OwnedValue::Int(read_int(reader)?),
+        FLOAT => OwnedValue::Float(read_float(reader)?),
+        STR => OwnedValue::Str(read_str(reader)?.into()),
+        BYTES => OwnedValue::Bytes(read_bytes(reader)?),
+        tag @ LIST..=TUPLE_STRUCT => {
+            if depth >= MAX_DEPTH {
+                return Err(invalid("value is nested too deeply"));
+            }
+            let depth = depth + 1;
+            let name = match tag {
+                STRUCT | TUPLE_STRUCT => read_str(reader)?,
+                _ => String::new(),
+            };
+            let len = read_len(reader)?;
+            match tag {
+                MAP => {
+                    let mut kvs = Vec::with_capacity(len.min(MAX_PREALLOC));
+                    for _ in 0..len {
+                        kvs.push((read_value(reader, depth)?, read_value(reader, depth)?));
+                    }
+                    OwnedValue::Map(kvs)
+                }
+                STRUCT => {
+                    let mut fields = Vec::with_capacity(len.min(MAX_PREALLOC));
+                    for _ in 0..len {
+                        fields.push((read_str(reader)?, read_value(reader, depth)?));
+                    }
+                    OwnedValue::Struct { name, fields }
+                }
+                _ => {
+                    let mut vs = Vec::with_capacity(len.min(MAX_PREALLOC));
+                    for _ in 0..len {
+                        vs.push(read_value(reader, depth)?);
+                    }
+                    match tag {
+                        LIST => OwnedValue::List(vs),
+                        TUPLE => OwnedValue::Tuple(vs),
+                        _ => OwnedValue::TupleStruct { name, fields: vs },
+                    }
+                }
+            }
+        }
+        tag => return Err(invalid(format!("unknown value tag {}", tag))),
+    };
+    Ok(value)
+}
+
+fn read_byte<R: Read + ?Sized>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_varint<R: Read + ?Sized>(reader: &mut R) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(reader)?;
+        v |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(invalid("varint is too long"))
+}
+
+fn read_int<R: Read + ?Sized>(reader: &mut R) -> io::Result<i64> {
+    let v = read_varint(reader)?;
+    Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+}
+
+fn read_float<R: Read + ?Sized>(reader: &mut R) -> io::Result<f64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+
+fn read_len<R: Read + ?Sized>(reader: &mut R) -> io::Result<usize> {
+    let len = read_varint(reader)?;
+    if len > usize::MAX as u64 {
+        return Err(invalid("length does not fit in a usize"));
+    }
+    Ok(len as usize)
+}
+
+fn read_bytes<R: Read + ?Sized>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    read_bytes_into(reader, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads a byte string into `buf`, which must be empty.
+fn read_bytes_into<R: Read + ?Sized>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<()> {
+    let len = read_len(reader)?;
+    buf.reserve(len.min(MAX_PREALLOC));
+    reader.take(len as u64).read_to_end(buf)?;
+    if buf.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_str<R: Read + ?Sized>(reader: &mut R) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| invalid_utf8())
+}
+
+/// Reads a string into `buf`, which must be empty, returning it.
+fn read_str_into<'a, R: Read + ?Sized>(
+    reader: &mut R,
+    buf: &'a mut Vec<u8>,
+) -> io::Result<&'a str> {
+    read_bytes_into(reader, buf)?;
+    std::str::from_utf8(buf).map_err(|_| invalid_utf8())
+}
+
+fn invalid_utf8() -> io::Error {
+    invalid("string is not valid UTF-8")
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ValueExt;
+
+    #[test]
+    fn round_trips() {
+        let value = OwnedValue::List(vec![
+            OwnedValue::Struct {
+                name: "Point".to_owned(),
+                fields: vec![
+                    ("x".to_owned(), OwnedValue::Int(i64::MIN)),
+                    ("y".to_owned(), OwnedValue::Float(2.5)),
+                ],
+            },
+            OwnedValue::TupleStruct {
+                name: "Unit".to_owned(),
+                fields: Vec::new(),
+            },
+            OwnedValue::Tuple(vec![
+                OwnedValue::Bytes(vec![1, 2]),
+                OwnedValue::Unit,
+                OwnedValue::Bool(true),
+            ]),
+            OwnedValue::Map(vec![(
+                OwnedValue::Uint(u64::MAX),
+                OwnedValue::Str("a".into()),
+            )]),
+        ]);
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        // Zigzag encodes -2 as the single byte 3.
+        OwnedValue::Int(-2).write_to(&mut buf).unwrap();
+        assert_eq!(buf[buf.len() - 2..], [INT, 3]);
+
+        let mut reader = &buf[..];
+        assert_eq!(OwnedValue::read_from(&mut reader).unwrap(), value);
+        assert_eq!(
+            OwnedValue::read_from(&mut reader).unwrap(),
+            OwnedValue::Int(-2)
+        );
+        assert!(reader.is_empty());
+
+        let err = OwnedValue::read_from(&mut &buf[..buf.len() - 3]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = OwnedValue::read_from(&mut &[6, 1, 0xff][..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let deep = [LIST, 1].repeat(MAX_DEPTH + 1);
+        let err = OwnedValue::read_from(&mut &deep[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn streams() {
+        use crate::{combinator::MaybeVisit, json::JsonVisit};
+        use std::error::Error as _;
+
+        let value = OwnedValue::Struct {
+            name: "Event".to_owned(),
+            fields: vec![
+                (
+                    "big".to_owned(),
+                    OwnedValue::List(vec![OwnedValue::Uint(1); 3]),
+                ),
+                (
+                    "tags".to_owned(),
+                    OwnedValue::Map(vec![(
+                        OwnedValue::Uint(1),
+                        OwnedValue::Tuple(vec![OwnedValue::Bool(true), OwnedValue::Unit]),
+                    )]),
+                ),
+            ],
+        };
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+        OwnedValue::Str("next".into())
+            .write_to(&mut buf)
+            .unwrap();
+
+        let mut reader = &buf[..];
+        let mut json = JsonVisit::new(String::new());
+        visit_from(&mut reader, &mut json).unwrap();
+        assert_eq!(json.into_inner(), Value::borrowed(&value).render_json());
+
+        // Values which are not visited are skipped.
+        let mut reader = &buf[..];
+        let mut json = MaybeVisit::new(JsonVisit::new(String::new()), |key| key != "big");
+        visit_from(&mut reader, &mut json).unwrap();
+        assert_eq!(
+            json.into_inner().into_inner(),
+            r#"{"tags":{"1":[true,null]}}"#
+        );
+        let next = OwnedValue::read_from(&mut reader).unwrap();
+        assert_eq!(next, OwnedValue::Str("next".into()));
+
+        let err = visit_from(&mut &buf[..4], &mut JsonVisit::new(String::new())).unwrap_err();
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
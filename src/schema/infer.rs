@@ -0,0 +1,308 @@
+use super::{Field, ScalarKind, Schema};
+use crate::{
+    owned::OwnedValue,
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::{fmt, mem};
+
+/// A `Visit` implementation that infers the [`Schema`] of visited values.
+///
+/// A `SchemaVisit` may visit any number of values; the schemas of each of
+/// them are [merged] together, so that the resulting schema describes all
+/// of them. For example, visiting many events logged by a program produces a
+/// schema whose struct fields are only required if every event had them.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{schema::{SchemaVisit, ScalarKind, Schema}, value};
+/// let mut visit = SchemaVisit::new();
+/// value!([1u8, 2u8]).visit(&mut visit).unwrap();
+/// value!([3u32]).visit(&mut visit).unwrap();
+/// assert_eq!(
+///     visit.finish().unwrap(),
+///     Schema::List(Box::new(Schema::Scalar(ScalarKind::U32))),
+/// );
+/// ```
+///
+/// [`Schema`]: enum.Schema.html
+/// [merged]: enum.Schema.html#method.merge
+#[derive(Debug)]
+pub struct SchemaVisit {
+    schema: Schema,
+    stack: Vec<Frame>,
+    /// The name passed to `named_type` for the next value, if any.
+    name: Option<String>,
+}
+
+#[derive(Debug)]
+enum Frame {
+    List(Schema),
+    Tuple {
+        name: Option<String>,
+        elems: Vec<Schema>,
+    },
+    Map {
+        key: Schema,
+        value: Schema,
+    },
+    Struct {
+        name: Option<String>,
+        fields: Vec<Field>,
+    },
+}
+
+// === impl SchemaVisit ===
+
+impl SchemaVisit {
+    /// Returns a new `SchemaVisit` which has not visited any values.
+    pub fn new() -> Self {
+        Self {
+            schema: Schema::Empty,
+            stack: Vec::new(),
+            name: None,
+        }
+    }
+
+    /// Consumes the `SchemaVisit`, returning the schema of all the values it
+    /// has visited.
+    ///
+    /// If no values were visited, this returns [`Schema::Empty`].
+    ///
+    /// # Errors
+    ///
+    /// If a value is still being visited (some container was opened but not
+    /// closed), an error is returned.
+    ///
+    /// [`Schema::Empty`]: enum.Schema.html#variant.Empty
+    pub fn finish(self) -> Result<Schema, Error> {
+        if !self.stack.is_empty() {
+            return Err(Error::unbalanced());
+        }
+        Ok(self.schema)
+    }
+
+    fn push(&mut self, schema: Schema) -> VisitResult {
+        self.name = None;
+        match self.stack.last_mut() {
+            Some(Frame::List(elem)) => {
+                let merged = mem::replace(elem, Schema::Empty).merge(schema);
+                *elem = merged;
+            }
+            Some(Frame::Tuple { elems, .. }) => elems.push(schema),
+            // Values in maps and structs must be visited with `visit_kv`.
+            Some(_) => return Err(Error::unbalanced()),
+            None => {
+                let merged = mem::replace(&mut self.schema, Schema::Empty).merge(schema);
+                self.schema = merged;
+            }
+        }
+        Ok(())
+    }
+
+    fn scalar(&mut self, kind: ScalarKind) -> VisitResult {
+        self.push(Schema::Scalar(kind))
+    }
+
+    fn open(&mut self, frame: Frame) -> VisitResult {
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    fn close(&mut self, f: impl FnOnce(Frame) -> Option<Schema>) -> VisitResult {
+        let schema = self.stack.pop().and_then(f).ok_or_else(Error::unbalanced)?;
+        self.push(schema)
+    }
+}
+
+impl Default for SchemaVisit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! scalars {
+    ($($method:ident($ty:ty) => $kind:ident),+ $(,)?) => {
+        $(
+            fn $method(&mut self, _: $ty) -> VisitResult {
+                self.scalar(ScalarKind::$kind)
+            }
+        )+
+    };
+}
+
+impl Visit for SchemaVisit {
+    scalars! {
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_uint(u64) => U64,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_int(i64) => I64,
+        visit_f32(f32) => F32,
+        visit_float(f64) => F64,
+        visit_str(&str) => Str,
+        visit_bytes(&[u8]) => Bytes,
+        visit_bool(bool) => Bool,
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        match self.name.take() {
+            Some(name) => self.push(Schema::TupleStruct {
+                name,
+                fields: Vec::new(),
+            }),
+            None => self.scalar(ScalarKind::Unit),
+        }
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        match self.stack.last_mut() {
+            Some(Frame::Map { key, value }) => {
+                *key = mem::replace(key, Schema::Empty).merge(Schema::of(&k)?);
+                *value = mem::replace(value, Schema::Empty).merge(Schema::of(&v)?);
+                Ok(())
+            }
+            Some(Frame::Struct { fields, .. }) => {
+                let name = OwnedValue::from_value(&k)?.into_key();
+                let schema = Schema::of(&v).map_err(|e| e.in_field(&name))?;
+                fields.push(Field {
+                    name,
+                    schema,
+                    required: true,
+                });
+                Ok(())
+            }
+            _ => Err(Error::unbalanced()),
+        }
+    }
+
+    fn visit_fmt(&mut self, _: fmt::Arguments<'_>) -> VisitResult {
+        self.scalar(ScalarKind::Str)
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        let fields = record
+            .iter()
+            .map(|(name, value)| {
+                let schema = Schema::of(&value).map_err(|e| e.in_field(name))?;
+                Ok(Field {
+                    name: name.to_owned(),
+                    schema,
+                    required: true,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        self.push(Schema::Struct { name: None, fields })
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        self.name = Some(name.to_owned());
+        Ok(())
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Frame::Map {
+            key: Schema::Empty,
+            value: Schema::Empty,
+        })
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(|frame| match frame {
+            Frame::Map { key, value } => Some(Schema::Map {
+                key: Box::new(key),
+                value: Box::new(value),
+            }),
+            _ => None,
+        })
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Frame::List(Schema::Empty))
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(|frame| match frame {
+            Frame::List(elem) => Some(Schema::List(Box::new(elem))),
+            _ => None,
+        })
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        let name = self.name.take();
+        self.open(Frame::Struct {
+            name,
+            fields: Vec::new(),
+        })
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(|frame| match frame {
+            Frame::Struct { name, fields } => Some(Schema::Struct { name, fields }),
+            _ => None,
+        })
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        let name = self.name.take();
+        self.open(Frame::Tuple {
+            name,
+            elems: Vec::new(),
+        })
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(|frame| match frame {
+            Frame::Tuple {
+                name: Some(name),
+                elems,
+            } => Some(Schema::TupleStruct {
+                name,
+                fields: elems,
+            }),
+            Frame::Tuple { name: None, elems } => Some(Schema::Tuple(elems)),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ValueExt;
+
+    #[test]
+    fn merges_structs() {
+        let first = OwnedValue::Struct {
+            name: "Event".to_owned(),
+            fields: vec![
+                ("id".to_owned(), OwnedValue::Uint(1)),
+                ("msg".to_owned(), OwnedValue::Str("a".into())),
+            ],
+        };
+        let second = OwnedValue::Struct {
+            name: "Event".to_owned(),
+            fields: vec![("id".to_owned(), OwnedValue::Uint(2))],
+        };
+        let mut visit = SchemaVisit::new();
+        first.visit(&mut visit).unwrap();
+        second.visit(&mut visit).unwrap();
+        let schema = visit.finish().unwrap();
+
+        assert_eq!(schema.field("id").map(|f| f.required), Some(true));
+        assert_eq!(schema.field("msg").map(|f| f.required), Some(false));
+        assert_eq!(
+            Value::borrowed(&schema).render_json(),
+            r#"{"type":"object","title":"Event","properties":{"id":{"type":"integer","format":"uint64"},"msg":{"type":"string"}},"required":["id"]}"#
+        );
+    }
+}
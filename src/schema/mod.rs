@@ -0,0 +1,378 @@
+//! Describing the shape of structured values.
+//!
+//! A [`Schema`] describes the structure of a value: the names of its structs
+//! and fields, the kinds of its scalars, and how they are nested. Schemas
+//! are inferred from visited values by [`SchemaVisit`], and may be used to
+//! document the values a program logs, or to check that values match an
+//! expected shape with [`ValidateVisit`].
+//!
+//! `Schema` implements `Visitable`, visiting a description in the style of
+//! [JSON Schema], so a schema may be written out using any visitor, such as
+//! [`JsonVisit`].
+//!
+//! [`Schema`]: enum.Schema.html
+//! [`SchemaVisit`]: struct.SchemaVisit.html
+//! [`ValidateVisit`]: struct.ValidateVisit.html
+//! [JSON Schema]: https://json-schema.org/
+//! [`JsonVisit`]: ../json/struct.JsonVisit.html
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::fmt;
+
+mod infer;
+mod validate;
+
+pub use self::{
+    infer::SchemaVisit,
+    validate::{Mismatch, MismatchKind, ValidateVisit},
+};
+
+/// A description of the structure of a value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema {
+    /// A scalar value of the given kind.
+    Scalar(ScalarKind),
+    /// A list whose elements all match the given schema.
+    List(Box<Schema>),
+    /// A tuple whose elements match the given schemas, in order.
+    Tuple(Vec<Schema>),
+    /// A map whose keys and values match the given schemas.
+    Map {
+        /// The schema of the map's keys.
+        key: Box<Schema>,
+        /// The schema of the map's values.
+        value: Box<Schema>,
+    },
+    /// A struct (or record) with named fields.
+    Struct {
+        /// The name of the struct's type, if it has one.
+        name: Option<String>,
+        /// The struct's fields, in order.
+        fields: Vec<Field>,
+    },
+    /// A named tuple struct with unnamed fields matching the given schemas.
+    TupleStruct {
+        /// The name of the struct's type.
+        name: String,
+        /// The schemas of the struct's fields, in order.
+        fields: Vec<Schema>,
+    },
+    /// Values which do not share a common structure.
+    Any,
+    /// No values at all, such as the elements of an empty list.
+    ///
+    /// Merging `Empty` with another schema results in the other schema.
+    Empty,
+}
+
+/// A named field of a [`Schema::Struct`].
+///
+/// [`Schema::Struct`]: enum.Schema.html#variant.Struct
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// The name of the field.
+    pub name: String,
+    /// The schema of the field's value.
+    pub schema: Schema,
+    /// Whether the field was present in every value the schema describes.
+    pub required: bool,
+}
+
+/// The kind of a scalar value.
+///
+/// Integers and floats are described by the width of their original type,
+/// as reported by the width-specific `Visit` methods.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ScalarKind {
+    /// A unit value.
+    Unit,
+    /// A boolean.
+    Bool,
+    /// An unsigned 8-bit integer.
+    U8,
+    /// An unsigned 16-bit integer.
+    U16,
+    /// An unsigned 32-bit integer.
+    U32,
+    /// An unsigned 64-bit integer.
+    U64,
+    /// A signed 8-bit integer.
+    I8,
+    /// A signed 16-bit integer.
+    I16,
+    /// A signed 32-bit integer.
+    I32,
+    /// A signed 64-bit integer.
+    I64,
+    /// A 32-bit float.
+    F32,
+    /// A 64-bit float.
+    F64,
+    /// A string, or a formatted value.
+    Str,
+    /// A byte string.
+    Bytes,
+}
+
+// === impl Schema ===
+
+impl Schema {
+    /// Infers the schema of `value`.
+    pub fn of(value: &Value<'_>) -> Result<Self, Error> {
+        let mut visit = SchemaVisit::new();
+        value.visit(&mut visit)?;
+        visit.finish()
+    }
+
+    /// Checks `value` against this schema, returning every mismatch.
+    ///
+    /// See [`ValidateVisit`] for details.
+    ///
+    /// [`ValidateVisit`]: struct.ValidateVisit.html
+    pub fn validate(&self, value: &Value<'_>) -> Result<Vec<Mismatch>, Error> {
+        let mut visit = ValidateVisit::new(self);
+        value.visit(&mut visit)?;
+        visit.finish()
+    }
+
+    /// Returns a schema describing the values described by both `self` and
+    /// `other`.
+    ///
+    /// Structs with the same name are merged field by field; fields which
+    /// are missing from either struct are no longer required. Lists, maps,
+    /// and tuples of the same length are merged element-wise, and integers
+    /// and floats of different widths are widened. Any other values which
+    /// do not share a structure are merged into [`Schema::Any`].
+    ///
+    /// [`Schema::Any`]: #variant.Any
+    pub fn merge(self, other: Schema) -> Schema {
+        match (self, other) {
+            (Schema::Empty, other) | (other, Schema::Empty) => other,
+            (Schema::Scalar(a), Schema::Scalar(b)) => match a.merge(b) {
+                Some(kind) => Schema::Scalar(kind),
+                None => Schema::Any,
+            },
+            (Schema::List(a), Schema::List(b)) => Schema::List(Box::new(a.merge(*b))),
+            (Schema::Tuple(a), Schema::Tuple(b)) if a.len() == b.len() => {
+                Schema::Tuple(merge_all(a, b))
+            }
+            (Schema::Map { key: ak, value: av }, Schema::Map { key: bk, value: bv }) => {
+                Schema::Map {
+                    key: Box::new(ak.merge(*bk)),
+                    value: Box::new(av.merge(*bv)),
+                }
+            }
+            (
+                Schema::Struct {
+                    name: an,
+                    fields: af,
+                },
+                Schema::Struct {
+                    name: bn,
+                    fields: bf,
+                },
+            ) if an == bn => Schema::Struct {
+                name: an,
+                fields: merge_fields(af, bf),
+            },
+            (
+                Schema::TupleStruct {
+                    name: an,
+                    fields: af,
+                },
+                Schema::TupleStruct {
+                    name: bn,
+                    fields: bf,
+                },
+            ) if an == bn && af.len() == bf.len() => Schema::TupleStruct {
+                name: an,
+                fields: merge_all(af, bf),
+            },
+            _ => Schema::Any,
+        }
+    }
+
+    /// Returns the maximum number of containers nested inside one another
+    /// in values described by this schema.
+    pub fn depth(&self) -> usize {
+        let max = |schemas: &mut dyn Iterator<Item = &Schema>| {
+            schemas.map(Schema::depth).max().unwrap_or(0) + 1
+        };
+        match self {
+            Schema::Scalar(_) | Schema::Any | Schema::Empty => 0,
+            Schema::List(elem) => elem.depth() + 1,
+            Schema::Tuple(elems) | Schema::TupleStruct { fields: elems, .. } => {
+                max(&mut elems.iter())
+            }
+            Schema::Map { key, value } => key.depth().max(value.depth()) + 1,
+            Schema::Struct { fields, .. } => max(&mut fields.iter().map(|f| &f.schema)),
+        }
+    }
+
+    /// Returns the field named `name`, if this is a struct schema with such
+    /// a field.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        match self {
+            Schema::Struct { fields, .. } => fields.iter().find(|f| f.name == name),
+            _ => None,
+        }
+    }
+}
+
+fn merge_all(a: Vec<Schema>, b: Vec<Schema>) -> Vec<Schema> {
+    a.into_iter().zip(b).map(|(a, b)| a.merge(b)).collect()
+}
+
+fn merge_fields(a: Vec<Field>, mut b: Vec<Field>) -> Vec<Field> {
+    let mut fields = Vec::with_capacity(a.len().max(b.len()));
+    for mut field in a {
+        match b.iter().position(|f| f.name == field.name) {
+            Some(idx) => {
+                let other = b.remove(idx);
+                field.schema = field.schema.merge(other.schema);
+                field.required &= other.required;
+            }
+            None => field.required = false,
+        }
+        fields.push(field);
+    }
+    fields.extend(b.into_iter().map(|field| Field {
+        required: false,
+        ..field
+    }));
+    fields
+}
+
+impl Visitable for Schema {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self {
+            Schema::Scalar(kind) => kind.visit(visitor),
+            Schema::List(elem) => {
+                visitor.visit_map(vec![kv(&"type", &"array"), kv(&"items", &**elem)])
+            }
+            Schema::Tuple(elems) => {
+                visitor.visit_map(vec![kv(&"type", &"array"), kv(&"prefixItems", elems)])
+            }
+            Schema::Map { key, value } => visitor.visit_map(vec![
+                kv(&"type", &"object"),
+                kv(&"propertyNames", &**key),
+                kv(&"additionalProperties", &**value),
+            ]),
+            Schema::Struct { name, fields } => {
+                let properties = Value::with_visit(fields, |fields, visitor| {
+                    visitor.visit_map(
+                        fields
+                            .iter()
+                            .map(|f| (Value::borrowed(&f.name), Value::borrowed(&f.schema))),
+                    )
+                });
+                let required = Value::with_visit(fields, |fields, visitor| {
+                    visitor.visit_list(
+                        fields
+                            .iter()
+                            .filter(|f| f.required)
+                            .map(|f| Value::borrowed(&f.name)),
+                    )
+                });
+                let mut entries = vec![kv(&"type", &"object")];
+                if let Some(name) = name {
+                    entries.push(kv(&"title", name));
+                }
+                entries.push((Value::borrowed(&"properties"), properties));
+                entries.push((Value::borrowed(&"required"), required));
+                visitor.visit_map(entries)
+            }
+            Schema::TupleStruct { name, fields } => visitor.visit_map(vec![
+                kv(&"type", &"array"),
+                kv(&"title", name),
+                kv(&"prefixItems", fields),
+            ]),
+            Schema::Any | Schema::Empty => visitor.visit_map(Vec::new()),
+        }
+    }
+}
+
+fn kv<'a>(key: &'static &'static str, value: &'a impl Visitable) -> (Value<'a>, Value<'a>) {
+    (Value::borrowed(key), Value::borrowed(value))
+}
+
+// === impl ScalarKind ===
+
+impl ScalarKind {
+    /// Returns the kind of scalar which can represent values of both `self`
+    /// and `other`, if there is one.
+    pub fn merge(self, other: ScalarKind) -> Option<ScalarKind> {
+        use self::ScalarKind::*;
+        let class = |kind| match kind {
+            U8 | U16 | U32 | U64 => Some(0),
+            I8 | I16 | I32 | I64 => Some(1),
+            F32 | F64 => Some(2),
+            _ => None,
+        };
+        if self == other {
+            return Some(self);
+        }
+        if class(self).is_none() || class(self) != class(other) {
+            return None;
+        }
+        // Within each class, the variants are declared in order of width.
+        Some(if self as u8 > other as u8 {
+            self
+        } else {
+            other
+        })
+    }
+
+    fn json_type(self) -> (&'static str, Option<&'static str>) {
+        use self::ScalarKind::*;
+        match self {
+            Unit => ("null", None),
+            Bool => ("boolean", None),
+            U8 => ("integer", Some("uint8")),
+            U16 => ("integer", Some("uint16")),
+            U32 => ("integer", Some("uint32")),
+            U64 => ("integer", Some("uint64")),
+            I8 => ("integer", Some("int8")),
+            I16 => ("integer", Some("int16")),
+            I32 => ("integer", Some("int32")),
+            I64 => ("integer", Some("int64")),
+            F32 => ("number", Some("float")),
+            F64 => ("number", Some("double")),
+            Str => ("string", None),
+            Bytes => ("string", Some("binary")),
+        }
+    }
+}
+
+impl Visitable for ScalarKind {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self.json_type() {
+            (ty, Some(format)) => visitor.visit_map(vec![kv(&"type", &ty), kv(&"format", &format)]),
+            (ty, None) => visitor.visit_map(vec![kv(&"type", &ty)]),
+        }
+    }
+}
+
+impl fmt::Display for ScalarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ScalarKind::*;
+        f.write_str(match self {
+            Unit => "unit",
+            Bool => "bool",
+            U8 => "u8",
+            U16 => "u16",
+            U32 => "u32",
+            U64 => "u64",
+            I8 => "i8",
+            I16 => "i16",
+            I32 => "i32",
+            I64 => "i64",
+            F32 => "f32",
+            F64 => "f64",
+            Str => "string",
+            Bytes => "bytes",
+        })
+    }
+}
@@ -0,0 +1,470 @@
+use super::{Field, ScalarKind, Schema};
+use crate::{
+    owned::{Builder, OwnedValue},
+    value::Visitable,
+    visitor::{self, Error, PathSegment, Visit, VisitResult},
+};
+use std::{convert::TryFrom, fmt};
+
+/// A `Visit` implementation that checks a visited value against a
+/// [`Schema`].
+///
+/// Rather than stopping at the first problem, a `ValidateVisit` reports
+/// every way in which the value does not match the schema: missing required
+/// fields, fields the schema does not describe, scalars of the wrong kind,
+/// and tuples of the wrong length. Optionally, it also reports values which
+/// are nested more deeply than a maximum depth.
+///
+/// Integers match any scalar kind of the same signedness which can
+/// represent them, so `300u64` does not match [`ScalarKind::U8`], but `3u64`
+/// does. [`Schema::Any`] and [`Schema::Empty`] match any value.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{schema::{Schema, ValidateVisit}, value};
+/// let schema = Schema::of(&value!([1u8, 2u8])).unwrap();
+///
+/// let mut visit = ValidateVisit::new(&schema);
+/// value!([3u8, 300u64, "four"]).visit(&mut visit).unwrap();
+/// let mismatches = visit.finish().unwrap();
+///
+/// assert_eq!(mismatches[0].to_string(), "expected u8, found unsigned integer 300 at `[1]`");
+/// assert_eq!(mismatches[1].to_string(), "expected u8, found string at `[2]`");
+/// ```
+///
+/// [`Schema`]: enum.Schema.html
+/// [`ScalarKind::U8`]: enum.ScalarKind.html#variant.U8
+/// [`Schema::Any`]: enum.Schema.html#variant.Any
+/// [`Schema::Empty`]: enum.Schema.html#variant.Empty
+pub struct ValidateVisit<'a> {
+    schema: &'a Schema,
+    max_depth: Option<usize>,
+    value: Builder,
+}
+
+/// A way in which a value does not match a [`Schema`].
+///
+/// [`Schema`]: enum.Schema.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    kind: MismatchKind,
+    path: Vec<PathSegment>,
+}
+
+/// The kinds of [`Mismatch`].
+///
+/// [`Mismatch`]: struct.Mismatch.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum MismatchKind {
+    /// The value was not of the expected type.
+    WrongType {
+        /// A description of the type the schema expected.
+        expected: String,
+        /// A description of the value that was found.
+        found: String,
+    },
+    /// A struct was missing a required field with the given name.
+    MissingField(String),
+    /// A struct had a field with the given name which the schema does not
+    /// describe.
+    UnexpectedField(String),
+    /// A tuple or tuple struct had the wrong number of elements.
+    WrongLength {
+        /// The number of elements the schema expected.
+        expected: usize,
+        /// The number of elements that were found.
+        found: usize,
+    },
+    /// A container was nested more deeply than the given maximum depth.
+    TooDeep(usize),
+}
+
+// === impl ValidateVisit ===
+
+impl<'a> ValidateVisit<'a> {
+    /// Returns a new `ValidateVisit` which checks a value against `schema`.
+    pub fn new(schema: &'a Schema) -> Self {
+        Self {
+            schema,
+            max_depth: None,
+            value: Builder::default(),
+        }
+    }
+
+    /// Also reports values whose containers are nested more than
+    /// `max_depth` deep, as measured by [`Schema::depth`].
+    ///
+    /// [`Schema::depth`]: enum.Schema.html#method.depth
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self {
+            max_depth: Some(max_depth),
+            ..self
+        }
+    }
+
+    /// Consumes the `ValidateVisit`, returning every mismatch between the
+    /// visited value and the schema.
+    ///
+    /// If the value matches the schema, the returned list is empty.
+    ///
+    /// # Errors
+    ///
+    /// If the value did not visit a balanced sequence of containers, an
+    /// error is returned.
+    pub fn finish(self) -> Result<Vec<Mismatch>, Error> {
+        let value = self.value.finish()?;
+        let mut check = Check {
+            max_depth: self.max_depth.unwrap_or(usize::MAX),
+            path: Vec::new(),
+            mismatches: Vec::new(),
+        };
+        check.value(self.schema, &value, 0);
+        Ok(check.mismatches)
+    }
+}
+
+impl fmt::Debug for ValidateVisit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValidateVisit")
+            .field("schema", &self.schema)
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
+
+impl Visit for ValidateVisit<'_> {
+    forward_visit! {
+        value =>
+            visit_uint,
+            visit_int,
+            visit_float,
+            visit_str,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_kv,
+            visit_field,
+            visit_named_field,
+            visit_fmt,
+            visit_display,
+            visit_record,
+            named_type,
+            visit_annotation,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+}
+
+// === impl Mismatch ===
+
+impl Mismatch {
+    /// Returns the kind of mismatch.
+    pub fn kind(&self) -> &MismatchKind {
+        &self.kind
+    }
+
+    /// Returns the path to the mismatched value, from the outermost
+    /// container inwards.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            MismatchKind::WrongType { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)?
+            }
+            MismatchKind::MissingField(name) => write!(f, "missing required field `{}`", name)?,
+            MismatchKind::UnexpectedField(name) => write!(f, "unexpected field `{}`", name)?,
+            MismatchKind::WrongLength { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)?
+            }
+            MismatchKind::TooDeep(max) => write!(f, "nested more than {} deep", max)?,
+        }
+        visitor::fmt_path(&self.path, f)
+    }
+}
+
+// === impl Check ===
+
+/// The state of comparing a buffered value against a schema.
+struct Check {
+    max_depth: usize,
+    path: Vec<PathSegment>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Check {
+    fn report(&mut self, kind: MismatchKind) {
+        self.mismatches.push(Mismatch {
+            kind,
+            path: self.path.clone(),
+        });
+    }
+
+    fn wrong_type(&mut self, schema: &Schema, value: &OwnedValue) {
+        self.report(MismatchKind::WrongType {
+            expected: describe_schema(schema),
+            found: describe_value(value),
+        });
+    }
+
+    fn nested(&mut self, segment: PathSegment, f: impl FnOnce(&mut Self)) {
+        self.path.push(segment);
+        f(self);
+        self.path.pop();
+    }
+
+    /// Checks `value`, which is enclosed by `depth` containers.
+    fn value(&mut self, schema: &Schema, value: &OwnedValue, depth: usize) {
+        let is_container = !matches!(
+            value,
+            OwnedValue::Unit
+                | OwnedValue::Bool(_)
+                | OwnedValue::Uint(_)
+                | OwnedValue::Int(_)
+                | OwnedValue::Float(_)
+                | OwnedValue::Str(_)
+                | OwnedValue::Bytes(_)
+        );
+        if is_container && depth >= self.max_depth {
+            // Don't also report the mismatches inside the too-deep value.
+            return self.report(MismatchKind::TooDeep(self.max_depth));
+        }
+        let depth = if is_container { depth + 1 } else { depth };
+
+        match (schema, value) {
+            (Schema::Any, _) | (Schema::Empty, _) => self.any(value, depth),
+            (Schema::Scalar(kind), value) => {
+                if !scalar_matches(*kind, value) {
+                    self.wrong_type(schema, value);
+                }
+            }
+            (Schema::List(elem), OwnedValue::List(values)) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.nested(PathSegment::Index(i), |c| c.value(elem, value, depth));
+                }
+            }
+            (Schema::Tuple(elems), OwnedValue::Tuple(values)) => self.elems(elems, values, depth),
+            (Schema::Map { key, value: schema }, OwnedValue::Map(pairs)) => {
+                for (k, v) in pairs {
+                    let segment = PathSegment::Field(k.clone().into_key());
+                    self.nested(segment, |c| {
+                        c.value(key, k, depth);
+                        c.value(schema, v, depth);
+                    });
+                }
+            }
+            (
+                Schema::Struct { name, fields },
+                OwnedValue::Struct {
+                    name: found,
+                    fields: values,
+                },
+            ) if name.as_ref() == Some(found) => {
+                let values = values.iter().map(|(k, v)| (k.as_str(), v));
+                self.fields(fields, values.collect(), depth)
+            }
+            (Schema::Struct { name: None, fields }, OwnedValue::Map(pairs)) => {
+                let keys = pairs
+                    .iter()
+                    .map(|(k, _)| k.clone().into_key())
+                    .collect::<Vec<_>>();
+                let values = keys
+                    .iter()
+                    .map(String::as_str)
+                    .zip(pairs.iter().map(|(_, v)| v));
+                self.fields(fields, values.collect(), depth)
+            }
+            (
+                Schema::TupleStruct { name, fields },
+                OwnedValue::TupleStruct {
+                    name: found,
+                    fields: values,
+                },
+            ) if name == found => self.elems(fields, values, depth),
+            (schema, value) => self.wrong_type(schema, value),
+        }
+    }
+
+    /// Checks only the depth of a value described by `Schema::Any`.
+    fn any(&mut self, value: &OwnedValue, depth: usize) {
+        if self.max_depth == usize::MAX {
+            return;
+        }
+        match value {
+            OwnedValue::List(values)
+            | OwnedValue::Tuple(values)
+            | OwnedValue::TupleStruct { fields: values, .. } => {
+                for (i, value) in values.iter().enumerate() {
+                    self.nested(PathSegment::Index(i), |c| {
+                        c.value(&Schema::Any, value, depth)
+                    });
+                }
+            }
+            OwnedValue::Map(pairs) => {
+                for (k, v) in pairs {
+                    let segment = PathSegment::Field(k.clone().into_key());
+                    self.nested(segment, |c| c.value(&Schema::Any, v, depth));
+                }
+            }
+            OwnedValue::Struct { fields, .. } => {
+                for (k, v) in fields {
+                    let segment = PathSegment::Field(k.clone());
+                    self.nested(segment, |c| c.value(&Schema::Any, v, depth));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn elems(&mut self, schemas: &[Schema], values: &[OwnedValue], depth: usize) {
+        if schemas.len() != values.len() {
+            return self.report(MismatchKind::WrongLength {
+                expected: schemas.len(),
+                found: values.len(),
+            });
+        }
+        for (i, (schema, value)) in schemas.iter().zip(values).enumerate() {
+            self.nested(PathSegment::Index(i), |c| c.value(schema, value, depth));
+        }
+    }
+
+    fn fields(&mut self, schemas: &[Field], values: Vec<(&str, &OwnedValue)>, depth: usize) {
+        for (name, value) in &values {
+            match schemas.iter().find(|f| f.name == *name) {
+                Some(field) => {
+                    let segment = PathSegment::Field((*name).to_owned());
+                    self.nested(segment, |c| c.value(&field.schema, value, depth));
+                }
+                None => self.report(MismatchKind::UnexpectedField((*name).to_owned())),
+            }
+        }
+        for field in schemas {
+            if field.required && !values.iter().any(|(name, _)| *name == field.name) {
+                self.report(MismatchKind::MissingField(field.name.clone()));
+            }
+        }
+    }
+}
+
+fn scalar_matches(kind: ScalarKind, value: &OwnedValue) -> bool {
+    use self::ScalarKind::*;
+    match (kind, value) {
+        (Unit, OwnedValue::Unit)
+        | (Bool, OwnedValue::Bool(_))
+        | (F32, OwnedValue::Float(_))
+        | (F64, OwnedValue::Float(_))
+        | (Str, OwnedValue::Str(_))
+        | (Bytes, OwnedValue::Bytes(_)) => true,
+        (U8, &OwnedValue::Uint(v)) => v <= u64::from(u8::MAX),
+        (U16, &OwnedValue::Uint(v)) => v <= u64::from(u16::MAX),
+        (U32, &OwnedValue::Uint(v)) => v <= u64::from(u32::MAX),
+        (U64, OwnedValue::Uint(_)) | (I64, OwnedValue::Int(_)) => true,
+        (I8, &OwnedValue::Int(v)) => i8::try_from(v).is_ok(),
+        (I16, &OwnedValue::Int(v)) => i16::try_from(v).is_ok(),
+        (I32, &OwnedValue::Int(v)) => i32::try_from(v).is_ok(),
+        _ => false,
+    }
+}
+
+fn describe_schema(schema: &Schema) -> String {
+    match schema {
+        Schema::Scalar(kind) => kind.to_string(),
+        Schema::List(_) => "list".to_owned(),
+        Schema::Tuple(elems) => format!("tuple of {} elements", elems.len()),
+        Schema::Map { .. } => "map".to_owned(),
+        Schema::Struct {
+            name: Some(name), ..
+        } => format!("struct `{}`", name),
+        Schema::Struct { name: None, .. } => "struct".to_owned(),
+        Schema::TupleStruct { name, .. } => format!("tuple struct `{}`", name),
+        Schema::Any | Schema::Empty => "any value".to_owned(),
+    }
+}
+
+fn describe_value(value: &OwnedValue) -> String {
+    match value {
+        OwnedValue::Unit => "unit".to_owned(),
+        OwnedValue::Bool(_) => "bool".to_owned(),
+        OwnedValue::Uint(v) => format!("unsigned integer {}", v),
+        OwnedValue::Int(v) => format!("signed integer {}", v),
+        OwnedValue::Float(_) => "float".to_owned(),
+        OwnedValue::Str(_) => "string".to_owned(),
+        OwnedValue::Bytes(_) => "bytes".to_owned(),
+        OwnedValue::List(_) => "list".to_owned(),
+        OwnedValue::Tuple(values) => format!("tuple of {} elements", values.len()),
+        OwnedValue::Map(_) => "map".to_owned(),
+        OwnedValue::Struct { name, .. } => format!("struct `{}`", name),
+        OwnedValue::TupleStruct { name, .. } => format!("tuple struct `{}`", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Value;
+
+    fn event(fields: Vec<(&str, OwnedValue)>) -> OwnedValue {
+        OwnedValue::Struct {
+            name: "Event".to_owned(),
+            fields: fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_all_mismatches() {
+        let span = |fields| OwnedValue::Map(fields);
+        let key = |k: &str| OwnedValue::Str(k.into());
+        let schema = Schema::of(&Value::borrowed(&event(vec![
+            ("id", OwnedValue::Uint(1)),
+            ("tags", OwnedValue::List(vec![key("a")])),
+            ("span", span(vec![(key("parent"), span(Vec::new()))])),
+        ])))
+        .unwrap();
+        let value = event(vec![
+            ("id", OwnedValue::Uint(300)),
+            ("tags", OwnedValue::List(vec![key("a"), OwnedValue::Int(2)])),
+            ("span", span(vec![(key("parent"), span(Vec::new()))])),
+            ("extra", OwnedValue::Bool(true)),
+        ]);
+
+        let mut visit = ValidateVisit::new(&schema).with_max_depth(2);
+        value.visit(&mut visit).unwrap();
+        let mismatches = visit
+            .finish()
+            .unwrap()
+            .iter()
+            .map(Mismatch::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mismatches,
+            vec![
+                "expected string, found signed integer 2 at `tags[1]`",
+                "nested more than 2 deep at `span.parent`",
+                "unexpected field `extra`",
+            ]
+        );
+        assert_eq!(
+            schema
+                .validate(&Value::borrowed(&event(Vec::new())))
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
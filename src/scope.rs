@@ -0,0 +1,162 @@
+//! Implicit, thread-local context.
+//!
+//! Rather than passing context (such as the ID of the request being
+//! handled) to every function which logs an event, middleware may [`push`]
+//! it onto a stack of context for the current thread. Any code running on
+//! that thread may then visit [`CurrentContext`], a record of every entry on
+//! the stack, to enrich its events with the context.
+//!
+//! Each entry stays on the stack until the [`Entered`] guard returned by
+//! [`push`] is dropped. When several entries have the same key, the most
+//! recently pushed entry shadows the others.
+//!
+//! This module requires the `scope` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::value::ValueExt;
+//! # use valuable::{scope::{self, CurrentContext}, value, value::Value};
+//! let _request = scope::push("request", value!(7)).unwrap();
+//! {
+//!     let _user = scope::push("user", value!("u-1")).unwrap();
+//!     assert_eq!(
+//!         Value::borrowed(&CurrentContext).render_json(),
+//!         r#"{"request":7,"user":"u-1"}"#
+//!     );
+//! }
+//! assert_eq!(Value::borrowed(&CurrentContext).render_json(), r#"{"request":7}"#);
+//! ```
+//!
+//! [`push`]: fn.push.html
+//! [`CurrentContext`]: struct.CurrentContext.html
+//! [`Entered`]: struct.Entered.html
+use crate::{
+    owned::OwnedValue,
+    record::Record,
+    value::{Value, Visitable},
+    visitor::{Error, Visit, VisitResult},
+};
+use std::{cell::RefCell, fmt, marker::PhantomData, rc::Rc};
+
+/// A record of every entry on the current thread's context stack.
+///
+/// Visiting a `CurrentContext` visits a single record whose fields are the
+/// keys and values pushed with [`push`], in the order in which they were
+/// pushed, omitting any entry shadowed by a later entry with the same key.
+///
+/// [`push`]: fn.push.html
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CurrentContext;
+
+/// A guard which pops an entry off the context stack when dropped.
+///
+/// This is returned by [`push`]. Dropping an `Entered` also pops any entries
+/// pushed after its entry which are still on the stack.
+///
+/// [`push`]: fn.push.html
+#[must_use = "the entry is popped as soon as the guard is dropped"]
+pub struct Entered {
+    depth: usize,
+    /// The stack belongs to the thread which pushed the entry, so the guard
+    /// may not be sent to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+type Entry = (Rc<str>, Rc<OwnedValue>);
+
+thread_local! {
+    static STACK: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pushes `value` onto the current thread's context stack under `key`.
+///
+/// The value is buffered into an [`OwnedValue`], so that it may outlive the
+/// data it borrows. It is popped off the stack when the returned guard is
+/// dropped.
+///
+/// # Errors
+///
+/// If visiting `value` fails, the error is returned, and nothing is pushed.
+///
+/// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+pub fn push(key: &str, value: Value<'_>) -> Result<Entered, Error> {
+    let value = OwnedValue::from_value(&value)?;
+    let depth = STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        stack.push((key.into(), Rc::new(value)));
+        stack.len() - 1
+    });
+    Ok(Entered {
+        depth,
+        _not_send: PhantomData,
+    })
+}
+
+// === impl CurrentContext ===
+
+impl CurrentContext {
+    /// Returns `true` if the current thread's context stack is empty.
+    pub fn is_empty(&self) -> bool {
+        STACK.with(|stack| stack.borrow().is_empty())
+    }
+}
+
+impl Visitable for CurrentContext {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        // Copy the entries, rather than holding the stack borrowed while
+        // visiting, so that the visitor may push context of its own.
+        let entries = STACK.with(|stack| stack.borrow().clone());
+        let shadowed = |i: usize, key: &str| entries[i + 1..].iter().any(|(k, _)| &**k == key);
+        let pairs = entries
+            .iter()
+            .enumerate()
+            .filter(|&(i, (key, _))| !shadowed(i, key))
+            .map(|(_, (key, value))| (&**key, Value::borrowed(&**value)))
+            .collect::<Vec<_>>();
+        visitor.visit_record(&Record::from_pairs(&pairs))
+    }
+}
+
+// === impl Entered ===
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        // The thread-local may already have been destroyed if the guard is
+        // dropped while the thread is exiting.
+        let _ = STACK.try_with(|stack| stack.borrow_mut().truncate(self.depth));
+    }
+}
+
+impl fmt::Debug for Entered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entered")
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ValueExt;
+
+    #[test]
+    fn shadows_and_pops() {
+        let render = || Value::borrowed(&CurrentContext).render_json();
+        assert!(CurrentContext.is_empty());
+
+        let a = push("a", value!(1)).unwrap();
+        let b = push("b", value!(2)).unwrap();
+        let shadow = push("a", value!(3)).unwrap();
+        assert_eq!(render(), r#"{"b":2,"a":3}"#);
+        drop(shadow);
+        assert_eq!(render(), r#"{"a":1,"b":2}"#);
+
+        // Dropping a guard out of order also pops the entries after it.
+        drop(a);
+        assert!(CurrentContext.is_empty());
+        drop(b);
+        assert_eq!(render(), "{}");
+    }
+}
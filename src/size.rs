@@ -0,0 +1,498 @@
+//! Estimating the serialized size of values.
+//!
+//! [`SizeEstimate`] visits a value and counts the bytes that a format would
+//! write for it, without writing anything. This allows producers to
+//! pre-allocate output buffers, or to enforce a size budget on each event
+//! before doing the real work of serializing it.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::{json::JsonVisit, size::{Profile, SizeEstimate}, value};
+//! let value = value!({ "id": 42, "tags": ["a", "b"] });
+//! let size = SizeEstimate::of(&value, Profile::Json).unwrap();
+//!
+//! let mut json = JsonVisit::new(String::with_capacity(size));
+//! value.visit(&mut json).unwrap();
+//! assert_eq!(json.into_inner().len(), size);
+//! ```
+//!
+//! [`SizeEstimate`]: struct.SizeEstimate.html
+use crate::{
+    escape::{write_json_str, JsonEscape},
+    float::{FloatFormat, NonFinite},
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, Visit, VisitResult},
+};
+use std::{
+    fmt::{self, Write as _},
+    mem,
+};
+
+/// The format whose output size is estimated by a [`SizeEstimate`].
+///
+/// [`SizeEstimate`]: struct.SizeEstimate.html
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Profile {
+    /// Compact JSON, as written by [`JsonVisit`] with its default options.
+    ///
+    /// [`JsonVisit`]: ../json/struct.JsonVisit.html
+    Json,
+    /// Single-line `fmt::Debug`-style output, as written by [`DebugVisit`]
+    /// with its default options (and by formatting a `Value` with `{:?}`).
+    ///
+    /// [`DebugVisit`]: ../debug/struct.DebugVisit.html
+    Debug,
+}
+
+/// A `Visit` implementation that estimates the serialized size of values.
+///
+/// Rather than writing a value, a `SizeEstimate` counts the bytes that
+/// would be written for it in the format described by its [`Profile`].
+/// Integers, booleans, and container delimiters are counted without
+/// formatting them, while strings, floats, and formatted values are
+/// formatted into a counter which discards them, so no memory is allocated.
+///
+/// The estimate is exact for most values, but map keys which are not
+/// strings are only approximated in the [`Profile::Json`] profile, as are
+/// values written with options other than the format's defaults.
+///
+/// If a [limit] is set, visiting fails as soon as the estimate exceeds it,
+/// so that the rest of an oversized value is not traversed.
+///
+/// [`Profile`]: enum.Profile.html
+/// [`Profile::Json`]: enum.Profile.html#variant.Json
+/// [limit]: #method.with_limit
+pub struct SizeEstimate {
+    profile: Profile,
+    size: usize,
+    limit: Option<usize>,
+    stack: Vec<Frame>,
+    /// Set while visiting the value half of a key-value pair, so that no
+    /// separator is counted before it.
+    in_kv_value: bool,
+    /// Set when a type name has been counted for the next value.
+    named: bool,
+    /// Set when strings are written without quoting, as `Debug` struct
+    /// field names are.
+    raw_strs: bool,
+    /// Set while visiting a JSON map key, so that keys which are not
+    /// strings are counted with quotes.
+    in_key: bool,
+    key_quoted: bool,
+}
+
+struct Frame {
+    kind: Container,
+    named: bool,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+/// A writer which counts the bytes written to it.
+struct Counter(usize);
+
+// === impl SizeEstimate ===
+
+impl SizeEstimate {
+    /// Returns a new `SizeEstimate` for the given format profile, with no
+    /// limit.
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            size: 0,
+            limit: None,
+            stack: Vec::new(),
+            in_kv_value: false,
+            named: false,
+            raw_strs: false,
+            in_key: false,
+            key_quoted: false,
+        }
+    }
+
+    /// Returns the estimated size, in bytes, of `value` in the format
+    /// described by `profile`.
+    pub fn of(value: &Value<'_>, profile: Profile) -> Result<usize, Error> {
+        let mut estimate = Self::new(profile);
+        value.visit(&mut estimate)?;
+        Ok(estimate.size())
+    }
+
+    /// Sets a limit on the estimated size.
+    ///
+    /// Once the estimate exceeds `limit` bytes, visiting fails, and
+    /// [`exceeded`] returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{size::{Profile, SizeEstimate}, value};
+    /// let mut estimate = SizeEstimate::new(Profile::Json).with_limit(16);
+    /// let value = value!({ "message": "a message which is too long" });
+    /// assert!(value.visit(&mut estimate).is_err());
+    /// assert!(estimate.exceeded());
+    /// ```
+    ///
+    /// [`exceeded`]: #method.exceeded
+    pub fn with_limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Returns the estimated size, in bytes, of the values visited so far.
+    ///
+    /// If the limit was exceeded, this is the size at which visiting
+    /// stopped, so the full value is at least this large.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if the estimated size exceeds this estimate's limit.
+    pub fn exceeded(&self) -> bool {
+        self.limit.map(|limit| self.size > limit).unwrap_or(false)
+    }
+
+    /// Resets the estimated size to zero, so that another value may be
+    /// estimated.
+    pub fn reset(&mut self) {
+        *self = Self {
+            limit: self.limit,
+            ..Self::new(self.profile)
+        };
+    }
+
+    fn add(&mut self, len: usize) -> VisitResult {
+        self.size += len;
+        match self.limit {
+            Some(limit) if self.size > limit => Err(Error::size_limit(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a separator before a value, if one is needed.
+    ///
+    /// Returns `true` if a type name was already counted for this value.
+    fn begin_value(&mut self) -> Result<bool, Error> {
+        if mem::replace(&mut self.named, false) {
+            return Ok(true);
+        }
+        if mem::replace(&mut self.in_kv_value, false) {
+            return Ok(false);
+        }
+        let len = match self.stack.last_mut() {
+            Some(frame) => {
+                frame.len += 1;
+                match (self.profile, frame.len) {
+                    (Profile::Json, 1) => 0,
+                    (Profile::Json, _) => 1,
+                    // Named structs and tuple structs write their opening
+                    // delimiter before their first field.
+                    (Profile::Debug, 1) if frame.named && frame.kind == Container::Struct => 3,
+                    (Profile::Debug, 1) if frame.named && frame.kind == Container::Tuple => 1,
+                    (Profile::Debug, 1) => 0,
+                    (Profile::Debug, _) => 2,
+                }
+            }
+            None => 0,
+        };
+        self.add(len)?;
+        Ok(false)
+    }
+
+    /// Counts a scalar value of `len` bytes.
+    fn scalar(&mut self, len: usize) -> VisitResult {
+        // A named scalar is written as `Name(value)`.
+        let parens = if self.begin_value()? { 2 } else { 0 };
+        self.add(len + parens)
+    }
+
+    fn open(&mut self, kind: Container) -> VisitResult {
+        let named = self.begin_value()?;
+        let len = match (self.profile, kind) {
+            (Profile::Json, _) => 1,
+            (Profile::Debug, Container::Map) | (Profile::Debug, Container::List) => 1,
+            (Profile::Debug, Container::Struct) if !named => 2,
+            (Profile::Debug, Container::Tuple) if !named => 1,
+            (Profile::Debug, _) => 0,
+        };
+        self.stack.push(Frame {
+            kind,
+            named,
+            len: 0,
+        });
+        self.add(len)
+    }
+
+    fn close(&mut self, kind: Container) -> VisitResult {
+        let frame = match self.stack.pop() {
+            Some(frame) if frame.kind == kind => frame,
+            _ => return Err(Error::unbalanced()),
+        };
+        let len = match (self.profile, kind) {
+            (Profile::Json, _) => 1,
+            (Profile::Debug, Container::Map) | (Profile::Debug, Container::List) => 1,
+            // Named structs and tuple structs with no fields are written as
+            // just their name.
+            (Profile::Debug, _) if frame.named && frame.len == 0 => 0,
+            (Profile::Debug, Container::Struct) if frame.len == 0 => 1,
+            (Profile::Debug, Container::Struct) => 2,
+            (Profile::Debug, Container::Tuple) if !frame.named && frame.len == 1 => 2,
+            (Profile::Debug, Container::Tuple) => 1,
+        };
+        self.add(len)
+    }
+
+    /// Counts a string of `len` bytes, including any quotes and escapes.
+    fn string(&mut self, len: usize) -> VisitResult {
+        self.key_quoted |= self.in_key;
+        self.scalar(len)
+    }
+}
+
+impl Visit for SizeEstimate {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.scalar(uint_len(value))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        let sign = if value < 0 { 1 } else { 0 };
+        self.scalar(sign + uint_len(value.unsigned_abs()))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        let mut counter = Counter(0);
+        match self.profile {
+            Profile::Json => {
+                let format = FloatFormat::new();
+                format.write(&mut counter, value, "null", write_json_str)?
+            }
+            Profile::Debug => {
+                let format = FloatFormat::new().with_non_finite(NonFinite::Literal);
+                format.write(&mut counter, value, "()", |w, s| write!(w, "{:?}", s))?
+            }
+        }
+        self.scalar(counter.0)
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        let mut counter = Counter(0);
+        match self.profile {
+            _ if self.raw_strs => return self.scalar(value.len()),
+            Profile::Json => write_json_str(&mut counter, value)?,
+            Profile::Debug => write!(counter, "{:?}", value)?,
+        }
+        self.string(counter.0)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.scalar(if value { 4 } else { 5 })
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        if self.profile == Profile::Json {
+            return self.scalar(4);
+        }
+        // A named unit value is written as just its name.
+        if self.begin_value()? {
+            return Ok(());
+        }
+        self.add(2)
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.begin_value()?;
+        let in_struct = self
+            .stack
+            .last()
+            .map(|frame| frame.kind == Container::Struct)
+            .unwrap_or(false);
+        match self.profile {
+            Profile::Json if in_struct => {
+                self.in_kv_value = true;
+                k.visit(self)?;
+            }
+            Profile::Json => {
+                // Keys which are not strings are written as strings.
+                self.in_kv_value = true;
+                self.in_key = true;
+                self.key_quoted = false;
+                let result = k.visit(self);
+                self.in_key = false;
+                result?;
+                if !mem::replace(&mut self.key_quoted, false) {
+                    self.add(2)?;
+                }
+            }
+            Profile::Debug => {
+                self.in_kv_value = true;
+                self.raw_strs = in_struct;
+                let result = k.visit(self);
+                self.raw_strs = false;
+                result?;
+            }
+        }
+        self.add(match self.profile {
+            Profile::Json => 1,
+            Profile::Debug => 2,
+        })?;
+        self.in_kv_value = true;
+        v.visit(self)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        let mut counter = Counter(0);
+        match self.profile {
+            Profile::Json => {
+                counter.write_str("\"\"")?;
+                fmt::write(&mut JsonEscape(&mut counter), args)?;
+            }
+            // Formatted values are written as-is.
+            Profile::Debug => counter.write_fmt(args)?,
+        }
+        self.string(counter.0)
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        if self.profile == Profile::Json {
+            // JSON has no representation for type names.
+            return Ok(());
+        }
+        self.begin_value()?;
+        self.named = true;
+        self.add(name.len())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let caps = Capabilities::UNIT
+            | Capabilities::MAPS
+            | Capabilities::LISTS
+            | Capabilities::STRUCTS
+            | Capabilities::TUPLES;
+        match self.profile {
+            Profile::Json => caps,
+            Profile::Debug => caps | Capabilities::NAMED_TYPES,
+        }
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple)
+    }
+}
+
+impl fmt::Debug for SizeEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SizeEstimate")
+            .field("profile", &self.profile)
+            .field("size", &self.size)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+/// Returns the number of decimal digits in `value`.
+fn uint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 10 {
+        value /= 10;
+        len += 1;
+    }
+    len
+}
+
+// === impl Counter ===
+
+impl fmt::Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, value::ValueExt};
+
+    #[test]
+    fn matches_output() {
+        let bad = "quote \" and\nnewline";
+        let values = [
+            value!({ "id": 42, "neg": -7, "ok": false, "none": (), "f": 1.5 }),
+            value!([1, [], {}, "a", 2.0e30, bad]),
+            value!({ 1: "one", "nested": { "x": [f64::NAN] } }),
+            Value::with_visit((), |_, visit| {
+                visit.visit_struct("Point", vec![("x", value!(1)), ("y", value!("b"))])?;
+                visit.visit_struct("Empty", vec![])
+            }),
+            Value::with_visit((), |_, visit| {
+                visit.visit_tuple_struct("Id", vec![value!(1), value!(2)])?;
+                visit.visit_tuple(vec![value!(1)])
+            }),
+        ];
+        for value in &values {
+            assert_eq!(
+                SizeEstimate::of(value, Profile::Json).unwrap(),
+                value.render_json().len(),
+                "{}",
+                value.render_json()
+            );
+            let debug = format!("{:?}", value);
+            assert_eq!(
+                SizeEstimate::of(value, Profile::Debug).unwrap(),
+                debug.len(),
+                "{}",
+                debug
+            );
+        }
+
+        let mut json = String::new();
+        values[0].visit(&mut JsonVisit::new(&mut json)).unwrap();
+        let mut estimate = SizeEstimate::new(Profile::Json).with_limit(json.len());
+        values[0].visit(&mut estimate).unwrap();
+        assert!(!estimate.exceeded());
+        estimate.reset();
+        assert_eq!(estimate.size(), 0);
+    }
+}
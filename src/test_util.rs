@@ -0,0 +1,229 @@
+//! Helpers for snapshot testing `Visitable` implementations.
+//!
+//! [`to_canonical_string`] renders a value as indented, human-readable text
+//! which does not depend on the iteration order of hash maps, so the output
+//! for a given value is the same on every run and every platform. The
+//! [`assert_value_snapshot!`] macro compares that text against an expected
+//! snapshot written inline in the test.
+//!
+//! This module requires the `test-util` feature, which is intended to be
+//! enabled only in `dev-dependencies`.
+//!
+//! [`to_canonical_string`]: fn.to_canonical_string.html
+//! [`assert_value_snapshot!`]: ../macro.assert_value_snapshot.html
+use crate::{owned::OwnedValue, value::Value};
+use std::fmt::Write as _;
+
+/// Renders `value` as deterministic, canonical text for snapshot tests.
+///
+/// The text resembles Rust's pretty-printed `{:#?}` output: named structs
+/// and tuple structs are written with their type names, every container
+/// with at least one element is written with one element per line, and
+/// strings are quoted and escaped. Map entries are sorted by the text of
+/// their keys, so maps (such as `HashMap`s) which visit their entries in an
+/// arbitrary order are always written the same way. Struct fields are kept
+/// in the order they were visited. Floats are written with the shortest
+/// representation which round-trips, always including a decimal point
+/// (e.g. `1.0`), and formatted values are rendered into strings.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{test_util::to_canonical_string, value};
+/// let text = to_canonical_string(&value!({ "b": [1.0], "a": true }));
+/// assert_eq!(text, "{\n    \"a\": true,\n    \"b\": [\n        1.0,\n    ],\n}");
+/// ```
+///
+/// # Panics
+///
+/// If visiting `value` fails, this panics with the error.
+pub fn to_canonical_string(value: &Value<'_>) -> String {
+    let value = match OwnedValue::from_value(value) {
+        Ok(value) => value,
+        Err(error) => panic!("visiting the value failed: {}", error),
+    };
+    let mut out = String::new();
+    write_value(&mut out, &value, 0);
+    out
+}
+
+/// Asserts that `actual` matches the inline snapshot `expected`.
+///
+/// Leading and trailing blank lines, and any indentation shared by every
+/// line of `expected`, are ignored, so that snapshots may be indented to
+/// match the surrounding code.
+///
+/// This is called by [`assert_value_snapshot!`]; it is public so that it may
+/// be used with text produced in other ways.
+///
+/// [`assert_value_snapshot!`]: ../macro.assert_value_snapshot.html
+#[track_caller]
+pub fn assert_snapshot(actual: &str, expected: &str) {
+    let expected = normalize(expected);
+    if actual != expected {
+        panic!(
+            "snapshot does not match\n--- expected ---\n{}\n--- actual ---\n{}\n",
+            expected, actual
+        );
+    }
+}
+
+/// Removes surrounding blank lines and common indentation from a snapshot.
+fn normalize(snapshot: &str) -> String {
+    let lines: Vec<&str> = snapshot.lines().collect();
+    let start = lines.iter().position(|line| !line.trim().is_empty());
+    let end = lines.iter().rposition(|line| !line.trim().is_empty());
+    let lines = match (start, end) {
+        (Some(start), Some(end)) => &lines[start..=end],
+        _ => return String::new(),
+    };
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect();
+    lines.join("\n")
+}
+
+fn write_value(out: &mut String, value: &OwnedValue, indent: usize) {
+    match value {
+        OwnedValue::Unit => out.push_str("()"),
+        OwnedValue::Bool(v) => write_display(out, v),
+        OwnedValue::Uint(v) => write_display(out, v),
+        OwnedValue::Int(v) => write_display(out, v),
+        OwnedValue::Float(v) => {
+            let _ = write!(out, "{:?}", v);
+        }
+        OwnedValue::Str(v) => {
+            let _ = write!(out, "{:?}", v);
+        }
+        OwnedValue::Bytes(v) => {
+            out.push_str("b\"");
+            for &b in v {
+                let _ = write!(out, "{}", std::ascii::escape_default(b));
+            }
+            out.push('"');
+        }
+        OwnedValue::List(vs) => write_seq(out, "[", "]", vs, indent),
+        OwnedValue::Tuple(vs) => write_seq(out, "(", ")", vs, indent),
+        OwnedValue::TupleStruct { name, fields } => {
+            out.push_str(name);
+            if !fields.is_empty() {
+                write_seq(out, "(", ")", fields, indent);
+            }
+        }
+        OwnedValue::Map(kvs) => {
+            let mut entries: Vec<(String, &OwnedValue)> = kvs
+                .iter()
+                .map(|(k, v)| {
+                    let mut key = String::new();
+                    write_value(&mut key, k, indent + 1);
+                    (key, v)
+                })
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            write_entries(out, "{", "}", entries, indent);
+        }
+        OwnedValue::Struct { name, fields } => {
+            out.push_str(name);
+            out.push(' ');
+            let entries = fields.iter().map(|(k, v)| (k.clone(), v)).collect();
+            write_entries(out, "{", "}", entries, indent);
+        }
+    }
+}
+
+fn write_display(out: &mut String, value: &dyn std::fmt::Display) {
+    let _ = write!(out, "{}", value);
+}
+
+fn write_seq(out: &mut String, open: &str, close: &str, vs: &[OwnedValue], indent: usize) {
+    out.push_str(open);
+    if !vs.is_empty() {
+        out.push('\n');
+        for v in vs {
+            push_indent(out, indent + 1);
+            write_value(out, v, indent + 1);
+            out.push_str(",\n");
+        }
+        push_indent(out, indent);
+    }
+    out.push_str(close);
+}
+
+fn write_entries(
+    out: &mut String,
+    open: &str,
+    close: &str,
+    entries: Vec<(String, &OwnedValue)>,
+    indent: usize,
+) {
+    out.push_str(open);
+    if !entries.is_empty() {
+        out.push('\n');
+        for (k, v) in entries {
+            push_indent(out, indent + 1);
+            out.push_str(&k);
+            out.push_str(": ");
+            write_value(out, v, indent + 1);
+            out.push_str(",\n");
+        }
+        push_indent(out, indent);
+    }
+    out.push_str(close);
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("    ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn snapshots() {
+        let map: HashMap<_, _> = (0..10u64).map(|i| (format!("k{}", i), i)).collect();
+        let text = to_canonical_string(&Value::borrowed(&map));
+        let keys: Vec<_> = text.lines().skip(1).take(3).collect();
+        assert_eq!(keys, ["    \"k0\": 0,", "    \"k1\": 1,", "    \"k2\": 2,"]);
+
+        let value = OwnedValue::Struct {
+            name: "Event".to_owned(),
+            fields: vec![
+                ("z".to_owned(), OwnedValue::Bytes(b"a\n".to_vec())),
+                (
+                    "a".to_owned(),
+                    OwnedValue::Tuple(vec![OwnedValue::Float(-0.5), OwnedValue::Unit]),
+                ),
+                (
+                    "unit".to_owned(),
+                    OwnedValue::TupleStruct {
+                        name: "Unit".to_owned(),
+                        fields: Vec::new(),
+                    },
+                ),
+                ("empty".to_owned(), OwnedValue::List(Vec::new())),
+            ],
+        };
+        crate::assert_value_snapshot!(value, @r#"
+            Event {
+                z: b"a\n",
+                a: (
+                    -0.5,
+                    (),
+                ),
+                unit: Unit,
+                empty: [],
+            }
+        "#);
+    }
+}
@@ -0,0 +1,204 @@
+//! Numbers with units of measure.
+//!
+//! The newtypes in this module wrap a number and visit it annotated with
+//! its [`Unit`], using the [`UNIT`] annotation key (see
+//! [`Visit::visit_annotation`]). Visitors which know about units may render
+//! the number for humans: [`ConsoleVisit`] writes a [`Bytes`] of 1572864 as
+//! `"1.5 MiB"`. Other visitors, such as [`JsonVisit`], ignore the annotation
+//! and write the raw number.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::value::ValueExt;
+//! # use valuable::{console::ConsoleVisit, units::{Bytes, Millis}, value};
+//! let size = Bytes(1572864);
+//! let elapsed = Millis(250);
+//! let value = value!({ "size": size, "elapsed": elapsed });
+//! assert_eq!(value.render_json(), r#"{"size":1572864,"elapsed":250}"#);
+//!
+//! let mut console = ConsoleVisit::new(String::new());
+//! value.visit(&mut console).unwrap();
+//! assert_eq!(
+//!     console.into_inner(),
+//!     r#"{"size": "1.5 MiB", "elapsed": "250 ms"}"#
+//! );
+//! ```
+//!
+//! [`Unit`]: enum.Unit.html
+//! [`UNIT`]: constant.UNIT.html
+//! [`Bytes`]: struct.Bytes.html
+//! [`Visit::visit_annotation`]: ../visitor/trait.Visit.html#method.visit_annotation
+//! [`ConsoleVisit`]: ../console/struct.ConsoleVisit.html
+//! [`JsonVisit`]: ../json/struct.JsonVisit.html
+use crate::{
+    value::Visitable,
+    visitor::{Visit, VisitResult},
+};
+use std::fmt;
+
+/// The annotation key under which a number's unit is visited.
+pub const UNIT: &str = "unit";
+
+/// A unit of measure.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Unit {
+    /// A number of bytes, annotated as `bytes`.
+    Bytes,
+    /// A duration in milliseconds, annotated as `ms`.
+    Millis,
+    /// A percentage, annotated as `percent`.
+    Percent,
+}
+
+/// A number of bytes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Bytes(pub u64);
+
+/// A duration in milliseconds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Millis(pub u64);
+
+/// A percentage, where `Percent(100.0)` is the whole.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Percent(pub f64);
+
+/// A number formatted for humans in its unit.
+struct Humanized {
+    unit: Unit,
+    value: f64,
+}
+
+// === impl Unit ===
+
+impl Unit {
+    /// Returns the annotation value for this unit.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Unit::Bytes => "bytes",
+            Unit::Millis => "ms",
+            Unit::Percent => "percent",
+        }
+    }
+
+    /// Returns the unit with the annotation value `s`, if there is one.
+    pub fn from_annotation(s: &str) -> Option<Unit> {
+        match s {
+            "bytes" => Some(Unit::Bytes),
+            "ms" => Some(Unit::Millis),
+            "percent" => Some(Unit::Percent),
+            _ => None,
+        }
+    }
+
+    /// Returns a formatter which writes `value` for humans, scaled to the
+    /// largest whole prefix of this unit (such as `1.5 MiB` or `2 min`).
+    pub fn humanize(self, value: f64) -> impl fmt::Display {
+        Humanized { unit: self, value }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// === impl Bytes, Millis, Percent ===
+
+impl Visitable for Bytes {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_annotation(UNIT, Unit::Bytes.as_str())?;
+        visitor.visit_uint(self.0)
+    }
+}
+
+impl Visitable for Millis {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_annotation(UNIT, Unit::Millis.as_str())?;
+        visitor.visit_uint(self.0)
+    }
+}
+
+impl Visitable for Percent {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_annotation(UNIT, Unit::Percent.as_str())?;
+        visitor.visit_float(self.0)
+    }
+}
+
+// === impl Humanized ===
+
+const BYTES: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const DURATIONS: &[(f64, &str)] = &[(1000.0, "s"), (60.0, "min"), (60.0, "h"), (24.0, "d")];
+
+impl fmt::Display for Humanized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut value = self.value;
+        let suffix = match self.unit {
+            Unit::Bytes => {
+                let mut prefixes = BYTES.iter();
+                let mut suffix = prefixes.next().unwrap();
+                for prefix in prefixes {
+                    if value.abs() < 1024.0 {
+                        break;
+                    }
+                    value /= 1024.0;
+                    suffix = prefix;
+                }
+                suffix
+            }
+            Unit::Millis => {
+                let mut suffix = "ms";
+                for &(scale, next) in DURATIONS {
+                    if value.abs() < scale {
+                        break;
+                    }
+                    value /= scale;
+                    suffix = next;
+                }
+                suffix
+            }
+            Unit::Percent => return write!(f, "{}%", OneDecimal(value)),
+        };
+        write!(f, "{} {}", OneDecimal(value), suffix)
+    }
+}
+
+/// Formats a float with at most one decimal place, omitting it when zero.
+struct OneDecimal(f64);
+
+impl fmt::Display for OneDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rounded = (self.0 * 10.0).round() / 10.0;
+        if rounded.fract() == 0.0 {
+            write!(f, "{:.0}", rounded)
+        } else {
+            write!(f, "{:.1}", rounded)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn humanize() {
+        let cases = [
+            (Unit::Bytes, 512.0, "512 B"),
+            (Unit::Bytes, 1024.0, "1 KiB"),
+            (Unit::Bytes, 1572864.0, "1.5 MiB"),
+            (Unit::Millis, 999.0, "999 ms"),
+            (Unit::Millis, 1500.0, "1.5 s"),
+            (Unit::Millis, 150_000.0, "2.5 min"),
+            (Unit::Millis, 172_800_000.0, "2 d"),
+            (Unit::Percent, 42.25, "42.3%"),
+            (Unit::Percent, 100.0, "100%"),
+        ];
+        for &(unit, value, expected) in &cases {
+            assert_eq!(unit.humanize(value).to_string(), expected);
+            assert_eq!(Unit::from_annotation(unit.as_str()), Some(unit));
+        }
+    }
+}
@@ -1,238 +1,822 @@
-use crate::visitor::{Visit, VisitResult};
-use std::{
-    borrow::Borrow,
-    collections,
-    fmt,
-    hash::Hash,
-};
-
-/// A structured field value of an erased type.
-///
-/// Implementors of `Value` may call the appropriate typed recording methods on
-/// the `Record` passed to `Record` in order to indicate how their data
-/// should be recorded.
-pub trait Visitable: Send {
-    /// Visits the value with the given `Visit`.
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult;
-}
+use crate::{json::JsonVisit, owned::OwnedValue, visitor::Error};
 
+pub use valuable_core::value::*;
 
-pub struct Value<'a> {
-    inner: ValueKind<'a>,
-}
+/// Derives `Visitable` for a struct or enum.
+///
+/// Structs with named fields are visited as structs, tuple structs as tuple
+/// structs, and unit structs as unit values, each with the struct's name as
+/// the type name.
+/// Each field must implement `Visitable`.
+///
+/// The following attributes may be used to change how a struct is visited:
+///
+/// - `#[visitable(rename = "...")]` on the struct visits it with the given
+///   type name.
+/// - `#[visitable(rename_all = "...")]` on the struct renames all of its
+///   fields according to a case convention: `lowercase`, `UPPERCASE`,
+///   `PascalCase`, `camelCase`, `snake_case`, `SCREAMING_SNAKE_CASE`,
+///   `kebab-case`, or `SCREAMING-KEBAB-CASE`.
+/// - `#[visitable(rename = "...")]` on a field visits it with the given
+///   name, ignoring `rename_all`.
+/// - `#[visitable(skip)]` on a field does not visit it at all. The field
+///   need not implement `Visitable`.
+/// - `#[visitable(skip_if_none)]` on a field of type `Option<T>` visits the
+///   contained value if it is `Some`, and does not visit the field if it is
+///   `None`.
+/// - `#[visitable(skip_if = "path")]` on a field does not visit it if the
+///   function at `path`, which must have the signature `fn(&T) -> bool` for
+///   a field of type `T`, returns `true` for its value. For example,
+///   `skip_if = "Vec::is_empty"` omits empty lists. If the field is also
+///   `skip_if_none`, the predicate is called with the `Option`.
+/// - `#[visitable(flatten)]` on a field visits the fields of its value,
+///   which must be visited as a map or struct, as if they were fields of
+///   the enclosing struct.
+/// - `#[visitable(with = "path")]` on a field visits it by calling the
+///   function at `path`, which must have the signature
+///   `fn(&T, &mut dyn Visit) -> VisitResult` for a field of type `T`. The
+///   field need not implement `Visitable`, so this may be used for types
+///   from other crates. If the field is also `skip_if_none`, `T` is the
+///   type contained in the `Option`.
+/// - `#[visitable(redact)]` on a field visits the string `"[REDACTED]"` in
+///   place of its value, which need not implement `Visitable`.
+///   `#[visitable(redact = "hash")]` instead visits a string containing a
+///   `#` followed by a hash of the value's JSON representation, as 16
+///   hexadecimal digits, so that redacted values may still be correlated.
+///   The hash is not salted or cryptographically secure, so it does not
+///   hide values which are easily guessed.
+///
+/// Named fields are visited in the order in which they are declared. With
+/// `#[visitable(sort_fields)]` on the struct, they are instead visited
+/// sorted by the names they are visited with, after renaming; flattened
+/// fields may not be sorted.
+///
+/// Only `skip`, `with`, and `redact` may be used on the fields of a tuple
+/// struct.
+///
+/// Generic structs and structs with lifetime parameters are supported. The
+/// derived implementation requires each type parameter used in the type of
+/// a visited field to implement `Visitable`. If these bounds are not
+/// correct (for example, if a field of type `Rc<T>` only requires `T` to
+/// implement some other trait), they may be replaced with
+/// `#[visitable(bound = "...")]` on the struct, which takes a
+/// comma-separated list of where clause predicates.
+///
+/// # Enums
+///
+/// By default, each variant of an enum is visited as a struct, tuple
+/// struct, or unit value named after the variant, as its `Debug` output
+/// would be. Since formats such as JSON discard type names, this loses the
+/// variant, so one of the following attributes may be used on the enum to
+/// record it the way `serde` would:
+///
+/// - `#[visitable(external)]` visits each variant as a map with a single
+///   key, the variant's name, whose value is the variant's contents. Unit
+///   variants are visited as just their name, as a string.
+/// - `#[visitable(tag = "type")]` visits each variant as a struct whose
+///   first field, `type`, is the variant's name, followed by the variant's
+///   fields. A newtype variant's value is flattened into the struct, so it
+///   must be visited as a map or struct; other tuple variants are not
+///   allowed.
+/// - `#[visitable(tag = "t", content = "c")]` visits each variant as a
+///   struct with the variant's name in the field `t` and its contents in the
+///   field `c`. Unit variants have no `c` field.
+/// - `#[visitable(untagged)]` visits each variant as just its contents.
+///
+/// A variant's contents are its fields as a struct or tuple, without a type
+/// name, except that the contents of a newtype variant are just its value,
+/// and those of a unit variant are a unit value.
+///
+/// On an enum, `rename_all` renames the variants rather than their fields;
+/// `#[visitable(rename = "...")]` and `#[visitable(rename_all = "...")]` on
+/// a variant rename the variant and its fields. `sort_fields` on an enum or
+/// a variant sorts the fields of its variants, or of that variant. The
+/// attributes on the fields of a variant are the same as those on the
+/// fields of a struct.
+///
+/// # Struct definitions
+///
+/// For a struct with named fields, none of which are flattened, the derive
+/// also implements [`StructLike`], whose definition lists the names of the
+/// fields which are visited, after renaming and without skipped fields, in
+/// the order in which they are visited. The definition's [`FieldOrder`]
+/// records whether the fields are sorted.
+///
+/// This macro requires the `derive` feature.
+///
+/// [`StructLike`]: trait.StructLike.html
+/// [`FieldOrder`]: enum.FieldOrder.html
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::{Value, Visitable};
+/// #[derive(Visitable)]
+/// #[visitable(rename_all = "camelCase")]
+/// struct Request {
+///     request_id: u64,
+///     #[visitable(rename = "path")]
+///     uri: &'static str,
+///     #[visitable(skip_if_none)]
+///     user_agent: Option<String>,
+///     #[visitable(flatten)]
+///     timing: Timing,
+/// }
+///
+/// #[derive(Visitable)]
+/// struct Timing {
+///     elapsed_ms: u64,
+///     #[visitable(skip)]
+///     started: std::time::Instant,
+/// }
+///
+/// let request = Request {
+///     request_id: 1,
+///     uri: "/",
+///     user_agent: None,
+///     timing: Timing { elapsed_ms: 3, started: std::time::Instant::now() },
+/// };
+/// assert_eq!(
+///     Value::borrowed(&request).render_debug(),
+///     r#"Request { requestId: 1, path: "/", elapsed_ms: 3 }"#,
+/// );
+/// ```
+#[cfg(feature = "derive")]
+pub use valuable_derive::Visitable;
+
+/// Methods on [`Value`] which use the visitors and buffers in this crate.
+///
+/// The core [`Value`] type is defined in `valuable-core`, which does not
+/// include serializers such as [`JsonVisit`], so these methods are provided
+/// by an extension trait.
+///
+/// [`Value`]: struct.Value.html
+/// [`JsonVisit`]: ../json/struct.JsonVisit.html
+pub trait ValueExt<'a>: Sized {
+    /// Returns a `Value` which visits `value` as the structured tree parsed
+    /// from its pretty-printed `fmt::Debug` output by [`debug::from_str`].
+    ///
+    /// Unlike [`Value::debug`], which visits the output as a single
+    /// formatted string, this allows a type which only implements `Debug` to
+    /// be recorded as structs, lists, and maps. `value` is formatted and
+    /// parsed each time the `Value` is visited; if the output cannot be
+    /// parsed, it is visited as a string.
+    ///
+    /// This method requires the `debug-tree` feature.
+    ///
+    /// [`debug::from_str`]: ../debug/fn.from_str.html
+    /// [`Value::debug`]: struct.Value.html#method.debug
+    #[cfg(feature = "debug-tree")]
+    fn debug_tree<T>(value: &'a T) -> Self
+    where
+        T: std::fmt::Debug + 'a;
 
-enum ValueKind<'a> {
-    Borrowed(&'a dyn Visitable),
-    Owned(Box<dyn Visitable + 'a>),
-    Display(&'a (dyn fmt::Display + Sync)),
-    Debug(&'a (dyn fmt::Debug + Sync)),
+    /// Renders this value into a `String` of JSON using [`JsonVisit`].
+    ///
+    /// If visiting the value fails, the output written before the error
+    /// occurred is returned.
+    ///
+    /// [`JsonVisit`]: ../json/struct.JsonVisit.html
+    fn render_json(&self) -> String;
+
+    /// Buffers this value into an [`OwnedValue`], and converts it into its
+    /// canonical form.
+    ///
+    /// See [`OwnedValue::canonicalize`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If buffering the value fails, the error is returned.
+    ///
+    /// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+    /// [`OwnedValue::canonicalize`]: ../owned/enum.OwnedValue.html#method.canonicalize
+    fn canonicalize(&self) -> Result<OwnedValue, Error>;
+
+    /// Converts this value into a sequence of OpenTelemetry attributes.
+    ///
+    /// The value is buffered into an [`OwnedValue`], which is then converted
+    /// as described in [`OwnedValue::into_key_values`].
+    ///
+    /// This method requires the `opentelemetry` feature.
+    ///
+    /// # Errors
+    ///
+    /// If buffering the value fails, the error is returned.
+    ///
+    /// [`OwnedValue`]: ../owned/enum.OwnedValue.html
+    /// [`OwnedValue::into_key_values`]: ../owned/enum.OwnedValue.html#method.into_key_values
+    #[cfg(feature = "opentelemetry")]
+    fn to_key_values(&self) -> Result<Vec<opentelemetry::KeyValue>, Error>;
 }
 
-impl<'a> Value<'a> {
-    pub fn display(value: &'a (impl fmt::Display + Sync)) -> Self {
-        Value {
-            inner: ValueKind::Display(value),
-        }
-    }
+// === impl Value ===
 
-    pub fn debug<T>(value: &'a T) -> Self
+impl<'a> ValueExt<'a> for Value<'a> {
+    #[cfg(feature = "debug-tree")]
+    fn debug_tree<T>(value: &'a T) -> Self
     where
-        T: fmt::Debug + Sync + 'a,
+        T: std::fmt::Debug + 'a,
     {
-        Value {
-            inner: ValueKind::Debug(value),
-        }
+        Value::with_visit(value, |value, visitor| {
+            crate::debug::from_str(&format!("{:#?}", value)).visit(visitor)
+        })
+    }
+
+    fn render_json(&self) -> String {
+        let mut visitor = JsonVisit::new(String::new());
+        let _ = self.visit(&mut visitor);
+        visitor.into_inner()
+    }
+
+    fn canonicalize(&self) -> Result<OwnedValue, Error> {
+        OwnedValue::from_value(self).map(OwnedValue::canonicalize)
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    fn to_key_values(&self) -> Result<Vec<opentelemetry::KeyValue>, Error> {
+        OwnedValue::from_value(self).map(OwnedValue::into_key_values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visitor::{Visit, VisitResult};
+    use std::{
+        cell::RefCell,
+        collections,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn locked_placeholders() {
+        let mutex = Mutex::new(1u64);
+        let guard = mutex.lock().unwrap();
+        assert_eq!(Value::borrowed(&mutex).render_json(), r#""<locked>""#);
+        drop(guard);
+        assert_eq!(Value::borrowed(&mutex).render_json(), "1");
+
+        let cell = RefCell::new(vec![1u64]);
+        let borrow = cell.borrow_mut();
+        assert_eq!(Value::borrowed(&cell).render_json(), r#""<borrowed>""#);
+        drop(borrow);
+        assert_eq!(Value::borrowed(&cell).render_json(), "[1]");
+    }
+
+    #[test]
+    fn atomics() {
+        use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+
+        let count = AtomicUsize::new(1);
+        count.fetch_add(2, Ordering::Relaxed);
+        let (delta, ready) = (AtomicI64::new(-4), AtomicBool::new(true));
+        let value = value!({ "count": count, "delta": delta, "ready": ready });
+        assert_eq!(value.render_json(), r#"{"count":3,"delta":-4,"ready":true}"#);
+    }
+
+    #[test]
+    fn custom_hashers() {
+        use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
+        type Hasher = BuildHasherDefault<DefaultHasher>;
+
+        let mut map = collections::HashMap::with_hasher(Hasher::default());
+        map.insert("a", 1u64);
+        assert_eq!(Value::borrowed(&map).render_json(), r#"{"a":1}"#);
+
+        let mut set = collections::HashSet::with_hasher(Hasher::default());
+        set.insert(true);
+        assert_eq!(Value::borrowed(&set).render_json(), "[true]");
+    }
+
+    #[test]
+    fn ranges() {
+        use std::ops::Bound;
+
+        assert_eq!(
+            Value::borrowed(&(1u64..5)).render_json(),
+            r#"{"start":1,"end":5}"#
+        );
+        assert_eq!(
+            format!("{:?}", Value::borrowed(&(1u64..=5))),
+            "RangeInclusive { start: 1, end: 5 }"
+        );
+        assert_eq!(format!("{:?}", Value::borrowed(&(..))), "RangeFull");
+        let bounds = vec![Bound::Included(1u64), Bound::Unbounded];
+        assert_eq!(
+            format!("{:?}", Value::borrowed(&bounds)),
+            "[Included(1), Unbounded]"
+        );
     }
 
-    pub fn borrowed(value: &'a impl Visitable) -> Self {
-        Value {
-            inner: ValueKind::Borrowed(value)
+    #[test]
+    fn rust_type_names() {
+        /// Records the type name of each value passed to `visit_any`.
+        #[derive(Default)]
+        struct TypeNames(Vec<&'static str>);
+
+        impl Visit for TypeNames {
+            fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+                self.0.push(value.rust_type_name());
+                Ok(())
+            }
         }
+
+        let shared = Arc::new(String::from("shared"));
+        let mut names = TypeNames::default();
+        Value::borrowed(&shared).visit(&mut names).unwrap();
+        Value::borrowed(&1u64).visit(&mut names).unwrap();
+        assert!(names.0[0].ends_with("String"));
+        assert_eq!(names.0[1], "u64");
     }
 
-    pub fn owned<V, B>(value: &B) -> Self
-    where
-        B: ToOwned<Owned = V>,
-        V: Visitable + Borrow<B> + 'a,
-    {
-        Value {
-            inner: ValueKind::Owned(Box::new(value.to_owned()))
+    #[test]
+    fn trait_objects_compose() {
+        fn json<T: Visitable>(value: T) -> String {
+            Value::borrowed(&value).render_json()
         }
+
+        fn json_mut<T: Visitable>(value: &mut T) -> String {
+            json(value)
+        }
+
+        let boxed: Box<dyn Visitable> = Box::new(vec![1u8, 2]);
+        let mut n = 3u64;
+        let list: Vec<Box<dyn Visitable>> = vec![boxed, Box::new(String::from("a"))];
+        assert_eq!(json(&list), r#"[[1,2],"a"]"#);
+        assert_eq!(json_mut(&mut n), "3");
+        assert_eq!(json(Box::new(&mut n)), "3");
     }
 
-    pub fn with_visit<T, F>(value: T, visit: F) -> Self
-    where
-        T: Send + 'a,
-        F: Fn(&T, &mut dyn Visit) -> VisitResult + Send + 'a,
-    {
-        struct WithVisit<T, F> {
-            visit: F,
-            value: T,
-        }
-
-        impl<T, F> Visitable for WithVisit<T, F>
-        where
-            T: Send,
-            F: Fn(&T, &mut dyn Visit) -> VisitResult + Send,
-        {
-            fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-                (self.visit)(&self.value, visitor)
-            }
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived() {
+        use crate::owned::OwnedValue;
+
+        #[derive(Visitable)]
+        struct Unit;
+
+        #[derive(Visitable)]
+        struct Pair(u8, #[visitable(skip)] Unit, &'static str);
+
+        #[derive(Visitable)]
+        #[visitable(rename_all = "kebab-case")]
+        struct Event {
+            r#type: &'static str,
+            #[visitable(skip_if_none)]
+            trace_id: Option<u64>,
+            #[visitable(flatten)]
+            extra: OwnedValue,
+        }
+
+        #[derive(Visitable)]
+        #[allow(dead_code)]
+        struct Login {
+            user: &'static str,
+            #[visitable(redact)]
+            password: std::time::Instant,
+            #[visitable(redact = "hash")]
+            email: &'static str,
         }
 
-        let with_visit = WithVisit {
-            value,
-            visit,
+        let login = Login {
+            user: "eliza",
+            password: std::time::Instant::now(),
+            email: "eliza@example.com",
         };
-        Value {
-            inner: ValueKind::Owned(Box::new(with_visit))
+        assert_eq!(
+            Value::borrowed(&login).render_json(),
+            r##"{"user":"eliza","password":"[REDACTED]","email":"#03b11d68444699cb"}"##
+        );
+        assert_eq!(Value::borrowed(&Unit).render_debug(), "Unit");
+        assert_eq!(Value::borrowed(&Pair(1, Unit, "a")).render_debug(), r#"Pair(1, "a")"#);
+
+        #[derive(Visitable)]
+        #[visitable(rename = "Req", rename_all = "camelCase")]
+        struct Request<T> {
+            request_id: T,
+            #[visitable(skip)]
+            _started: std::time::Instant,
+            #[visitable(skip_if_none, rename = "ua")]
+            user_agent: Option<&'static str>,
         }
+
+        let def = Request::<u64>::definition();
+        assert_eq!(def.name(), "Req");
+        assert_eq!(def.fields().names(), ["requestId", "ua"]);
+        assert_eq!(Login::definition().fields().names(), ["user", "password", "email"]);
+        assert_eq!(Value::borrowed(&login).type_name(), Some("Login"));
+        assert_eq!(Value::borrowed(&Box::new(Unit)).type_name(), Some("Unit"));
+
+        let mut event = Event {
+            r#type: "click",
+            trace_id: Some(7),
+            extra: OwnedValue::Map(vec![(
+                OwnedValue::Str("x".into()),
+                OwnedValue::Uint(1),
+            )]),
+        };
+        assert_eq!(
+            Value::borrowed(&event).render_json(),
+            r#"{"type":"click","trace-id":7,"x":1}"#
+        );
+
+        event.extra = OwnedValue::Uint(1);
+        let err = Value::borrowed(&event).visit(&mut JsonVisit::new(String::new()));
+        assert!(err.unwrap_err().is_unsupported());
     }
 
-    pub fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        match self.inner {
-            ValueKind::Borrowed(ref v) => v.visit(visitor),
-            ValueKind::Owned(ref v) => v.as_ref().visit(visitor),
-            ValueKind::Display(ref v) => visitor.visit_fmt(format_args!("{}", v)),
-            ValueKind::Debug(ref v) => visitor.visit_fmt(format_args!("{:?}", v)),
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_skip_if() {
+        use crate::extract::FromValue;
+
+        #[derive(Visitable, FromValue)]
+        struct Sparse {
+            id: u64,
+            #[visitable(skip_if = "Vec::is_empty")]
+            tags: Vec<String>,
+            #[visitable(skip_if_none, skip_if = "is_root")]
+            parent: Option<u64>,
+        }
+
+        fn is_root(parent: &Option<u64>) -> bool {
+            *parent == Some(0)
         }
+
+        let sparse = Sparse {
+            id: 1,
+            tags: Vec::new(),
+            parent: Some(0),
+        };
+        assert_eq!(Value::borrowed(&sparse).render_json(), r#"{"id":1}"#);
+        let extracted = Sparse::from_value(&Value::borrowed(&sparse)).unwrap();
+        assert_eq!((extracted.tags.len(), extracted.parent), (0, None));
+
+        let full = Sparse {
+            id: 2,
+            tags: vec!["a".into()],
+            parent: Some(1),
+        };
+        assert_eq!(
+            Value::borrowed(&full).render_json(),
+            r#"{"id":2,"tags":["a"],"parent":1}"#
+        );
+
+        let none = None::<u64>;
+        let map = MapValue::new()
+            .entry("id", value!(1))
+            .entry_opt("parent", none.as_ref().map(Value::borrowed));
+        assert_eq!(Value::borrowed(&map).render_json(), r#"{"id":1}"#);
     }
-}
 
-macro_rules! impl_values {
-    ( $( $visit:ident( $( $whatever:tt)+ ) ),+ ) => {
-        $(
-            impl_value!{ $visit( $( $whatever )+ ) }
-        )+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_field_order() {
+        #[derive(Visitable)]
+        struct Declared {
+            zone: u8,
+            app: u8,
+        }
+
+        #[derive(Visitable)]
+        #[visitable(sort_fields)]
+        struct Sorted {
+            zone: u8,
+            #[visitable(rename = "mid")]
+            app: u8,
+            #[visitable(skip_if_none)]
+            build: Option<u8>,
+        }
+
+        #[derive(Visitable)]
+        enum Event {
+            #[visitable(sort_fields)]
+            Start { zone: u8, app: u8 },
+        }
+
+        let declared = Declared { zone: 1, app: 2 };
+        assert_eq!(Value::borrowed(&declared).render_json(), r#"{"zone":1,"app":2}"#);
+        assert_eq!(Declared::definition().order(), FieldOrder::Declaration);
+
+        let sorted = Sorted {
+            zone: 1,
+            app: 2,
+            build: Some(3),
+        };
+        assert_eq!(
+            Value::borrowed(&sorted).render_json(),
+            r#"{"build":3,"mid":2,"zone":1}"#
+        );
+        assert_eq!(Sorted::definition().fields().names(), ["build", "mid", "zone"]);
+        assert_eq!(Sorted::definition().order(), FieldOrder::Sorted);
+
+        let event = Event::Start { zone: 1, app: 2 };
+        assert_eq!(Value::borrowed(&event).render_json(), r#"{"app":2,"zone":1}"#);
     }
-}
-macro_rules! impl_value {
-    ( $visit:ident( $( $value_ty:ty ),+ ) ) => {
-        $(
-            impl Visitable for $value_ty {
-                fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-                    visitor.$visit(*self)
-                }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_flatten_named_fields() {
+        #[derive(Visitable)]
+        struct Inner {
+            x: u8,
+        }
+
+        #[derive(Visitable)]
+        struct Outer {
+            id: u8,
+            #[visitable(flatten)]
+            inner: Inner,
+        }
+
+        /// Records the names of named fields, and rejects keys visited as
+        /// values.
+        #[derive(Default)]
+        struct Names(Vec<String>);
+
+        impl Visit for Names {
+            fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+                Ok(())
             }
-        )+
-    };
-    ( $visit:ident( $( $value_ty:ty ),+ as $as_ty:ty) ) => {
-        $(
-            impl Visitable for $value_ty {
-                fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-                    visitor.$visit(*self as $as_ty)
-                }
+
+            fn visit_kv(&mut self, _: Value<'_>, _: Value<'_>) -> VisitResult {
+                Err(Error::unsupported("keys visited as values"))
             }
-        )+
-    };
-}
 
-impl_values! {
-    visit_byte(u8),
-    visit_uint(u64),
-    visit_uint(usize, u32, u16 as u64),
-    visit_int(i64),
-    visit_int(isize, i32, i16, i8 as i64),
-    visit_float(f64, f32 as f64),
-    visit_bool(bool)
-}
+            fn visit_named_field(&mut self, name: &str, _: Value<'_>) -> VisitResult {
+                self.0.push(name.to_owned());
+                Ok(())
+            }
 
-impl<'a> Visitable for &'a str {
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_str(self)
-    }
-}
+            fn open_struct(&mut self) -> VisitResult {
+                Ok(())
+            }
 
-impl<T> Visitable for [T]
-where
-    T: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_list(self.iter().map(Value::borrowed))
-    }
-}
+            fn close_struct(&mut self) -> VisitResult {
+                Ok(())
+            }
+        }
 
-impl<T> Visitable for Vec<T>
-where
-    T: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        self.as_slice().visit(visitor)
+        let outer = Outer {
+            id: 1,
+            inner: Inner { x: 2 },
+        };
+        let mut names = Names::default();
+        Value::borrowed(&outer).visit(&mut names).unwrap();
+        assert_eq!(names.0, ["id", "x"]);
     }
-}
 
-impl<K, V> Visitable for collections::HashMap<K, V>
-where
-    K: Visitable + Hash + Eq,
-    V: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_map(self.iter().map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))))
-    }
-}
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_enums() {
+        macro_rules! shape {
+            ($(#[$meta:meta])* $name:ident) => {
+                #[derive(Visitable)]
+                $(#[$meta])*
+                enum $name {
+                    Unit,
+                    Newtype(crate::owned::OwnedValue),
+                    #[visitable(rename_all = "camelCase")]
+                    Point { x_pos: i64, y_pos: i64 },
+                }
+
+                impl $name {
+                    fn render() -> Vec<String> {
+                        let map = crate::owned::OwnedValue::Map(vec![(
+                            crate::owned::OwnedValue::Str("k".into()),
+                            crate::owned::OwnedValue::Bool(true),
+                        )]);
+                        let point = $name::Point { x_pos: 1, y_pos: -1 };
+                        let rendered = vec![
+                            Value::borrowed(&$name::Unit).render_json(),
+                            Value::borrowed(&$name::Newtype(map)).render_json(),
+                            Value::borrowed(&point).render_json(),
+                            Value::borrowed(&point).render_debug(),
+                        ];
+                        rendered
+                    }
+                }
+            };
+        }
+
+        shape!(Named);
+        shape!(#[visitable(external, rename_all = "snake_case")] External);
+        shape!(#[visitable(tag = "type")] Internal);
+        shape!(#[visitable(tag = "t", content = "c")] Adjacent);
+        shape!(#[visitable(untagged)] Untagged);
 
-impl<T> Visitable for collections::HashSet<T>
-where
-    T: Visitable + Hash + Eq,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_tuple(self.iter().map(Value::borrowed))
+        assert_eq!(
+            Named::render(),
+            [
+                "null",
+                r#"[{"k":true}]"#,
+                r#"{"xPos":1,"yPos":-1}"#,
+                "Point { xPos: 1, yPos: -1 }",
+            ]
+        );
+        assert_eq!(
+            External::render(),
+            [
+                r#""unit""#,
+                r#"{"newtype":{"k":true}}"#,
+                r#"{"point":{"xPos":1,"yPos":-1}}"#,
+                r#"{"point": { xPos: 1, yPos: -1 }}"#,
+            ]
+        );
+        assert_eq!(
+            Internal::render(),
+            [
+                r#"{"type":"Unit"}"#,
+                r#"{"type":"Newtype","k":true}"#,
+                r#"{"type":"Point","xPos":1,"yPos":-1}"#,
+                r#"Point { type: "Point", xPos: 1, yPos: -1 }"#,
+            ]
+        );
+        assert_eq!(
+            Adjacent::render(),
+            [
+                r#"{"t":"Unit"}"#,
+                r#"{"t":"Newtype","c":{"k":true}}"#,
+                r#"{"t":"Point","c":{"xPos":1,"yPos":-1}}"#,
+                r#"Adjacent { t: "Point", c: { xPos: 1, yPos: -1 } }"#,
+            ]
+        );
+        assert_eq!(Value::borrowed(&Named::Unit).type_name(), Some("Unit"));
+        assert_eq!(Value::borrowed(&External::Unit).type_name(), None);
+        assert_eq!(Value::borrowed(&Internal::Unit).type_name(), Some("Unit"));
+        assert_eq!(Value::borrowed(&Adjacent::Unit).type_name(), Some("Adjacent"));
+        assert_eq!(Value::borrowed(&Untagged::Unit).type_name(), None);
+        assert_eq!(
+            Untagged::render(),
+            [
+                "null",
+                r#"{"k":true}"#,
+                r#"{"xPos":1,"yPos":-1}"#,
+                "{ xPos: 1, yPos: -1 }",
+            ]
+        );
     }
-}
 
-impl<K, V> Visitable for collections::BTreeMap<K, V>
-where
-    K: Visitable + Eq,
-    V: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_map(self.iter().map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))))
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_generics() {
+        use std::{marker::PhantomData, time::Duration};
+
+        #[derive(Visitable)]
+        struct Wrapper<'a, T, U> {
+            inner: &'a [T],
+            #[visitable(skip)]
+            _marker: PhantomData<U>,
+        }
+
+        #[derive(Visitable)]
+        struct Tagged<T> {
+            id: u64,
+            kind: PhantomData<T>,
+        }
+
+        #[derive(Visitable)]
+        struct Timeout(#[visitable(with = "visit_duration")] Duration);
+
+        fn visit_duration(d: &Duration, visitor: &mut dyn Visit) -> VisitResult {
+            visitor.visit_float(d.as_secs_f64())
+        }
+
+        #[derive(Visitable)]
+        #[visitable(bound = "T: std::fmt::Display")]
+        struct Shown<T>(#[visitable(skip)] T, Vec<String>);
+
+        // `U` need not implement `Visitable`, since it is only used by a
+        // skipped field.
+        let wrapper = Wrapper::<_, std::time::Instant> {
+            inner: &[1, 2],
+            _marker: PhantomData,
+        };
+        assert_eq!(Value::borrowed(&wrapper).render_json(), r#"{"inner":[1,2]}"#);
+        // Nor need `T`, since `PhantomData` is visited as unit.
+        let tagged = Tagged::<std::time::Instant> {
+            id: 1,
+            kind: PhantomData,
+        };
+        assert_eq!(Value::borrowed(&tagged).render_json(), r#"{"id":1,"kind":null}"#);
+        let shown = Shown(1.5, vec!["a".to_owned()]);
+        assert_eq!(Value::borrowed(&shown).render_json(), r#"[["a"]]"#);
+        let timeout = Timeout(Duration::from_millis(1500));
+        assert_eq!(Value::borrowed(&timeout).render_debug(), "Timeout(1.5)");
     }
-}
 
-impl<T> Visitable for collections::BTreeSet<T>
-where
-    T: Visitable + Eq,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_tuple(self.iter().map(Value::borrowed))
+    #[cfg(feature = "location")]
+    #[test]
+    fn record_locations() {
+        use crate::{combinator::DepthLimit, record::Record};
+
+        /// Records the line of each visited record's location.
+        #[derive(Default)]
+        struct Lines(Vec<Option<u32>>);
+
+        impl Visit for Lines {
+            fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+                value.visit(self)
+            }
+
+            fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+                self.0.push(record.location().map(|l| l.line()));
+                Ok(())
+            }
+        }
+
+        let pairs = [("a", Value::borrowed(&1))];
+        let (record, line) = (Record::from_pairs(&pairs).located(), line!());
+        let mut visit = DepthLimit::new(Lines::default(), 2);
+        record.visit(&mut visit).unwrap();
+        Record::from_pairs(&pairs).visit(&mut visit).unwrap();
+        assert_eq!(visit.into_inner().0, vec![Some(line), None]);
     }
-}
 
-impl<T> Visitable for collections::LinkedList<T>
-where
-    T: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_list(self.iter().map(Value::borrowed))
+    #[test]
+    fn value_lists() {
+        let (id, name) = (7, "a");
+        let values = [Value::borrowed(&id), Value::borrowed(&name), value!([1.5])];
+        assert_eq!(Value::borrowed(&values).render_json(), r#"[7,"a",[1.5]]"#);
+
+        let slice: &[Value<'_>] = &values[1..];
+        assert_eq!(Value::borrowed(&slice).render_json(), r#"["a",[1.5]]"#);
+
+        let dynamic = vec![Value::borrowed(&id), Value::display(&name)];
+        assert_eq!(Value::borrowed(&dynamic).render_debug(), "[7, a]");
     }
-}
 
-impl<T> Visitable for collections::VecDeque<T>
-where
-    T: Visitable,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        visitor.visit_list(self.iter().map(Value::borrowed))
+    #[test]
+    fn map_strs() {
+        let nested = "Nested";
+        let value = value!({ "Key": ["A", 1, { "Inner": nested }] });
+        assert_eq!(
+            value.map_strs(|s| s.to_lowercase()).render_json(),
+            r#"{"Key":["a",1,{"Inner":"nested"}]}"#
+        );
     }
-}
 
-impl<T> Visitable for collections::BinaryHeap<T>
-where
-    T: Visitable + Ord,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        // NOTE: the values will *not* be visited in order --- is that something
-        // we want to guarantee?
-        visitor.visit_list(self.iter().map(Value::borrowed))
+    #[test]
+    fn annotations() {
+        #[derive(Default)]
+        struct Annotations(Vec<String>);
+
+        impl Visit for Annotations {
+            fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+                Ok(())
+            }
+
+            fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+                self.0.push(format!("{}={}", key, value));
+                Ok(())
+            }
+
+            fn named_type(&mut self, name: &str) -> VisitResult {
+                self.0.push(name.to_owned());
+                Ok(())
+            }
+        }
+
+        let bytes = value!(1024)
+            .annotate("unit", "bytes")
+            .annotate("semantic", "size");
+        let mut visit = Annotations::default();
+        bytes.visit(&mut visit).unwrap();
+        assert_eq!(visit.0, ["semantic=size", "unit=bytes"]);
+
+        // Annotations don't end the search for a type name to replace.
+        let unit = Value::with_visit((), |_, v| {
+            v.named_type("A")?;
+            v.visit_str("a")
+        });
+        let renamed = unit
+            .annotate("unit", "ms")
+            .rename_type("B");
+        let mut visit = Annotations::default();
+        renamed.visit(&mut visit).unwrap();
+        assert_eq!(visit.0, ["unit=ms", "B"]);
     }
-}
 
-impl<'a, T> Visitable for &'a T
-where
-    T: Visitable + Sync + 'a,
-{
-    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
-        (*self).visit(visitor)
+    #[test]
+    fn rename_type() {
+        let inner = OwnedValue::TupleStruct {
+            name: "Inner".to_owned(),
+            fields: vec![OwnedValue::Uint(1)],
+        };
+        let value = OwnedValue::TupleStruct {
+            name: "Outer".to_owned(),
+            fields: vec![inner.clone()],
+        };
+        assert_eq!(
+            Value::borrowed(&value).rename_type("Foo").render_debug(),
+            "Foo(Inner(1))"
+        );
+
+        // Nested type names are not renamed if the value itself has none.
+        let list = OwnedValue::List(vec![inner]);
+        assert_eq!(
+            Value::borrowed(&list).rename_type("Foo").render_debug(),
+            "[Inner(1)]"
+        );
     }
 }
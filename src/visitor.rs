@@ -1,239 +1,265 @@
-use crate::value::{Value, Visitable};
-use std::fmt;
-pub type VisitResult = Result<(), Error>;
+pub use valuable_core::visitor::*;
 
-pub struct Error {
-    // TODO
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        json::JsonVisit,
+        record::{FieldId, Record},
+        value::{Value, ValueExt, Visitable},
+    };
+    use std::fmt;
 
-/// An object-safe streaming visitor.
-pub trait Visit {
-    /// Visit an unsigned integer value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to unsigned integers may override the default
-    /// implementation.
-    fn visit_uint(&mut self, value: u64) -> VisitResult {
-        self.visit_any(&value)
-    }
+    struct Unbalanced;
 
-    /// Visit a signed integer value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to signed integers may override the default
-    /// implementation.
-    fn visit_int(&mut self, value: i64) -> VisitResult {
-        self.visit_any(&value)
+    impl Visitable for Unbalanced {
+        fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+            visitor.close_map()
+        }
     }
 
-    /// Visit a floating-point value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to floating-point values may override the
-    /// default implementation.
-    fn visit_float(&mut self, value: f64) -> VisitResult {
-        self.visit_any(&value)
+    #[test]
+    fn error_path() {
+        let bad = Unbalanced;
+        let value = value!({
+            "request": { "method": "GET", "headers": [1, 2, 3, bad] },
+        });
+        let err = value.visit(&mut JsonVisit::new(String::new())).unwrap_err();
+        assert_eq!(
+            err.path(),
+            &[
+                PathSegment::Field("request".to_owned()),
+                PathSegment::Field("headers".to_owned()),
+                PathSegment::Index(3),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "container closed without a matching open at `request.headers[3]`"
+        );
     }
 
-    /// Visit a string value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to strings may override the default
-    /// implementation.
-    fn visit_str(&mut self, value: &str) -> VisitResult {
-        self.visit_any(&value)
-    }
+    #[test]
+    fn error_modes() {
+        struct Fails;
 
-    /// Visit an unsigned 8-bit value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to byte values may override the default
-    /// implementation.
-    fn visit_byte(&mut self, value: u8) -> VisitResult {
-        self.visit_any(&value)
+        impl Visitable for Fails {
+            fn visit(&self, _: &mut dyn Visit) -> VisitResult {
+                Err(Error::unsupported("value"))
+            }
+        }
+
+        let bad = Fails;
+        let value = value!({ "a": [1, { "b": bad }], "c": 2 });
+        let render = |mode| {
+            let mut json = JsonVisit::new(String::new()).with_error_mode(mode);
+            let err = value.visit(&mut json).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "visiting a value is not supported at `a[1].b`"
+            );
+            json.into_inner()
+        };
+        assert_eq!(render(ErrorMode::Abort), r#"{"a":[1,{"b":"#);
+        assert_eq!(
+            render(ErrorMode::BestEffortClose),
+            r#"{"a":[1,{"b":null}]}"#
+        );
     }
 
-    /// Visit a boolean value.
-    ///
-    /// This defaults to calling `self.visit_any()`; implementations wishing to
-    /// provide behaviour specific to booleans may override the default
-    /// implementation.
-    fn visit_bool(&mut self, value: bool) -> VisitResult {
-        self.visit_any(&value)
+    #[test]
+    fn capabilities() {
+        use crate::{combinator::Tee, owned::{Builder, OwnedValue}};
+
+        let both = Tee::new(JsonVisit::new(String::new()), Builder::default());
+        assert!(both.capabilities().contains(Capabilities::MAPS));
+        assert!(!both.capabilities().contains(Capabilities::BYTES));
+        assert_eq!(
+            format!("{:?}", Capabilities::BYTES | Capabilities::UNIT),
+            "{BYTES, UNIT}"
+        );
+
+        let mut builder = Builder::default();
+        (&mut builder as &mut dyn Visit)
+            .visit_bytes_or_hex(&[0xde, 0xad])
+            .unwrap();
+        assert_eq!(builder.finish().unwrap(), OwnedValue::Bytes(vec![0xde, 0xad]));
     }
 
-    /// Visit an arbitrarily-typed value.
-    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult;
-
-    /// Visit a key-value association.
-    ///
-    /// The key and the value are both known to implement `Value`.
-    fn visit_kv(&mut self, k: Value, v: Value) -> VisitResult;
-
-    /// Visit an arbitrary set of pre-compiled format arguments.
-    fn visit_fmt(&mut self, args: fmt::Arguments) -> VisitResult;
-
-    /// Indicates that the next visited value is a named type with the name
-    /// `name`.
-    ///
-    /// This is called prior to visiting structs, tuple structs, and enum
-    /// variants.
-    fn named_type(&mut self, name: &str) -> VisitResult;
-
-    /// Begin visiting a key-value map.
-    ///
-    /// After this function has returned `Ok(())`, the `Visit` may expect
-    /// that all subsequent calls will be to `visit_kv` (representing the
-    /// key-value pairs in the map) until `close_map` is called.
-    ///
-    /// The visitor should perform any internal state transitions necessary to
-    /// visit a map.
-    fn open_map(&mut self) -> VisitResult;
-
-    /// Finish visiting a map.
-    ///
-    /// When this is called, the visitor should expect calls to arbitrary
-    /// `visit` methods.
-    fn close_map(&mut self) -> VisitResult;
-
-    /// Begin visiting an ordered list of values.
-    ///
-    /// When this function has returned `Ok(())` any subsequent calls to
-    /// `visit` represent the elements of the list, until `close_list` is
-    /// called.
-    ///
-    /// The visitor should perform any internal state transitions necessary to
-    /// visit a list (for example, begin serializing comma-delimited values).
-    fn open_list(&mut self) -> VisitResult;
-
-    /// Finish visiting a list.
-    fn close_list(&mut self) -> VisitResult;
-
-    /// Begin visiting a `struct`.
-    ///
-    /// After this function has returned `Ok(())`, the `Visit` may expect
-    /// that all subsequent calls will be to `visit_kv` (representing the
-    /// field names and values of the struct) until `close_struct` is called.
-    ///
-    /// The visitor should perform any internal state transitions necessary to
-    /// visit a struct.
-    fn open_struct(&mut self) -> VisitResult;
-
-    /// Finish visiting a `struct`.
-    fn close_struct(&mut self) -> VisitResult;
-
-    /// Begin visiting a tuple.
-    ///
-    ///  When this function has returned `Ok(())` any subsequent calls to
-    /// `visit` represent the elements of the tuple, until `close_tuple` is
-    /// called.
-    ///
-    /// The visitor should perform any internal state transitions necessary to
-    /// visit a tuple.
-    fn open_tuple(&mut self) -> VisitResult;
-
-    /// Finish visiting a `struct`.
-    fn close_tuple(&mut self) -> VisitResult;
-}
+    #[test]
+    fn formatting() {
+        let long = "x".repeat(1000);
+        for s in &["", "static", "short", &long[..300], &long] {
+            let formatted = args_to_str(format_args!("{}{}", s, 1), str::to_owned).unwrap();
+            assert_eq!(formatted, format!("{}1", s));
+        }
+
+        /// Keeps `Display` values, and formats `fmt::Arguments`.
+        #[derive(Default)]
+        struct Deferred(Vec<String>);
+
+        impl Visit for Deferred {
+            fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+                Err(Error::unsupported("value"))
+            }
 
-impl<'v> dyn Visit + 'v {
-    /// Visit a map of key-value data.
-    ///
-    /// This function manages calling `open_map`, visiting the key-value
-    /// data in the given iterator, and closing the map.
-    ///
-    /// This is the suggested way for `Value` implementations to visit maps,
-    /// rather than calling those functions directly, unless different behaviour
-    /// is needed.
-    pub fn visit_map<'a, I>(&mut self, i: I) -> VisitResult
-    where
-        I: IntoIterator<Item = (Value<'a>, Value<'a>)>,
-    {
-        self.open_map()?;
-        for (k, v) in i {
-            self.visit_kv(k, v)?;
+            fn visit_str(&mut self, value: &str) -> VisitResult {
+                self.0.push(format!("str {}", value));
+                Ok(())
+            }
+
+            fn visit_display(&mut self, value: &dyn fmt::Display) -> VisitResult {
+                self.0.push(format!("display {}", value));
+                Ok(())
+            }
         }
-        self.close_map()
+
+        let mut visit = crate::combinator::DepthLimit::new(Deferred::default(), 1);
+        Value::display(&1.5).visit(&mut visit).unwrap();
+        Value::debug(&"a").visit(&mut visit).unwrap();
+        assert_eq!(visit.into_inner().0, ["display 1.5", "str \"a\""]);
     }
 
-    /// Visit an ordered list of `Value`s.
-    ///
-    /// This function manages calling `open_list`, visiting the list elements
-    /// in the given iterator, and closing the list.
-    ///
-    /// This is the suggested way for `Value` implementations to visit lists,
-    /// rather than calling those functions directly, unless different behaviour
-    /// is needed.
-    pub fn visit_list<'a, I>(&mut self, i: I) -> VisitResult
-    where
-        I: IntoIterator<Item = Value<'a>>,
-    {
-        self.open_list()?;
-        for v in i {
-            v.visit(self)?;
+    #[test]
+    fn integer_widths() {
+        /// Records the width of each visited unsigned integer.
+        #[derive(Default)]
+        struct Widths(Vec<&'static str>);
+
+        impl Visit for Widths {
+            fn visit_uint(&mut self, _: u64) -> VisitResult {
+                self.0.push("u64");
+                Ok(())
+            }
+
+            fn visit_u16(&mut self, _: u16) -> VisitResult {
+                self.0.push("u16");
+                Ok(())
+            }
+
+            fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+                value.visit(self)
+            }
+
+            fn open_list(&mut self) -> VisitResult {
+                Ok(())
+            }
+
+            fn close_list(&mut self) -> VisitResult {
+                Ok(())
+            }
         }
-        self.close_list()
+
+        // `u32` is widened by the default `visit_u32`, while widths are
+        // preserved through visitor wrappers.
+        let value = value!([1u16, 2u32, 3u64]);
+        let mut widths = crate::combinator::DepthLimit::new(Widths::default(), 2);
+        value.visit(&mut widths).unwrap();
+        assert_eq!(widths.into_inner().0, vec!["u16", "u64", "u64"]);
+
+        // Containers the visitor doesn't implement are unsupported.
+        let err = value!({ "a": 1u16 })
+            .visit(&mut Widths::default())
+            .unwrap_err();
+        assert!(err.is_unsupported());
+        assert_eq!(err.to_string(), "visiting a map is not supported");
     }
 
-    /// Visit a `struct` of `Value`s, given the struct's `name` and an
-    /// iterator over its `fields`.
-    ///
-    /// This function manages calling `open_struct`, visiting the struct's
-    /// fields, and closing the struct.
-    ///
-    /// This is the suggested way for `Value` implementations to visit structs,
-    /// rather than calling those functions directly, unless different behaviour
-    /// is needed.
-    pub fn visit_struct<'a, I>(&mut self, name: &str, fields: I) -> VisitResult
-    where
-        I: IntoIterator<Item = (&'a str, Value<'a>)>,
-    {
-        self.named_type(name);
-        self.open_struct()?;
-        for (name, v) in fields {
-            self.visit_kv(Value::borrowed(&name), v)?;
+    #[test]
+    fn error_chain() {
+        #[derive(Debug)]
+        struct Config {
+            source: std::num::ParseIntError,
         }
-        self.close_struct()
-    }
 
-    /// Visit a tuple.
-    ///
-    /// This function manages calling `open_tuple`, visiting the tuple's
-    /// fields, and closing the tuple.
-    ///
-    /// This is the suggested way for `Value` implementations to visit tuples,
-    /// rather than calling those functions directly, unless different behaviour
-    /// is needed.
-    pub fn visit_tuple<'a, I>(&mut self, i: I) -> VisitResult
-    where
-        I: IntoIterator<Item = Value<'a>>,
-    {
-        self.open_tuple()?;
-        for v in i {
-            v.visit(self)?;
+        impl fmt::Display for Config {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("invalid config")
+            }
         }
-        self.close_tuple()
+
+        impl std::error::Error for Config {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.source)
+            }
+        }
+
+        let render = |error: &dyn Fn(&mut dyn Visit) -> VisitResult| {
+            let mut json = JsonVisit::new(String::new());
+            error(&mut json).unwrap();
+            json.into_inner()
+        };
+
+        // Only the type of the outermost error is known.
+        let error = Config {
+            source: "".parse::<u32>().unwrap_err(),
+        };
+        assert_eq!(
+            render(&|visitor| visitor.visit_error_chain(&error)),
+            concat!(
+                r#"[{"message":"invalid config","#,
+                r#""type":"valuable::visitor::tests::error_chain::Config"},"#,
+                r#"{"message":"cannot parse integer from empty string"}]"#,
+            )
+        );
+
+        let error = std::io::Error::new(std::io::ErrorKind::NotFound, "no config");
+        assert_eq!(
+            render(&|visitor| visitor.visit_error_chain(&error)),
+            r#"[{"message":"no config","type":"std::io::error::Error"}]"#
+        );
     }
 
+    #[test]
+    fn field_ids() {
+        use crate::{combinator::Truncate, record::FieldSet};
 
-    /// Visit a tuple `struct` of `Value`s, given the struct's `name` and an
-    /// iterator over its `fields`.
-    ///
-    /// This function manages calling `open_struct`, visiting the struct's
-    /// fields, and closing the struct.
-    ///
-    /// This is the suggested way for `Value` implementations to visit structs,
-    /// rather than calling those functions directly, unless different behaviour
-    /// is needed.
-    pub fn visit_tuple_struct<'a, I>(&mut self, name: &str, fields: I) -> VisitResult
-    where
-        I: IntoIterator<Item = Value<'a>>,
-    {
-        self.named_type(name);
-        self.open_tuple()?;
-        for v in fields {
-            v.visit(self)?;
+        /// Records the index of each field visited with `visit_field`, and
+        /// the JSON of each key visited with `visit_kv`.
+        #[derive(Default)]
+        struct Columns(Vec<String>);
+
+        impl Visit for Columns {
+            fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+                value.visit(self)
+            }
+
+            fn visit_kv(&mut self, k: Value<'_>, _: Value<'_>) -> VisitResult {
+                self.0.push(k.render_json());
+                Ok(())
+            }
+
+            fn visit_field(&mut self, field: FieldId, _: Value<'_>) -> VisitResult {
+                self.0.push(field.index().to_string());
+                Ok(())
+            }
+
+            fn open_map(&mut self) -> VisitResult {
+                Ok(())
+            }
+
+            fn close_map(&mut self) -> VisitResult {
+                Ok(())
+            }
         }
-        self.close_tuple()
+
+        static FIELDS: FieldSet = FieldSet::new(&["a", "b"]);
+        assert_eq!(FIELDS.field("b"), FIELDS.fields().nth(1));
+
+        let values = [Value::borrowed(&1), Value::borrowed(&2)];
+        let record = FIELDS.record(&values);
+        let pairs = [("a", Value::borrowed(&1))];
+
+        // `FieldId`s are preserved through visitor wrappers, unless the
+        // record's fields are truncated.
+        let mut columns = Truncate::new(Columns::default());
+        record.visit(&mut columns).unwrap();
+        Record::from_pairs(&pairs).visit(&mut columns).unwrap();
+        let mut columns = columns.with_max_map_entries(1);
+        record.visit(&mut columns).unwrap();
+        assert_eq!(
+            columns.into_inner().0,
+            vec!["0", "1", "\"a\"", "\"a\"", "\"…\""]
+        );
     }
 }
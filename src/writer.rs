@@ -0,0 +1,227 @@
+//! Writing output to I/O streams.
+//!
+//! The visitors in this crate which write output, such as [`JsonVisit`] and
+//! [`CsvVisit`], write to a `fmt::Write`. An [`IoWriter`] adapts any
+//! `io::Write` (such as a file, a socket, or standard output) into a
+//! `fmt::Write`, buffering the output so that each small write made while
+//! visiting a value does not become a system call.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::{json::JsonVisit, value, writer::{FlushPolicy, IoWriter}};
+//! let writer = IoWriter::new(Vec::new()).with_flush_policy(FlushPolicy::EachLine);
+//! let mut visit = JsonVisit::new(writer);
+//! value!({ "id": 1 }).visit(&mut visit).unwrap();
+//!
+//! let bytes = visit.into_inner().finish().unwrap();
+//! assert_eq!(bytes, br#"{"id":1}"#);
+//! ```
+//!
+//! [`JsonVisit`]: ../json/struct.JsonVisit.html
+//! [`CsvVisit`]: ../csv/struct.CsvVisit.html
+//! [`IoWriter`]: struct.IoWriter.html
+use crate::visitor::Error;
+use std::{
+    fmt,
+    io::{self, Write as _},
+};
+
+/// When an [`IoWriter`] writes its buffered output to the underlying
+/// stream.
+///
+/// Regardless of the policy, buffered output is written whenever the buffer
+/// is full, and when the writer is explicitly [flushed] or [finished].
+///
+/// [`IoWriter`]: struct.IoWriter.html
+/// [flushed]: struct.IoWriter.html#method.flush
+/// [finished]: struct.IoWriter.html#method.finish
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FlushPolicy {
+    /// Buffered output is only written when the buffer is full.
+    WhenFull,
+    /// Buffered output is also written, and the stream flushed, each time a
+    /// newline is written. This suits line-oriented formats, so that each
+    /// line is complete as soon as it is written.
+    EachLine,
+}
+
+/// Adapts an `io::Write` into a buffered `fmt::Write`.
+///
+/// `fmt::Write` cannot return an `io::Error`, so if writing to the stream
+/// fails, the error is stored and a `fmt::Error` is returned; visiting a
+/// value with a visitor writing to the `IoWriter` then fails with a
+/// formatting error. The stored error is returned, converted into a
+/// [`visitor::Error`], by the next call to [`flush`] or [`finish`], and no
+/// more output is written until then.
+///
+/// Output which is still buffered when an `IoWriter` is dropped is written,
+/// but any error writing it is ignored, so [`finish`] should be called once
+/// all values have been written.
+///
+/// [`visitor::Error`]: ../visitor/struct.Error.html
+/// [`flush`]: #method.flush
+/// [`finish`]: #method.finish
+pub struct IoWriter<W: io::Write> {
+    inner: io::BufWriter<W>,
+    policy: FlushPolicy,
+    error: Option<io::Error>,
+}
+
+// === impl IoWriter ===
+
+impl<W: io::Write> IoWriter<W> {
+    /// Returns a new `IoWriter` which writes to `inner`, with the default
+    /// buffer capacity (currently 8 KiB).
+    ///
+    /// By default, the [`FlushPolicy`] is [`FlushPolicy::WhenFull`].
+    ///
+    /// [`FlushPolicy`]: enum.FlushPolicy.html
+    /// [`FlushPolicy::WhenFull`]: enum.FlushPolicy.html#variant.WhenFull
+    pub fn new(inner: W) -> Self {
+        Self::from_buf(io::BufWriter::new(inner))
+    }
+
+    /// Returns a new `IoWriter` which writes to `inner`, buffering up to
+    /// `capacity` bytes of output.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self::from_buf(io::BufWriter::with_capacity(capacity, inner))
+    }
+
+    fn from_buf(inner: io::BufWriter<W>) -> Self {
+        Self {
+            inner,
+            policy: FlushPolicy::WhenFull,
+            error: None,
+        }
+    }
+
+    /// Sets when buffered output is written to the underlying stream.
+    pub fn with_flush_policy(self, policy: FlushPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Borrows the underlying stream.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Mutably borrows the underlying stream.
+    ///
+    /// Writing to the stream directly may interleave with output which is
+    /// still buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// Returns the output which is buffered but not yet written.
+    pub fn buffer(&self) -> &[u8] {
+        self.inner.buffer()
+    }
+
+    /// Writes any buffered output to the underlying stream, and flushes it.
+    ///
+    /// # Errors
+    ///
+    /// If an earlier write failed, that error is returned (and cleared, so
+    /// that writing may be retried). Otherwise, if writing or flushing
+    /// fails, the error is returned.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+        self.inner.flush().map_err(Error::from)
+    }
+
+    /// Flushes the `IoWriter`, returning the underlying stream.
+    ///
+    /// # Errors
+    ///
+    /// As with [`flush`], if an earlier write failed, or writing the
+    /// buffered output fails, the error is returned, and the stream is
+    /// dropped.
+    ///
+    /// [`flush`]: #method.flush
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush()?;
+        self.inner
+            .into_inner()
+            .map_err(|error| error.into_error().into())
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.error.is_some() {
+            return Err(fmt::Error);
+        }
+        let mut result = self.inner.write_all(s.as_bytes());
+        if result.is_ok() && self.policy == FlushPolicy::EachLine && s.contains('\n') {
+            result = self.inner.flush();
+        }
+        result.map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+impl<W: io::Write + fmt::Debug> fmt::Debug for IoWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoWriter")
+            .field("inner", self.get_ref())
+            .field("buffered", &self.buffer().len())
+            .field("policy", &self.policy)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json::JsonVisit, value::Value};
+
+    /// A stream which accepts `remaining` bytes, then fails.
+    #[derive(Debug)]
+    struct Short {
+        written: Vec<u8>,
+        remaining: usize,
+    }
+
+    impl io::Write for Short {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.remaining {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            self.remaining -= buf.len();
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn policies_and_errors() {
+        let mut writer = IoWriter::new(Vec::new()).with_flush_policy(FlushPolicy::EachLine);
+        fmt::Write::write_str(&mut writer, "a").unwrap();
+        assert_eq!(writer.get_ref(), b"");
+        fmt::Write::write_str(&mut writer, "b\n").unwrap();
+        assert_eq!(writer.get_ref(), b"ab\n");
+
+        let short = Short {
+            written: Vec::new(),
+            remaining: 4,
+        };
+        let mut visit = JsonVisit::new(IoWriter::with_capacity(2, short));
+        let value = vec!["abc", "def"];
+        let error = Value::borrowed(&value).visit(&mut visit).unwrap_err();
+        assert!(!error.is_unsupported());
+
+        let error = visit.into_inner().finish().unwrap_err();
+        assert!(error.to_string().contains("full"), "{}", error);
+    }
+}
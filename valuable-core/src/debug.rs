@@ -0,0 +1,460 @@
+use crate::{
+    float::{FloatFormat, NonFinite},
+    pool,
+    value::{Value, Visitable},
+    visitor::{Capabilities, Error, Visit, VisitResult},
+};
+use std::{
+    fmt::{self, Write as _},
+    mem,
+};
+
+/// A `Visit` implementation that writes values in the style of Rust's
+/// `fmt::Debug` output.
+///
+/// Named structs are written as `Name { field: value }`, named tuple structs
+/// as `Name(value)`, lists as `[value]`, tuples as `(value,)`, and maps as
+/// `{key: value}`. Strings are quoted and escaped as they would be by
+/// `fmt::Debug`, while formatted values (such as those constructed with
+/// `Value::display` or `Value::debug`) are written as-is. Floats are written
+/// as by `fmt::Debug` unless a different format is set with
+/// [`with_float_format`].
+///
+/// By default, values are written on a single line, as with `{:?}`. The
+/// [`FormatOptions`] set with [`with_options`] may instead write them over
+/// several indented lines, as with `{:#?}`, and pad or round numbers, as
+/// `fmt::Debug` does when a width or precision is given.
+///
+/// [`with_float_format`]: #method.with_float_format
+/// [`FormatOptions`]: struct.FormatOptions.html
+/// [`with_options`]: #method.with_options
+pub struct DebugVisit<W> {
+    writer: W,
+    stack: Vec<Frame>,
+    /// Set while visiting the value half of a key-value pair, so that no
+    /// separator is written before it.
+    in_kv_value: bool,
+    /// Set when a type name has been written for the next value.
+    named: bool,
+    /// Set when strings should be written without quoting, as struct field
+    /// names are.
+    raw_strs: bool,
+    float_format: FloatFormat,
+    options: FormatOptions,
+    /// The nesting depth of the first value, when writing a map key.
+    base_depth: usize,
+}
+
+/// Options for formatting values, corresponding to the flags and
+/// parameters of a `fmt::Formatter`.
+///
+/// These are usually taken from the `Formatter` a value is being written
+/// to, using [`from_formatter`], so that `{:#?}` and `{:8.2?}` affect the
+/// output as they would for types implementing `fmt::Debug`:
+///
+/// - when `alternate` is set, containers are written with one item per
+///   line, indented by four spaces, with a trailing comma after each item,
+/// - when a `width` is set, numbers are padded with spaces to at least that
+///   width, aligned to the right, and booleans and unit values are padded
+///   aligned to the left. Strings are not padded,
+/// - when a `precision` is set, floats are written with that many digits
+///   after the decimal point.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value;
+/// let value = value!({ "x": 1.5, "ok": true });
+/// assert_eq!(format!("{:?}", value), r#"{"x": 1.5, "ok": true}"#);
+/// assert_eq!(format!("{:.2?}", value), r#"{"x": 1.50, "ok": true}"#);
+/// assert_eq!(format!("{:#?}", value), "{\n    \"x\": 1.5,\n    \"ok\": true,\n}");
+/// ```
+///
+/// [`from_formatter`]: #method.from_formatter
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FormatOptions {
+    alternate: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+struct Frame {
+    kind: Container,
+    named: bool,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Container {
+    Map,
+    List,
+    Struct,
+    Tuple,
+}
+
+// === impl DebugVisit ===
+
+impl<W: fmt::Write> DebugVisit<W> {
+    /// Returns a new `DebugVisit` that writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            stack: Vec::new(),
+            in_kv_value: false,
+            named: false,
+            raw_strs: false,
+            float_format: FloatFormat::new().with_non_finite(NonFinite::Literal),
+            options: FormatOptions::new(),
+            base_depth: 0,
+        }
+    }
+
+    /// Sets the [`FormatOptions`] used to write values.
+    ///
+    /// A precision set in the options takes precedence over the precision
+    /// of the float format.
+    ///
+    /// [`FormatOptions`]: struct.FormatOptions.html
+    pub fn with_options(self, options: FormatOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Sets how floats are written.
+    ///
+    /// The [`NonFinite::Null`] policy writes NaN and infinities as `()`.
+    ///
+    /// [`NonFinite::Null`]: ../float/enum.NonFinite.html#variant.Null
+    pub fn with_float_format(self, float_format: FloatFormat) -> Self {
+        Self {
+            float_format,
+            ..self
+        }
+    }
+
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Mutably borrows the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `DebugVisit`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes a separator before a value, if one is needed.
+    ///
+    /// Returns `true` if a type name was already written for this value.
+    fn begin_value(&mut self) -> Result<bool, Error> {
+        if mem::replace(&mut self.named, false) {
+            return Ok(true);
+        }
+        if mem::replace(&mut self.in_kv_value, false) {
+            return Ok(false);
+        }
+        let depth = self.base_depth + self.stack.len();
+        if let Some(frame) = self.stack.last_mut() {
+            frame.len += 1;
+            if self.options.alternate {
+                match frame.len {
+                    1 if frame.named && frame.kind == Container::Struct => {
+                        self.writer.write_str(" {")?
+                    }
+                    1 if frame.named && frame.kind == Container::Tuple => {
+                        self.writer.write_char('(')?
+                    }
+                    1 => {}
+                    _ => self.writer.write_char(',')?,
+                }
+                newline(&mut self.writer, depth)?;
+                return Ok(false);
+            }
+            match frame.len {
+                // Named structs and tuple structs write their opening
+                // delimiter lazily, so that empty ones are written as just
+                // their name.
+                1 if frame.named && frame.kind == Container::Struct => {
+                    self.writer.write_str(" { ")?
+                }
+                1 if frame.named && frame.kind == Container::Tuple => {
+                    self.writer.write_char('(')?
+                }
+                1 => {}
+                _ => self.writer.write_str(", ")?,
+            }
+        }
+        Ok(false)
+    }
+
+    fn scalar(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        if self.begin_value()? {
+            write!(self.writer, "({})", args)?;
+        } else {
+            self.writer.write_fmt(args)?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, kind: Container) -> VisitResult {
+        let named = self.begin_value()?;
+        match kind {
+            Container::Map => self.writer.write_char('{')?,
+            Container::List => self.writer.write_char('[')?,
+            Container::Struct if !named && self.options.alternate => self.writer.write_char('{')?,
+            Container::Struct if !named => self.writer.write_str("{ ")?,
+            Container::Tuple if !named => self.writer.write_char('(')?,
+            _ => {}
+        }
+        self.stack.push(Frame {
+            kind,
+            named,
+            len: 0,
+        });
+        Ok(())
+    }
+
+    fn close(&mut self, kind: Container) -> VisitResult {
+        let frame = match self.stack.pop() {
+            Some(frame) if frame.kind == kind => frame,
+            _ => return Err(Error::unbalanced()),
+        };
+        if self.options.alternate {
+            if frame.len > 0 {
+                self.writer.write_char(',')?;
+                newline(&mut self.writer, self.base_depth + self.stack.len())?;
+            }
+            // Named structs and tuple structs with no fields are written as
+            // just their name.
+            match kind {
+                Container::Map => self.writer.write_char('}')?,
+                Container::List => self.writer.write_char(']')?,
+                Container::Struct | Container::Tuple if frame.named && frame.len == 0 => {}
+                Container::Struct => self.writer.write_char('}')?,
+                Container::Tuple => self.writer.write_char(')')?,
+            }
+            return Ok(());
+        }
+        match kind {
+            Container::Map => self.writer.write_char('}')?,
+            Container::List => self.writer.write_char(']')?,
+            Container::Struct if frame.named && frame.len == 0 => {}
+            Container::Struct if frame.len == 0 => self.writer.write_char('}')?,
+            Container::Struct => self.writer.write_str(" }")?,
+            Container::Tuple if frame.named && frame.len == 0 => {}
+            Container::Tuple if !frame.named && frame.len == 1 => self.writer.write_str(",)")?,
+            Container::Tuple => self.writer.write_char(')')?,
+        }
+        Ok(())
+    }
+}
+
+impl<W: fmt::Write> Visit for DebugVisit<W> {
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        let width = self.options.width.unwrap_or(0);
+        self.scalar(format_args!("{:1$?}", value, width))
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        let width = self.options.width.unwrap_or(0);
+        self.scalar(format_args!("{:1$?}", value, width))
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        let named = self.begin_value()?;
+        if named {
+            self.writer.write_char('(')?;
+        }
+        let format = match self.options.precision {
+            Some(precision) => self.float_format.with_precision(precision),
+            None => self.float_format,
+        };
+        match self.options.width {
+            Some(width) => pool::with_string(|buf| {
+                format.write(buf, value, "()", |w, s| write!(w, "{:?}", s))?;
+                write!(self.writer, "{:>1$}", buf, width)?;
+                Ok::<_, Error>(())
+            })?,
+            None => format.write(&mut self.writer, value, "()", |w, s| write!(w, "{:?}", s))?,
+        }
+        if named {
+            self.writer.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        if self.raw_strs {
+            self.scalar(format_args!("{}", value))
+        } else {
+            self.scalar(format_args!("{:?}", value))
+        }
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        let width = self.options.width.unwrap_or(0);
+        self.scalar(format_args!("{:1$?}", value, width))
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        // A named unit value (such as a unit struct or enum variant) is
+        // written as just its name.
+        if !self.begin_value()? {
+            let width = self.options.width.unwrap_or(0);
+            write!(self.writer, "{:1$?}", (), width)?;
+        }
+        Ok(())
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        self.begin_value()?;
+        let in_struct = self
+            .stack
+            .last()
+            .map(|frame| frame.kind == Container::Struct)
+            .unwrap_or(false);
+        // Always use a trait object for the key's writer, so that visiting
+        // keys doesn't recursively instantiate `DebugVisit<&mut W>`.
+        let writer: &mut dyn fmt::Write = &mut self.writer;
+        let mut key = DebugVisit::new(writer)
+            .with_float_format(self.float_format)
+            .with_options(self.options);
+        key.raw_strs = in_struct;
+        key.base_depth = self.base_depth + self.stack.len();
+        k.visit(&mut key)?;
+        self.writer.write_str(": ")?;
+        self.in_kv_value = true;
+        v.visit(self)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments<'_>) -> VisitResult {
+        self.scalar(args)
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        self.begin_value()?;
+        self.writer.write_str(name)?;
+        self.named = true;
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::NAMED_TYPES
+            | Capabilities::UNIT
+            | Capabilities::MAPS
+            | Capabilities::LISTS
+            | Capabilities::STRUCTS
+            | Capabilities::TUPLES
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        self.open(Container::Map)
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        self.close(Container::Map)
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        self.open(Container::List)
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        self.close(Container::List)
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        self.open(Container::Struct)
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        self.close(Container::Struct)
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        self.open(Container::Tuple)
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        self.close(Container::Tuple)
+    }
+}
+
+fn newline<W: fmt::Write>(writer: &mut W, depth: usize) -> fmt::Result {
+    writer.write_char('\n')?;
+    for _ in 0..depth {
+        writer.write_str("    ")?;
+    }
+    Ok(())
+}
+
+impl<W: fmt::Debug> fmt::Debug for DebugVisit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugVisit")
+            .field("writer", &self.writer)
+            .field("depth", &self.stack.len())
+            .finish()
+    }
+}
+
+// === impl FormatOptions ===
+
+impl FormatOptions {
+    /// Returns `FormatOptions` with no flags or parameters set, as for
+    /// `{:?}`.
+    pub const fn new() -> Self {
+        Self {
+            alternate: false,
+            width: None,
+            precision: None,
+        }
+    }
+
+    /// Returns the options set on `f`.
+    pub fn from_formatter(f: &fmt::Formatter<'_>) -> Self {
+        Self {
+            alternate: f.alternate(),
+            width: f.width(),
+            precision: f.precision(),
+        }
+    }
+
+    /// Sets whether values are written over several indented lines.
+    pub fn with_alternate(self, alternate: bool) -> Self {
+        Self { alternate, ..self }
+    }
+
+    /// Sets the minimum width of numbers, booleans, and unit values.
+    pub fn with_width(self, width: Option<usize>) -> Self {
+        Self { width, ..self }
+    }
+
+    /// Sets the number of digits written after the decimal point of floats.
+    pub fn with_precision(self, precision: Option<usize>) -> Self {
+        Self { precision, ..self }
+    }
+
+    /// Returns `true` if values are written over several indented lines.
+    pub fn alternate(&self) -> bool {
+        self.alternate
+    }
+
+    /// Returns the minimum width of numbers, booleans, and unit values, if
+    /// one is set.
+    pub fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    /// Returns the number of digits written after the decimal point of
+    /// floats, if set.
+    pub fn precision(&self) -> Option<usize> {
+        self.precision
+    }
+}
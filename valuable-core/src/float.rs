@@ -0,0 +1,209 @@
+//! Float formatting options.
+//!
+//! Different consumers of serialized output disagree about how floating
+//! point numbers should be written: some can't parse NaN or infinities, some
+//! want a fixed number of decimal places, and some require (or forbid)
+//! scientific notation. A [`FloatFormat`] describes how the built-in
+//! serializers (such as [`JsonVisit`] and [`DebugVisit`]) write floats, so
+//! that they can be adapted to a backend's requirements.
+//!
+//! [`FloatFormat`]: struct.FloatFormat.html
+//! [`JsonVisit`]: ../json/struct.JsonVisit.html
+//! [`DebugVisit`]: ../debug/struct.DebugVisit.html
+use crate::visitor::Error;
+use std::fmt;
+
+/// Describes how floating point numbers are written.
+///
+/// By default, finite floats are written in the shortest form that
+/// round-trips (as by `fmt::Debug`), which uses scientific notation for very
+/// large and very small numbers, and NaN and infinities are written as the
+/// format's null value.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{float::{FloatFormat, NonFinite}, json::JsonVisit, value};
+/// let format = FloatFormat::new()
+///     .with_non_finite(NonFinite::Str)
+///     .with_precision(2)
+///     .with_scientific(1e-3, 1e6);
+/// let mut visit = JsonVisit::new(String::new()).with_float_format(format);
+/// value!([1.0 / 3.0, 12345678.9, std::f64::NEG_INFINITY]).visit(&mut visit).unwrap();
+/// assert_eq!(visit.into_inner(), r#"[0.33,1.23e7,"-Infinity"]"#);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FloatFormat {
+    non_finite: NonFinite,
+    precision: Option<usize>,
+    scientific: Option<(f64, f64)>,
+}
+
+/// How NaN and infinite floats are written.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NonFinite {
+    /// Fail with an [unsupported] error.
+    ///
+    /// [unsupported]: ../visitor/struct.Error.html#method.is_unsupported
+    Error,
+    /// Write the format's null value, as a unit value would be written.
+    Null,
+    /// Write a string: `"NaN"`, `"Infinity"`, or `"-Infinity"`.
+    Str,
+    /// Write Rust's representation (`NaN`, `inf`, or `-inf`) unquoted.
+    ///
+    /// This is not valid in all formats; in particular, it is not valid
+    /// JSON.
+    Literal,
+}
+
+// === impl FloatFormat ===
+
+impl FloatFormat {
+    /// Returns the default `FloatFormat`.
+    pub const fn new() -> Self {
+        Self {
+            non_finite: NonFinite::Null,
+            precision: None,
+            scientific: None,
+        }
+    }
+
+    /// Sets how NaN and infinities are written.
+    pub fn with_non_finite(self, non_finite: NonFinite) -> Self {
+        Self { non_finite, ..self }
+    }
+
+    /// Writes finite floats with exactly `precision` digits after the
+    /// decimal point.
+    ///
+    /// Unless [`with_scientific`] is also used, floats written with a fixed
+    /// precision never use scientific notation.
+    ///
+    /// [`with_scientific`]: #method.with_scientific
+    pub fn with_precision(self, precision: usize) -> Self {
+        Self {
+            precision: Some(precision),
+            ..self
+        }
+    }
+
+    /// Writes finite floats whose magnitude is less than `min` or at least
+    /// `max` in scientific notation, and all others in decimal notation.
+    ///
+    /// Zero is always written in decimal notation.
+    pub fn with_scientific(self, min: f64, max: f64) -> Self {
+        Self {
+            scientific: Some((min, max)),
+            ..self
+        }
+    }
+
+    /// Returns how NaN and infinities are written.
+    pub fn non_finite(&self) -> NonFinite {
+        self.non_finite
+    }
+
+    /// Writes `value` to `writer` according to this format.
+    ///
+    /// Finite floats are written using the configured precision and
+    /// notation. NaN and infinities are handled according to the
+    /// [`NonFinite`] policy, with `null` written as the format's null value.
+    /// The `Str` policy writes its string using `write_str`, which should
+    /// quote and escape it for the format.
+    ///
+    /// [`NonFinite`]: enum.NonFinite.html
+    pub fn write<W>(
+        &self,
+        writer: &mut W,
+        value: f64,
+        null: &str,
+        write_str: impl FnOnce(&mut W, &str) -> fmt::Result,
+    ) -> Result<(), Error>
+    where
+        W: fmt::Write + ?Sized,
+    {
+        if value.is_finite() {
+            self.write_finite(writer, value)?;
+            return Ok(());
+        }
+        match self.non_finite {
+            NonFinite::Error => return Err(Error::unsupported("non-finite float")),
+            NonFinite::Null => writer.write_str(null)?,
+            NonFinite::Str if value.is_nan() => write_str(writer, "NaN")?,
+            NonFinite::Str if value > 0.0 => write_str(writer, "Infinity")?,
+            NonFinite::Str => write_str(writer, "-Infinity")?,
+            NonFinite::Literal => write!(writer, "{:?}", value)?,
+        }
+        Ok(())
+    }
+
+    fn write_finite<W: fmt::Write + ?Sized>(&self, writer: &mut W, value: f64) -> fmt::Result {
+        let scientific = match self.scientific {
+            Some((min, max)) => {
+                let abs = value.abs();
+                abs != 0.0 && (abs < min || abs >= max)
+            }
+            None if self.precision.is_some() => false,
+            // Use `fmt::Debug`'s own choice of notation.
+            None => return write!(writer, "{:?}", value),
+        };
+        match (scientific, self.precision) {
+            (true, Some(precision)) => write!(writer, "{:.*e}", precision, value),
+            (true, None) => write!(writer, "{:e}", value),
+            (false, Some(precision)) => write!(writer, "{:.*}", precision, value),
+            (false, None) => {
+                // `fmt::Display` never uses scientific notation, but writes
+                // integral floats without a fractional part.
+                write!(writer, "{}", value)?;
+                if value.fract() == 0.0 {
+                    writer.write_str(".0")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for FloatFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write as _;
+
+    fn render(format: FloatFormat, value: f64) -> Result<String, Error> {
+        let mut buf = String::new();
+        format.write(&mut buf, value, "null", |w, s| write!(w, "'{}'", s))?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn formats() {
+        let default = FloatFormat::new();
+        assert_eq!(render(default, 1.0).unwrap(), "1.0");
+        assert_eq!(render(default, 1e20).unwrap(), "1e20");
+        assert_eq!(render(default, f64::NAN).unwrap(), "null");
+
+        let sci = FloatFormat::new().with_scientific(0.01, 1e3);
+        assert_eq!(render(sci, 1e20).unwrap(), "1e20");
+        assert_eq!(render(sci, 1e2).unwrap(), "100.0");
+        assert_eq!(render(sci, -0.001).unwrap(), "-1e-3");
+        assert_eq!(render(sci, 0.0).unwrap(), "0.0");
+        assert_eq!(render(sci.with_precision(1), 1234.0).unwrap(), "1.2e3");
+
+        let fixed = FloatFormat::new().with_precision(3);
+        assert_eq!(render(fixed, 1e20).unwrap(), "100000000000000000000.000");
+
+        let strs = FloatFormat::new().with_non_finite(NonFinite::Str);
+        assert_eq!(render(strs, f64::INFINITY).unwrap(), "'Infinity'");
+        let literal = strs.with_non_finite(NonFinite::Literal);
+        assert_eq!(render(literal, f64::NEG_INFINITY).unwrap(), "-inf");
+        let err = render(literal.with_non_finite(NonFinite::Error), f64::NAN).unwrap_err();
+        assert!(err.is_unsupported());
+    }
+}
@@ -0,0 +1,5 @@
+//! `Visitable` implementations for types from other crates.
+#[cfg(feature = "serde_json")]
+mod serde_json;
+#[cfg(feature = "toml")]
+mod toml;
@@ -0,0 +1,37 @@
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use serde_json::{Number, Value as JsonValue};
+
+impl Visitable for JsonValue {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self {
+            JsonValue::Null => visitor.visit_unit(),
+            JsonValue::Bool(v) => visitor.visit_bool(*v),
+            JsonValue::Number(n) => n.visit(visitor),
+            JsonValue::String(v) => visitor.visit_str(v),
+            JsonValue::Array(vs) => visitor.visit_list(vs.iter().map(Value::borrowed)),
+            JsonValue::Object(map) => visitor.visit_map(
+                map.iter()
+                    .map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))),
+            ),
+        }
+    }
+}
+
+impl Visitable for Number {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if let Some(n) = self.as_u64() {
+            visitor.visit_uint(n)
+        } else if let Some(n) = self.as_i64() {
+            visitor.visit_int(n)
+        } else if let Some(n) = self.as_f64() {
+            visitor.visit_float(n)
+        } else {
+            // With `serde_json`'s `arbitrary_precision` feature, a number may
+            // not be representable as any primitive; preserve its text.
+            visitor.visit_display(self)
+        }
+    }
+}
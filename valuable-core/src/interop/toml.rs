@@ -0,0 +1,30 @@
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use toml::{value::Datetime, Value as TomlValue};
+
+impl Visitable for TomlValue {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self {
+            TomlValue::String(v) => visitor.visit_str(v),
+            TomlValue::Integer(v) => visitor.visit_int(*v),
+            TomlValue::Float(v) => visitor.visit_float(*v),
+            TomlValue::Boolean(v) => visitor.visit_bool(*v),
+            TomlValue::Datetime(v) => v.visit(visitor),
+            TomlValue::Array(vs) => visitor.visit_list(vs.iter().map(Value::borrowed)),
+            TomlValue::Table(table) => visitor.visit_map(
+                table
+                    .iter()
+                    .map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))),
+            ),
+        }
+    }
+}
+
+impl Visitable for Datetime {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        // TOML datetimes are visited using their RFC 3339 representation.
+        visitor.visit_display(self)
+    }
+}
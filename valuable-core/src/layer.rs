@@ -0,0 +1,57 @@
+use crate::visitor::Visit;
+
+/// Wraps a visitor in another visitor.
+///
+/// A `VisitLayer` describes a visitor combinator without the visitor it
+/// wraps, so that combinators may be configured separately from the
+/// visitors they are applied to, and stacked declaratively using
+/// [`VisitExt::with`]. Each call to `with` wraps the visitor built so far,
+/// so the last layer added is the outermost, and sees each value first.
+///
+/// This trait is implemented for closures which take the wrapped visitor and
+/// return a new visitor, so combinators without a dedicated layer type (or
+/// user-defined wrappers) may be used as layers too.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{
+/// #     combinator::{DepthLimitLayer, MaybeVisitLayer, Truncate},
+/// #     json::JsonVisit,
+/// #     value,
+/// #     visitor::VisitExt,
+/// # };
+/// let mut visit = JsonVisit::new(String::new())
+///     .with(|inner| Truncate::new(inner).with_max_str_len(8))
+///     .with(MaybeVisitLayer::new(|key: &str| key != "password"))
+///     .with(DepthLimitLayer::new(8));
+///
+/// value!({ "user": "alice", "password": "a very long password" })
+///     .visit(&mut visit)
+///     .unwrap();
+/// let json = visit.into_inner().into_inner().into_inner().into_inner();
+/// assert_eq!(json, r#"{"user":"alice"}"#);
+/// ```
+///
+/// [`VisitExt::with`]: ../visitor/trait.VisitExt.html#method.with
+pub trait VisitLayer<V> {
+    /// The visitor returned by this layer.
+    type Visit: Visit;
+
+    /// Wraps `inner` in this layer's visitor.
+    fn layer(self, inner: V) -> Self::Visit;
+}
+
+// === impl VisitLayer ===
+
+impl<V, W, F> VisitLayer<V> for F
+where
+    F: FnOnce(V) -> W,
+    W: Visit,
+{
+    type Visit = W;
+
+    fn layer(self, inner: V) -> W {
+        self(inner)
+    }
+}
@@ -0,0 +1,29 @@
+//! The core traits of [`valuable`].
+//!
+//! This crate contains the object-safe [`Visit`] and [`Visitable`] traits,
+//! the erased [`Value`] type, and the [`Record`] of named fields, along with
+//! `Visitable` implementations for types from the standard library. It is
+//! intended for libraries which only need to accept or produce structured
+//! values, and do not want to depend on the visitors, serializers, and
+//! derive macros provided by [`valuable`]. Everything in this crate is
+//! re-exported by `valuable`, so the two may be used together freely.
+//!
+//! [`valuable`]: https://docs.rs/valuable
+//! [`Visit`]: visitor/trait.Visit.html
+//! [`Visitable`]: value/trait.Visitable.html
+//! [`Value`]: value/struct.Value.html
+//! [`Record`]: record/struct.Record.html
+#[macro_use]
+mod macros;
+
+pub mod visitor;
+pub mod value;
+pub mod record;
+pub mod debug;
+pub mod float;
+pub mod layer;
+pub mod pool;
+pub mod sample;
+
+mod interop;
+mod transform;
@@ -0,0 +1,177 @@
+/// Implements `Visit` methods which forward to the visitor in the field
+/// `$inner` (or returned by the method `$inner()`).
+///
+/// This is used by visitor wrappers which only need to intercept some of the
+/// `Visit` methods.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! forward_visit {
+    ($inner:ident() => $($method:ident),+ $(,)?) => {
+        $(
+            $crate::forward_visit!(@method [$inner()] $method);
+        )+
+    };
+    ($inner:ident => $($method:ident),+ $(,)?) => {
+        $(
+            $crate::forward_visit!(@method [$inner] $method);
+        )+
+    };
+    (@method [$($inner:tt)+] visit_uint) => {
+        fn visit_uint(&mut self, value: u64) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_uint(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_int) => {
+        fn visit_int(&mut self, value: i64) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_int(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_float) => {
+        fn visit_float(&mut self, value: f64) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_float(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_u16) => {
+        fn visit_u16(&mut self, value: u16) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_u16(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_u32) => {
+        fn visit_u32(&mut self, value: u32) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_u32(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_i8) => {
+        fn visit_i8(&mut self, value: i8) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_i8(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_i16) => {
+        fn visit_i16(&mut self, value: i16) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_i16(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_i32) => {
+        fn visit_i32(&mut self, value: i32) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_i32(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_f32) => {
+        fn visit_f32(&mut self, value: f32) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_f32(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_str) => {
+        fn visit_str(&mut self, value: &str) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_str(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_u8) => {
+        fn visit_u8(&mut self, value: u8) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_u8(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_bytes) => {
+        fn visit_bytes(&mut self, value: &[u8]) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_bytes(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_bool) => {
+        fn visit_bool(&mut self, value: bool) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_bool(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_unit) => {
+        fn visit_unit(&mut self) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_unit()
+        }
+    };
+    (@method [$($inner:tt)+] visit_any) => {
+        fn visit_any(
+            &mut self,
+            value: &dyn $crate::value::Visitable,
+        ) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_any(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_kv) => {
+        fn visit_kv(
+            &mut self,
+            k: $crate::value::Value<'_>,
+            v: $crate::value::Value<'_>,
+        ) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_kv(k, v)
+        }
+    };
+    (@method [$($inner:tt)+] visit_field) => {
+        fn visit_field(
+            &mut self,
+            field: $crate::record::FieldId,
+            value: $crate::value::Value<'_>,
+        ) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_field(field, value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_named_field) => {
+        fn visit_named_field(
+            &mut self,
+            name: &str,
+            value: $crate::value::Value<'_>,
+        ) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_named_field(name, value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_fmt) => {
+        fn visit_fmt(&mut self, args: ::std::fmt::Arguments<'_>) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_fmt(args)
+        }
+    };
+    (@method [$($inner:tt)+] visit_display) => {
+        fn visit_display(&mut self, value: &dyn ::std::fmt::Display) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_display(value)
+        }
+    };
+    (@method [$($inner:tt)+] visit_record) => {
+        fn visit_record(
+            &mut self,
+            record: &$crate::record::Record<'_>,
+        ) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_record(record)
+        }
+    };
+    (@method [$($inner:tt)+] named_type) => {
+        fn named_type(&mut self, name: &str) -> $crate::visitor::VisitResult {
+            self.$($inner)+.named_type(name)
+        }
+    };
+    (@method [$($inner:tt)+] visit_annotation) => {
+        fn visit_annotation(&mut self, key: &str, value: &str) -> $crate::visitor::VisitResult {
+            self.$($inner)+.visit_annotation(key, value)
+        }
+    };
+    (@method [$($inner:tt)+] wants_value) => {
+        fn wants_value(&self, key: &str) -> bool {
+            self.$($inner)+.wants_value(key)
+        }
+    };
+    (@method [$($inner:tt)+] error_mode) => {
+        fn error_mode(&self) -> $crate::visitor::ErrorMode {
+            self.$($inner)+.error_mode()
+        }
+    };
+    (@method [$($inner:tt)+] capabilities) => {
+        fn capabilities(&self) -> $crate::visitor::Capabilities {
+            self.$($inner)+.capabilities()
+        }
+    };
+    (@method [$($inner:tt)+] keys_only) => {
+        fn keys_only(&self) -> bool {
+            self.$($inner)+.keys_only()
+        }
+    };
+    (@method [$($inner:tt)+] $method:ident) => {
+        fn $method(&mut self) -> $crate::visitor::VisitResult {
+            self.$($inner)+.$method()
+        }
+    };
+}
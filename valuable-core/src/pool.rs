@@ -0,0 +1,245 @@
+//! Reusable scratch buffers.
+//!
+//! Some visitors need a temporary buffer for each value they visit: for
+//! example, [`JsonVisit`] converts each map key into a string before
+//! escaping it, [`args_to_str`] formats long values into a `String`, and
+//! [`owned::visit_from`] reads each string and byte string into a buffer
+//! before visiting it. Rather than allocating a new buffer each time, these
+//! take one from a [`BufferPool`], and return it once they are done with it.
+//!
+//! By default, buffers are kept in a small pool for each thread (see
+//! [`ThreadLocalPool`]). Programs which need control over how scratch
+//! buffers are allocated (for example, because they use a dedicated
+//! allocator, or must not keep memory between events) may replace it with
+//! their own pool using [`set_pool`].
+//!
+//! [`JsonVisit`]: ../json/struct.JsonVisit.html
+//! [`args_to_str`]: ../visitor/fn.args_to_str.html
+//! [`owned::visit_from`]: ../owned/fn.visit_from.html
+//! [`BufferPool`]: trait.BufferPool.html
+//! [`ThreadLocalPool`]: struct.ThreadLocalPool.html
+//! [`set_pool`]: fn.set_pool.html
+use std::{cell::RefCell, error, fmt, sync::OnceLock};
+
+/// A source of reusable scratch buffers.
+///
+/// Buffers returned by `take_*` methods must be empty, but may have spare
+/// capacity. Each buffer taken is returned with the corresponding `put_*`
+/// method once it is no longer needed, unless the thread panics while it is
+/// in use; the pool may keep it (after clearing it) or drop it.
+pub trait BufferPool: Sync {
+    /// Returns an empty `String`.
+    fn take_string(&self) -> String;
+
+    /// Returns a `String` taken with `take_string` to the pool.
+    fn put_string(&self, buf: String);
+
+    /// Returns an empty `Vec<u8>`.
+    fn take_bytes(&self) -> Vec<u8>;
+
+    /// Returns a `Vec<u8>` taken with `take_bytes` to the pool.
+    fn put_bytes(&self, buf: Vec<u8>);
+}
+
+/// The default [`BufferPool`], which keeps a few buffers for each thread.
+///
+/// Up to 4 buffers of each type are kept per thread. Buffers which have
+/// grown larger than 64 KiB are dropped rather than kept, so that a single
+/// large value does not hold on to memory indefinitely.
+///
+/// [`BufferPool`]: trait.BufferPool.html
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ThreadLocalPool {
+    _p: (),
+}
+
+/// A [`BufferPool`] which allocates a new buffer each time one is taken,
+/// and drops buffers when they are returned.
+///
+/// [`BufferPool`]: trait.BufferPool.html
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoPool {
+    _p: (),
+}
+
+/// The error returned by [`set_pool`] if a pool was already set.
+///
+/// [`set_pool`]: fn.set_pool.html
+#[derive(Debug)]
+pub struct SetPoolError {
+    _p: (),
+}
+
+static POOL: OnceLock<&'static dyn BufferPool> = OnceLock::new();
+
+static THREAD_LOCAL: ThreadLocalPool = ThreadLocalPool::new();
+
+const MAX_POOLED: usize = 4;
+
+const MAX_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    static STRINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static BYTES: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets the [`BufferPool`] used by every visitor in this crate.
+///
+/// The pool may only be set once, and should be set before any values are
+/// visited.
+///
+/// # Errors
+///
+/// If a pool was already set, this returns an error, and the pool is not
+/// changed.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::pool::{self, NoPool};
+/// static POOL: NoPool = NoPool::new();
+///
+/// pool::set_pool(&POOL).unwrap();
+/// assert!(pool::set_pool(&POOL).is_err());
+/// ```
+///
+/// [`BufferPool`]: trait.BufferPool.html
+pub fn set_pool(pool: &'static dyn BufferPool) -> Result<(), SetPoolError> {
+    POOL.set(pool).map_err(|_| SetPoolError { _p: () })
+}
+
+/// Returns the pool set with `set_pool`, or the default pool.
+#[doc(hidden)]
+pub fn current() -> &'static dyn BufferPool {
+    match POOL.get() {
+        Some(pool) => *pool,
+        None => &THREAD_LOCAL,
+    }
+}
+
+/// Calls `f` with an empty scratch `String` taken from the pool.
+#[doc(hidden)]
+pub fn with_string<R>(f: impl FnOnce(&mut String) -> R) -> R {
+    let pool = current();
+    let mut buf = pool.take_string();
+    let result = f(&mut buf);
+    pool.put_string(buf);
+    result
+}
+
+/// Calls `f` with an empty scratch `Vec<u8>` taken from the pool.
+#[doc(hidden)]
+pub fn with_bytes<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    let pool = current();
+    let mut buf = pool.take_bytes();
+    let result = f(&mut buf);
+    pool.put_bytes(buf);
+    result
+}
+
+// === impl ThreadLocalPool ===
+
+impl ThreadLocalPool {
+    /// Returns a new `ThreadLocalPool`.
+    ///
+    /// All `ThreadLocalPool`s share the same buffers.
+    pub const fn new() -> Self {
+        Self { _p: () }
+    }
+}
+
+impl BufferPool for ThreadLocalPool {
+    fn take_string(&self) -> String {
+        take(&STRINGS)
+    }
+
+    fn put_string(&self, mut buf: String) {
+        if buf.capacity() <= MAX_CAPACITY {
+            buf.clear();
+            put(&STRINGS, buf);
+        }
+    }
+
+    fn take_bytes(&self) -> Vec<u8> {
+        take(&BYTES)
+    }
+
+    fn put_bytes(&self, mut buf: Vec<u8>) {
+        if buf.capacity() <= MAX_CAPACITY {
+            buf.clear();
+            put(&BYTES, buf);
+        }
+    }
+}
+
+fn take<T: Default + 'static>(pool: &'static std::thread::LocalKey<RefCell<Vec<T>>>) -> T {
+    // If the thread's pool has already been destroyed, allocate a buffer.
+    pool.try_with(|pool| pool.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn put<T: 'static>(pool: &'static std::thread::LocalKey<RefCell<Vec<T>>>, buf: T) {
+    let _ = pool.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED {
+            pool.push(buf);
+        }
+    });
+}
+
+// === impl NoPool ===
+
+impl NoPool {
+    /// Returns a new `NoPool`.
+    pub const fn new() -> Self {
+        Self { _p: () }
+    }
+}
+
+impl BufferPool for NoPool {
+    fn take_string(&self) -> String {
+        String::new()
+    }
+
+    fn put_string(&self, _: String) {}
+
+    fn take_bytes(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn put_bytes(&self, _: Vec<u8>) {}
+}
+
+// === impl SetPoolError ===
+
+impl fmt::Display for SetPoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a buffer pool has already been set")
+    }
+}
+
+impl error::Error for SetPoolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_buffers() {
+        let pool = ThreadLocalPool::new();
+        let mut buf = pool.take_string();
+        buf.push_str("scratch");
+        let ptr = buf.as_ptr();
+        pool.put_string(buf);
+
+        let buf = pool.take_string();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_ptr(), ptr);
+
+        // Buffers which have grown too large are not kept.
+        pool.put_string(String::with_capacity(MAX_CAPACITY + 1));
+        assert!(pool.take_string().capacity() <= MAX_CAPACITY);
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Range,
+    slice,
+};
+
+#[cfg(feature = "location")]
+use std::panic::Location;
+
+/// A static set of field names.
+///
+/// A `FieldSet` describes the names of the fields that make up a [`Record`],
+/// such as the fields attached to an event at a particular callsite. Because
+/// the names are `'static`, a `FieldSet` may be declared once (e.g. in a
+/// `static`) and shared by every `Record` produced for it.
+///
+/// [`Record`]: struct.Record.html
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FieldSet {
+    names: &'static [&'static str],
+}
+
+/// The identity of a single field in a [`FieldSet`].
+///
+/// A `FieldId` is passed to [`Visit::visit_field`] when visiting a [`Record`]
+/// constructed from a `FieldSet`. Since a `FieldSet` is declared once and
+/// shared by every record produced for it, a `Visit` implementation may map
+/// each `FieldId` to some state of its own (such as the index of a column)
+/// the first time it sees the field, rather than comparing or hashing the
+/// field's name for every record.
+///
+/// Two `FieldId`s are equal if they refer to the same field of the same
+/// `FieldSet`. `FieldSet`s are identified by the address of their names, so
+/// a `FieldSet` should be declared in a `static` rather than a `const`.
+/// Comparing and hashing `FieldId`s never looks at the field names.
+///
+/// [`FieldSet`]: struct.FieldSet.html
+/// [`Visit::visit_field`]: ../visitor/trait.Visit.html#method.visit_field
+/// [`Record`]: struct.Record.html
+#[derive(Copy, Clone)]
+pub struct FieldId {
+    names: &'static [&'static str],
+    index: usize,
+}
+
+/// An iterator over the [`FieldId`]s of a [`FieldSet`].
+///
+/// This is returned by [`FieldSet::fields`].
+///
+/// [`FieldId`]: struct.FieldId.html
+/// [`FieldSet`]: struct.FieldSet.html
+/// [`FieldSet::fields`]: struct.FieldSet.html#method.fields
+#[derive(Clone, Debug)]
+pub struct Fields {
+    names: &'static [&'static str],
+    indices: Range<usize>,
+}
+
+/// A set of named field values.
+///
+/// A `Record` groups several `Value`s under field names, representing (for
+/// example) all the fields of a single event. A `Record` may be constructed
+/// either from a static [`FieldSet`] and a slice of values in the same order,
+/// or from a slice of `(name, value)` pairs.
+///
+/// [`FieldSet`]: struct.FieldSet.html
+pub struct Record<'a> {
+    inner: RecordKind<'a>,
+    #[cfg(feature = "location")]
+    location: Option<&'static Location<'static>>,
+}
+
+/// An iterator over the fields of a [`Record`].
+///
+/// This is returned by [`Record::iter`].
+///
+/// [`Record`]: struct.Record.html
+/// [`Record::iter`]: struct.Record.html#method.iter
+pub struct Iter<'a> {
+    inner: IterKind<'a>,
+}
+
+/// A record which visits the fields of one record followed by those of
+/// another.
+///
+/// This is returned by [`Record::chain`].
+///
+/// [`Record::chain`]: struct.Record.html#method.chain
+pub struct Chain<'a> {
+    base: &'a Record<'a>,
+    fields: &'a Record<'a>,
+}
+
+enum RecordKind<'a> {
+    Pairs(&'a [(&'a str, Value<'a>)]),
+    FieldSet {
+        fields: &'a FieldSet,
+        values: &'a [Value<'a>],
+    },
+}
+
+enum IterKind<'a> {
+    Pairs(slice::Iter<'a, (&'a str, Value<'a>)>),
+    FieldSet {
+        names: slice::Iter<'static, &'static str>,
+        values: slice::Iter<'a, Value<'a>>,
+    },
+}
+
+// === impl FieldSet ===
+
+impl FieldSet {
+    /// Returns a new `FieldSet` with the given field names.
+    pub const fn new(names: &'static [&'static str]) -> Self {
+        Self { names }
+    }
+
+    /// Returns the names of the fields in this `FieldSet`, in order.
+    pub fn names(&self) -> &'static [&'static str] {
+        self.names
+    }
+
+    /// Returns the number of fields in this `FieldSet`.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if this `FieldSet` contains no fields.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the index of the field named `name`, if it is in this
+    /// `FieldSet`.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|&n| n == name)
+    }
+
+    /// Returns `true` if this `FieldSet` contains a field named `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.index_of(name).is_some()
+    }
+
+    /// Returns the `FieldId` of the field named `name`, if it is in this
+    /// `FieldSet`.
+    ///
+    /// This may be used to register interest in a field ahead of time, so
+    /// that later calls to [`Visit::visit_field`] may be matched by
+    /// comparing `FieldId`s.
+    ///
+    /// [`Visit::visit_field`]: ../visitor/trait.Visit.html#method.visit_field
+    pub fn field(&self, name: &str) -> Option<FieldId> {
+        self.index_of(name).map(|index| FieldId {
+            names: self.names,
+            index,
+        })
+    }
+
+    /// Returns an iterator over the `FieldId`s of the fields in this
+    /// `FieldSet`, in order.
+    pub fn fields(&self) -> Fields {
+        Fields {
+            names: self.names,
+            indices: 0..self.names.len(),
+        }
+    }
+
+    /// Returns a `Record` of the given `values` for the fields in this
+    /// `FieldSet`.
+    ///
+    /// This is equivalent to calling [`Record::new`].
+    ///
+    /// # Panics
+    ///
+    /// If the number of `values` is not equal to the number of fields in this
+    /// `FieldSet`.
+    ///
+    /// [`Record::new`]: struct.Record.html#method.new
+    pub fn record<'a>(&'a self, values: &'a [Value<'a>]) -> Record<'a> {
+        Record::new(self, values)
+    }
+}
+
+impl fmt::Debug for FieldSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names).finish()
+    }
+}
+
+// === impl FieldId ===
+
+impl FieldId {
+    /// Returns the name of this field.
+    pub fn name(&self) -> &'static str {
+        self.names[self.index]
+    }
+
+    /// Returns the index of this field in its `FieldSet`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the `FieldSet` this field belongs to.
+    pub fn field_set(&self) -> FieldSet {
+        FieldSet::new(self.names)
+    }
+}
+
+impl PartialEq for FieldId {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && std::ptr::eq(self.names, other.names)
+    }
+}
+
+impl Eq for FieldId {}
+
+impl Hash for FieldId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.names.as_ptr() as usize).hash(state);
+        self.index.hash(state);
+    }
+}
+
+impl fmt::Debug for FieldId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FieldId").field(&self.name()).finish()
+    }
+}
+
+// === impl Fields ===
+
+impl Iterator for Fields {
+    type Item = FieldId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.indices.next()?;
+        Some(FieldId {
+            names: self.names,
+            index,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Fields {}
+
+// === impl Record ===
+
+impl<'a> Record<'a> {
+    /// Returns a new `Record` of the given `values` for the fields in
+    /// `fields`.
+    ///
+    /// Each value is associated with the field name at the same index in the
+    /// `FieldSet`.
+    ///
+    /// # Panics
+    ///
+    /// If the number of `values` is not equal to the number of fields in the
+    /// `FieldSet`.
+    pub fn new(fields: &'a FieldSet, values: &'a [Value<'a>]) -> Self {
+        assert_eq!(
+            fields.len(),
+            values.len(),
+            "a record must have exactly one value for each field in its field set",
+        );
+        Record {
+            inner: RecordKind::FieldSet { fields, values },
+            #[cfg(feature = "location")]
+            location: None,
+        }
+    }
+
+    /// Returns a new `Record` from a slice of `(name, value)` pairs.
+    pub fn from_pairs(pairs: &'a [(&'a str, Value<'a>)]) -> Self {
+        Record {
+            inner: RecordKind::Pairs(pairs),
+            #[cfg(feature = "location")]
+            location: None,
+        }
+    }
+
+    /// Returns the `FieldSet` this `Record` was constructed with, if it has
+    /// one.
+    pub fn field_set(&self) -> Option<&'a FieldSet> {
+        match self.inner {
+            RecordKind::FieldSet { fields, .. } => Some(fields),
+            RecordKind::Pairs(_) => None,
+        }
+    }
+
+    /// Returns the number of fields in this `Record`.
+    pub fn len(&self) -> usize {
+        match self.inner {
+            RecordKind::Pairs(pairs) => pairs.len(),
+            RecordKind::FieldSet { values, .. } => values.len(),
+        }
+    }
+
+    /// Returns `true` if this `Record` contains no fields.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of the field named `name`, if this `Record` has
+    /// such a field.
+    pub fn get(&self, name: &str) -> Option<Value<'_>> {
+        self.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over the names and values of the fields in this
+    /// `Record`.
+    pub fn iter(&self) -> Iter<'_> {
+        let inner = match self.inner {
+            RecordKind::Pairs(pairs) => IterKind::Pairs(pairs.iter()),
+            RecordKind::FieldSet { fields, values } => IterKind::FieldSet {
+                names: fields.names().iter(),
+                values: values.iter(),
+            },
+        };
+        Iter { inner }
+    }
+
+    /// Visits the record with the given `Visit`.
+    ///
+    /// This calls [`Visit::visit_record`] on the visitor.
+    ///
+    /// [`Visit::visit_record`]: ../visitor/trait.Visit.html#method.visit_record
+    pub fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_record(self)
+    }
+
+    /// Visits the record as a complete top-level value, then calls
+    /// [`Visit::finish`] on the visitor.
+    ///
+    /// [`Visit::finish`]: ../visitor/trait.Visit.html#method.finish
+    pub fn visit_and_finish(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.visit(visitor)?;
+        visitor.finish()
+    }
+
+    /// Returns a record which visits the fields of `base`, followed by the
+    /// fields of `fields`.
+    ///
+    /// A field of `fields` shadows any field of `base` with the same name,
+    /// so that a value common to many records (such as the fields of the
+    /// request an event was logged while handling) may be overridden by a
+    /// single record. The chained record is visited as a single record,
+    /// constructed from pairs, with the location (if any) of `fields`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::{record::Record, value, value::Value};
+    /// let base = [("request", value!(7)), ("level", value!("info"))];
+    /// let event = [("level", value!("warn")), ("message", value!("slow"))];
+    /// let (base, event) = (Record::from_pairs(&base), Record::from_pairs(&event));
+    ///
+    /// let chain = Record::chain(&base, &event);
+    /// assert_eq!(
+    ///     Value::borrowed(&chain).render_json(),
+    ///     r#"{"request":7,"level":"warn","message":"slow"}"#
+    /// );
+    /// ```
+    pub fn chain(base: &'a Record<'a>, fields: &'a Record<'a>) -> Chain<'a> {
+        Chain { base, fields }
+    }
+
+    /// Calls `f` with a copy of this record in which each value is wrapped
+    /// by `wrap`.
+    ///
+    /// If this record was constructed from a `FieldSet`, so is the copy, so
+    /// that visitors wrapping another visitor can pass records through
+    /// without losing their `FieldId`s.
+    #[doc(hidden)]
+    pub fn map_values<'b, T: Visitable>(
+        &'b self,
+        wrap: impl FnMut(Value<'b>) -> T,
+        f: impl FnOnce(&Record<'_>) -> VisitResult,
+    ) -> VisitResult {
+        let nested = self
+            .iter()
+            .map(|(_, value)| value)
+            .map(wrap)
+            .collect::<Vec<_>>();
+        let values = nested.iter().map(Value::borrowed);
+        match self.inner {
+            RecordKind::FieldSet { fields, .. } => {
+                let values = values.collect::<Vec<_>>();
+                f(&Record::new(fields, &values).inherit(self))
+            }
+            RecordKind::Pairs(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .zip(values)
+                    .collect::<Vec<_>>();
+                f(&Record::from_pairs(&pairs).inherit(self))
+            }
+        }
+    }
+
+    /// Copies the metadata (such as the location) of `other` to this record.
+    #[cfg(feature = "location")]
+    fn inherit(self, other: &Record<'_>) -> Self {
+        Record {
+            location: other.location,
+            ..self
+        }
+    }
+
+    #[cfg(not(feature = "location"))]
+    fn inherit(self, _: &Record<'_>) -> Self {
+        self
+    }
+}
+
+/// Source locations.
+///
+/// These methods require the `location` feature.
+#[cfg(feature = "location")]
+impl Record<'_> {
+    /// Attaches the location of the caller to this record.
+    #[track_caller]
+    pub fn located(self) -> Self {
+        self.with_location(Location::caller())
+    }
+
+    /// Attaches the source location `location` to this record.
+    pub fn with_location(self, location: &'static Location<'static>) -> Self {
+        Record {
+            location: Some(location),
+            ..self
+        }
+    }
+
+    /// Returns the source location attached to this record, if there is
+    /// one.
+    ///
+    /// Visitor wrappers in this crate which forward records to the visitor
+    /// they wrap preserve their locations, so a visitor may query the
+    /// location of a record in `visit_record`.
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        self.location
+    }
+}
+
+impl<'r, 'a> IntoIterator for &'r Record<'a> {
+    type Item = (&'r str, Value<'r>);
+    type IntoIter = Iter<'r>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// === impl Chain ===
+
+impl Chain<'_> {
+    /// Visits the chained record with the given `Visit`.
+    ///
+    /// This calls [`Visit::visit_record`] on the visitor.
+    ///
+    /// [`Visit::visit_record`]: ../visitor/trait.Visit.html#method.visit_record
+    pub fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let shadowed = |name: &str| self.fields.iter().any(|(n, _)| n == name);
+        let pairs = self
+            .base
+            .iter()
+            .filter(|(name, _)| !shadowed(name))
+            .chain(self.fields.iter())
+            .collect::<Vec<_>>();
+        visitor.visit_record(&Record::from_pairs(&pairs).inherit(self.fields))
+    }
+}
+
+impl Visitable for Chain<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        Chain::visit(self, visitor)
+    }
+}
+
+// === impl Iter ===
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            IterKind::Pairs(ref mut pairs) => {
+                pairs.next().map(|(name, value)| (*name, value.by_ref()))
+            }
+            IterKind::FieldSet {
+                ref mut names,
+                ref mut values,
+            } => {
+                let name = names.next()?;
+                let value = values.next()?;
+                Some((*name, value.by_ref()))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.inner {
+            IterKind::Pairs(ref pairs) => pairs.size_hint(),
+            IterKind::FieldSet { ref values, .. } => values.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FIELDS: FieldSet = FieldSet::new(&["id", "name", "ok"]);
+
+    #[test]
+    fn field_set_lookup() {
+        assert_eq!(FIELDS.index_of("name"), Some(1));
+        assert_eq!(FIELDS.index_of("missing"), None);
+        assert!(FIELDS.contains("ok"));
+        assert!(!FIELDS.contains("missing"));
+
+        let name = FIELDS.field("name").unwrap();
+        assert_eq!((name.name(), name.index()), ("name", 1));
+        assert_eq!(FIELDS.fields().nth(1), Some(name));
+        assert_eq!(FIELDS.field("missing"), None);
+    }
+
+    #[test]
+    fn record_fields() {
+        let values = [
+            Value::borrowed(&7u64),
+            Value::borrowed(&"eliza"),
+            Value::borrowed(&true),
+        ];
+        let record = FIELDS.record(&values);
+        assert_eq!(record.len(), 3);
+        assert_eq!(
+            record.get("name").map(|v| v.render_debug()),
+            Some(r#""eliza""#.to_owned())
+        );
+        assert!(record.get("missing").is_none());
+
+        // Fields are iterated in the order of the `FieldSet`.
+        let fields = record
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value.render_debug()))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["id=7", r#"name="eliza""#, "ok=true"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exactly one value for each field")]
+    fn record_length_mismatch() {
+        let values = [Value::borrowed(&7u64)];
+        let _ = Record::new(&FIELDS, &values);
+    }
+}
@@ -0,0 +1,107 @@
+//! Sampling values at hot call sites.
+//!
+//! Visiting a value with expensive fields on every event may cost more than
+//! a hot call site can afford. A [`Sampler`] decides which visits of a value
+//! are forwarded, and [`Value::sampled`] wraps a value so that it is only
+//! visited when its sampler selects it; otherwise, the placeholder string
+//! [`NOT_SAMPLED`] is visited in its place.
+//!
+//! # Examples
+//!
+//! ```
+//! # use valuable::value::ValueExt;
+//! # use valuable::{sample::Sampler, value};
+//! static SAMPLER: Sampler = Sampler::every(3);
+//!
+//! let rendered: Vec<String> = (0..4)
+//!     .map(|i| value!({ "i": i }).sampled(&SAMPLER).render_json())
+//!     .collect();
+//! assert_eq!(
+//!     rendered,
+//!     [r#"{"i":0}"#, r#""<not sampled>""#, r#""<not sampled>""#, r#"{"i":3}"#]
+//! );
+//! ```
+//!
+//! [`Sampler`]: struct.Sampler.html
+//! [`Value::sampled`]: ../value/struct.Value.html#method.sampled
+//! [`NOT_SAMPLED`]: constant.NOT_SAMPLED.html
+use crate::{
+    value::{Value, Visitable},
+    visitor::{Visit, VisitResult},
+};
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The placeholder string visited in place of a value which was not
+/// sampled.
+pub const NOT_SAMPLED: &str = "<not sampled>";
+
+/// Selects a fraction of visits to forward.
+///
+/// A `Sampler` counts the visits it is asked about, and selects the first of
+/// every `n`. Since it is shared by every visit of a call site, it is
+/// usually stored in a `static`. Counting is done with relaxed atomic
+/// operations, so a `Sampler` may be shared between threads, although which
+/// thread's visit is selected is unspecified.
+pub struct Sampler {
+    every: usize,
+    count: AtomicUsize,
+}
+
+/// A value visited only when its sampler selects it.
+pub(crate) struct Sampled<'a> {
+    pub(crate) value: Value<'a>,
+    pub(crate) sampler: &'a Sampler,
+}
+
+// === impl Sampler ===
+
+impl Sampler {
+    /// Returns a `Sampler` which selects the first of every `n` visits.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is zero.
+    pub const fn every(n: usize) -> Self {
+        assert!(n > 0, "a sampler must select one of at least one visit");
+        Self {
+            every: n,
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts a visit, returning `true` if it is selected.
+    pub fn sample(&self) -> bool {
+        self.count
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.every)
+    }
+
+    /// Returns the number of visits counted so far.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("every", &self.every)
+            .field("count", &self.count())
+            .finish()
+    }
+}
+
+// === impl Sampled ===
+
+impl Visitable for Sampled<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if self.sampler.sample() {
+            self.value.visit(visitor)
+        } else {
+            visitor.visit_str(NOT_SAMPLED)
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! `Visitable` adapters which transform the output of a visited value.
+//!
+//! These back the combinator methods on [`Value`], such as
+//! [`Value::map_strs`], [`Value::rename_type`], and [`Value::annotate`].
+//!
+//! [`Value`]: ../value/struct.Value.html
+//! [`Value::map_strs`]: ../value/struct.Value.html#method.map_strs
+//! [`Value::rename_type`]: ../value/struct.Value.html#method.rename_type
+//! [`Value::annotate`]: ../value/struct.Value.html#method.annotate
+use crate::{
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+    visitor::{Capabilities, ErrorMode, Visit, VisitResult},
+};
+
+/// A `Visitable` that visits a value, replacing every string it contains
+/// with the result of calling `f` on that string.
+pub(crate) struct MapStrs<'a, F> {
+    pub(crate) value: Value<'a>,
+    pub(crate) f: F,
+}
+
+/// A `Visitable` that visits a value, replacing the type name of the value
+/// itself (but not any values nested inside it).
+pub(crate) struct RenameType<'a> {
+    pub(crate) value: Value<'a>,
+    pub(crate) name: &'a str,
+}
+
+/// A `Visitable` that visits an annotation before a value.
+pub(crate) struct Annotated<'a> {
+    pub(crate) value: Value<'a>,
+    pub(crate) key: &'a str,
+    pub(crate) annotation: &'a str,
+}
+
+/// A value nested inside a `MapStrs`, visited with the same function.
+struct NestedStrs<'a, F> {
+    value: Value<'a>,
+    f: &'a F,
+}
+
+struct StrMapper<'a, F> {
+    inner: &'a mut dyn Visit,
+    f: &'a F,
+}
+
+struct Renamer<'a> {
+    inner: &'a mut dyn Visit,
+    /// The new type name, if the renamer has not yet visited anything.
+    pending: Option<&'a str>,
+}
+
+// === impl MapStrs ===
+
+impl<F, S> Visitable for MapStrs<'_, F>
+where
+    F: Fn(&str) -> S,
+    S: AsRef<str>,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut StrMapper {
+            inner: visitor,
+            f: &self.f,
+        })
+    }
+}
+
+impl<F, S> Visitable for NestedStrs<'_, F>
+where
+    F: Fn(&str) -> S,
+    S: AsRef<str>,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut StrMapper {
+            inner: visitor,
+            f: self.f,
+        })
+    }
+}
+
+impl<F, S> Visit for StrMapper<'_, F>
+where
+    F: Fn(&str) -> S,
+    S: AsRef<str>,
+{
+    forward_visit! {
+        inner =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_fmt,
+            visit_display,
+            named_type,
+            visit_annotation,
+            wants_value,
+            error_mode,
+            capabilities,
+            keys_only,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.inner.visit_str((self.f)(value).as_ref())
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        value.visit(self)
+    }
+
+    fn visit_kv(&mut self, k: Value<'_>, v: Value<'_>) -> VisitResult {
+        // Keys (including struct field names) are passed through unchanged;
+        // only the value is wrapped, since the inner visitor will visit it
+        // itself.
+        let v = NestedStrs {
+            value: v,
+            f: self.f,
+        };
+        self.inner.visit_kv(k, Value::borrowed(&v))
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        let value = NestedStrs { value, f: self.f };
+        self.inner.visit_field(field, Value::borrowed(&value))
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        let value = NestedStrs { value, f: self.f };
+        self.inner.visit_named_field(name, Value::borrowed(&value))
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        let f = self.f;
+        let inner = &mut self.inner;
+        record.map_values(
+            |value| NestedStrs { value, f },
+            |record| inner.visit_record(record),
+        )
+    }
+}
+
+// === impl RenameType ===
+
+impl Visitable for RenameType<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.value.visit(&mut Renamer {
+            inner: visitor,
+            pending: Some(self.name),
+        })
+    }
+}
+
+impl Renamer<'_> {
+    /// Returns the inner visitor, once the renamer has seen a method call
+    /// other than `named_type`.
+    ///
+    /// The type name of the value itself is always the first thing visited,
+    /// so any later `named_type` calls belong to nested values.
+    fn inner(&mut self) -> &mut dyn Visit {
+        self.pending = None;
+        &mut *self.inner
+    }
+}
+
+impl Visit for Renamer<'_> {
+    forward_visit! {
+        inner() =>
+            visit_uint,
+            visit_u16,
+            visit_u32,
+            visit_i8,
+            visit_i16,
+            visit_i32,
+            visit_int,
+            visit_float,
+            visit_f32,
+            visit_str,
+            visit_u8,
+            visit_bytes,
+            visit_bool,
+            visit_unit,
+            visit_kv,
+            visit_field,
+            visit_named_field,
+            visit_fmt,
+            visit_display,
+            visit_record,
+            open_map,
+            close_map,
+            open_list,
+            close_list,
+            open_struct,
+            close_struct,
+            open_tuple,
+            close_tuple,
+            finish,
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        // Values such as `Arc`s visit their contents with `visit_any`, so
+        // keep looking for the type name inside them.
+        if self.pending.is_some() {
+            value.visit(self)
+        } else {
+            self.inner.visit_any(value)
+        }
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        let name = self.pending.take().unwrap_or(name);
+        self.inner.named_type(name)
+    }
+
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        // Annotations precede the value's type name, so they don't end the
+        // search for it.
+        self.inner.visit_annotation(key, value)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        self.inner.wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        self.inner.error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        self.inner.keys_only()
+    }
+}
+
+// === impl Annotated ===
+
+impl Visitable for Annotated<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_annotation(self.key, self.annotation)?;
+        self.value.visit(visitor)
+    }
+}
@@ -0,0 +1,1650 @@
+use crate::{
+    debug::{DebugVisit, FormatOptions},
+    record::FieldSet,
+    sample::{Sampled, Sampler},
+    transform::{Annotated, MapStrs, RenameType},
+    visitor::{Visit, VisitExt, VisitResult},
+};
+use std::{
+    borrow::Borrow,
+    cell::{Cell, RefCell},
+    collections,
+    convert::{Infallible, TryFrom},
+    fmt,
+    hash::Hash,
+    marker::{PhantomData, PhantomPinned},
+    num::Saturating,
+    ops::{Bound, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive},
+    rc::Rc,
+    sync::{atomic, Arc, Mutex, RwLock, TryLockError},
+    time::Duration,
+};
+
+#[cfg(feature = "location")]
+use std::panic::Location;
+
+/// A structured field value of an erased type.
+///
+/// Implementors of `Visitable` may call the appropriate typed visiting methods
+/// on the `Visit` passed to `visit` in order to indicate how their data
+/// should be recorded.
+///
+/// `Visitable` does not require `Send` or `Sync`; code which sends values
+/// to other threads must require those bounds itself.
+pub trait Visitable {
+    /// Visits the value with the given `Visit`.
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult;
+
+    /// Visits the value with the given concrete `Visit` type, using static
+    /// dispatch.
+    ///
+    /// This defaults to calling `self.visit()` with the visitor as a trait
+    /// object. Implementations for which visiting is performance-sensitive
+    /// may override it to call the visitor's methods directly, allowing the
+    /// visit to be monomorphized and inlined. Implementations that override
+    /// this method must visit the value in the same way as `visit`.
+    fn visit_with<V>(&self, visitor: &mut V) -> VisitResult
+    where
+        Self: Sized,
+        V: Visit,
+    {
+        self.visit(visitor)
+    }
+
+    /// Returns the name of the value's Rust type, as returned by
+    /// [`std::any::type_name`].
+    ///
+    /// This is intended for diagnostics: a `Visit` implementation which
+    /// receives a value it doesn't otherwise understand in `visit_any` may
+    /// use it to describe the value. Like `type_name`, the returned name is
+    /// not guaranteed to be stable, and should not be relied upon to
+    /// identify types.
+    ///
+    /// [`std::any::type_name`]: https://doc.rust-lang.org/std/any/fn.type_name.html
+    fn rust_type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Returns the type name this value is visited with, if it is known
+    /// without visiting the value.
+    ///
+    /// This is the name passed to [`Visit::named_type`] when the value is
+    /// visited. It defaults to `None`; when the `derive` feature is enabled,
+    /// `#[derive(Visitable)]` implements it for structs and for enums whose
+    /// variants are visited with a type name, and smart pointers such as
+    /// `Box` return the name of the value they point to.
+    ///
+    /// [`Visit::named_type`]: ../visitor/trait.Visit.html#method.named_type
+    fn type_name(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A `Visitable` type which is always visited as a struct with the same
+/// name and fields.
+///
+/// This allows the names of a type's fields to be known without visiting a
+/// value of the type, such as to register a schema, or to allocate columns
+/// for a table, ahead of time. When the `derive` feature is enabled,
+/// `#[derive(Visitable)]` implements `StructLike` for structs with named
+/// fields, unless a field is flattened (since the names of a flattened
+/// value's fields cannot be known ahead of time).
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{record::FieldSet, value::{StructDef, StructLike, Value, Visitable}, visitor::{Visit, VisitResult}};
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// impl Visitable for Point {
+///     fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+///         visitor.visit_struct(
+///             "Point",
+///             vec![("x", Value::borrowed(&self.x)), ("y", Value::borrowed(&self.y))],
+///         )
+///     }
+/// }
+///
+/// impl StructLike for Point {
+///     fn definition() -> &'static StructDef {
+///         static DEFINITION: StructDef = StructDef::new("Point", &["x", "y"]);
+///         &DEFINITION
+///     }
+/// }
+///
+/// assert_eq!(Point::definition().fields().names(), ["x", "y"]);
+/// ```
+pub trait StructLike: Visitable {
+    /// Returns the definition of the struct this type is visited as.
+    ///
+    /// Every field of the definition is visited in the definition's
+    /// [order], except for fields which are omitted when they have no value
+    /// (such as `#[visitable(skip_if_none)]` fields).
+    ///
+    /// [order]: struct.StructDef.html#method.order
+    fn definition() -> &'static StructDef
+    where
+        Self: Sized;
+}
+
+/// The name and fields of a struct, returned by
+/// [`StructLike::definition`].
+///
+/// [`StructLike::definition`]: trait.StructLike.html#tymethod.definition
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StructDef {
+    name: &'static str,
+    fields: FieldSet,
+    order: FieldOrder,
+}
+
+/// The order in which the fields of a [`StructDef`] are listed and visited.
+///
+/// Since systems which map fields to columns by position rely on the order
+/// of a struct's fields, it is part of the struct's definition. Derived
+/// implementations of [`StructLike`] use [`FieldOrder::Declaration`] unless
+/// the struct has the `#[visitable(sort_fields)]` attribute.
+///
+/// [`StructDef`]: struct.StructDef.html
+/// [`StructLike`]: trait.StructLike.html
+/// [`FieldOrder::Declaration`]: #variant.Declaration
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum FieldOrder {
+    /// Fields are in the order in which they are declared.
+    Declaration,
+    /// Fields are sorted by name.
+    Sorted,
+}
+
+impl StructDef {
+    /// Returns a new `StructDef` for a struct named `name` with the fields
+    /// `fields`, in [declaration order].
+    ///
+    /// [declaration order]: enum.FieldOrder.html#variant.Declaration
+    pub const fn new(name: &'static str, fields: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            fields: FieldSet::new(fields),
+            order: FieldOrder::Declaration,
+        }
+    }
+
+    /// Sets the order of the definition's fields.
+    ///
+    /// This does not reorder `fields`, which must already be in the given
+    /// order.
+    pub const fn with_order(self, order: FieldOrder) -> Self {
+        Self { order, ..self }
+    }
+
+    /// Returns the order of the struct's fields.
+    pub fn order(&self) -> FieldOrder {
+        self.order
+    }
+
+    /// Returns the struct's type name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the struct's fields.
+    pub fn fields(&self) -> FieldSet {
+        self.fields
+    }
+}
+
+pub struct Value<'a> {
+    inner: ValueKind<'a>,
+    /// The source location at which the value was constructed, if one was
+    /// attached.
+    #[cfg(feature = "location")]
+    location: Option<&'static Location<'static>>,
+}
+
+enum ValueKind<'a> {
+    Borrowed(&'a dyn Visitable),
+    Owned(Box<dyn Visitable + 'a>),
+    Display(&'a (dyn fmt::Display + Sync)),
+    Debug(&'a (dyn fmt::Debug + Sync)),
+}
+
+impl<'a> Value<'a> {
+    fn new(inner: ValueKind<'a>) -> Self {
+        Value {
+            inner,
+            #[cfg(feature = "location")]
+            location: None,
+        }
+    }
+
+    pub fn display(value: &'a (impl fmt::Display + Sync)) -> Self {
+        Value::new(ValueKind::Display(value))
+    }
+
+    pub fn debug<T>(value: &'a T) -> Self
+    where
+        T: fmt::Debug + Sync + 'a,
+    {
+        Value::new(ValueKind::Debug(value))
+    }
+
+    /// Returns a `Value` representing the absence of a value.
+    ///
+    /// An empty value is visited with [`Visit::visit_unit`], which most
+    /// visitors record as a null value (such as JSON `null`). This provides
+    /// a canonical way to record an optional field which is not present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value::Value;
+    /// let name: Option<&str> = None;
+    /// let value = name.as_ref().map_or_else(Value::empty, Value::borrowed);
+    /// assert_eq!(value.render_json(), "null");
+    /// ```
+    ///
+    /// [`Visit::visit_unit`]: ../visitor/trait.Visit.html#method.visit_unit
+    pub fn empty() -> Self {
+        Value::borrowed(&())
+    }
+
+    /// Returns a `Value` borrowing `value`.
+    ///
+    /// This does not allocate, but the `Value` may not outlive `value`, so
+    /// it cannot borrow a temporary (such as a value computed inside an
+    /// iterator's `map` closure) and be returned:
+    ///
+    /// ```compile_fail
+    /// # use valuable::value::Value;
+    /// let names = ["alice", "bob"];
+    /// // error: cannot return value referencing temporary value
+    /// let lens = names.iter().map(|name| Value::borrowed(&name.len()));
+    /// # drop(lens);
+    /// ```
+    ///
+    /// Nor can it be returned from the function which owns `value`:
+    ///
+    /// ```compile_fail
+    /// # use valuable::value::Value;
+    /// fn port() -> Value<'static> {
+    ///     let port = 8080u16;
+    ///     // error: cannot return value referencing local variable `port`
+    ///     Value::borrowed(&port)
+    /// }
+    /// # drop(port());
+    /// ```
+    ///
+    /// The same applies to data borrowed by the closure passed to
+    /// [`Value::with_visit`], which must outlive the `Value`:
+    ///
+    /// ```compile_fail
+    /// # use valuable::value::Value;
+    /// let value = {
+    ///     let name = String::from("alice");
+    ///     // error: `name` does not live long enough
+    ///     Value::with_visit((), |_, visitor| visitor.visit_str(&name))
+    /// };
+    /// # let _ = value.type_name();
+    /// ```
+    ///
+    /// Use [`Value::of`] to construct a `Value` which owns such a value.
+    ///
+    /// [`Value::of`]: #method.of
+    /// [`Value::with_visit`]: #method.with_visit
+    pub fn borrowed(value: &'a impl Visitable) -> Self {
+        Value::new(ValueKind::Borrowed(value))
+    }
+
+    /// Returns a `Value` which owns `value`.
+    ///
+    /// Unlike [`Value::borrowed`], this moves `value` into the `Value`, so
+    /// the `Value` may be constructed from a temporary and returned from a
+    /// closure. The `Value` may still borrow any data which `value` itself
+    /// borrows, for the lifetime `'a`. This allocates, so `Value::borrowed`
+    /// should be preferred when the value outlives the `Value`.
+    ///
+    /// A `Value<'a>` is covariant in `'a`, so any `Value` may be used where
+    /// a `Value` with a shorter lifetime is expected, such as when collecting
+    /// values borrowing data of different lifetimes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value::{ListValue, Value, Visitable};
+    /// let names = vec![String::from("alice"), String::from("bob")];
+    /// let lens = names.iter().map(|name| Value::of(name.len())).collect::<ListValue<'_>>();
+    /// assert_eq!(Value::borrowed(&lens).render_json(), "[5,3]");
+    ///
+    /// // Items of the iterator may borrow the list they came from.
+    /// let value = Value::with_visit(&names, |names, visitor| {
+    ///     visitor.visit_list(names.iter().map(|name| Value::of(name.as_str())))
+    /// });
+    /// assert_eq!(value.render_json(), r#"["alice","bob"]"#);
+    ///
+    /// // A borrowed slice, or a boxed trait object, may be owned as well.
+    /// let ports: &[u16] = &[80, 443];
+    /// assert_eq!(Value::of(ports).render_json(), "[80,443]");
+    ///
+    /// let erased: Box<dyn Visitable> = Box::new(true);
+    /// assert_eq!(Value::of(erased).render_json(), "true");
+    /// ```
+    ///
+    /// [`Value::borrowed`]: #method.borrowed
+    pub fn of(value: impl Visitable + 'a) -> Self {
+        Value::new(ValueKind::Owned(Box::new(value)))
+    }
+
+    /// Returns a `Value` borrowing `value`, which may be a dynamically sized
+    /// type such as a slice or a `dyn Visitable`.
+    ///
+    /// [`Value::borrowed`] requires a sized value, since it stores `value` as
+    /// a `&dyn Visitable`. This instead stores the reference itself, as
+    /// with [`Value::of`], so it allocates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value::{Value, Visitable};
+    /// let ports: &[u16] = &[80, 443];
+    /// assert_eq!(Value::from_ref(ports).render_json(), "[80,443]");
+    ///
+    /// let erased: &dyn Visitable = &true;
+    /// assert_eq!(Value::from_ref(erased).render_json(), "true");
+    /// ```
+    ///
+    /// [`Value::borrowed`]: #method.borrowed
+    /// [`Value::of`]: #method.of
+    pub fn from_ref<T>(value: &'a T) -> Self
+    where
+        T: Visitable + ?Sized,
+    {
+        Value::of(value)
+    }
+
+    pub fn owned<V, B>(value: &B) -> Self
+    where
+        B: ToOwned<Owned = V>,
+        V: Visitable + Borrow<B> + 'a,
+    {
+        Value::new(ValueKind::Owned(Box::new(value.to_owned())))
+    }
+
+    pub fn with_visit<T, F>(value: T, visit: F) -> Self
+    where
+        T: 'a,
+        F: Fn(&T, &mut dyn Visit) -> VisitResult + 'a,
+    {
+        struct WithVisit<T, F> {
+            visit: F,
+            value: T,
+        }
+
+        impl<T, F> Visitable for WithVisit<T, F>
+        where
+            F: Fn(&T, &mut dyn Visit) -> VisitResult,
+        {
+            fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+                (self.visit)(&self.value, visitor)
+            }
+        }
+
+        let with_visit = WithVisit {
+            value,
+            visit,
+        };
+        Value::new(ValueKind::Owned(Box::new(with_visit)))
+    }
+
+    /// Returns a new `Value` which visits the items yielded by `iter` as a
+    /// list, without collecting them first.
+    ///
+    /// The iterator is cloned each time the value is visited. See
+    /// [`VisitableIter`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value::Value;
+    /// let names = ["alice", "bob"];
+    /// let value = Value::list_from_iter(names.iter().map(|name| name.len()));
+    /// assert_eq!(value.render_json(), "[5,3]");
+    /// ```
+    ///
+    /// [`VisitableIter`]: struct.VisitableIter.html
+    pub fn list_from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator + Clone + 'a,
+        I::Item: Visitable,
+    {
+        Value::new(ValueKind::Owned(Box::new(VisitableIter::new(iter))))
+    }
+
+    /// Returns a new `Value` which visits the key-value pairs yielded by
+    /// `iter` as a map, without collecting them first.
+    ///
+    /// As with [`list_from_iter`], the iterator is cloned each time the value
+    /// is visited. To build a map incrementally, use [`MapValue`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value::Value;
+    /// let names = ["alice", "bob"];
+    /// let value = Value::map_from_iter(names.iter().map(|name| (name, name.len())));
+    /// assert_eq!(value.render_json(), r#"{"alice":5,"bob":3}"#);
+    /// ```
+    ///
+    /// [`list_from_iter`]: #method.list_from_iter
+    /// [`MapValue`]: struct.MapValue.html
+    pub fn map_from_iter<I, K, V>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)> + Clone + 'a,
+        K: Visitable,
+        V: Visitable,
+    {
+        Value::new(ValueKind::Owned(Box::new(MapIter { iter })))
+    }
+
+    /// Returns a new `Value` borrowing the data referenced by this `Value`.
+    ///
+    /// This allows a `Value` that is only available by reference (such as one
+    /// stored in a [`Record`]) to be passed to methods that take a `Value` by
+    /// value, such as [`Visit::visit_kv`], without copying the underlying
+    /// data.
+    ///
+    /// [`Record`]: ../record/struct.Record.html
+    /// [`Visit::visit_kv`]: ../visitor/trait.Visit.html#tymethod.visit_kv
+    pub fn by_ref(&self) -> Value<'_> {
+        let inner = match self.inner {
+            ValueKind::Borrowed(v) => ValueKind::Borrowed(v),
+            ValueKind::Owned(ref v) => ValueKind::Borrowed(v.as_ref()),
+            ValueKind::Display(v) => ValueKind::Display(v),
+            ValueKind::Debug(v) => ValueKind::Debug(v),
+        };
+        Value {
+            inner,
+            #[cfg(feature = "location")]
+            location: self.location,
+        }
+    }
+
+    /// Returns the type name this value is visited with, if it is known
+    /// without visiting the value.
+    ///
+    /// This allows a `Visit` implementation which only acts on certain types
+    /// to skip other values without traversing them. A value which returns
+    /// `None` may still be a named type; see [`Visitable::type_name`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::{Value, Visitable};
+    /// # use valuable::visitor::{Visit, VisitResult};
+    /// struct Point(i64, i64);
+    ///
+    /// impl Visitable for Point {
+    ///     fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+    ///         let fields = vec![Value::borrowed(&self.0), Value::borrowed(&self.1)];
+    ///         visitor.visit_tuple_struct("Point", fields)
+    ///     }
+    ///
+    ///     fn type_name(&self) -> Option<&'static str> {
+    ///         Some("Point")
+    ///     }
+    /// }
+    ///
+    /// let point = Box::new(Point(1, 2));
+    /// assert_eq!(Value::borrowed(&point).type_name(), Some("Point"));
+    /// assert_eq!(Value::borrowed(&1).type_name(), None);
+    /// ```
+    ///
+    /// [`Visitable::type_name`]: trait.Visitable.html#method.type_name
+    pub fn type_name(&self) -> Option<&'static str> {
+        match self.inner {
+            ValueKind::Borrowed(v) => v.type_name(),
+            ValueKind::Owned(ref v) => v.type_name(),
+            ValueKind::Display(_) | ValueKind::Debug(_) => None,
+        }
+    }
+
+    pub fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self.inner {
+            ValueKind::Borrowed(v) => v.visit(visitor),
+            ValueKind::Owned(ref v) => v.as_ref().visit(visitor),
+            ValueKind::Display(ref v) => visitor.visit_display(v),
+            ValueKind::Debug(ref v) => visitor.visit_fmt(format_args!("{:?}", v)),
+        }
+    }
+
+    /// Visits this value as a complete top-level value, then calls
+    /// [`Visit::finish`] on the visitor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{value, value::Visitable, visitor::{Visit, VisitResult}};
+    /// #[derive(Default)]
+    /// struct Count(usize);
+    ///
+    /// impl Visit for Count {
+    ///     fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn finish(&mut self) -> VisitResult {
+    ///         self.0 += 1;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut count = Count::default();
+    /// value!(1).visit(&mut count).unwrap();
+    /// value!(2).visit_and_finish(&mut count).unwrap();
+    /// assert_eq!(count.0, 1);
+    /// ```
+    ///
+    /// [`Visit::finish`]: ../visitor/trait.Visit.html#method.finish
+    pub fn visit_and_finish(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.visit(visitor)?;
+        visitor.finish()
+    }
+
+    /// Returns a new `Value` which visits this value with every string it
+    /// contains replaced by the result of calling `f` on that string.
+    ///
+    /// Only strings visited with [`Visit::visit_str`] are transformed; map
+    /// keys, struct field names, and formatted values (such as those
+    /// constructed with `Value::display`) are passed through unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value;
+    /// let value = value!({ "name": "ELIZA" }).map_strs(|s| s.to_lowercase());
+    /// assert_eq!(value.render_json(), r#"{"name":"eliza"}"#);
+    /// ```
+    ///
+    /// [`Visit::visit_str`]: ../visitor/trait.Visit.html#method.visit_str
+    pub fn map_strs<F, S>(self, f: F) -> Value<'a>
+    where
+        F: Fn(&str) -> S + 'a,
+        S: AsRef<str> + 'a,
+    {
+        Value::new(ValueKind::Owned(Box::new(MapStrs { value: self, f })))
+    }
+
+    /// Returns a new `Value` which visits this value with its type name
+    /// replaced by `name`.
+    ///
+    /// Only the type name of this value itself is replaced; the names of any
+    /// structs nested inside it are unchanged. If this value is not a named
+    /// type, it is visited as-is.
+    pub fn rename_type(self, name: &'a str) -> Value<'a> {
+        Value::new(ValueKind::Owned(Box::new(RenameType { value: self, name })))
+    }
+
+    /// Returns a new `Value` which visits this value with an annotation.
+    ///
+    /// The annotation is visited with [`Visit::visit_annotation`] before the
+    /// value itself. Several annotations may be attached by calling this
+    /// repeatedly; each wraps the value it is attached to, so the most
+    /// recently attached annotation is visited first. Visitors which do not
+    /// handle annotations ignore them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::ValueExt;
+    /// # use valuable::value;
+    /// let latency = value!(12).annotate("unit", "ms");
+    ///
+    /// // JSON has no representation for annotations.
+    /// assert_eq!(latency.render_json(), "12");
+    /// ```
+    ///
+    /// [`Visit::visit_annotation`]: ../visitor/trait.Visit.html#method.visit_annotation
+    pub fn annotate(self, key: &'a str, annotation: &'a str) -> Value<'a> {
+        Value::new(ValueKind::Owned(Box::new(Annotated {
+            value: self,
+            key,
+            annotation,
+        })))
+    }
+
+    /// Returns a new `Value` which is only visited when `sampler` selects
+    /// it.
+    ///
+    /// Each time the returned value is visited, the visit is counted by
+    /// `sampler`. Visits which are not selected visit the placeholder string
+    /// [`NOT_SAMPLED`] instead, without visiting this value at all. See the
+    /// [`sample`] module for details.
+    ///
+    /// [`NOT_SAMPLED`]: ../sample/constant.NOT_SAMPLED.html
+    /// [`sample`]: ../sample/index.html
+    pub fn sampled(self, sampler: &'a Sampler) -> Value<'a> {
+        Value::new(ValueKind::Owned(Box::new(Sampled {
+            value: self,
+            sampler,
+        })))
+    }
+
+    /// Renders this value into a `String` using [`DebugVisit`].
+    ///
+    /// If visiting the value fails, the output written before the error
+    /// occurred is returned.
+    ///
+    /// [`DebugVisit`]: ../debug/struct.DebugVisit.html
+    pub fn render_debug(&self) -> String {
+        let mut visitor = DebugVisit::new(String::new());
+        let _ = self.visit(&mut visitor);
+        visitor.into_inner()
+    }
+}
+
+/// Source locations.
+///
+/// These methods require the `location` feature.
+#[cfg(feature = "location")]
+impl Value<'_> {
+    /// Attaches the location of the caller to this value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::value::Value;
+    /// let value = Value::borrowed(&"hello").located();
+    /// assert_eq!(value.location().unwrap().file(), file!());
+    /// ```
+    #[track_caller]
+    pub fn located(self) -> Self {
+        self.with_location(Location::caller())
+    }
+
+    /// Attaches the source location `location` to this value.
+    pub fn with_location(self, location: &'static Location<'static>) -> Self {
+        Value {
+            location: Some(location),
+            ..self
+        }
+    }
+
+    /// Returns the source location attached to this value, if there is one.
+    ///
+    /// Locations are preserved by [`by_ref`], so a visitor may query the
+    /// location of the values passed to `visit_kv` by the extension methods
+    /// on `dyn Visit`. Combinators which wrap a value (such as [`map_strs`])
+    /// and visitor wrappers which wrap the values they forward do not
+    /// preserve locations.
+    ///
+    /// [`by_ref`]: #method.by_ref
+    /// [`map_strs`]: #method.map_strs
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        self.location
+    }
+}
+
+/// Writes the value using [`DebugVisit`], with the [`FormatOptions`] of the
+/// formatter.
+///
+/// So `{:#?}` writes the value over several indented lines, and a width or
+/// precision (as in `{:8.2?}`) pads numbers and rounds floats, as it would
+/// for types implementing `fmt::Debug`.
+///
+/// If visiting the value fails for any reason other than an error writing
+/// to the formatter, the output written before the error occurred is kept,
+/// as with [`render_debug`].
+///
+/// [`DebugVisit`]: ../debug/struct.DebugVisit.html
+/// [`FormatOptions`]: ../debug/struct.FormatOptions.html
+/// [`render_debug`]: #method.render_debug
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = FormatOptions::from_formatter(f);
+        let mut visitor = DebugVisit::new(f).with_options(options);
+        match self.visit(&mut visitor) {
+            Err(error) if error.is_fmt() => Err(fmt::Error),
+            _ => Ok(()),
+        }
+    }
+}
+
+macro_rules! impl_values {
+    ( $( $visit:ident( $( $whatever:tt)+ ) ),+ ) => {
+        $(
+            impl_value!{ $visit( $( $whatever )+ ) }
+        )+
+    }
+}
+macro_rules! impl_value {
+    ( $visit:ident( $( $value_ty:ty ),+ ) ) => {
+        $(
+            impl Visitable for $value_ty {
+                fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+                    visitor.$visit(*self)
+                }
+
+                fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+                    visitor.$visit(*self)
+                }
+            }
+        )+
+    };
+    ( $visit:ident( $( $value_ty:ty ),+ as $as_ty:ty) ) => {
+        $(
+            impl Visitable for $value_ty {
+                fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+                    visitor.$visit(*self as $as_ty)
+                }
+
+                fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+                    visitor.$visit(*self as $as_ty)
+                }
+            }
+        )+
+    };
+}
+
+impl_values! {
+    visit_u8(u8),
+    visit_u16(u16),
+    visit_u32(u32),
+    visit_uint(u64),
+    visit_uint(usize as u64),
+    visit_i8(i8),
+    visit_i16(i16),
+    visit_i32(i32),
+    visit_int(i64),
+    visit_int(isize as i64),
+    visit_f32(f32),
+    visit_float(f64),
+    visit_bool(bool)
+}
+
+/// Implements `Visitable` for atomic types by loading them with `Relaxed`
+/// ordering and visiting the loaded value.
+macro_rules! impl_atomics {
+    ( $( $(#[$meta:meta])* $atomic:ident ),+ $(,)? ) => {
+        $(
+            $(#[$meta])*
+            impl Visitable for atomic::$atomic {
+                fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+                    self.load(atomic::Ordering::Relaxed).visit(visitor)
+                }
+
+                fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+                    self.load(atomic::Ordering::Relaxed).visit_with(visitor)
+                }
+            }
+        )+
+    };
+}
+
+impl_atomics! {
+    AtomicBool,
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    #[cfg(target_has_atomic = "64")]
+    AtomicU64,
+    AtomicUsize,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    #[cfg(target_has_atomic = "64")]
+    AtomicI64,
+    AtomicIsize,
+}
+
+/// A byte string, visited as binary data using [`Visit::visit_bytes`].
+///
+/// Slices and `Vec`s of `u8` are visited as lists of integers, like slices
+/// of any other type. Wrapping them in `Bytes` indicates that they are binary
+/// data instead, allowing visitors for formats with a binary data type to
+/// represent them as such.
+///
+/// [`Visit::visit_bytes`]: ../visitor/trait.Visit.html#method.visit_bytes
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl Visitable for Bytes<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_bytes(self.0)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_bytes(self.0)
+    }
+}
+
+/// A list whose elements are produced lazily by an iterator.
+///
+/// Each time a `VisitableIter` is visited, it clones the wrapped
+/// `IntoIterator` and visits each item it yields as an element of a list.
+/// This allows the results of iterator adaptors (such as `map` and `filter`)
+/// to be visited without first collecting them into a `Vec`. Since the
+/// iterator is cloned, it should be cheap to clone, as iterators over
+/// borrowed collections are.
+///
+/// A `VisitableIter` may also be constructed using
+/// [`Value::list_from_iter`].
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::value::{Value, VisitableIter};
+/// let ids = [1, 2, 3, 4];
+/// let even = VisitableIter::new(ids.iter().filter(|id| *id % 2 == 0));
+/// assert_eq!(Value::borrowed(&even).render_json(), "[2,4]");
+/// ```
+///
+/// [`Value::list_from_iter`]: struct.Value.html#method.list_from_iter
+#[derive(Clone, Debug)]
+pub struct VisitableIter<I> {
+    iter: I,
+}
+
+impl<I> VisitableIter<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: Visitable,
+{
+    /// Returns a new `VisitableIter` which visits the items yielded by
+    /// `iter`.
+    pub fn new(iter: I) -> Self {
+        Self { iter }
+    }
+
+    /// Consumes the `VisitableIter`, returning the wrapped iterator.
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I> Visitable for VisitableIter<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.open_list()?;
+        for (idx, v) in self.iter.clone().into_iter().enumerate() {
+            v.visit(visitor).map_err(|e| e.in_index(idx))?;
+        }
+        visitor.close_list()
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.open_list()?;
+        for (idx, v) in self.iter.clone().into_iter().enumerate() {
+            v.visit_with(visitor).map_err(|e| e.in_index(idx))?;
+        }
+        visitor.close_list()
+    }
+}
+
+/// A map built from key-value pairs at runtime.
+///
+/// `MapValue` allows code which doesn't have a concrete type to represent
+/// some structured data (such as a set of fields determined by
+/// configuration) to visit it as a map. Entries are visited in the order
+/// they were added.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::value::{MapValue, Value};
+/// let user = "alice";
+/// let attempts = 3;
+/// let map = MapValue::new()
+///     .entry("user", Value::borrowed(&user))
+///     .entry("attempts", Value::borrowed(&attempts));
+/// assert_eq!(Value::borrowed(&map).render_json(), r#"{"user":"alice","attempts":3}"#);
+/// ```
+#[derive(Default)]
+pub struct MapValue<'a> {
+    entries: Vec<(&'a str, Value<'a>)>,
+}
+
+/// A list built from values of any type at runtime.
+///
+/// A `Vec<T>` can only hold values of a single type, so a `ListValue`
+/// allows lists whose items have different types (such as the arguments of
+/// a call, or the errors in a chain) to be built one item at a time and
+/// visited as a list. Items are visited in the order they were added.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::value::{ListValue, Value};
+/// let (path, mode, create) = ("/tmp/out", 0o644, true);
+/// let mut args = ListValue::new()
+///     .item(Value::borrowed(&path))
+///     .item(Value::borrowed(&mode));
+/// args.push(Value::borrowed(&create));
+/// assert_eq!(Value::borrowed(&args).render_json(), r#"["/tmp/out",420,true]"#);
+/// ```
+#[derive(Default)]
+pub struct ListValue<'a> {
+    items: Vec<Value<'a>>,
+}
+
+/// A struct built from named fields at runtime.
+///
+/// Like [`MapValue`], a `StructValue` allows data whose shape is only known
+/// at runtime to be visited, but visits it as a named struct, so that
+/// visitors which record type names (such as [`DebugVisit`]) may do so.
+/// Fields are visited in the order they were added.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::{StructValue, Value};
+/// let (method, path) = ("GET", "/health");
+/// let request = StructValue::named("Request")
+///     .field("method", Value::borrowed(&method))
+///     .field("path", Value::borrowed(&path));
+/// assert_eq!(
+///     Value::borrowed(&request).render_debug(),
+///     r#"Request { method: "GET", path: "/health" }"#,
+/// );
+/// ```
+///
+/// [`MapValue`]: struct.MapValue.html
+/// [`DebugVisit`]: ../debug/struct.DebugVisit.html
+pub struct StructValue<'a> {
+    name: &'a str,
+    fields: Vec<(&'a str, Value<'a>)>,
+}
+
+/// A map whose entries are produced lazily by an iterator.
+struct MapIter<I> {
+    iter: I,
+}
+
+// === impl MapValue ===
+
+impl<'a> MapValue<'a> {
+    /// Returns a new, empty `MapValue`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry with the given `key` and `value` to the map.
+    pub fn entry(mut self, key: &'a str, value: Value<'a>) -> Self {
+        self.entries.push((key, value));
+        self
+    }
+
+    /// Adds an entry with the given `key` and `value` to the map, in place.
+    pub fn insert(&mut self, key: &'a str, value: Value<'a>) {
+        self.entries.push((key, value));
+    }
+
+    /// Adds an entry with the given `key` to the map if `value` is `Some`.
+    ///
+    /// This allows sparse maps to omit the entries which have no value,
+    /// rather than visiting them as unit values.
+    pub fn entry_opt(mut self, key: &'a str, value: Option<Value<'a>>) -> Self {
+        self.entries.extend(value.map(|value| (key, value)));
+        self
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Visitable for MapValue<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_map(
+            self.entries
+                .iter()
+                .map(|(k, v)| (Value::borrowed(k), v.by_ref())),
+        )
+    }
+}
+
+impl<'a> Extend<(&'a str, Value<'a>)> for MapValue<'a> {
+    fn extend<I: IntoIterator<Item = (&'a str, Value<'a>)>>(&mut self, iter: I) {
+        self.entries.extend(iter)
+    }
+}
+
+impl<'a> std::iter::FromIterator<(&'a str, Value<'a>)> for MapValue<'a> {
+    fn from_iter<I: IntoIterator<Item = (&'a str, Value<'a>)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Debug for MapValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapValue")
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+// === impl ListValue ===
+
+impl<'a> ListValue<'a> {
+    /// Returns a new, empty `ListValue`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `value` to the end of the list.
+    pub fn item(mut self, value: Value<'a>) -> Self {
+        self.items.push(value);
+        self
+    }
+
+    /// Adds `value` to the end of the list, in place.
+    pub fn push(&mut self, value: Value<'a>) {
+        self.items.push(value);
+    }
+
+    /// Returns the number of items in the list.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if the list has no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl Visitable for ListValue<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.items.visit(visitor)
+    }
+}
+
+impl<'a> Extend<Value<'a>> for ListValue<'a> {
+    fn extend<I: IntoIterator<Item = Value<'a>>>(&mut self, iter: I) {
+        self.items.extend(iter)
+    }
+}
+
+impl<'a> std::iter::FromIterator<Value<'a>> for ListValue<'a> {
+    fn from_iter<I: IntoIterator<Item = Value<'a>>>(iter: I) -> Self {
+        Self {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Debug for ListValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListValue")
+            .field("len", &self.items.len())
+            .finish()
+    }
+}
+
+// === impl StructValue ===
+
+impl<'a> StructValue<'a> {
+    /// Returns a new `StructValue` with the type name `name` and no fields.
+    pub fn named(name: &'a str) -> Self {
+        Self {
+            name,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field with the given `name` and `value` to the struct.
+    pub fn field(mut self, name: &'a str, value: Value<'a>) -> Self {
+        self.fields.push((name, value));
+        self
+    }
+
+    /// Adds a field with the given `name` and `value` to the struct, in
+    /// place.
+    pub fn insert(&mut self, name: &'a str, value: Value<'a>) {
+        self.fields.push((name, value));
+    }
+
+    /// Adds a field with the given `name` to the struct if `value` is
+    /// `Some`, as with [`MapValue::entry_opt`].
+    ///
+    /// [`MapValue::entry_opt`]: struct.MapValue.html#method.entry_opt
+    pub fn field_opt(mut self, name: &'a str, value: Option<Value<'a>>) -> Self {
+        self.fields.extend(value.map(|value| (name, value)));
+        self
+    }
+
+    /// Returns the struct's type name.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the number of fields in the struct.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the struct has no fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl Visitable for StructValue<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct(
+            self.name,
+            self.fields.iter().map(|(k, v)| (*k, v.by_ref())),
+        )
+    }
+}
+
+impl<'a> Extend<(&'a str, Value<'a>)> for StructValue<'a> {
+    fn extend<I: IntoIterator<Item = (&'a str, Value<'a>)>>(&mut self, iter: I) {
+        self.fields.extend(iter)
+    }
+}
+
+impl fmt::Debug for StructValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StructValue")
+            .field("name", &self.name)
+            .field("len", &self.fields.len())
+            .finish()
+    }
+}
+
+// === impl MapIter ===
+
+impl<I, K, V> Visitable for MapIter<I>
+where
+    I: IntoIterator<Item = (K, V)> + Clone,
+    K: Visitable,
+    V: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.open_map()?;
+        for (k, v) in self.iter.clone() {
+            let k = Value::borrowed(&k);
+            visitor
+                .visit_kv(k.by_ref(), Value::borrowed(&v))
+                .map_err(|e| e.in_key(&k))?;
+        }
+        visitor.close_map()
+    }
+}
+
+impl Visitable for &str {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_str(self)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_str(self)
+    }
+}
+
+impl Visitable for String {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_str(self)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_str(self)
+    }
+}
+
+impl Visitable for fmt::Arguments<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_fmt(*self)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_fmt(*self)
+    }
+}
+
+impl Visitable for () {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_unit()
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_unit()
+    }
+}
+
+/// A `PhantomData` is visited as a unit value, regardless of whether `T`
+/// implements `Visitable`.
+impl<T: ?Sized> Visitable for PhantomData<T> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_unit()
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_unit()
+    }
+}
+
+impl Visitable for PhantomPinned {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_unit()
+    }
+}
+
+impl Visitable for Infallible {
+    fn visit(&self, _: &mut dyn Visit) -> VisitResult {
+        match *self {}
+    }
+}
+
+/// A `Duration` is visited as a formatted value such as `1.5s` if the
+/// visitor [is human-readable], and as its number of nanoseconds (saturating
+/// at `u64::MAX`) otherwise.
+///
+/// [is human-readable]: ../visitor/trait.Visit.html#method.is_human_readable
+impl Visitable for Duration {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if visitor.is_human_readable() {
+            return visitor.visit_fmt(format_args!("{:?}", self));
+        }
+        visitor.visit_uint(u64::try_from(self.as_nanos()).unwrap_or(u64::MAX))
+    }
+}
+
+impl<T> Visitable for [T]
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_list(self.iter().map(Value::borrowed))
+    }
+}
+
+impl<T> Visitable for Vec<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.as_slice().visit(visitor)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_list_of(self)
+    }
+}
+
+// `Value` is not itself `Visitable`, so lists of values have impls of their
+// own, allowing heterogeneous lists to be built as `[Value; N]`.
+impl Visitable for [Value<'_>] {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_list(self.iter().map(Value::by_ref))
+    }
+}
+
+impl<const N: usize> Visitable for [Value<'_>; N] {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self[..].visit(visitor)
+    }
+}
+
+impl Visitable for Vec<Value<'_>> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.as_slice().visit(visitor)
+    }
+}
+
+impl<K, V, S> Visitable for collections::HashMap<K, V, S>
+where
+    K: Visitable + Hash + Eq,
+    V: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_map(self.iter().map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))))
+    }
+}
+
+impl<T, S> Visitable for collections::HashSet<T, S>
+where
+    T: Visitable + Hash + Eq,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_tuple(self.iter().map(Value::borrowed))
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_tuple_of(self)
+    }
+}
+
+impl<K, V> Visitable for collections::BTreeMap<K, V>
+where
+    K: Visitable + Eq,
+    V: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_map(self.iter().map(|(k, v)| (Value::borrowed(k), Value::borrowed(v))))
+    }
+}
+
+impl<T> Visitable for collections::BTreeSet<T>
+where
+    T: Visitable + Eq,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_tuple(self.iter().map(Value::borrowed))
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_tuple_of(self)
+    }
+}
+
+impl<T> Visitable for collections::LinkedList<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_list(self.iter().map(Value::borrowed))
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_list_of(self)
+    }
+}
+
+impl<T> Visitable for collections::VecDeque<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_list(self.iter().map(Value::borrowed))
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_list_of(self)
+    }
+}
+
+impl<T> Visitable for collections::BinaryHeap<T>
+where
+    T: Visitable + Ord,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        // NOTE: the values will *not* be visited in order --- is that something
+        // we want to guarantee?
+        visitor.visit_list(self.iter().map(Value::borrowed))
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        visitor.visit_list_of(self)
+    }
+}
+
+impl<'a, T> Visitable for &'a T
+where
+    T: Visitable + ?Sized + 'a,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        (*self).visit(visitor)
+    }
+
+    fn rust_type_name(&self) -> &'static str {
+        (*self).rust_type_name()
+    }
+
+    fn type_name(&self) -> Option<&'static str> {
+        (*self).type_name()
+    }
+}
+
+impl<'a, T> Visitable for &'a mut T
+where
+    T: Visitable + ?Sized + 'a,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        (**self).visit(visitor)
+    }
+
+    fn rust_type_name(&self) -> &'static str {
+        (**self).rust_type_name()
+    }
+
+    fn type_name(&self) -> Option<&'static str> {
+        (**self).type_name()
+    }
+}
+
+impl<T> Visitable for Box<T>
+where
+    T: Visitable + ?Sized,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        (**self).visit(visitor)
+    }
+
+    fn rust_type_name(&self) -> &'static str {
+        (**self).rust_type_name()
+    }
+
+    fn type_name(&self) -> Option<&'static str> {
+        (**self).type_name()
+    }
+}
+
+impl<T> Visitable for Arc<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        // Shared values are visited with `visit_any`, so that visitors can
+        // identify them by address (e.g. to detect cycles).
+        visitor.visit_any(&**self)
+    }
+
+    fn type_name(&self) -> Option<&'static str> {
+        (**self).type_name()
+    }
+}
+
+impl<T> Visitable for Rc<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_any(&**self)
+    }
+
+    fn type_name(&self) -> Option<&'static str> {
+        (**self).type_name()
+    }
+}
+
+impl<T> Visitable for Cell<T>
+where
+    T: Visitable + Copy,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.get().visit(visitor)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        self.get().visit_with(visitor)
+    }
+}
+
+/// A `RefCell` is visited by borrowing its contents. If the `RefCell` is
+/// already mutably borrowed (for instance, because it is being visited from
+/// inside the code that is modifying it), the placeholder string
+/// `"<borrowed>"` is visited instead, rather than panicking.
+impl<T> Visitable for RefCell<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self.try_borrow() {
+            Ok(value) => value.visit(visitor),
+            Err(_) => visitor.visit_str("<borrowed>"),
+        }
+    }
+}
+
+impl<T> Visitable for Saturating<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        self.0.visit(visitor)
+    }
+
+    fn visit_with<V: Visit>(&self, visitor: &mut V) -> VisitResult {
+        self.0.visit_with(visitor)
+    }
+}
+
+/// A `Mutex` is visited by locking it with `try_lock`, so visiting never
+/// blocks (or deadlocks, if the visit happens while the current thread holds
+/// the lock). If the lock is held elsewhere, the placeholder string
+/// `"<locked>"` is visited instead. A poisoned `Mutex` is still visited,
+/// since visiting the value cannot observe it being modified.
+impl<T> Visitable for Mutex<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self.try_lock() {
+            Ok(value) => value.visit(visitor),
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().visit(visitor),
+            Err(TryLockError::WouldBlock) => visitor.visit_str("<locked>"),
+        }
+    }
+}
+
+/// An `RwLock` is visited by acquiring a read lock with `try_read`, so
+/// visiting never blocks. If the lock is held by a writer, the placeholder
+/// string `"<locked>"` is visited instead. A poisoned `RwLock` is still
+/// visited.
+impl<T> Visitable for RwLock<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self.try_read() {
+            Ok(value) => value.visit(visitor),
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().visit(visitor),
+            Err(TryLockError::WouldBlock) => visitor.visit_str("<locked>"),
+        }
+    }
+}
+
+/// A `Range` is visited as a struct named `Range`, with `start` and `end`
+/// fields. The other range types are visited in the same way, with only the
+/// fields they have; a `RangeFull` is visited as a unit value named
+/// `RangeFull`.
+impl<T> Visitable for Range<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct(
+            "Range",
+            [
+                ("start", Value::borrowed(&self.start)),
+                ("end", Value::borrowed(&self.end)),
+            ],
+        )
+    }
+}
+
+impl<T> Visitable for RangeInclusive<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct(
+            "RangeInclusive",
+            [
+                ("start", Value::borrowed(self.start())),
+                ("end", Value::borrowed(self.end())),
+            ],
+        )
+    }
+}
+
+impl<T> Visitable for RangeFrom<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct("RangeFrom", [("start", Value::borrowed(&self.start))])
+    }
+}
+
+impl<T> Visitable for RangeTo<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct("RangeTo", [("end", Value::borrowed(&self.end))])
+    }
+}
+
+impl<T> Visitable for RangeToInclusive<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_struct("RangeToInclusive", [("end", Value::borrowed(&self.end))])
+    }
+}
+
+impl Visitable for RangeFull {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.named_type("RangeFull")?;
+        visitor.visit_unit()
+    }
+}
+
+/// A `Bound` is visited as a derived enum with the default representation
+/// would be visited: `Included` and `Excluded` as tuple structs containing
+/// the bound's value, and `Unbounded` as a unit value named `Unbounded`
+/// (which formats that discard type names, such as JSON, write as `null`).
+impl<T> Visitable for Bound<T>
+where
+    T: Visitable,
+{
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        match self {
+            Bound::Included(value) => {
+                visitor.visit_tuple_struct("Included", [Value::borrowed(value)])
+            }
+            Bound::Excluded(value) => {
+                visitor.visit_tuple_struct("Excluded", [Value::borrowed(value)])
+            }
+            Bound::Unbounded => {
+                visitor.named_type("Unbounded")?;
+                visitor.visit_unit()
+            }
+        }
+    }
+}
@@ -0,0 +1,1661 @@
+use crate::{
+    layer::VisitLayer,
+    pool,
+    record::{FieldId, Record},
+    value::{Value, Visitable},
+};
+use std::{error, fmt, io, ops};
+
+pub type VisitResult = Result<(), Error>;
+
+/// An error returned by a `Visit` implementation.
+///
+/// When an error occurs while visiting a value nested inside a map, struct,
+/// list, or tuple, the extension methods on `dyn Visit` (such as
+/// `visit_map` and `visit_struct`) record where the error occurred as it is
+/// propagated. The location is available from [`Error::path`], and is
+/// included in the error's `Display` output.
+///
+/// [`Error::path`]: struct.Error.html#method.path
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    path: Vec<PathSegment>,
+}
+
+/// A segment of the path to the value at which an [`Error`] occurred.
+///
+/// [`Error`]: struct.Error.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PathSegment {
+    /// A named struct field or record field, or a map key.
+    ///
+    /// Map keys which are not strings are represented by their JSON
+    /// representation.
+    Field(String),
+    /// The index of a list or tuple element.
+    Index(usize),
+}
+
+/// What the container extension methods on `dyn Visit` do when visiting an
+/// element of a container returns an error.
+///
+/// Visitors return their preferred mode from [`Visit::error_mode`].
+///
+/// [`Visit::error_mode`]: trait.Visit.html#method.error_mode
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorMode {
+    /// Return the error immediately, without closing the container.
+    ///
+    /// This is the default. It suits visitors whose output is discarded
+    /// when visiting fails, such as visitors which build an in-memory
+    /// value.
+    Abort,
+    /// Close the container before returning the error.
+    ///
+    /// Each enclosing container is closed in turn as the error propagates,
+    /// so a visitor which writes output incrementally (such as a JSON
+    /// writer) is not left with unterminated containers. Any error returned
+    /// while closing is ignored in favor of the original error. A container
+    /// which failed to open is never closed.
+    BestEffortClose,
+}
+
+/// The `Visit` methods which a visitor handles itself, rather than through
+/// their default implementations.
+///
+/// The default implementations of many `Visit` methods lose information:
+/// narrow integers are widened, byte strings are visited as lists of `u8`s,
+/// and type names are discarded. Since new methods may be added to `Visit`
+/// over time, a visitor written before a method was added will always use
+/// its default. So a producer which has a better fallback than the default
+/// (such as writing a byte string as hex, for a visitor which does not
+/// handle byte strings) may check the visitor's [`Visit::capabilities`]
+/// before choosing how to visit a value.
+///
+/// Each capability is a flag, and capabilities may be combined with `|`.
+/// Visitors should only return the capabilities they actually handle;
+/// returning [`Capabilities::all`] would claim support for capabilities
+/// added in the future.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::{json::JsonVisit, visitor::{Capabilities, Visit}};
+/// let visit = JsonVisit::new(String::new());
+/// assert!(visit.capabilities().contains(Capabilities::MAPS));
+/// assert!(!visit.capabilities().contains(Capabilities::BYTES));
+/// ```
+///
+/// [`Visit::capabilities`]: trait.Visit.html#method.capabilities
+/// [`Capabilities::all`]: #method.all
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Capabilities(u32);
+
+/// Configuration shared by the visitors in this crate which serialize
+/// values.
+///
+/// Currently, this only selects whether the output is human-readable, like
+/// serde's `Serializer::is_human_readable`. A serializer configured to be
+/// human-readable includes [`Capabilities::HUMAN_READABLE`] in its
+/// [`Visit::capabilities`], so that `Visitable` implementations may check
+/// [`is_human_readable`] to choose between a readable form of their value
+/// and a more compact one.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::value::ValueExt;
+/// # use valuable::{json::JsonVisit, value::Value, visitor::SerializeConfig};
+/// # use std::time::Duration;
+/// let timeout = Duration::from_millis(1500);
+/// assert_eq!(Value::borrowed(&timeout).render_json(), "1500000000");
+///
+/// let config = SerializeConfig::new().with_human_readable(true);
+/// let mut visit = JsonVisit::new(String::new()).with_config(config);
+/// Value::borrowed(&timeout).visit(&mut visit).unwrap();
+/// assert_eq!(visit.into_inner(), r#""1.5s""#);
+/// ```
+///
+/// [`Capabilities::HUMAN_READABLE`]: struct.Capabilities.html#associatedconstant.HUMAN_READABLE
+/// [`Visit::capabilities`]: trait.Visit.html#method.capabilities
+/// [`is_human_readable`]: trait.Visit.html#method.is_human_readable
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SerializeConfig {
+    human_readable: bool,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    /// Writing formatted output failed.
+    Fmt,
+    /// A container was closed without being opened, or closed with the
+    /// wrong `close_*` method.
+    Unbalanced,
+    /// A value was nested more deeply than a `DepthLimit` allows.
+    DepthLimit(usize),
+    /// A value's estimated size exceeded a `SizeEstimate`'s limit.
+    SizeLimit(usize),
+    /// The visitor does not support visiting values of the given kind.
+    Unsupported(&'static str),
+    /// Reading or writing an I/O stream failed.
+    Io(io::Error),
+    /// A visitor wrapped in a `CatchUnwind` panicked, with the given message
+    /// if the panic had one.
+    Panicked(Option<String>),
+}
+
+/// An object-safe streaming visitor.
+///
+/// Only `visit_any` must be implemented. The scalar methods default to
+/// calling `visit_any`, and the container methods default to returning an
+/// [unsupported] error, so a visitor which only handles some kinds of value
+/// need only implement the methods for those.
+///
+/// [unsupported]: struct.Error.html#method.unsupported
+pub trait Visit {
+    /// Visit an unsigned integer value.
+    ///
+    /// This defaults to calling `self.visit_any()`; implementations wishing to
+    /// provide behaviour specific to unsigned integers may override the default
+    /// implementation.
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        self.visit_any(&value)
+    }
+
+    /// Visit a signed integer value.
+    ///
+    /// This defaults to calling `self.visit_any()`; implementations wishing to
+    /// provide behaviour specific to signed integers may override the default
+    /// implementation.
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        self.visit_any(&value)
+    }
+
+    /// Visit a floating-point value.
+    ///
+    /// This defaults to calling `self.visit_any()`; implementations wishing to
+    /// provide behaviour specific to floating-point values may override the
+    /// default implementation.
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        self.visit_any(&value)
+    }
+
+    /// Visit an unsigned 8-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_uint()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_u8(&mut self, value: u8) -> VisitResult {
+        self.visit_uint(value.into())
+    }
+
+    /// Visit an unsigned 16-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_uint()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_u16(&mut self, value: u16) -> VisitResult {
+        self.visit_uint(value.into())
+    }
+
+    /// Visit an unsigned 32-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_uint()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_u32(&mut self, value: u32) -> VisitResult {
+        self.visit_uint(value.into())
+    }
+
+    /// Visit a signed 8-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_int()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_i8(&mut self, value: i8) -> VisitResult {
+        self.visit_int(value.into())
+    }
+
+    /// Visit a signed 16-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_int()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_i16(&mut self, value: i16) -> VisitResult {
+        self.visit_int(value.into())
+    }
+
+    /// Visit a signed 32-bit integer value.
+    ///
+    /// This defaults to calling `self.visit_int()`, widening the value;
+    /// implementations wishing to preserve the width of the original integer
+    /// type may override the default implementation.
+    fn visit_i32(&mut self, value: i32) -> VisitResult {
+        self.visit_int(value.into())
+    }
+
+    /// Visit a 32-bit floating-point value.
+    ///
+    /// This defaults to calling `self.visit_float()`, widening the value;
+    /// implementations wishing to preserve the width of the original
+    /// floating-point type may override the default implementation.
+    fn visit_f32(&mut self, value: f32) -> VisitResult {
+        self.visit_float(value.into())
+    }
+
+    /// Visit a string value.
+    ///
+    /// This defaults to calling `self.visit_any()`; implementations wishing to
+    /// provide behaviour specific to strings may override the default
+    /// implementation.
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        self.visit_any(&value)
+    }
+
+    /// Visit a byte string.
+    ///
+    /// This is used for binary data, such as values wrapped in
+    /// [`value::Bytes`], rather than for lists which happen to contain `u8`s
+    /// (which are visited like any other list). This defaults to visiting the
+    /// bytes as a list of `u8`s; implementations for formats with a binary
+    /// data type may override the default implementation.
+    ///
+    /// [`value::Bytes`]: ../value/struct.Bytes.html
+    fn visit_bytes(&mut self, value: &[u8]) -> VisitResult {
+        self.open_list()?;
+        for (idx, byte) in value.iter().enumerate() {
+            self.visit_u8(*byte).map_err(|e| e.in_index(idx))?;
+        }
+        self.close_list()
+    }
+
+    /// Visit a boolean value.
+    ///
+    /// This defaults to calling `self.visit_any()`; implementations wishing to
+    /// provide behaviour specific to booleans may override the default
+    /// implementation.
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        self.visit_any(&value)
+    }
+
+    /// Visit a unit value, such as `()` or the absence of a value.
+    ///
+    /// This defaults to visiting an empty tuple, since `()` is the empty
+    /// tuple type; implementations wishing to provide behaviour specific to
+    /// unit values (such as writing `null`) may override the default
+    /// implementation.
+    fn visit_unit(&mut self) -> VisitResult {
+        self.open_tuple()?;
+        self.close_tuple()
+    }
+
+    /// Visit an arbitrarily-typed value.
+    ///
+    /// Shared pointers such as `Arc` and `Rc` visit the value they point to
+    /// using this method, so that visitors may identify shared values by
+    /// their address. Implementations which don't need to treat such values
+    /// specially can simply call `value.visit(self)`. Fallback
+    /// implementations may describe the value using
+    /// [`Visitable::rust_type_name`].
+    ///
+    /// [`Visitable::rust_type_name`]: ../value/trait.Visitable.html#method.rust_type_name
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult;
+
+    /// Visit a key-value association.
+    ///
+    /// The key and the value are both known to implement `Value`. This
+    /// defaults to returning an [unsupported] error, like `open_map`.
+    ///
+    /// [unsupported]: struct.Error.html#method.unsupported
+    fn visit_kv(&mut self, k: Value, v: Value) -> VisitResult {
+        let _ = (k, v);
+        Err(Error::unsupported("map"))
+    }
+
+    /// Visit the value of a field of a [`Record`] constructed from a
+    /// [`FieldSet`].
+    ///
+    /// The default implementation of `visit_record` calls this for each
+    /// field of such a record, in place of `visit_kv`. Since `FieldId`s may
+    /// be compared and hashed cheaply, implementations which look up state
+    /// for each field (such as the column a field is written to) may
+    /// override this to do so without comparing field names. This defaults
+    /// to calling `self.visit_kv()` with the field's name as the key.
+    ///
+    /// [`Record`]: ../record/struct.Record.html
+    /// [`FieldSet`]: ../record/struct.FieldSet.html
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        self.visit_named_field(field.name(), value)
+    }
+
+    /// Visit a key-value association whose key is a string, such as a field
+    /// of a struct or of a [`Record`].
+    ///
+    /// The helpers on `dyn Visit` (such as `visit_struct`), the default
+    /// implementations of `visit_record` and `visit_field`, and derived
+    /// `Visitable` implementations call this in place of `visit_kv`, so that
+    /// the key is passed as a `&str` rather than wrapped in a `Value`.
+    /// Implementations which handle string keys directly may override this
+    /// to avoid visiting each key through a `Value`. This defaults to
+    /// calling `self.visit_kv()` with the name as the key.
+    ///
+    /// [`Record`]: ../record/struct.Record.html
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        self.visit_kv(Value::borrowed(&name), value)
+    }
+
+    /// Visit an arbitrary set of pre-compiled format arguments.
+    ///
+    /// This defaults to formatting the arguments with [`args_to_str`] and
+    /// calling `self.visit_str()`, which allocates only if the formatted
+    /// string is long; implementations which can write formatted output
+    /// directly should override the default implementation to avoid copying
+    /// it.
+    ///
+    /// [`args_to_str`]: fn.args_to_str.html
+    fn visit_fmt(&mut self, args: fmt::Arguments) -> VisitResult {
+        args_to_str(args, |s| self.visit_str(s))?
+    }
+
+    /// Visit a value which is formatted using its `Display` implementation,
+    /// such as one constructed with [`Value::display`].
+    ///
+    /// This defaults to calling `self.visit_fmt()` with the formatted value.
+    /// Implementations which would rather keep the value itself (for
+    /// example, to format it later, or not at all) may override this.
+    ///
+    /// [`Value::display`]: ../value/struct.Value.html#method.display
+    fn visit_display(&mut self, value: &dyn fmt::Display) -> VisitResult {
+        self.visit_fmt(format_args!("{}", value))
+    }
+
+    /// Visit a [`Record`] of named fields.
+    ///
+    /// This defaults to visiting the record's fields as the key-value pairs
+    /// of a map, using [`visit_field`] if the record was constructed from a
+    /// `FieldSet` and `visit_kv` otherwise. Implementations wishing to
+    /// provide behaviour specific to records (for example, writing an
+    /// event's fields without surrounding delimiters) may override the
+    /// default implementation.
+    ///
+    /// [`Record`]: ../record/struct.Record.html
+    /// [`visit_field`]: #method.visit_field
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        self.open_map()?;
+        match record.field_set() {
+            Some(fields) => {
+                for (field, (_, value)) in fields.fields().zip(record) {
+                    self.visit_field(field, value)
+                        .map_err(|e| e.in_field(field.name()))?;
+                }
+            }
+            None => {
+                for (name, value) in record {
+                    self.visit_named_field(name, value)
+                        .map_err(|e| e.in_field(name))?;
+                }
+            }
+        }
+        self.close_map()
+    }
+
+    /// Indicates that the next visited value is a named type with the name
+    /// `name`.
+    ///
+    /// This is called prior to visiting structs, tuple structs, and enum
+    /// variants. This defaults to ignoring the name.
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        let _ = name;
+        Ok(())
+    }
+
+    /// Visit an annotation on the next visited value.
+    ///
+    /// Annotations are out-of-band metadata describing a value, such as
+    /// its unit (`"unit"`, `"ms"`) or its meaning (`"semantic"`,
+    /// `"user_id"`), which backends such as telemetry exporters may use to
+    /// interpret it. Any annotations for a value are visited before the
+    /// value, and before any call to `named_type` for it; see
+    /// [`Value::annotate`].
+    ///
+    /// This defaults to ignoring the annotation.
+    ///
+    /// [`Value::annotate`]: ../value/struct.Value.html#method.annotate
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        let _ = (key, value);
+        Ok(())
+    }
+
+    /// Returns whether this visitor will record the value for the map key or
+    /// field named `key`.
+    ///
+    /// A producer whose values are expensive to compute (such as a formatted
+    /// value) may call this before computing them, and skip any that are not
+    /// wanted; [`visit_kv_lazy`], [`visit_struct`], and
+    /// [`visit_struct_lazy`] do so. A visitor which discards some
+    /// fields (for example, because it is sampling events, or writes only a
+    /// fixed set of columns) may override this to return `false` for those
+    /// fields. Returning `false` is only a hint: producers are not required
+    /// to ask, so a visitor must still handle a call to `visit_kv` for a key
+    /// it did not want. This defaults to `true`.
+    ///
+    /// [`visit_kv_lazy`]: #method.visit_kv_lazy
+    /// [`visit_struct`]: #method.visit_struct
+    /// [`visit_struct_lazy`]: #method.visit_struct_lazy
+    fn wants_value(&self, key: &str) -> bool {
+        let _ = key;
+        true
+    }
+
+    /// Returns how the container extension methods on `dyn Visit` (such as
+    /// [`visit_map`]) should behave when visiting an element of a container
+    /// fails.
+    ///
+    /// This defaults to [`ErrorMode::Abort`]. Visitors which need their
+    /// output to remain balanced after an error may return
+    /// [`ErrorMode::BestEffortClose`], in which case they must accept a call
+    /// to the matching `close_*` method after any element returns an error.
+    ///
+    /// [`visit_map`]: #method.visit_map
+    /// [`ErrorMode::Abort`]: enum.ErrorMode.html#variant.Abort
+    /// [`ErrorMode::BestEffortClose`]: enum.ErrorMode.html#variant.BestEffortClose
+    fn error_mode(&self) -> ErrorMode {
+        ErrorMode::Abort
+    }
+
+    /// Returns the `Visit` methods which this visitor handles itself.
+    ///
+    /// Producers may use this to choose a better fallback than a method's
+    /// default implementation when a visitor does not handle it; see
+    /// [`Capabilities`]. This defaults to [`Capabilities::empty`], so a
+    /// visitor which does not override it is assumed to rely on every
+    /// default.
+    ///
+    /// [`Capabilities`]: struct.Capabilities.html
+    /// [`Capabilities::empty`]: struct.Capabilities.html#method.empty
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::empty()
+    }
+
+    /// Returns `true` if this visitor only records the keys and field names
+    /// of values, and not their leaf values.
+    ///
+    /// Visitors which register schemas, or check filters before deciding
+    /// whether to serialize a value, may only need its keys. Producers may
+    /// skip computing values for such visitors: values visited with
+    /// [`visit_kv_lazy`] or [`visit_struct_lazy`] are not computed, and are
+    /// visited as unit values instead, as are the derived fields visited by a
+    /// `#[visitable(with = "...")]` function or hashed for redaction. Keys
+    /// nested inside those values are therefore not visited.
+    ///
+    /// This defaults to `false`. The [`KeysOnly`] wrapper returns `true`, and
+    /// replaces every leaf value with a unit value.
+    ///
+    /// [`visit_kv_lazy`]: #method.visit_kv_lazy
+    /// [`visit_struct_lazy`]: #method.visit_struct_lazy
+    /// [`KeysOnly`]: ../combinator/struct.KeysOnly.html
+    fn keys_only(&self) -> bool {
+        false
+    }
+
+    /// Begin visiting a key-value map.
+    ///
+    /// After this function has returned `Ok(())`, the `Visit` may expect
+    /// that all subsequent calls will be to `visit_kv` or `visit_field`
+    /// (representing the key-value pairs in the map) until `close_map` is
+    /// called.
+    ///
+    /// The visitor should perform any internal state transitions necessary to
+    /// visit a map. This defaults to returning an [unsupported] error, so
+    /// that implementations which don't expect maps need not implement it.
+    ///
+    /// [unsupported]: struct.Error.html#method.unsupported
+    fn open_map(&mut self) -> VisitResult {
+        Err(Error::unsupported("map"))
+    }
+
+    /// Finish visiting a map.
+    ///
+    /// When this is called, the visitor should expect calls to arbitrary
+    /// `visit` methods. This defaults to returning an unsupported error,
+    /// like `open_map`.
+    fn close_map(&mut self) -> VisitResult {
+        Err(Error::unsupported("map"))
+    }
+
+    /// Begin visiting an ordered list of values.
+    ///
+    /// When this function has returned `Ok(())` any subsequent calls to
+    /// `visit` represent the elements of the list, until `close_list` is
+    /// called.
+    ///
+    /// The visitor should perform any internal state transitions necessary to
+    /// visit a list (for example, begin serializing comma-delimited values).
+    /// This defaults to returning an unsupported error.
+    fn open_list(&mut self) -> VisitResult {
+        Err(Error::unsupported("list"))
+    }
+
+    /// Finish visiting a list.
+    ///
+    /// This defaults to returning an unsupported error, like `open_list`.
+    fn close_list(&mut self) -> VisitResult {
+        Err(Error::unsupported("list"))
+    }
+
+    /// Begin visiting a `struct`.
+    ///
+    /// After this function has returned `Ok(())`, the `Visit` may expect
+    /// that all subsequent calls will be to `visit_kv` (representing the
+    /// field names and values of the struct) until `close_struct` is called.
+    ///
+    /// The visitor should perform any internal state transitions necessary to
+    /// visit a struct. This defaults to returning an unsupported error.
+    fn open_struct(&mut self) -> VisitResult {
+        Err(Error::unsupported("struct"))
+    }
+
+    /// Finish visiting a `struct`.
+    ///
+    /// This defaults to returning an unsupported error, like `open_struct`.
+    fn close_struct(&mut self) -> VisitResult {
+        Err(Error::unsupported("struct"))
+    }
+
+    /// Begin visiting a tuple.
+    ///
+    ///  When this function has returned `Ok(())` any subsequent calls to
+    /// `visit` represent the elements of the tuple, until `close_tuple` is
+    /// called.
+    ///
+    /// The visitor should perform any internal state transitions necessary to
+    /// visit a tuple. This defaults to returning an unsupported error.
+    fn open_tuple(&mut self) -> VisitResult {
+        Err(Error::unsupported("tuple"))
+    }
+
+    /// Finish visiting a tuple.
+    ///
+    /// This defaults to returning an unsupported error, like `open_tuple`.
+    fn close_tuple(&mut self) -> VisitResult {
+        Err(Error::unsupported("tuple"))
+    }
+
+    /// Finish visiting a complete top-level value.
+    ///
+    /// Producers which visit a sequence of independent values or records
+    /// (such as a logger emitting events) call this once each of them has
+    /// been visited, usually with [`Value::visit_and_finish`] or
+    /// [`Record::visit_and_finish`]. Visitors writing record-oriented
+    /// formats may use it to write any framing which follows each record,
+    /// such as a trailing newline. It is never called for values nested
+    /// inside other values, and `Value::visit` never calls it.
+    ///
+    /// This defaults to doing nothing.
+    ///
+    /// [`Value::visit_and_finish`]: ../value/struct.Value.html#method.visit_and_finish
+    /// [`Record::visit_and_finish`]: ../record/struct.Record.html#method.visit_and_finish
+    fn finish(&mut self) -> VisitResult {
+        Ok(())
+    }
+}
+
+impl<'v> dyn Visit + 'v {
+    /// Visit a map of key-value data.
+    ///
+    /// This function manages calling `open_map`, visiting the key-value
+    /// data in the given iterator, and closing the map.
+    ///
+    /// If visiting an entry fails, the map is closed before the error is
+    /// returned only if this visitor's [`error_mode`] is
+    /// `ErrorMode::BestEffortClose`. The other container methods behave the
+    /// same way.
+    ///
+    /// This is the suggested way for `Value` implementations to visit maps,
+    /// rather than calling those functions directly, unless different behaviour
+    /// is needed.
+    ///
+    /// [`error_mode`]: trait.Visit.html#method.error_mode
+    pub fn visit_map<'a, I>(&mut self, i: I) -> VisitResult
+    where
+        I: IntoIterator<Item = (Value<'a>, Value<'a>)>,
+    {
+        self.open_map()?;
+        let result = i
+            .into_iter()
+            .try_for_each(|(k, v)| self.visit_kv(k.by_ref(), v).map_err(|e| e.in_key(&k)));
+        close_after(self, result, |v| v.close_map())
+    }
+
+    /// Visit a key-value pair whose value is computed by `f`, only if this
+    /// visitor [wants the value][wants] for `key`.
+    ///
+    /// If the visitor does not want the value, `f` is never called, and
+    /// nothing is visited. If the visitor only wants [keys], `f` is never
+    /// called, and the key is visited with a unit value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{csv::CsvVisit, record::FieldSet, visitor::Visit};
+    /// static COLUMNS: FieldSet = FieldSet::new(&["id"]);
+    /// let mut csv = CsvVisit::new(String::new(), COLUMNS);
+    /// let visit: &mut dyn Visit = &mut csv;
+    ///
+    /// visit.open_map().unwrap();
+    /// visit.visit_kv_lazy("id", || 1).unwrap();
+    /// visit.visit_kv_lazy("report", || -> String { unreachable!() }).unwrap();
+    /// visit.close_map().unwrap();
+    /// assert_eq!(csv.into_inner(), "1\n");
+    /// ```
+    ///
+    /// [wants]: trait.Visit.html#method.wants_value
+    /// [keys]: trait.Visit.html#method.keys_only
+    pub fn visit_kv_lazy<T, F>(&mut self, key: &str, f: F) -> VisitResult
+    where
+        T: Visitable,
+        F: FnOnce() -> T,
+    {
+        if !self.wants_value(key) {
+            return Ok(());
+        }
+        let result = if self.keys_only() {
+            self.visit_named_field(key, Value::empty())
+        } else {
+            self.visit_named_field(key, Value::borrowed(&f()))
+        };
+        result.map_err(|e| e.in_field(key))
+    }
+
+    /// Visit an ordered list of `Value`s.
+    ///
+    /// This function manages calling `open_list`, visiting the list elements
+    /// in the given iterator, and closing the list.
+    ///
+    /// This is the suggested way for `Value` implementations to visit lists,
+    /// rather than calling those functions directly, unless different behaviour
+    /// is needed.
+    pub fn visit_list<'a, I>(&mut self, i: I) -> VisitResult
+    where
+        I: IntoIterator<Item = Value<'a>>,
+    {
+        self.open_list()?;
+        let result = i
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(idx, v)| v.visit(self).map_err(|e| e.in_index(idx)));
+        close_after(self, result, |v| v.close_list())
+    }
+
+    /// Visit a `struct` of `Value`s, given the struct's `name` and an
+    /// iterator over its `fields`.
+    ///
+    /// This function manages calling `open_struct`, visiting the struct's
+    /// fields, and closing the struct. Fields which the visitor does not
+    /// [want the value][wants] for are skipped.
+    ///
+    /// This is the suggested way for `Value` implementations to visit structs,
+    /// rather than calling those functions directly, unless different behaviour
+    /// is needed.
+    ///
+    /// [wants]: trait.Visit.html#method.wants_value
+    pub fn visit_struct<'a, I>(&mut self, name: &str, fields: I) -> VisitResult
+    where
+        I: IntoIterator<Item = (&'a str, Value<'a>)>,
+    {
+        self.named_type(name)?;
+        self.open_struct()?;
+        let result = fields.into_iter().try_for_each(|(name, v)| {
+            if !self.wants_value(name) {
+                return Ok(());
+            }
+            self.visit_named_field(name, v)
+                .map_err(|e| e.in_field(name))
+        });
+        close_after(self, result, |v| v.close_struct())
+    }
+
+    /// Visit a `struct` whose fields are visited by closures, given the
+    /// struct's `name` and an iterator over its field names and closures.
+    ///
+    /// This behaves like [`visit_struct`], except that the closure for a
+    /// field the visitor does not [want the value][wants] for is never
+    /// called. This allows a struct with expensive fields to avoid computing
+    /// them when they will not be recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{
+    /// #     combinator::ProjectedVisit,
+    /// #     json::JsonVisit,
+    /// #     record::FieldSet,
+    /// #     visitor::{Visit, VisitResult},
+    /// # };
+    /// static FIELDS: FieldSet = FieldSet::new(&["id"]);
+    /// let mut visit = ProjectedVisit::new(JsonVisit::new(String::new()), FIELDS);
+    /// let dyn_visit: &mut dyn Visit = &mut visit;
+    ///
+    /// let id = |v: &mut dyn Visit| v.visit_uint(7);
+    /// let report = |_: &mut dyn Visit| -> VisitResult { unreachable!() };
+    /// let fields: [(&str, &dyn Fn(&mut dyn Visit) -> VisitResult); 2] =
+    ///     [("id", &id), ("report", &report)];
+    /// dyn_visit.visit_struct_lazy("Job", fields).unwrap();
+    /// assert_eq!(visit.into_inner().into_inner(), r#"{"id":7}"#);
+    /// ```
+    ///
+    /// [`visit_struct`]: #method.visit_struct
+    /// [wants]: trait.Visit.html#method.wants_value
+    pub fn visit_struct_lazy<'a, I>(&mut self, name: &str, fields: I) -> VisitResult
+    where
+        I: IntoIterator<Item = (&'a str, &'a dyn Fn(&mut dyn Visit) -> VisitResult)>,
+    {
+        self.named_type(name)?;
+        self.open_struct()?;
+        let result = fields.into_iter().try_for_each(|(name, f)| {
+            if !self.wants_value(name) {
+                return Ok(());
+            }
+            self.visit_named_field(name, Value::borrowed(&LazyField(f)))
+                .map_err(|e| e.in_field(name))
+        });
+        close_after(self, result, |v| v.close_struct())
+    }
+
+    /// Visit a tuple.
+    ///
+    /// This function manages calling `open_tuple`, visiting the tuple's
+    /// fields, and closing the tuple.
+    ///
+    /// This is the suggested way for `Value` implementations to visit tuples,
+    /// rather than calling those functions directly, unless different behaviour
+    /// is needed.
+    pub fn visit_tuple<'a, I>(&mut self, i: I) -> VisitResult
+    where
+        I: IntoIterator<Item = Value<'a>>,
+    {
+        self.open_tuple()?;
+        let result = i
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(idx, v)| v.visit(self).map_err(|e| e.in_index(idx)));
+        close_after(self, result, |v| v.close_tuple())
+    }
+
+    /// Visit a tuple `struct` of `Value`s, given the struct's `name` and an
+    /// iterator over its `fields`.
+    ///
+    /// This function manages calling `open_struct`, visiting the struct's
+    /// fields, and closing the struct.
+    ///
+    /// This is the suggested way for `Value` implementations to visit structs,
+    /// rather than calling those functions directly, unless different behaviour
+    /// is needed.
+    pub fn visit_tuple_struct<'a, I>(&mut self, name: &str, fields: I) -> VisitResult
+    where
+        I: IntoIterator<Item = Value<'a>>,
+    {
+        self.named_type(name)?;
+        self.open_tuple()?;
+        let result = fields
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(idx, v)| v.visit(self).map_err(|e| e.in_index(idx)));
+        close_after(self, result, |v| v.close_tuple())
+    }
+
+    /// Visit a byte string, as a lowercase hexadecimal string if this
+    /// visitor does not handle byte strings.
+    ///
+    /// The default implementation of [`Visit::visit_bytes`] visits a byte
+    /// string as a list of `u8`s, which is lossless but verbose in most
+    /// output formats. If this visitor's [`capabilities`] do not include
+    /// [`Capabilities::BYTES`], this visits `value` as a hex string instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{json::JsonVisit, visitor::Visit};
+    /// let mut json = JsonVisit::new(String::new());
+    /// (&mut json as &mut dyn Visit).visit_bytes_or_hex(b"\x01\xab").unwrap();
+    /// assert_eq!(json.into_inner(), r#""01ab""#);
+    /// ```
+    ///
+    /// [`Visit::visit_bytes`]: trait.Visit.html#method.visit_bytes
+    /// [`capabilities`]: trait.Visit.html#method.capabilities
+    /// [`Capabilities::BYTES`]: struct.Capabilities.html#associatedconstant.BYTES
+    pub fn visit_bytes_or_hex(&mut self, value: &[u8]) -> VisitResult {
+        if self.capabilities().contains(Capabilities::BYTES) {
+            self.visit_bytes(value)
+        } else {
+            self.visit_fmt(format_args!("{}", Hex(value)))
+        }
+    }
+
+    /// Returns `true` if this visitor writes output to be read by people.
+    ///
+    /// This checks for [`Capabilities::HUMAN_READABLE`], so that `Visitable`
+    /// implementations may choose how to visit their values; see
+    /// [`SerializeConfig`].
+    ///
+    /// [`Capabilities::HUMAN_READABLE`]: struct.Capabilities.html#associatedconstant.HUMAN_READABLE
+    /// [`SerializeConfig`]: struct.SerializeConfig.html
+    pub fn is_human_readable(&self) -> bool {
+        self.capabilities().contains(Capabilities::HUMAN_READABLE)
+    }
+
+    /// Visit an error and the chain of errors which caused it.
+    ///
+    /// The chain is visited as a list, starting with `error` and followed by
+    /// each of its [sources] in turn. Each error is visited as a struct
+    /// named `Error`, whose `message` field is the error's `Display` output.
+    /// The first error also has a `type` field, the name of its type as
+    /// returned by [`std::any::type_name`] (which, like `type_name`, should
+    /// not be relied upon to be stable). Since the sources of an error are
+    /// trait objects, their types are not known, so they have no `type`
+    /// field. This allows every visitor to render errors in the same way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use valuable::{json::JsonVisit, visitor::Visit};
+    /// let error = "x".parse::<u8>().unwrap_err();
+    /// let mut json = JsonVisit::new(String::new());
+    /// (&mut json as &mut dyn Visit).visit_error_chain(&error).unwrap();
+    /// assert_eq!(
+    ///     json.into_inner(),
+    ///     concat!(
+    ///         r#"[{"message":"invalid digit found in string","#,
+    ///         r#""type":"core::num::error::ParseIntError"}]"#,
+    ///     )
+    /// );
+    /// ```
+    ///
+    /// [sources]: https://doc.rust-lang.org/std/error/trait.Error.html#method.source
+    /// [`std::any::type_name`]: https://doc.rust-lang.org/std/any/fn.type_name.html
+    pub fn visit_error_chain<E>(&mut self, error: &E) -> VisitResult
+    where
+        E: error::Error + 'static,
+    {
+        let head = ChainedError {
+            error,
+            type_name: Some(std::any::type_name::<E>()),
+        };
+        let sources = std::iter::successors(error.source(), |error| error.source());
+        let errors = std::iter::once(head)
+            .chain(sources.map(|error| ChainedError {
+                error,
+                type_name: None,
+            }))
+            .collect::<Vec<_>>();
+        self.visit_list(errors.iter().map(Value::borrowed))
+    }
+}
+
+/// An error in a chain visited by `visit_error_chain`, and the name of its
+/// type, if it is known.
+struct ChainedError<'a> {
+    error: &'a (dyn error::Error + 'static),
+    type_name: Option<&'static str>,
+}
+
+impl Visitable for ChainedError<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        let message = Message(self.error);
+        let type_name = self.type_name.as_ref().map(|name| ("type", Value::borrowed(name)));
+        visitor.visit_struct(
+            "Error",
+            std::iter::once(("message", Value::borrowed(&message))).chain(type_name),
+        )
+    }
+}
+
+struct Message<'a>(&'a dyn error::Error);
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.0, f)
+    }
+}
+
+impl Visitable for Message<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        visitor.visit_display(self)
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Non-object-safe extension methods for visiting concrete `Visitable`
+/// types with static dispatch.
+///
+/// The methods on [`Visit`] and the extension methods on `dyn Visit` must
+/// be called through a trait object, so every value visited through them
+/// results in a virtual call. When both the visitor and the visited type are
+/// known at compile time, these methods allow the calls to be monomorphized
+/// (and inlined), which may be significantly faster for serializers on hot
+/// paths.
+///
+/// This trait is implemented for all `Visit` implementations.
+///
+/// Note that values erased behind a [`Value`] are always visited with
+/// dynamic dispatch, as are the keys and values of maps and structs, since
+/// they are passed to [`Visit::visit_kv`] as `Value`s.
+///
+/// [`Visit`]: trait.Visit.html
+/// [`Value`]: ../value/struct.Value.html
+/// [`Visit::visit_kv`]: trait.Visit.html#tymethod.visit_kv
+pub trait VisitExt: Visit {
+    /// Visit a value of a concrete `Visitable` type with static dispatch.
+    ///
+    /// This calls [`Visitable::visit_with`] with this visitor.
+    ///
+    /// [`Visitable::visit_with`]: ../value/trait.Visitable.html#method.visit_with
+    fn visit_value<T>(&mut self, value: &T) -> VisitResult
+    where
+        Self: Sized,
+        T: Visitable,
+    {
+        value.visit_with(self)
+    }
+
+    /// Visit an ordered list of values of a concrete `Visitable` type with
+    /// static dispatch.
+    ///
+    /// This is equivalent to `visit_list`, but does not erase the elements
+    /// of the list.
+    fn visit_list_of<'a, T, I>(&mut self, i: I) -> VisitResult
+    where
+        Self: Sized,
+        T: Visitable + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.open_list()?;
+        let result = i
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(idx, v)| v.visit_with(self).map_err(|e| e.in_index(idx)));
+        close_after(self, result, |v| v.close_list())
+    }
+
+    /// Visit a tuple of values of a concrete `Visitable` type with static
+    /// dispatch.
+    ///
+    /// This is equivalent to `visit_tuple`, but does not erase the elements
+    /// of the tuple.
+    fn visit_tuple_of<'a, T, I>(&mut self, i: I) -> VisitResult
+    where
+        Self: Sized,
+        T: Visitable + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.open_tuple()?;
+        let result = i
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(idx, v)| v.visit_with(self).map_err(|e| e.in_index(idx)));
+        close_after(self, result, |v| v.close_tuple())
+    }
+
+    /// Wraps this visitor in a [`VisitLayer`].
+    ///
+    /// Since each call wraps the visitor built so far, layers may be stacked
+    /// by chaining calls to `with`; the last layer added sees each value
+    /// first. See the [`VisitLayer`] documentation for an example.
+    ///
+    /// [`VisitLayer`]: ../combinator/trait.VisitLayer.html
+    fn with<L>(self, layer: L) -> L::Visit
+    where
+        Self: Sized,
+        L: VisitLayer<Self>,
+    {
+        layer.layer(self)
+    }
+}
+
+impl<V: Visit + ?Sized> VisitExt for V {}
+
+/// Formats `args`, and calls `f` with the formatted string.
+///
+/// Visitors which need the string produced by a set of format arguments
+/// (such as those passed to [`Visit::visit_fmt`]) may use this to avoid
+/// allocating a `String` for each one. Arguments without any values to
+/// format are passed to `f` without being copied, and other arguments are
+/// formatted into a buffer on the stack, unless the formatted string is too
+/// long to fit in it, in which case it is formatted into a `String` taken
+/// from the [buffer pool] instead.
+///
+/// # Errors
+///
+/// If a formatted value's `Display` or `Debug` implementation returns an
+/// error, this returns an error, and `f` is not called.
+///
+/// # Examples
+///
+/// ```
+/// # use valuable::visitor::args_to_str;
+/// let len = args_to_str(format_args!("{}-{}", "a", 1), |s| s.len()).unwrap();
+/// assert_eq!(len, 3);
+/// ```
+///
+/// [`Visit::visit_fmt`]: trait.Visit.html#method.visit_fmt
+/// [buffer pool]: ../pool/index.html
+pub fn args_to_str<F, R>(args: fmt::Arguments<'_>, f: F) -> Result<R, Error>
+where
+    F: FnOnce(&str) -> R,
+{
+    if let Some(s) = args.as_str() {
+        return Ok(f(s));
+    }
+    let mut buf = StackBuf {
+        buf: [0; StackBuf::CAPACITY],
+        len: 0,
+        spilled: None,
+    };
+    let result = fmt::write(&mut buf, args)
+        .map(|_| f(buf.as_str()))
+        .map_err(Error::from);
+    if let Some(spilled) = buf.spilled {
+        pool::current().put_string(spilled);
+    }
+    result
+}
+
+/// The buffer used by `args_to_str`.
+struct StackBuf {
+    buf: [u8; StackBuf::CAPACITY],
+    len: usize,
+    /// Set if the formatted string did not fit in `buf`.
+    spilled: Option<String>,
+}
+
+impl StackBuf {
+    const CAPACITY: usize = 256;
+
+    fn as_str(&self) -> &str {
+        match self.spilled {
+            Some(ref s) => s,
+            // Only whole `str`s are copied into the buffer, so it is always
+            // valid UTF-8.
+            None => std::str::from_utf8(&self.buf[..self.len]).unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Write for StackBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(ref mut spilled) = self.spilled {
+            spilled.push_str(s);
+            return Ok(());
+        }
+        let end = self.len + s.len();
+        if end <= Self::CAPACITY {
+            self.buf[self.len..end].copy_from_slice(s.as_bytes());
+            self.len = end;
+        } else {
+            let mut spilled = pool::current().take_string();
+            spilled.push_str(self.as_str());
+            spilled.push_str(s);
+            self.spilled = Some(spilled);
+        }
+        Ok(())
+    }
+}
+
+/// A field visited by a closure, for `visit_struct_lazy`.
+struct LazyField<'a>(&'a dyn Fn(&mut dyn Visit) -> VisitResult);
+
+impl Visitable for LazyField<'_> {
+    fn visit(&self, visitor: &mut dyn Visit) -> VisitResult {
+        if visitor.keys_only() {
+            return visitor.visit_unit();
+        }
+        (self.0)(visitor)
+    }
+}
+
+/// Closes a container whose elements were visited with the given `result`,
+/// according to the visitor's [`ErrorMode`].
+///
+/// [`ErrorMode`]: enum.ErrorMode.html
+fn close_after<V, F>(visitor: &mut V, result: VisitResult, close: F) -> VisitResult
+where
+    V: Visit + ?Sized,
+    F: FnOnce(&mut V) -> VisitResult,
+{
+    match result {
+        Ok(()) => close(visitor),
+        Err(error) => {
+            if visitor.error_mode() == ErrorMode::BestEffortClose {
+                let _ = close(visitor);
+            }
+            Err(error)
+        }
+    }
+}
+
+impl<V> Visit for &mut V
+where
+    V: Visit + ?Sized,
+{
+    fn visit_uint(&mut self, value: u64) -> VisitResult {
+        (**self).visit_uint(value)
+    }
+
+    fn visit_int(&mut self, value: i64) -> VisitResult {
+        (**self).visit_int(value)
+    }
+
+    fn visit_float(&mut self, value: f64) -> VisitResult {
+        (**self).visit_float(value)
+    }
+
+    fn visit_u16(&mut self, value: u16) -> VisitResult {
+        (**self).visit_u16(value)
+    }
+
+    fn visit_u32(&mut self, value: u32) -> VisitResult {
+        (**self).visit_u32(value)
+    }
+
+    fn visit_i8(&mut self, value: i8) -> VisitResult {
+        (**self).visit_i8(value)
+    }
+
+    fn visit_i16(&mut self, value: i16) -> VisitResult {
+        (**self).visit_i16(value)
+    }
+
+    fn visit_i32(&mut self, value: i32) -> VisitResult {
+        (**self).visit_i32(value)
+    }
+
+    fn visit_f32(&mut self, value: f32) -> VisitResult {
+        (**self).visit_f32(value)
+    }
+
+    fn visit_str(&mut self, value: &str) -> VisitResult {
+        (**self).visit_str(value)
+    }
+
+    fn visit_u8(&mut self, value: u8) -> VisitResult {
+        (**self).visit_u8(value)
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> VisitResult {
+        (**self).visit_bytes(value)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> VisitResult {
+        (**self).visit_bool(value)
+    }
+
+    fn visit_unit(&mut self) -> VisitResult {
+        (**self).visit_unit()
+    }
+
+    fn visit_any(&mut self, value: &dyn Visitable) -> VisitResult {
+        (**self).visit_any(value)
+    }
+
+    fn visit_kv(&mut self, k: Value, v: Value) -> VisitResult {
+        (**self).visit_kv(k, v)
+    }
+
+    fn visit_field(&mut self, field: FieldId, value: Value<'_>) -> VisitResult {
+        (**self).visit_field(field, value)
+    }
+
+    fn visit_named_field(&mut self, name: &str, value: Value<'_>) -> VisitResult {
+        (**self).visit_named_field(name, value)
+    }
+
+    fn visit_fmt(&mut self, args: fmt::Arguments) -> VisitResult {
+        (**self).visit_fmt(args)
+    }
+
+    fn visit_display(&mut self, value: &dyn fmt::Display) -> VisitResult {
+        (**self).visit_display(value)
+    }
+
+    fn visit_record(&mut self, record: &Record<'_>) -> VisitResult {
+        (**self).visit_record(record)
+    }
+
+    fn named_type(&mut self, name: &str) -> VisitResult {
+        (**self).named_type(name)
+    }
+
+    fn visit_annotation(&mut self, key: &str, value: &str) -> VisitResult {
+        (**self).visit_annotation(key, value)
+    }
+
+    fn wants_value(&self, key: &str) -> bool {
+        (**self).wants_value(key)
+    }
+
+    fn error_mode(&self) -> ErrorMode {
+        (**self).error_mode()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+
+    fn keys_only(&self) -> bool {
+        (**self).keys_only()
+    }
+
+    fn open_map(&mut self) -> VisitResult {
+        (**self).open_map()
+    }
+
+    fn close_map(&mut self) -> VisitResult {
+        (**self).close_map()
+    }
+
+    fn open_list(&mut self) -> VisitResult {
+        (**self).open_list()
+    }
+
+    fn close_list(&mut self) -> VisitResult {
+        (**self).close_list()
+    }
+
+    fn open_struct(&mut self) -> VisitResult {
+        (**self).open_struct()
+    }
+
+    fn close_struct(&mut self) -> VisitResult {
+        (**self).close_struct()
+    }
+
+    fn open_tuple(&mut self) -> VisitResult {
+        (**self).open_tuple()
+    }
+
+    fn close_tuple(&mut self) -> VisitResult {
+        (**self).close_tuple()
+    }
+
+    fn finish(&mut self) -> VisitResult {
+        (**self).finish()
+    }
+}
+
+// === impl Error ===
+
+impl Error {
+    fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn unbalanced() -> Self {
+        Self::new(ErrorKind::Unbalanced)
+    }
+
+    #[doc(hidden)]
+    pub fn depth_limit(max_depth: usize) -> Self {
+        Self::new(ErrorKind::DepthLimit(max_depth))
+    }
+
+    #[doc(hidden)]
+    pub fn size_limit(limit: usize) -> Self {
+        Self::new(ErrorKind::SizeLimit(limit))
+    }
+
+    #[doc(hidden)]
+    pub fn panicked(message: Option<String>) -> Self {
+        Self::new(ErrorKind::Panicked(message))
+    }
+
+    /// Returns an error indicating that a visitor does not support visiting
+    /// values of the kind described by `what` (such as `"map"`).
+    ///
+    /// This is returned by the default implementations of the `Visit`
+    /// container methods, and may be returned by `Visit` implementations
+    /// which only support some kinds of values.
+    pub fn unsupported(what: &'static str) -> Self {
+        Self::new(ErrorKind::Unsupported(what))
+    }
+
+    /// Returns `true` if this error was returned because a visitor does not
+    /// support visiting some kind of value.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self.kind, ErrorKind::Unsupported(_))
+    }
+
+    /// Returns `true` if this error was returned because a visitor
+    /// panicked (or had panicked earlier), and the panic was caught by a
+    /// `CatchUnwind`.
+    pub fn is_panic(&self) -> bool {
+        matches!(self.kind, ErrorKind::Panicked(_))
+    }
+
+    /// Returns `true` if this error was returned because writing formatted
+    /// output failed.
+    #[doc(hidden)]
+    pub fn is_fmt(&self) -> bool {
+        matches!(self.kind, ErrorKind::Fmt)
+    }
+
+    /// Returns the path from the root of the visited value to the value at
+    /// which this error occurred.
+    ///
+    /// If the error occurred at the root value, or the value was not visited
+    /// using the extension methods on `dyn Visit`, the path is empty.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// Records that this error occurred inside the struct or record field
+    /// `name`.
+    #[doc(hidden)]
+    pub fn in_field(self, name: &str) -> Self {
+        self.in_segment(PathSegment::Field(name.to_owned()))
+    }
+
+    /// Records that this error occurred inside the value for the map key
+    /// `key`.
+    #[doc(hidden)]
+    pub fn in_key(self, key: &Value<'_>) -> Self {
+        // String keys are recorded as-is, and other keys as they are written
+        // by `DebugVisit`.
+        struct StrKey(Option<String>);
+
+        impl Visit for StrKey {
+            fn visit_any(&mut self, _: &dyn Visitable) -> VisitResult {
+                Ok(())
+            }
+
+            fn visit_str(&mut self, value: &str) -> VisitResult {
+                self.0 = Some(value.to_owned());
+                Ok(())
+            }
+        }
+
+        let mut str_key = StrKey(None);
+        let _ = key.visit(&mut str_key);
+        let key = str_key.0.unwrap_or_else(|| key.render_debug());
+        self.in_segment(PathSegment::Field(key))
+    }
+
+    /// Records that this error occurred inside the list or tuple element at
+    /// index `idx`.
+    #[doc(hidden)]
+    pub fn in_index(self, idx: usize) -> Self {
+        self.in_segment(PathSegment::Index(idx))
+    }
+
+    fn in_segment(mut self, segment: PathSegment) -> Self {
+        // Errors are propagated from the innermost value outwards, so each
+        // enclosing segment goes before the ones already recorded.
+        self.path.insert(0, segment);
+        self
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Self::new(ErrorKind::Fmt)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::new(ErrorKind::Io(error))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Fmt => f.write_str("an error occurred while formatting output")?,
+            ErrorKind::Unbalanced => f.write_str("container closed without a matching open")?,
+            ErrorKind::DepthLimit(max) => write!(f, "exceeded maximum nesting depth of {}", max)?,
+            ErrorKind::SizeLimit(max) => write!(f, "exceeded maximum size of {} bytes", max)?,
+            ErrorKind::Unsupported(what) => write!(f, "visiting a {} is not supported", what)?,
+            ErrorKind::Io(ref error) => write!(f, "an I/O error occurred: {}", error)?,
+            ErrorKind::Panicked(Some(ref message)) => write!(f, "visitor panicked: {}", message)?,
+            ErrorKind::Panicked(None) => f.write_str("visitor panicked")?,
+        }
+        fmt_path(&self.path, f)
+    }
+}
+
+/// Writes ` at `path``, if `path` is not empty.
+#[doc(hidden)]
+pub fn fmt_path(path: &[PathSegment], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if path.is_empty() {
+        return Ok(());
+    }
+    f.write_str(" at `")?;
+    fmt_segments(path.iter(), f)?;
+    f.write_str("`")
+}
+
+/// Writes `path` in the form `a.b[3]`.
+#[doc(hidden)]
+pub fn fmt_segments<'a>(
+    path: impl Iterator<Item = &'a PathSegment>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    for (i, segment) in path.enumerate() {
+        if i > 0 {
+            if let PathSegment::Field(_) = segment {
+                f.write_str(".")?;
+            }
+        }
+        fmt::Display::fmt(segment, f)?;
+    }
+    Ok(())
+}
+
+// === impl Capabilities ===
+
+macro_rules! capabilities {
+    ($($(#[$meta:meta])* $name:ident = $bit:expr;)+) => {
+        impl Capabilities {
+            $(
+                $(#[$meta])*
+                pub const $name: Capabilities = Capabilities(1 << $bit);
+            )+
+
+            const NAMES: &'static [(Capabilities, &'static str)] = &[
+                $((Capabilities::$name, stringify!($name))),+
+            ];
+
+            /// Returns every capability.
+            ///
+            /// Visitors should not return this from [`Visit::capabilities`],
+            /// since it includes capabilities which may be added in later
+            /// versions.
+            ///
+            /// [`Visit::capabilities`]: trait.Visit.html#method.capabilities
+            pub const fn all() -> Self {
+                Capabilities($(Capabilities::$name.0)|+)
+            }
+        }
+    };
+}
+
+capabilities! {
+    /// The visitor handles byte strings in [`Visit::visit_bytes`], rather
+    /// than visiting them as lists of `u8`s.
+    ///
+    /// [`Visit::visit_bytes`]: trait.Visit.html#method.visit_bytes
+    BYTES = 0;
+    /// The visitor handles the `visit_u8`, `visit_u16`, `visit_u32`,
+    /// `visit_i8`, `visit_i16`, and `visit_i32` methods, rather than
+    /// widening narrow integers.
+    NARROW_INTS = 1;
+    /// The visitor handles [`Visit::visit_f32`], rather than widening `f32`s.
+    ///
+    /// [`Visit::visit_f32`]: trait.Visit.html#method.visit_f32
+    F32 = 2;
+    /// The visitor handles [`Visit::visit_unit`], rather than visiting the
+    /// unit value as an empty tuple.
+    ///
+    /// [`Visit::visit_unit`]: trait.Visit.html#method.visit_unit
+    UNIT = 3;
+    /// The visitor records type names passed to [`Visit::named_type`].
+    ///
+    /// [`Visit::named_type`]: trait.Visit.html#method.named_type
+    NAMED_TYPES = 4;
+    /// The visitor supports maps.
+    MAPS = 5;
+    /// The visitor supports lists.
+    LISTS = 6;
+    /// The visitor supports structs.
+    STRUCTS = 7;
+    /// The visitor supports tuples.
+    TUPLES = 8;
+    /// The visitor writes output to be read by people, rather than by
+    /// programs.
+    ///
+    /// Unlike the other capabilities, this does not describe the methods a
+    /// visitor handles, but how producers should visit their values: a
+    /// `Duration` visits itself as a string such as `"1.5s"` for a visitor
+    /// with this capability, and as a number of nanoseconds otherwise. See
+    /// [`SerializeConfig`].
+    ///
+    /// [`SerializeConfig`]: struct.SerializeConfig.html
+    HUMAN_READABLE = 9;
+}
+
+impl Capabilities {
+    /// Returns an empty set of capabilities.
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    /// Returns `true` if `self` includes every capability in `other`.
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if `self` includes no capabilities.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the capabilities in either `self` or `other`.
+    pub const fn union(self, other: Capabilities) -> Self {
+        Capabilities(self.0 | other.0)
+    }
+
+    /// Returns the capabilities in both `self` and `other`.
+    ///
+    /// This is useful for visitors which forward values to several other
+    /// visitors, which support only the capabilities supported by all of
+    /// them.
+    pub const fn intersection(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & other.0)
+    }
+
+    /// Returns the capabilities in `self` but not in `other`.
+    pub const fn difference(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & !other.0)
+    }
+
+    /// Returns the raw bits of this set of capabilities.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl ops::BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, other: Self) {
+        *self = self.union(other);
+    }
+}
+
+impl ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.intersection(other)
+    }
+}
+
+impl fmt::Debug for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        for &(cap, name) in Capabilities::NAMES {
+            if self.contains(cap) {
+                set.entry(&format_args!("{}", name));
+            }
+        }
+        set.finish()
+    }
+}
+
+// === impl SerializeConfig ===
+
+impl SerializeConfig {
+    /// Returns a new `SerializeConfig` for machine-readable output.
+    pub const fn new() -> Self {
+        Self {
+            human_readable: false,
+        }
+    }
+
+    /// Sets whether the output is human-readable.
+    pub const fn with_human_readable(self, human_readable: bool) -> Self {
+        Self { human_readable }
+    }
+
+    /// Returns `true` if the output is human-readable.
+    pub const fn is_human_readable(self) -> bool {
+        self.human_readable
+    }
+
+    /// Returns the capabilities which a serializer with this configuration
+    /// adds to those it handles itself.
+    pub const fn capabilities(self) -> Capabilities {
+        if self.human_readable {
+            Capabilities::HUMAN_READABLE
+        } else {
+            Capabilities::empty()
+        }
+    }
+}
+
+// === impl PathSegment ===
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) => f.write_str(name),
+            PathSegment::Index(idx) => write!(f, "[{}]", idx),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.kind {
+            ErrorKind::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::case::RenameRule;
+use proc_macro2::Span;
+use syn::{
+    punctuated::Punctuated, spanned::Spanned, Attribute, Error, LitStr, Path, Result, Token,
+    WherePredicate,
+};
+
+/// The `#[visitable(...)]` attributes on a type or enum variant.
+#[derive(Default)]
+pub(crate) struct Container {
+    pub(crate) rename: Option<String>,
+    pub(crate) rename_all: Option<RenameRule>,
+    /// Where clause predicates replacing the inferred bounds, if any.
+    pub(crate) bound: Option<Vec<WherePredicate>>,
+    pub(crate) tag: Option<String>,
+    pub(crate) content: Option<String>,
+    pub(crate) untagged: bool,
+    pub(crate) external: bool,
+    /// Set if named fields are visited sorted by name, rather than in
+    /// declaration order.
+    pub(crate) sort_fields: bool,
+    /// The span of the first attribute which is only valid on a type, if
+    /// any, for errors on enum variants.
+    type_only: Option<Span>,
+    /// The span of the first attribute which is only valid on an enum, if
+    /// any, for errors on structs.
+    enum_only: Option<Span>,
+}
+
+/// How the variants of an enum are represented.
+pub(crate) enum Repr {
+    /// Each variant is visited as a named struct, tuple struct, or unit
+    /// value with the variant's name.
+    Named,
+    /// Each variant is visited as a map with a single key, the variant's
+    /// name, whose value is the variant's contents.
+    External,
+    /// Each variant is visited as a struct with the variant's name in the
+    /// `tag` field, followed by the variant's fields.
+    Internal { tag: String },
+    /// Each variant is visited as a struct with the variant's name in the
+    /// `tag` field, and the variant's contents in the `content` field.
+    Adjacent { tag: String, content: String },
+    /// Each variant is visited as just its contents.
+    Untagged,
+}
+
+/// The `#[visitable(...)]` attributes on a field.
+#[derive(Default)]
+pub(crate) struct Field {
+    pub(crate) rename: Option<String>,
+    pub(crate) skip: bool,
+    pub(crate) skip_if_none: bool,
+    /// A predicate which skips the field if it returns `true` for the
+    /// field's value.
+    pub(crate) skip_if: Option<Path>,
+    pub(crate) flatten: bool,
+    /// A function which visits the field's value in place of `Visitable`.
+    pub(crate) with: Option<Path>,
+    pub(crate) redact: Option<Redact>,
+}
+
+/// How a `#[visitable(redact)]` field is visited.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Redact {
+    /// Visit the string `"[REDACTED]"`.
+    Placeholder,
+    /// Visit a hash of the value.
+    Hash,
+}
+
+impl Container {
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut container = Container::default();
+        for attr in visitable_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                let span = meta.path.span();
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    container.rename = Some(name.value());
+                } else if meta.path.is_ident("rename_all") {
+                    let rule: LitStr = meta.value()?.parse()?;
+                    match RenameRule::from_str(&rule.value()) {
+                        Some(rule) => container.rename_all = Some(rule),
+                        None => {
+                            let msg = format!(
+                                "unknown `rename_all` case; expected one of {}",
+                                RenameRule::names()
+                            );
+                            return Err(Error::new(rule.span(), msg));
+                        }
+                    }
+                } else if meta.path.is_ident("bound") {
+                    let bound: LitStr = meta.value()?.parse()?;
+                    let predicates = bound
+                        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+                    container.bound = Some(predicates.into_iter().collect());
+                    container.type_only.get_or_insert(span);
+                } else if meta.path.is_ident("tag") {
+                    let tag: LitStr = meta.value()?.parse()?;
+                    container.tag = Some(tag.value());
+                    container.enum_only.get_or_insert(span);
+                } else if meta.path.is_ident("content") {
+                    let content: LitStr = meta.value()?.parse()?;
+                    container.content = Some(content.value());
+                    container.enum_only.get_or_insert(span);
+                } else if meta.path.is_ident("untagged") {
+                    container.untagged = true;
+                    container.enum_only.get_or_insert(span);
+                } else if meta.path.is_ident("external") {
+                    container.external = true;
+                    container.enum_only.get_or_insert(span);
+                } else if meta.path.is_ident("sort_fields") {
+                    container.sort_fields = true;
+                } else {
+                    return Err(meta.error("unknown `visitable` attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(container)
+    }
+
+    /// Checks that no attributes which are only valid on enums were used on
+    /// a struct.
+    pub(crate) fn check_struct(&self) -> Result<()> {
+        match self.enum_only {
+            Some(span) => Err(Error::new(span, "this attribute may only be used on enums")),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that only attributes which are valid on enum variants were
+    /// used on a variant.
+    pub(crate) fn check_variant(&self) -> Result<()> {
+        match self.type_only.or(self.enum_only) {
+            Some(span) => Err(Error::new(
+                span,
+                "this attribute may not be used on enum variants",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the representation of an enum with these attributes.
+    pub(crate) fn repr(&self, span: Span) -> Result<Repr> {
+        let repr = match (&self.tag, &self.content, self.untagged, self.external) {
+            (None, None, false, false) => Repr::Named,
+            (None, None, false, true) => Repr::External,
+            (None, None, true, false) => Repr::Untagged,
+            (Some(tag), None, false, false) => Repr::Internal { tag: tag.clone() },
+            (Some(tag), Some(content), false, false) => Repr::Adjacent {
+                tag: tag.clone(),
+                content: content.clone(),
+            },
+            (None, Some(_), false, false) => {
+                return Err(Error::new(span, "`content` requires a `tag`"))
+            }
+            _ => {
+                return Err(Error::new(
+                    span,
+                    "only one of `tag`, `untagged`, or `external` may be used",
+                ))
+            }
+        };
+        Ok(repr)
+    }
+}
+
+impl Field {
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut field = Field::default();
+        for attr in visitable_attrs(attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    field.rename = Some(name.value());
+                } else if meta.path.is_ident("skip") {
+                    field.skip = true;
+                } else if meta.path.is_ident("skip_if_none") {
+                    field.skip_if_none = true;
+                } else if meta.path.is_ident("skip_if") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    field.skip_if = Some(path.parse()?);
+                } else if meta.path.is_ident("flatten") {
+                    field.flatten = true;
+                } else if meta.path.is_ident("with") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    field.with = Some(path.parse()?);
+                } else if meta.path.is_ident("redact") {
+                    if !meta.input.peek(Token![=]) {
+                        field.redact = Some(Redact::Placeholder);
+                        return Ok(());
+                    }
+                    let mode: LitStr = meta.value()?.parse()?;
+                    match mode.value().as_str() {
+                        "hash" => field.redact = Some(Redact::Hash),
+                        _ => return Err(Error::new(mode.span(), "expected `redact = \"hash\"`")),
+                    }
+                } else {
+                    return Err(meta.error("unknown `visitable` field attribute"));
+                }
+                Ok(())
+            })?;
+            if field.flatten
+                && (field.rename.is_some() || field.skip_if_none || field.redact.is_some())
+            {
+                return Err(Error::new(
+                    attr.span(),
+                    "`flatten` cannot be combined with `rename`, `skip_if_none`, or `redact`",
+                ));
+            }
+        }
+        Ok(field)
+    }
+}
+
+fn visitable_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("visitable"))
+}
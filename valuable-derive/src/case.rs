@@ -0,0 +1,137 @@
+/// A case convention applied to field names by `#[visitable(rename_all)]`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    /// The names accepted by `rename_all`, as in `serde`.
+    const ALL: &'static [(&'static str, RenameRule)] = &[
+        ("lowercase", RenameRule::Lower),
+        ("UPPERCASE", RenameRule::Upper),
+        ("PascalCase", RenameRule::Pascal),
+        ("camelCase", RenameRule::Camel),
+        ("snake_case", RenameRule::Snake),
+        ("SCREAMING_SNAKE_CASE", RenameRule::ScreamingSnake),
+        ("kebab-case", RenameRule::Kebab),
+        ("SCREAMING-KEBAB-CASE", RenameRule::ScreamingKebab),
+    ];
+
+    pub(crate) fn from_str(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(rule, _)| *rule == name)
+            .map(|&(_, rule)| rule)
+    }
+
+    /// Returns a list of the accepted names, for error messages.
+    pub(crate) fn names() -> String {
+        let names: Vec<_> = Self::ALL
+            .iter()
+            .map(|(name, _)| format!("`{}`", name))
+            .collect();
+        names.join(", ")
+    }
+
+    /// Applies this rule to an enum variant name, which is assumed to be in
+    /// `PascalCase`.
+    pub(crate) fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::Pascal => variant.to_owned(),
+            RenameRule::Lower => variant.to_ascii_lowercase(),
+            RenameRule::Upper => variant.to_ascii_uppercase(),
+            RenameRule::Camel => {
+                let mut chars = variant.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                    None => String::new(),
+                }
+            }
+            _ => {
+                let mut snake = String::new();
+                for (i, c) in variant.char_indices() {
+                    if c.is_uppercase() && i > 0 {
+                        snake.push('_');
+                    }
+                    snake.push(c.to_ascii_lowercase());
+                }
+                self.apply(&snake)
+            }
+        }
+    }
+
+    /// Applies this rule to a field name, which is assumed to be in
+    /// `snake_case`.
+    pub(crate) fn apply(self, field: &str) -> String {
+        let words = field.split('_').filter(|word| !word.is_empty());
+        match self {
+            RenameRule::Lower | RenameRule::Snake => field.to_ascii_lowercase(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Kebab => field.to_ascii_lowercase().replace('_', "-"),
+            RenameRule::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+            RenameRule::Pascal => words.map(capitalize).collect(),
+            RenameRule::Camel => words
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.to_ascii_lowercase()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => {
+            first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+        }
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        let cases = [
+            ("lowercase", "request_id"),
+            ("UPPERCASE", "REQUEST_ID"),
+            ("PascalCase", "RequestId"),
+            ("camelCase", "requestId"),
+            ("snake_case", "request_id"),
+            ("SCREAMING_SNAKE_CASE", "REQUEST_ID"),
+            ("kebab-case", "request-id"),
+            ("SCREAMING-KEBAB-CASE", "REQUEST-ID"),
+        ];
+        for &(rule, expected) in &cases {
+            let rule = RenameRule::from_str(rule).unwrap();
+            assert_eq!(rule.apply("request_id"), expected);
+        }
+        assert_eq!(RenameRule::Camel.apply("id"), "id");
+
+        let variants = [
+            ("lowercase", "requestid"),
+            ("camelCase", "requestId"),
+            ("snake_case", "request_id"),
+            ("SCREAMING-KEBAB-CASE", "REQUEST-ID"),
+        ];
+        for &(rule, expected) in &variants {
+            let rule = RenameRule::from_str(rule).unwrap();
+            assert_eq!(rule.apply_to_variant("RequestId"), expected);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use crate::{
+    attr::{self, Container, Redact, Repr},
+    case::RenameRule,
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse_quote, visit::Visit, Data, DataEnum, DeriveInput, Error, Fields, Generics,
+    Ident, Index, Result, Type, TypePath, Variant, WherePredicate,
+};
+
+pub(crate) fn derive_visitable(input: DeriveInput) -> Result<TokenStream> {
+    let container = Container::from_attrs(&input.attrs)?;
+    // The types of the fields which are visited, which must implement
+    // `Visitable`.
+    let mut visited = Vec::new();
+    // The type name and field names of a struct with statically known
+    // fields, for its `StructLike` implementation.
+    let mut definition = None;
+    let (body, type_name) = match &input.data {
+        Data::Struct(data) => {
+            container.check_struct()?;
+            let name = container
+                .rename
+                .clone()
+                .unwrap_or_else(|| input.ident.unraw().to_string());
+            let shape = Shape::new(
+                &data.fields,
+                container.rename_all,
+                container.sort_fields,
+                |i, field| match &field.ident {
+                    Some(ident) => quote!(&self.#ident),
+                    None => {
+                        let idx = Index::from(i);
+                        quote!(&self.#idx)
+                    }
+                },
+                &mut visited,
+            )?;
+            definition = shape
+                .keys()
+                .map(|keys| (name.clone(), keys.to_vec(), container.sort_fields));
+            (
+                shape.named(&name),
+                quote!(::std::option::Option::Some(#name)),
+            )
+        }
+        Data::Enum(data) => expand_enum(&input.ident, &container, data, &mut visited)?,
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "`Visitable` cannot be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let generics = add_bounds(
+        &input.generics,
+        container.bound,
+        &visited,
+        quote!(::valuable::value::Visitable),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let struct_like = definition.map(|(name, keys, sorted)| {
+        let order = if sorted {
+            quote!(.with_order(::valuable::value::FieldOrder::Sorted))
+        } else {
+            quote!()
+        };
+        quote! {
+            impl #impl_generics ::valuable::value::StructLike for #ident #ty_generics #where_clause {
+                fn definition() -> &'static ::valuable::value::StructDef {
+                    static DEFINITION: ::valuable::value::StructDef =
+                        ::valuable::value::StructDef::new(#name, &[#(#keys),*])#order;
+                    &DEFINITION
+                }
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::valuable::value::Visitable for #ident #ty_generics #where_clause {
+            fn visit(
+                &self,
+                visitor: &mut dyn ::valuable::visitor::Visit,
+            ) -> ::valuable::visitor::VisitResult {
+                #body
+            }
+
+            fn type_name(&self) -> ::std::option::Option<&'static str> {
+                #type_name
+            }
+        }
+
+        #struct_like
+    })
+}
+
+fn expand_enum<'a>(
+    ident: &Ident,
+    container: &Container,
+    data: &'a DataEnum,
+    visited: &mut Vec<&'a Type>,
+) -> Result<(TokenStream, TokenStream)> {
+    let repr = container.repr(ident.span())?;
+    let enum_name = container
+        .rename
+        .clone()
+        .unwrap_or_else(|| ident.unraw().to_string());
+    if data.variants.is_empty() {
+        return Ok((quote!(match *self {}), quote!(match *self {})));
+    }
+
+    let mut arms = Vec::new();
+    // The type name each variant is visited with, if any.
+    let mut name_arms = Vec::new();
+    for variant in &data.variants {
+        let attrs = Container::from_attrs(&variant.attrs)?;
+        attrs.check_variant()?;
+        let name = variant_name(&attrs, variant, container.rename_all);
+
+        // Bind each field to `__self_N`. The leading underscore keeps
+        // bindings of skipped fields from being reported as unused.
+        let binding = |i: usize| format_ident!("__self_{}", i);
+        let shape = Shape::new(
+            &variant.fields,
+            attrs.rename_all,
+            container.sort_fields || attrs.sort_fields,
+            |i, _| {
+                let binding = binding(i);
+                quote!(#binding)
+            },
+            visited,
+        )?;
+        let variant_ident = &variant.ident;
+        let pattern = match &variant.fields {
+            Fields::Named(fields) => {
+                let fields = fields.named.iter().enumerate().map(|(i, field)| {
+                    let ident = &field.ident;
+                    let binding = binding(i);
+                    quote!(#ident: #binding)
+                });
+                quote!(Self::#variant_ident { #(#fields),* })
+            }
+            Fields::Unnamed(fields) => {
+                let bindings = (0..fields.unnamed.len()).map(binding);
+                quote!(Self::#variant_ident(#(#bindings),*))
+            }
+            Fields::Unit => quote!(Self::#variant_ident),
+        };
+        let wildcard = match &variant.fields {
+            Fields::Named(_) => quote!(Self::#variant_ident { .. }),
+            Fields::Unnamed(_) => quote!(Self::#variant_ident(..)),
+            Fields::Unit => quote!(Self::#variant_ident),
+        };
+        let type_name = match &repr {
+            Repr::Named | Repr::Internal { .. } => quote!(::std::option::Option::Some(#name)),
+            Repr::Adjacent { .. } => quote!(::std::option::Option::Some(#enum_name)),
+            Repr::External | Repr::Untagged => quote!(::std::option::Option::None),
+        };
+        name_arms.push(quote!(#wildcard => #type_name,));
+
+        let body = match &repr {
+            Repr::Named => shape.named(&name),
+            Repr::External => match shape {
+                Shape::Unit => quote!(::valuable::visitor::Visit::visit_str(visitor, #name)),
+                _ => {
+                    let content = shape.content();
+                    quote! {
+                        ::valuable::visitor::Visit::open_map(visitor)?;
+                        ::valuable::__private::visit_field(visitor, #name, &#content)?;
+                        ::valuable::visitor::Visit::close_map(visitor)
+                    }
+                }
+            },
+            Repr::Internal { tag } => {
+                let fields = match &shape {
+                    Shape::Named { visits, .. } => quote!(#(#visits)*),
+                    Shape::Unnamed {
+                        values,
+                        newtype: true,
+                    } => {
+                        let value = &values[0];
+                        quote!(::valuable::__private::visit_flattened(visitor, #value)?;)
+                    }
+                    Shape::Unit => quote!(),
+                    Shape::Unnamed { .. } => {
+                        return Err(Error::new_spanned(
+                            variant,
+                            "internally tagged enums may only have struct, newtype, and unit \
+                             variants",
+                        ))
+                    }
+                };
+                quote! {
+                    ::valuable::visitor::Visit::named_type(visitor, #name)?;
+                    ::valuable::visitor::Visit::open_struct(visitor)?;
+                    ::valuable::__private::visit_field(visitor, #tag, &#name)?;
+                    #fields
+                    ::valuable::visitor::Visit::close_struct(visitor)
+                }
+            }
+            Repr::Adjacent { tag, content } => {
+                let value = match shape {
+                    Shape::Unit => quote!(),
+                    _ => {
+                        let value = shape.content();
+                        quote!(::valuable::__private::visit_field(visitor, #content, &#value)?;)
+                    }
+                };
+                quote! {
+                    ::valuable::visitor::Visit::named_type(visitor, #enum_name)?;
+                    ::valuable::visitor::Visit::open_struct(visitor)?;
+                    ::valuable::__private::visit_field(visitor, #tag, &#name)?;
+                    #value
+                    ::valuable::visitor::Visit::close_struct(visitor)
+                }
+            }
+            Repr::Untagged => shape.anonymous(),
+        };
+        arms.push(quote!(#pattern => { #body }));
+    }
+    Ok((
+        quote! {
+            match self {
+                #(#arms)*
+            }
+        },
+        quote! {
+            match self {
+                #(#name_arms)*
+            }
+        },
+    ))
+}
+
+/// The visited fields of a struct or enum variant.
+enum Shape {
+    Named {
+        /// Statements visiting each named field.
+        visits: Vec<TokenStream>,
+        /// The names of the visited fields, unless a field is flattened.
+        keys: Option<Vec<String>>,
+    },
+    /// Expressions borrowing each visited unnamed field as a `Visitable`.
+    Unnamed {
+        values: Vec<TokenStream>,
+        /// Set if there is exactly one field, which is visited.
+        newtype: bool,
+    },
+    Unit,
+}
+
+impl Shape {
+    /// Returns the shape of `fields`, given a function returning an
+    /// expression which borrows the `i`th field.
+    ///
+    /// If `sort_fields` is set, named fields are visited sorted by the key
+    /// they are visited with.
+    fn new<'a>(
+        fields: &'a Fields,
+        rename_all: Option<RenameRule>,
+        sort_fields: bool,
+        access: impl Fn(usize, &syn::Field) -> TokenStream,
+        visited: &mut Vec<&'a Type>,
+    ) -> Result<Self> {
+        let shape = match fields {
+            Fields::Named(fields) => {
+                // Each visited field's key, unless it is flattened, and the
+                // statement visiting it.
+                let mut visits = Vec::new();
+                for (i, field) in fields.named.iter().enumerate() {
+                    let attrs = attr::Field::from_attrs(&field.attrs)?;
+                    if attrs.skip {
+                        continue;
+                    }
+                    if sort_fields && attrs.flatten {
+                        return Err(Error::new_spanned(
+                            field,
+                            "`flatten` fields cannot be used with `sort_fields`",
+                        ));
+                    }
+                    let key = field_key(&attrs, field, rename_all);
+                    let value = if attrs.skip_if_none {
+                        quote!(value)
+                    } else {
+                        access(i, field)
+                    };
+                    let value = field_value(&attrs, value, &field.ty, visited);
+                    let (key, visit) = if attrs.flatten {
+                        let visit = quote! {
+                            ::valuable::__private::visit_flattened(visitor, #value)?;
+                        };
+                        (None, visit)
+                    } else {
+                        let visit = quote! {
+                            ::valuable::__private::visit_field(visitor, #key, #value)?;
+                        };
+                        (Some(key), visit)
+                    };
+                    let visit = if attrs.skip_if_none {
+                        let field = access(i, field);
+                        quote! {
+                            if let ::std::option::Option::Some(value) = #field {
+                                #visit
+                            }
+                        }
+                    } else {
+                        visit
+                    };
+                    let visit = match &attrs.skip_if {
+                        Some(skip_if) => {
+                            let field = access(i, field);
+                            quote! {
+                                if !#skip_if(#field) {
+                                    #visit
+                                }
+                            }
+                        }
+                        None => visit,
+                    };
+                    visits.push((key, visit));
+                }
+                if sort_fields {
+                    visits.sort_by(|(a, _), (b, _)| a.cmp(b));
+                }
+                let keys = visits.iter().map(|(key, _)| key.clone()).collect();
+                let visits = visits.into_iter().map(|(_, visit)| visit).collect();
+                Shape::Named { visits, keys }
+            }
+            Fields::Unnamed(fields) => {
+                let mut values = Vec::new();
+                for (i, field) in fields.unnamed.iter().enumerate() {
+                    let attrs = attr::Field::from_attrs(&field.attrs)?;
+                    if attrs.rename.is_some()
+                        || attrs.skip_if_none
+                        || attrs.skip_if.is_some()
+                        || attrs.flatten
+                    {
+                        return Err(Error::new_spanned(
+                            field,
+                            "tuple struct fields may only use `skip`, `with`, or `redact`",
+                        ));
+                    }
+                    if attrs.skip {
+                        continue;
+                    }
+                    values.push(field_value(&attrs, access(i, field), &field.ty, visited));
+                }
+                let newtype = fields.unnamed.len() == 1 && values.len() == 1;
+                Shape::Unnamed { values, newtype }
+            }
+            Fields::Unit => Shape::Unit,
+        };
+        Ok(shape)
+    }
+
+    /// Returns the names of the visited fields, if these are named fields
+    /// and none of them are flattened.
+    fn keys(&self) -> Option<&[String]> {
+        match self {
+            Shape::Named {
+                keys: Some(keys), ..
+            } => Some(keys),
+            _ => None,
+        }
+    }
+
+    /// Returns an expression visiting these fields as a named struct, tuple
+    /// struct, or unit value.
+    fn named(&self, name: &str) -> TokenStream {
+        let fields = self.anonymous_fields();
+        quote! {
+            ::valuable::visitor::Visit::named_type(visitor, #name)?;
+            #fields
+        }
+    }
+
+    /// Returns an expression visiting these fields without a type name.
+    ///
+    /// Unlike `named`, a newtype's field is visited as just its value.
+    fn anonymous(&self) -> TokenStream {
+        match self {
+            Shape::Unnamed {
+                values,
+                newtype: true,
+            } => {
+                let value = &values[0];
+                quote!(::valuable::value::Visitable::visit(#value, visitor))
+            }
+            _ => self.anonymous_fields(),
+        }
+    }
+
+    /// Returns an expression for a `Visitable` which visits these fields
+    /// as `anonymous` does.
+    fn content(&self) -> TokenStream {
+        let visit = self.anonymous();
+        quote! {
+            ::valuable::__private::Content(|visitor: &mut dyn ::valuable::visitor::Visit| {
+                #visit
+            })
+        }
+    }
+
+    fn anonymous_fields(&self) -> TokenStream {
+        match self {
+            Shape::Named { visits, .. } => quote! {
+                ::valuable::visitor::Visit::open_struct(visitor)?;
+                #(#visits)*
+                ::valuable::visitor::Visit::close_struct(visitor)
+            },
+            Shape::Unnamed { values, .. } => {
+                let visits = values.iter().enumerate().map(|(idx, value)| {
+                    quote!(::valuable::__private::visit_element(visitor, #idx, #value)?;)
+                });
+                quote! {
+                    ::valuable::visitor::Visit::open_tuple(visitor)?;
+                    #(#visits)*
+                    ::valuable::visitor::Visit::close_tuple(visitor)
+                }
+            }
+            Shape::Unit => quote!(::valuable::visitor::Visit::visit_unit(visitor)),
+        }
+    }
+}
+
+/// Returns the name an enum variant is visited with.
+pub(crate) fn variant_name(
+    attrs: &Container,
+    variant: &Variant,
+    rename_all: Option<RenameRule>,
+) -> String {
+    match (&attrs.rename, rename_all) {
+        (Some(rename), _) => rename.clone(),
+        (None, Some(rule)) => rule.apply_to_variant(&variant.ident.unraw().to_string()),
+        (None, None) => variant.ident.unraw().to_string(),
+    }
+}
+
+/// Returns the key a named field is visited with.
+pub(crate) fn field_key(
+    attrs: &attr::Field,
+    field: &syn::Field,
+    rename_all: Option<RenameRule>,
+) -> String {
+    if let Some(rename) = &attrs.rename {
+        return rename.clone();
+    }
+    let ident = field.ident.as_ref().expect("named fields have idents");
+    let key = ident.unraw().to_string();
+    match rename_all {
+        Some(rule) => rule.apply(&key),
+        None => key,
+    }
+}
+
+/// Returns an expression for the `&dyn Visitable` visited for a field, given
+/// an expression borrowing the field's value.
+///
+/// If the field's type must implement `Visitable`, it is added to `visited`.
+fn field_value<'a>(
+    attrs: &attr::Field,
+    value: TokenStream,
+    ty: &'a Type,
+    visited: &mut Vec<&'a Type>,
+) -> TokenStream {
+    if attrs.redact == Some(Redact::Placeholder) {
+        return quote!(&::valuable::__private::REDACTED);
+    }
+    let value = match &attrs.with {
+        Some(with) => quote!(&::valuable::__private::With::new(#value, #with)),
+        None => {
+            visited.push(ty);
+            value
+        }
+    };
+    match attrs.redact {
+        Some(Redact::Hash) => quote!(&::valuable::__private::Hashed(#value)),
+        _ => value,
+    }
+}
+
+/// Adds a `bound` bound (such as `Visitable`) for each type parameter used
+/// in one of `types`, or the predicates of a `#[visitable(bound = "...")]`
+/// attribute in place of them.
+pub(crate) fn add_bounds(
+    generics: &Generics,
+    predicates: Option<Vec<WherePredicate>>,
+    types: &[&Type],
+    bound: TokenStream,
+) -> Generics {
+    let mut generics = generics.clone();
+    let predicates = match predicates {
+        Some(predicates) => predicates,
+        None => generics
+            .type_params()
+            .filter(|param| types.iter().any(|ty| mentions(ty, &param.ident)))
+            .map(|param| {
+                let ident = &param.ident;
+                parse_quote!(#ident: #bound)
+            })
+            .collect(),
+    };
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+/// Returns `true` if `ty` refers to the type parameter `param`, other than
+/// as the parameter of a `PhantomData`.
+fn mentions(ty: &Type, param: &Ident) -> bool {
+    struct Mentions<'a> {
+        param: &'a Ident,
+        found: bool,
+    }
+
+    impl<'ast> Visit<'ast> for Mentions<'_> {
+        fn visit_type_path(&mut self, ty: &'ast TypePath) {
+            // `PhantomData<T>` implements every trait derived here
+            // regardless of `T`, so it doesn't require a bound.
+            if ty.path.segments.last().map(|s| s.ident == "PhantomData") == Some(true) {
+                return;
+            }
+            if ty.qself.is_none() && ty.path.segments.first().map(|s| &s.ident) == Some(self.param)
+            {
+                self.found = true;
+            }
+            syn::visit::visit_type_path(self, ty);
+        }
+    }
+
+    let mut mentions = Mentions {
+        param,
+        found: false,
+    };
+    mentions.visit_type(ty);
+    mentions.found
+}
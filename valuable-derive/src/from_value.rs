@@ -0,0 +1,336 @@
+use crate::{
+    attr::{self, Container, Repr},
+    case::RenameRule,
+    expand::{add_bounds, field_key, variant_name},
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{ext::IdentExt, Data, DataEnum, DeriveInput, Error, Fields, Ident, Result, Type};
+
+pub(crate) fn derive_from_value(input: DeriveInput) -> Result<TokenStream> {
+    let container = Container::from_attrs(&input.attrs)?;
+    // The types of the fields which are extracted, which must implement
+    // `FromValue`.
+    let mut extracted = Vec::new();
+    let body = match &input.data {
+        Data::Struct(data) => {
+            container.check_struct()?;
+            let shape = Shape::new(&data.fields, container.rename_all, &mut extracted)?;
+            shape.named(&quote!(Self), quote!(value))
+        }
+        Data::Enum(data) => expand_enum(&input.ident, &container, data, &mut extracted)?,
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "`FromValue` cannot be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let generics = add_bounds(
+        &input.generics,
+        container.bound,
+        &extracted,
+        quote!(::valuable::extract::FromValue),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::valuable::extract::FromValue for #ident #ty_generics #where_clause {
+            fn from_owned(
+                value: ::valuable::owned::OwnedValue,
+            ) -> ::std::result::Result<Self, ::valuable::extract::ExtractError> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_enum<'a>(
+    ident: &Ident,
+    container: &Container,
+    data: &'a DataEnum,
+    extracted: &mut Vec<&'a Type>,
+) -> Result<TokenStream> {
+    let repr = container.repr(ident.span())?;
+    let enum_name = container
+        .rename
+        .clone()
+        .unwrap_or_else(|| ident.unraw().to_string());
+    let no_variant = format!("a variant of `{}`", enum_name);
+    if data.variants.is_empty() {
+        return Ok(quote! {
+            let _ = value;
+            ::std::result::Result::Err(::valuable::extract::ExtractError::invalid_value(#no_variant))
+        });
+    }
+
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let attrs = Container::from_attrs(&variant.attrs)?;
+        attrs.check_variant()?;
+        let name = variant_name(&attrs, variant, container.rename_all);
+        let shape = Shape::new(&variant.fields, attrs.rename_all, extracted)?;
+        let variant_ident = &variant.ident;
+        let path = quote!(Self::#variant_ident);
+        let build = match &repr {
+            Repr::Named => shape.named(&path, quote!(value)),
+            Repr::External => in_field(&name, shape.anonymous(&path, quote!(__content))),
+            Repr::Internal { .. } => match shape {
+                Shape::Named { .. } => shape.build_fields(&path),
+                Shape::Unnamed { newtype: true, .. } => {
+                    quote!(::std::result::Result::Ok(#path(__fields.rest()?)))
+                }
+                Shape::Unit => quote!(::std::result::Result::Ok(#path)),
+                Shape::Unnamed { .. } => {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "internally tagged enums may only have struct, newtype, and unit \
+                         variants",
+                    ))
+                }
+            },
+            Repr::Adjacent { content, .. } => {
+                in_field(content, shape.anonymous(&path, quote!(__content)))
+            }
+            Repr::Untagged => {
+                let build = shape.anonymous(&path, quote!(value.clone()));
+                arms.push(quote! {
+                    let result = (|| -> ::std::result::Result<Self, ::valuable::extract::ExtractError> {
+                        #build
+                    })();
+                    if let ::std::result::Result::Ok(value) = result {
+                        return ::std::result::Result::Ok(value);
+                    }
+                });
+                continue;
+            }
+        };
+        arms.push(quote!(#name => #build,));
+    }
+
+    let unknown = quote! {
+        _ => ::std::result::Result::Err(::valuable::extract::ExtractError::unknown_variant(&__name)),
+    };
+    let body = match &repr {
+        Repr::Named => quote! {
+            let __name = ::valuable::__private::type_name(&value)?.to_owned();
+            match __name.as_str() {
+                #(#arms)*
+                #unknown
+            }
+        },
+        Repr::External => quote! {
+            let (__name, __content) = ::valuable::__private::external_variant(value)?;
+            match __name.as_str() {
+                #(#arms)*
+                #unknown
+            }
+        },
+        Repr::Internal { tag } => quote! {
+            #[allow(unused_mut)]
+            let mut __fields = ::valuable::__private::Fields::from_owned(value)?;
+            let __name: ::std::string::String = __fields.take(#tag)?;
+            match __name.as_str() {
+                #(#arms)*
+                #unknown
+            }
+        },
+        Repr::Adjacent { tag, content } => quote! {
+            let mut __fields = ::valuable::__private::Fields::from_owned(value)?;
+            let __name: ::std::string::String = __fields.take(#tag)?;
+            let __content = __fields
+                .take_optional(#content)?
+                .unwrap_or(::valuable::owned::OwnedValue::Unit);
+            match __name.as_str() {
+                #(#arms)*
+                #unknown
+            }
+        },
+        Repr::Untagged => quote! {
+            #(#arms)*
+            ::std::result::Result::Err(::valuable::extract::ExtractError::invalid_value(#no_variant))
+        },
+    };
+    Ok(body)
+}
+
+/// Wraps an expression extracting the contents of a variant, so that errors
+/// are reported in the field `name`.
+fn in_field(name: &str, build: TokenStream) -> TokenStream {
+    quote! {{
+        let result = (move || -> ::std::result::Result<Self, ::valuable::extract::ExtractError> {
+            #build
+        })();
+        result.map_err(|e| e.in_field(#name))
+    }}
+}
+
+/// The extracted fields of a struct or enum variant.
+enum Shape {
+    Named {
+        /// Statements binding each extracted field to a local.
+        lets: Vec<TokenStream>,
+        /// Initializers for each field of the struct.
+        inits: Vec<TokenStream>,
+    },
+    Unnamed {
+        /// Expressions for each field of the struct, in order.
+        inits: Vec<TokenStream>,
+        /// The number of fields which are extracted.
+        len: usize,
+        /// Set if there is exactly one field, which is extracted.
+        newtype: bool,
+    },
+    Unit,
+}
+
+impl Shape {
+    fn new<'a>(
+        fields: &'a Fields,
+        rename_all: Option<RenameRule>,
+        extracted: &mut Vec<&'a Type>,
+    ) -> Result<Self> {
+        let shape = match fields {
+            Fields::Named(fields) => {
+                let mut lets = Vec::new();
+                let mut inits = Vec::new();
+                // The flattened field takes all of the fields which remain,
+                // so it is extracted last.
+                let mut flattened = None;
+                for (i, field) in fields.named.iter().enumerate() {
+                    let attrs = attr::Field::from_attrs(&field.attrs)?;
+                    let ident = field.ident.as_ref().expect("named fields have idents");
+                    if attrs.skip {
+                        inits.push(quote!(#ident: ::std::default::Default::default()));
+                        continue;
+                    }
+                    check_field(&attrs, field)?;
+                    let local = format_ident!("__field_{}", i);
+                    inits.push(quote!(#ident: #local));
+                    extracted.push(&field.ty);
+                    if attrs.flatten {
+                        if flattened.is_some() {
+                            return Err(Error::new_spanned(
+                                field,
+                                "`FromValue` cannot be derived for more than one flattened field",
+                            ));
+                        }
+                        flattened = Some(quote!(let #local = __fields.rest()?;));
+                        continue;
+                    }
+                    let key = field_key(&attrs, field, rename_all);
+                    lets.push(if attrs.skip_if_none {
+                        quote!(let #local = __fields.take_optional(#key)?;)
+                    } else if attrs.skip_if.is_some() {
+                        // A field which was skipped takes its default value.
+                        quote! {
+                            let #local = ::std::option::Option::unwrap_or_default(
+                                __fields.take_optional(#key)?,
+                            );
+                        }
+                    } else {
+                        quote!(let #local = __fields.take(#key)?;)
+                    });
+                }
+                lets.extend(flattened);
+                Shape::Named { lets, inits }
+            }
+            Fields::Unnamed(fields) => {
+                let mut inits = Vec::new();
+                let mut len = 0;
+                for field in &fields.unnamed {
+                    let attrs = attr::Field::from_attrs(&field.attrs)?;
+                    if attrs.rename.is_some()
+                        || attrs.skip_if_none
+                        || attrs.skip_if.is_some()
+                        || attrs.flatten
+                    {
+                        return Err(Error::new_spanned(
+                            field,
+                            "tuple struct fields may only use `skip`, `with`, or `redact`",
+                        ));
+                    }
+                    if attrs.skip {
+                        inits.push(quote!(::std::default::Default::default()));
+                        continue;
+                    }
+                    check_field(&attrs, field)?;
+                    inits.push(quote!(__elements.take()?));
+                    extracted.push(&field.ty);
+                    len += 1;
+                }
+                let newtype = fields.unnamed.len() == 1 && len == 1;
+                Shape::Unnamed {
+                    inits,
+                    len,
+                    newtype,
+                }
+            }
+            Fields::Unit => Shape::Unit,
+        };
+        Ok(shape)
+    }
+
+    /// Returns an expression constructing `path` from a struct or map
+    /// visited by the `Visitable` derive's `named`.
+    fn named(&self, path: &TokenStream, value: TokenStream) -> TokenStream {
+        match self {
+            Shape::Named { .. } => {
+                let build = self.build_fields(path);
+                quote! {{
+                    #[allow(unused_mut)]
+                    let mut __fields = ::valuable::__private::Fields::from_owned(#value)?;
+                    #build
+                }}
+            }
+            Shape::Unnamed { inits, len, .. } => quote! {{
+                #[allow(unused_mut)]
+                let mut __elements = ::valuable::__private::Elements::from_owned(#value, #len)?;
+                ::std::result::Result::Ok(#path(#(#inits),*))
+            }},
+            Shape::Unit => quote! {{
+                <() as ::valuable::extract::FromValue>::from_owned(#value)?;
+                ::std::result::Result::Ok(#path)
+            }},
+        }
+    }
+
+    /// Returns an expression constructing `path` from a value visited by the
+    /// `Visitable` derive's `anonymous`.
+    fn anonymous(&self, path: &TokenStream, value: TokenStream) -> TokenStream {
+        match self {
+            Shape::Unnamed { newtype: true, .. } => quote! {
+                ::std::result::Result::Ok(#path(
+                    ::valuable::extract::FromValue::from_owned(#value)?,
+                ))
+            },
+            _ => self.named(path, value),
+        }
+    }
+
+    /// Returns an expression constructing `path` from the named fields in
+    /// the local `__fields`.
+    fn build_fields(&self, path: &TokenStream) -> TokenStream {
+        match self {
+            Shape::Named { lets, inits } => quote! {{
+                #(#lets)*
+                ::std::result::Result::Ok(#path { #(#inits),* })
+            }},
+            _ => unreachable!("only named fields are built from `__fields`"),
+        }
+    }
+}
+
+/// Checks that an extracted field does not use attributes whose effect
+/// cannot be reversed.
+fn check_field(attrs: &attr::Field, field: &syn::Field) -> Result<()> {
+    if attrs.with.is_some() || attrs.redact.is_some() {
+        return Err(Error::new_spanned(
+            field,
+            "`FromValue` cannot be derived for fields using `with` or `redact`, unless they \
+             are also `skip`ped",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Derive macros for [`valuable`].
+//!
+//! This crate should not be used directly; enable `valuable`'s `derive`
+//! feature and use the re-exported `valuable::value::Visitable` and
+//! `valuable::extract::FromValue` derives instead.
+//!
+//! [`valuable`]: https://docs.rs/valuable
+extern crate proc_macro;
+
+mod attr;
+mod case;
+mod expand;
+mod from_value;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Derives `Visitable` for a struct or enum.
+///
+/// See the documentation of the re-export in `valuable::value` for the
+/// supported attributes.
+#[proc_macro_derive(Visitable, attributes(visitable))]
+pub fn derive_visitable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand::derive_visitable(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `FromValue` for a struct or enum.
+///
+/// See the documentation of the re-export in `valuable::extract` for the
+/// supported attributes.
+#[proc_macro_derive(FromValue, attributes(visitable))]
+pub fn derive_from_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_value::derive_from_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}